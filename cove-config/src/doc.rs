@@ -232,6 +232,24 @@ impl Document for bool {
     }
 }
 
+impl Document for u16 {
+    fn doc() -> Doc {
+        let mut doc = Doc::default();
+        doc.value_info.required = Some(true);
+        doc.value_info.r#type = Some("integer".to_string());
+        doc
+    }
+}
+
+impl Document for u64 {
+    fn doc() -> Doc {
+        let mut doc = Doc::default();
+        doc.value_info.required = Some(true);
+        doc.value_info.r#type = Some("integer".to_string());
+        doc
+    }
+}
+
 impl Document for PathBuf {
     fn doc() -> Doc {
         let mut doc = Doc::default();
@@ -250,6 +268,17 @@ impl<I: Document> Document for Option<I> {
     }
 }
 
+impl<I: Document> Document for Vec<I> {
+    fn doc() -> Doc {
+        let inner = I::doc();
+        let mut doc = Doc::default();
+        doc.value_info.required = Some(true);
+        doc.value_info.r#type = inner.value_info.r#type.map(|ty| format!("list of {ty}s"));
+        doc.value_info.values = inner.value_info.values;
+        doc
+    }
+}
+
 impl<I: Document> Document for HashMap<String, I> {
     fn doc() -> Doc {
         let mut doc = Doc::default();
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::doc::Document;
+use crate::{ConnectWindow, NotifyOn};
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Document)]
 #[serde(rename_all = "snake_case")]
@@ -12,6 +13,14 @@ pub enum RoomsSortOrder {
     Importance,
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum OpenAt {
+    #[default]
+    Newest,
+    OldestUnseen,
+}
+
 // TODO Mark favourite rooms via printable ascii characters
 #[derive(Debug, Clone, Default, Deserialize, Document)]
 pub struct EuphRoom {
@@ -19,6 +28,24 @@ pub struct EuphRoom {
     #[serde(default)]
     pub autojoin: bool,
 
+    /// Times of the week during which this room should be connected, like
+    /// `["mon-fri 09:00-18:00"]`.
+    ///
+    /// The room is connected when one of the windows starts and disconnected
+    /// when it ends, in the time zone set by `time_zone`. In between, it can
+    /// still be connected to or disconnected from manually. When set,
+    /// `autojoin` is ignored. Nothing happens in offline mode.
+    ///
+    /// Each window consists of the days it starts on, as a comma-separated list
+    /// of days (`mon`, `tue`, ...) and ranges of days (`mon-fri`), followed by
+    /// a start and end time. If the days are left out, the window applies to
+    /// every day. A window ending before it starts, like `22:00-02:00`, ends
+    /// on the next day.
+    ///
+    /// If not set, the room is never connected or disconnected automatically
+    /// except for `autojoin`.
+    pub schedule: Option<Vec<ConnectWindow>>,
+
     /// If set, cove will set this username upon joining if there is no username
     /// associated with the current session.
     pub username: Option<String>,
@@ -32,10 +59,73 @@ pub struct EuphRoom {
     /// If set, cove will try once to use this password to authenticate, should
     /// the room be password-protected.
     pub password: Option<String>,
+
+    /// Whether to ask for confirmation before sending a message to this room.
+    ///
+    /// The message is shown as it will appear in the room and is only sent
+    /// once confirmed. Useful for rooms where an accidental message would be
+    /// embarrassing, like announcement rooms.
+    #[serde(default)]
+    pub confirm_send: bool,
+
+    /// Like the global `open_at` option, but only for this room. Takes
+    /// precedence over the global option if set.
+    pub open_at: Option<OpenAt>,
+
+    /// Like the global `notify.on` option, but only for this room. Takes
+    /// precedence over the global option if set.
+    ///
+    /// Set to `[]` to never run `notify.command` for this room.
+    pub notify_on: Option<Vec<NotifyOn>>,
+
+    /// Whether messages arriving in this room are shown in the combined feed.
+    ///
+    /// The feed can be opened from the rooms list and shows the messages of
+    /// all rooms with this option enabled in one list, newest first. Only
+    /// messages that arrive while cove is running are shown.
+    #[serde(default)]
+    pub feed: bool,
+
+    /// If set, messages sent by this nick are considered pinned.
+    ///
+    /// Pinned messages are listed in a popup, so information the room's
+    /// community considers important can be found without scrolling. Rooms
+    /// usually have a bot for pinning messages, whose nick goes here. If
+    /// `pin_prefix` is set as well, messages must match both.
+    pub pin_nick: Option<String>,
+
+    /// If set, messages starting with this text are considered pinned.
+    ///
+    /// See also `pin_nick`.
+    pub pin_prefix: Option<String>,
+
+    /// Like the global `reconnect_attempts` option, but only for this room.
+    /// Takes precedence over the global option if set.
+    pub reconnect_attempts: Option<u64>,
+
+    /// Like the global `send_interval` option, but only for this room. Takes
+    /// precedence over the global option if set.
+    pub send_interval: Option<u64>,
+
+    /// Whether to keep this room's messages and seen state only in memory.
+    ///
+    /// While enabled, nothing about the room is written to the vault, but
+    /// other rooms are still recorded as usual. The room's history from
+    /// earlier visits is not shown. This can also be toggled at runtime from
+    /// within the room.
+    #[serde(default)]
+    pub incognito: bool,
 }
 
 #[derive(Debug, Default, Deserialize, Document)]
 pub struct EuphServer {
+    /// URL of a room directory listing the server's public rooms.
+    ///
+    /// Some self-hosted servers publish such a directory. If set, its rooms
+    /// can be browsed from the rooms list. The directory must be a JSON array
+    /// of objects, each with a `name` and optionally a `description` string.
+    pub directory: Option<String>,
+
     #[document(metavar = "room")]
     pub rooms: HashMap<String, EuphRoom>,
 }
@@ -44,4 +134,16 @@ pub struct EuphServer {
 pub struct Euph {
     #[document(metavar = "domain")]
     pub servers: HashMap<String, EuphServer>,
+
+    /// Room templates with the same options as
+    /// `euph.servers.<domain>.rooms.<room>`.
+    ///
+    /// When connecting to a new room from the rooms list, one of these can be
+    /// chosen. The room then uses the template's options, and cove remembers
+    /// the choice for later sessions. Options like `autojoin` therefore also
+    /// work for rooms that are not listed in the config file. Rooms listed in
+    /// the config file always use their own options instead.
+    #[serde(default)]
+    #[document(metavar = "template")]
+    pub templates: HashMap<String, EuphRoom>,
 }
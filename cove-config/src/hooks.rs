@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+use crate::doc::Document;
+
+#[derive(Debug, Default, Deserialize, Document)]
+pub struct Hooks {
+    /// Command to run after successfully joining a room.
+    ///
+    /// Hook commands are executed via `sh -c` (or `cmd /C` on Windows) in the
+    /// background. Information about the event is passed to the command via
+    /// the following environment variables:
+    ///
+    /// - `COVE_HOOK`: name of the hook, e.g. `on_join`
+    /// - `COVE_DOMAIN`: domain of the room, e.g. `euphoria.leet.nu`
+    /// - `COVE_ROOM`: name of the room, without the leading `&`
+    /// - `COVE_NICK`: your current nick in the room, if any
+    pub on_join: Option<String>,
+
+    /// Command to run after the connection to a room was lost.
+    ///
+    /// See `hooks.on_join` for how the command is executed.
+    pub on_disconnect: Option<String>,
+
+    /// Command to run when a new message mentions your current nick.
+    ///
+    /// See `hooks.on_join` for how the command is executed. In addition to the
+    /// usual environment variables, `COVE_SENDER` contains the nick of the
+    /// message's sender, `COVE_CONTENT` the message's content and
+    /// `COVE_MESSAGE_ID` the message's id.
+    pub on_mention: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Document)]
+pub struct Webhook {
+    /// URL to send new messages to.
+    ///
+    /// Messages are sent via HTTP POST requests with a JSON body of the form
+    /// `{"messages": [{"domain": ..., "room": ..., "message": ...}]}`, where
+    /// `message` is a message in the format used by the euphoria API. Messages
+    /// arriving in quick succession are sent in a single request. Failed
+    /// requests are retried a few times before giving up.
+    pub url: String,
+
+    /// Only send messages from rooms on this domain.
+    pub domain: Option<String>,
+
+    /// Only send messages from rooms with this name (without the leading `&`).
+    pub room: Option<String>,
+
+    /// Only send messages whose sender has this nick.
+    pub sender: Option<String>,
+
+    /// Only send messages containing this keyword, ignoring case.
+    pub keyword: Option<String>,
+}
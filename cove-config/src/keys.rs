@@ -27,6 +27,14 @@ default_bindings! {
         pub fn focus => ["tab"];
         pub fn help => ["f1"];
         pub fn log => ["f12"];
+        pub fn away => ["f9"];
+        pub fn low_bandwidth => ["f8"];
+        pub fn search => ["f4"];
+        pub fn back => ["alt+left"];
+        pub fn forward => ["alt+right"];
+        pub fn recent => ["f3"];
+        pub fn sidebar => ["f2"];
+        pub fn suspend => ["ctrl+z"];
     }
 
     pub mod scroll {
@@ -62,6 +70,8 @@ default_bindings! {
         pub fn delete => ["ctrl+d", "delete"];
         pub fn clear => ["ctrl+l"];
         pub fn external => ["ctrl+x", "alt+e"];
+        pub fn snippet => ["ctrl+o"];
+        pub fn new_thread => ["ctrl+n"];
     }
 
     pub mod rooms_action {
@@ -74,6 +84,9 @@ default_bindings! {
         pub fn new => ["n"];
         pub fn delete => ["X"];
         pub fn change_sort_order => ["s"];
+        pub fn mark_away_seen => ["S"];
+        pub fn browse => ["b"];
+        pub fn feed => ["f"];
     }
 
     pub mod room_action {
@@ -81,6 +94,16 @@ default_bindings! {
         pub fn nick => ["n"];
         pub fn more_messages => ["m"];
         pub fn account => ["A"];
+        pub fn catch_up => ["w"];
+        pub fn stats => ["ctrl+t"];
+        pub fn activity => ["alt+t"];
+        pub fn scheduled => ["alt+s"];
+        pub fn pinned => ["alt+p"];
+        pub fn search => ["/"];
+        pub fn connect => ["o"];
+        pub fn incognito => ["alt+i"];
+        pub fn toggle_bots => ["b"];
+        pub fn toggle_lurkers => ["B"];
     }
 
     pub mod tree_cursor {
@@ -92,6 +115,7 @@ default_bindings! {
         pub fn to_newer_message => ["l", "right"];
         pub fn to_older_unseen_message => ["H", "ctrl+left"];
         pub fn to_newer_unseen_message => ["L", "ctrl+right"];
+        pub fn to_next_unseen_thread => ["u"];
     }
 
     pub mod tree_action {
@@ -106,6 +130,15 @@ default_bindings! {
         pub fn links => ["I"];
         pub fn increase_caesar => ["c"];
         pub fn decrease_caesar => ["C"];
+        pub fn translate => ["T"];
+        pub fn subscribe => ["f"];
+        pub fn note => ["N"];
+        pub fn zen => ["Z"];
+        pub fn follow => ["F"];
+        pub fn copy_id => ["y"];
+        pub fn copy_sender_id => ["Y"];
+        pub fn expand => ["e"];
+        pub fn load_ancestors => ["E"];
     }
 
 }
@@ -131,6 +164,30 @@ pub struct General {
     /// Show log.
     #[serde(default = "default::general::log")]
     pub log: KeyBinding,
+    /// Start or stop being away.
+    #[serde(default = "default::general::away")]
+    pub away: KeyBinding,
+    /// Turn low bandwidth mode on or off.
+    #[serde(default = "default::general::low_bandwidth")]
+    pub low_bandwidth: KeyBinding,
+    /// Search messages in all rooms.
+    #[serde(default = "default::general::search")]
+    pub search: KeyBinding,
+    /// Go back to the previously viewed room.
+    #[serde(default = "default::general::back")]
+    pub back: KeyBinding,
+    /// Go forward again after going back.
+    #[serde(default = "default::general::forward")]
+    pub forward: KeyBinding,
+    /// Show recently viewed rooms.
+    #[serde(default = "default::general::recent")]
+    pub recent: KeyBinding,
+    /// Move focus between the rooms sidebar and the current room.
+    #[serde(default = "default::general::sidebar")]
+    pub sidebar: KeyBinding,
+    /// Suspend cove and return to the shell.
+    #[serde(default = "default::general::suspend")]
+    pub suspend: KeyBinding,
 }
 
 #[derive(Debug, Deserialize, Document, KeyGroup)]
@@ -220,6 +277,12 @@ pub struct EditorAction {
     /// Edit in external editor.
     #[serde(default = "default::editor_action::external")]
     pub external: KeyBinding,
+    /// Expand or insert snippet.
+    #[serde(default = "default::editor_action::snippet")]
+    pub snippet: KeyBinding,
+    /// Start a new thread instead of replying.
+    #[serde(default = "default::editor_action::new_thread")]
+    pub new_thread: KeyBinding,
 }
 
 #[derive(Debug, Default, Deserialize, Document)]
@@ -263,6 +326,15 @@ pub struct RoomsAction {
     /// Change sort order.
     #[serde(default = "default::rooms_action::change_sort_order")]
     pub change_sort_order: KeyBinding,
+    /// Mark messages sent while you were last away as seen.
+    #[serde(default = "default::rooms_action::mark_away_seen")]
+    pub mark_away_seen: KeyBinding,
+    /// Browse the room directories of servers that have one.
+    #[serde(default = "default::rooms_action::browse")]
+    pub browse: KeyBinding,
+    /// Show the combined feed of rooms with the `feed` option enabled.
+    #[serde(default = "default::rooms_action::feed")]
+    pub feed: KeyBinding,
 }
 
 #[derive(Debug, Default, Deserialize, Document)]
@@ -287,6 +359,36 @@ pub struct RoomAction {
     /// Manage account.
     #[serde(default = "default::room_action::account")]
     pub account: KeyBinding,
+    /// Move to first unseen message sent while you were last away.
+    #[serde(default = "default::room_action::catch_up")]
+    pub catch_up: KeyBinding,
+    /// Show traffic statistics.
+    #[serde(default = "default::room_action::stats")]
+    pub stats: KeyBinding,
+    /// Show on which days messages were sent.
+    #[serde(default = "default::room_action::activity")]
+    pub activity: KeyBinding,
+    /// Show messages scheduled to be sent later.
+    #[serde(default = "default::room_action::scheduled")]
+    pub scheduled: KeyBinding,
+    /// Show pinned messages.
+    #[serde(default = "default::room_action::pinned")]
+    pub pinned: KeyBinding,
+    /// Search messages in this room.
+    #[serde(default = "default::room_action::search")]
+    pub search: KeyBinding,
+    /// Connect to room if not connected.
+    #[serde(default = "default::room_action::connect")]
+    pub connect: KeyBinding,
+    /// Toggle whether room history is kept only in memory.
+    #[serde(default = "default::room_action::incognito")]
+    pub incognito: KeyBinding,
+    /// Show or hide bots in the nick list.
+    #[serde(default = "default::room_action::toggle_bots")]
+    pub toggle_bots: KeyBinding,
+    /// Show or hide lurkers in the nick list.
+    #[serde(default = "default::room_action::toggle_lurkers")]
+    pub toggle_lurkers: KeyBinding,
 }
 
 #[derive(Debug, Default, Deserialize, Document)]
@@ -323,6 +425,9 @@ pub struct TreeCursor {
     /// Move to newer unseen message.
     #[serde(default = "default::tree_cursor::to_newer_unseen_message")]
     pub to_newer_unseen_message: KeyBinding,
+    /// Mark the current thread as seen and move to the oldest unseen message.
+    #[serde(default = "default::tree_cursor::to_next_unseen_thread")]
+    pub to_next_unseen_thread: KeyBinding,
     // TODO Bindings inspired by vim's ()/[]/{} bindings?
 }
 
@@ -362,6 +467,34 @@ pub struct TreeAction {
     /// Decrease caesar cipher rotation.
     #[serde(default = "default::tree_action::decrease_caesar")]
     pub decrease_caesar: KeyBinding,
+    /// Show or hide translation of message.
+    #[serde(default = "default::tree_action::translate")]
+    pub translate: KeyBinding,
+    /// Subscribe to or unsubscribe from replies to message.
+    #[serde(default = "default::tree_action::subscribe")]
+    pub subscribe: KeyBinding,
+    /// Edit local note attached to message.
+    #[serde(default = "default::tree_action::note")]
+    pub note: KeyBinding,
+    /// Toggle zen mode, which hides everything except the messages.
+    #[serde(default = "default::tree_action::zen")]
+    pub zen: KeyBinding,
+    /// Toggle following new messages as they arrive.
+    #[serde(default = "default::tree_action::follow")]
+    pub follow: KeyBinding,
+    /// Copy id of the selected message to the clipboard.
+    #[serde(default = "default::tree_action::copy_id")]
+    pub copy_id: KeyBinding,
+    /// Copy user id of the selected message's sender to the clipboard.
+    #[serde(default = "default::tree_action::copy_sender_id")]
+    pub copy_sender_id: KeyBinding,
+    /// Show long words and the nick of the selected message in full, or load
+    /// it if it is missing.
+    #[serde(default = "default::tree_action::expand")]
+    pub expand: KeyBinding,
+    /// Load all missing messages above the selected message.
+    #[serde(default = "default::tree_action::load_ancestors")]
+    pub load_ancestors: KeyBinding,
 }
 
 #[derive(Debug, Default, Deserialize, Document)]
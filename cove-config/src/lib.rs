@@ -11,17 +11,32 @@
 
 pub mod doc;
 mod euph;
+mod hooks;
 mod keys;
+mod notify;
+mod schedule;
+mod search;
+mod seen;
+mod theme;
+mod translate;
 
+use std::collections::HashMap;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
 use doc::Document;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub use crate::euph::*;
+pub use crate::hooks::*;
 pub use crate::keys::*;
+pub use crate::notify::*;
+pub use crate::schedule::*;
+pub use crate::search::*;
+pub use crate::seen::*;
+pub use crate::theme::*;
+pub use crate::translate::*;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -31,8 +46,151 @@ pub enum Error {
     Toml(#[from] toml::de::Error),
 }
 
-#[derive(Debug, Default, Deserialize, Document)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum Composer {
+    #[default]
+    Inline,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConnectOnOpen {
+    #[default]
+    Never,
+    Always,
+    Ask,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum CursorAfterSend {
+    #[default]
+    Sent,
+    Previous,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum LongWords {
+    #[default]
+    Break,
+    Punctuation,
+    Truncate,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum OwnMessages {
+    #[default]
+    Plain,
+    Marker,
+    Background,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeMode {
+    #[default]
+    Color,
+    HighContrast,
+    Mono,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum SiblingOrder {
+    #[default]
+    Id,
+    Time,
+    NewestFirst,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Document)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkSeen {
+    #[default]
+    Explicit,
+    Cursor,
+    Render,
+}
+
+#[derive(Debug, Deserialize, Document)]
 pub struct Config {
+    /// After how many minutes without any key presses you are considered away.
+    ///
+    /// When you press a key after being away, the time you were away for is
+    /// remembered, just like when starting and stopping being away manually.
+    /// Messages sent during that time can then be marked as seen in bulk from
+    /// the rooms list, or reviewed one by one starting from the oldest one.
+    ///
+    /// If not set, you are only away when you say so.
+    pub away_after: Option<u64>,
+
+    /// Command to copy text to the clipboard with, e.g. `"wl-copy"` or
+    /// `"xclip -selection clipboard"`.
+    ///
+    /// The command is executed via `sh -c` (or `cmd /C` on Windows) and
+    /// receives the text via stdin.
+    ///
+    /// If not set, cove asks the terminal to copy the text using the OSC 52
+    /// escape sequence. Not all terminals support this.
+    pub clipboard_command: Option<String>,
+
+    /// How many seconds the local clock may differ from a server's clock
+    /// before a warning is shown in the room's status bar.
+    ///
+    /// The server's time is taken from the pings it regularly sends. Message
+    /// timestamps come from the server, but cove compares them with the local
+    /// clock, so a skewed clock makes `stale_reply_after` misjudge how old a
+    /// message is. Messages scheduled with `/in` and rooms with a `schedule`
+    /// also follow the local clock and are sent or connected at the wrong time.
+    ///
+    /// Set to `0` to never show the warning.
+    #[serde(default = "default_clock_skew_warning")]
+    pub clock_skew_warning: u64,
+
+    /// Where the editor is shown while composing a message.
+    ///
+    /// `"inline"` shows the editor inside the message tree, right where the
+    /// new message will appear.
+    ///
+    /// `"bottom"` shows the editor in a bar at the bottom of the room, with
+    /// the message being replied to shown above it. The position of the new
+    /// message is still kept visible in the message tree.
+    #[serde(default)]
+    pub composer: Composer,
+
+    /// Whether to ask for confirmation before quitting while rooms are
+    /// connected, messages are being sent or unsent messages are in the
+    /// editor.
+    #[serde(default)]
+    pub confirm_quit: bool,
+
+    /// What happens when opening a room that is not connected.
+    ///
+    /// `"never"` shows the room's history without connecting. The room can be
+    /// connected to from within the room.
+    ///
+    /// `"always"` connects to the room right away.
+    ///
+    /// `"ask"` asks whether to connect every time.
+    #[serde(default)]
+    pub connect_on_open: ConnectOnOpen,
+
+    /// Where the cursor goes after a message was sent successfully.
+    ///
+    /// `"sent"` moves the cursor to the message that was just sent.
+    ///
+    /// `"previous"` moves the cursor back to where it was before the editor
+    /// was opened.
+    ///
+    /// `"bottom"` moves the cursor to the bottom of the chat.
+    #[serde(default)]
+    pub cursor_after_send: CursorAfterSend,
+
     /// The directory that cove stores its data in when not running in ephemeral
     /// mode.
     ///
@@ -42,6 +200,26 @@ pub struct Config {
     #[document(default = "platform-dependent")]
     pub data_dir: Option<PathBuf>,
 
+    /// For how many days to keep the content of messages deleted by the
+    /// server.
+    ///
+    /// When the server deletes a message and removes its content, the original
+    /// content is kept in the vault and shown when inspecting the message. This
+    /// way, accidental deletions don't immediately lose local history.
+    ///
+    /// If not set, the original content is still kept briefly, but discarded
+    /// within an hour.
+    pub deleted_retention: Option<u64>,
+
+    /// Every how many seconds unsent messages in the editor are saved to the
+    /// vault.
+    ///
+    /// If cove exits uncleanly, the saved message is offered for restoring
+    /// the next time the room is opened.
+    ///
+    /// If not set, unsent messages are not saved.
+    pub draft_autosave: Option<u64>,
+
     /// Whether to start in ephemeral mode.
     ///
     /// In ephemeral mode, cove doesn't store any data. It completely ignores
@@ -51,6 +229,57 @@ pub struct Config {
     #[serde(default)]
     pub ephemeral: bool,
 
+    /// Whether to leave bots out of the nick list and the people summary.
+    ///
+    /// This is only the default and can be toggled from within each room (see
+    /// `room.action.toggle_bots`).
+    #[serde(default)]
+    pub hide_bots: bool,
+
+    /// Whether to leave lurkers (sessions without a nick) out of the nick
+    /// list and the people summary.
+    ///
+    /// This is only the default and can be toggled from within each room (see
+    /// `room.action.toggle_lurkers`).
+    #[serde(default)]
+    pub hide_lurkers: bool,
+
+    /// How very long words like URLs or hashes are wrapped in the chat.
+    ///
+    /// `"break"` breaks them wherever the line ends.
+    ///
+    /// `"punctuation"` prefers breaking them after punctuation characters like
+    /// `/`, `.` or `-`.
+    ///
+    /// `"truncate"` shortens them and appends `…`. The full message can be
+    /// shown by expanding it (see `tree.action.expand`).
+    #[serde(default)]
+    pub long_words: LongWords,
+
+    /// Whether to start in low bandwidth mode, for metered or flaky
+    /// connections.
+    ///
+    /// In low bandwidth mode, cove doesn't download older messages in the
+    /// background. Messages arriving while connected are still received, and
+    /// older messages can still be requested manually. The mode can also be
+    /// toggled at runtime.
+    #[serde(default)]
+    pub low_bandwidth: bool,
+
+    /// When messages are marked as seen.
+    ///
+    /// `"explicit"` only marks messages as seen when using the key bindings for
+    /// doing so, for example `tree.action.toggle_seen` or
+    /// `tree.action.mark_visible_seen`.
+    ///
+    /// `"cursor"` additionally marks a message as seen once the cursor is moved
+    /// onto it.
+    ///
+    /// `"render"` additionally marks all messages as seen as soon as they are
+    /// visible on screen.
+    #[serde(default)]
+    pub mark_seen: MarkSeen,
+
     /// Whether to measure the width of characters as displayed by the terminal
     /// emulator instead of guessing the width.
     ///
@@ -62,6 +291,26 @@ pub struct Config {
     #[serde(default)]
     pub measure_widths: bool,
 
+    /// Address to serve metrics on, like `"127.0.0.1:9100"`.
+    ///
+    /// The metrics are in the Prometheus text format and can be requested via
+    /// HTTP at any path. They include the amount of joined rooms, connection
+    /// attempts, received messages and how long the vault takes to respond,
+    /// which is useful for keeping an eye on long-running instances. While
+    /// no interactive instance is running, the daemon serves them instead.
+    ///
+    /// If not set, no metrics are served.
+    pub metrics_addr: Option<String>,
+
+    /// Maximum width of nicks in the chat, in columns.
+    ///
+    /// Longer nicks are shortened and end in `…` so they don't take space
+    /// away from the content of messages. The full nick of a message can be
+    /// shown by expanding it (see `tree.action.expand`).
+    ///
+    /// If not set, nicks are never shortened.
+    pub nick_width: Option<u16>,
+
     /// Whether to start in offline mode.
     ///
     /// In offline mode, cove won't automatically join rooms marked via the
@@ -72,6 +321,45 @@ pub struct Config {
     #[serde(default)]
     pub offline: bool,
 
+    /// Where the cursor is placed when opening a room.
+    ///
+    /// `"newest"` leaves the cursor where it was, which is at the newest
+    /// message unless it was moved before.
+    ///
+    /// `"oldest-unseen"` moves the cursor to the oldest unseen message, if
+    /// there is one.
+    ///
+    /// See also the per-room `open_at` option.
+    #[serde(default)]
+    pub open_at: OpenAt,
+
+    /// How your own messages are distinguished from other messages in the
+    /// chat.
+    ///
+    /// `"plain"` doesn't distinguish them.
+    ///
+    /// `"marker"` shows a marker in the seen marker column, so it is hidden
+    /// along with the seen markers.
+    ///
+    /// `"background"` gives them a subtle background.
+    ///
+    /// Messages count as your own if they were sent with the same user id as
+    /// your current session, no matter which nick they were sent with. When
+    /// logged in, this is your account.
+    #[serde(default)]
+    pub own_messages: OwnMessages,
+
+    /// How many times in a row cove tries to connect to a room before giving
+    /// up.
+    ///
+    /// A room that cove gave up on is shown as such and can be reconnected to
+    /// manually. The count is reset whenever the room is joined successfully.
+    ///
+    /// If not set, cove keeps trying forever.
+    ///
+    /// See also the per-room `reconnect_attempts` option.
+    pub reconnect_attempts: Option<u64>,
+
     /// Initial sort order of rooms list.
     ///
     /// `"alphabet"` sorts rooms in alphabetic order.
@@ -80,11 +368,120 @@ pub struct Config {
     /// order of priority):
     ///
     /// 1. connected rooms before unconnected rooms
-    /// 2. rooms with unread messages before rooms without
-    /// 3. alphabetic order
+    /// 2. rooms with unread mentions before rooms without
+    /// 3. rooms with unread messages before rooms without
+    /// 4. alphabetic order
     #[serde(default)]
     pub rooms_sort_order: RoomsSortOrder,
 
+    /// Whether to render the UI in a way that works well with screen readers.
+    ///
+    /// When enabled, threads are indented with spaces instead of box drawing
+    /// characters, messages explicitly mention who they are replying to, and
+    /// the room status bar describes what the cursor is currently on.
+    #[serde(default)]
+    pub screen_reader: bool,
+
+    /// How many messages of a single thread are laid out at once.
+    ///
+    /// Threads with more messages only show the messages around the cursor.
+    /// The rest is replaced by a placeholder and loaded from the vault again
+    /// once the cursor moves closer to them. This keeps memory usage and
+    /// redraw times in check in rooms with giant threads.
+    ///
+    /// If not set, threads are always laid out completely.
+    pub scrollback: Option<usize>,
+
+    /// How many lines of context are kept above and below the cursor in the
+    /// chat when moving it, like vim's `scrolloff` option.
+    ///
+    /// If the chat is too small, less context is kept.
+    #[serde(default = "default_scrolloff")]
+    pub scrolloff: u16,
+
+    /// Minimum number of seconds between two messages you send in a room.
+    ///
+    /// Useful to avoid flooding a room or to follow a room's norms. While the
+    /// interval has not yet passed, the editor shows how many seconds are
+    /// left and sending is refused.
+    ///
+    /// If not set, messages can be sent as quickly as you like.
+    ///
+    /// See also the per-room `send_interval` option.
+    pub send_interval: Option<u64>,
+
+    /// How replies to the same message are ordered.
+    ///
+    /// `"id"` orders them by their id, which on euphoria is almost always the
+    /// order in which they were sent.
+    ///
+    /// `"time"` orders them by the time they were sent, oldest first.
+    ///
+    /// `"newest-first"` orders them by their id, but in reverse, so the newest
+    /// reply is directly below its parent.
+    ///
+    /// Threads themselves are always ordered oldest first. Text exports always
+    /// order replies by their id.
+    #[serde(default)]
+    pub sibling_order: SiblingOrder,
+
+    /// Minimum terminal width at which the rooms list is shown as a sidebar.
+    ///
+    /// When the terminal is at least this many columns wide, the rooms list
+    /// stays visible to the left of the current room instead of replacing it.
+    /// Focus can be moved between the sidebar and the room.
+    ///
+    /// If not set, the rooms list is never shown as a sidebar.
+    pub sidebar_min_width: Option<u16>,
+
+    /// After how many hours a message is considered too old to reply to.
+    ///
+    /// When replying to a message older than this, a warning is shown above
+    /// the editor, along with the key for starting a new thread instead.
+    ///
+    /// If not set, no warning is shown.
+    pub stale_reply_after: Option<u64>,
+
+    /// After how many seconds without any event from the server a connection
+    /// is considered stalled.
+    ///
+    /// Stalled connections are closed and a new connection is opened right
+    /// away. Euphoria servers send a ping event every 30 seconds, so values
+    /// below that will lead to needless reconnects.
+    ///
+    /// If not set, connections are never considered stalled.
+    pub stall_timeout: Option<u64>,
+
+    /// Directory for sharing which messages were seen with cove running on
+    /// other machines.
+    ///
+    /// The directory should be kept in sync between the machines, for example
+    /// by a file syncing service. Each machine regularly writes the messages
+    /// seen on it to its own file in the directory and marks the messages seen
+    /// on the other machines as seen. Only messages already downloaded on a
+    /// machine are affected.
+    ///
+    /// If not set, nothing is shared. Nothing is shared in ephemeral mode
+    /// either.
+    pub sync_dir: Option<PathBuf>,
+
+    /// How cove distinguishes between different UI elements.
+    ///
+    /// `"color"` uses colors, for example for the unseen message markers and
+    /// for highlighting the selected message.
+    ///
+    /// `"high-contrast"` replaces these colors with bold and reversed text, and
+    /// avoids dim colors for indentation and timestamps.
+    ///
+    /// `"mono"` only uses text attributes like reversed or underlined text
+    /// instead of colors. Nicks are shown in bold instead of in their own
+    /// colors. Mentions, room names and emoji in messages keep their colors.
+    ///
+    /// This currently affects the chat view and the rooms list. The colors used
+    /// by `"color"` can be changed via the `theme` options.
+    #[serde(default)]
+    pub theme_mode: ThemeMode,
+
     /// Time zone that chat timestamps should be displayed in.
     ///
     /// This option is interpreted as a POSIX TZ string. It is described here in
@@ -106,30 +503,102 @@ pub struct Config {
     #[document(default = "`$TZ` or local system time zone")]
     pub time_zone: Option<String>,
 
+    /// User agent sent with the HTTP requests cove makes, for example to
+    /// webhooks and translation APIs.
+    ///
+    /// This lets the operators of those services tell cove instances apart.
+    /// The connections to euphoria servers are not affected since the library
+    /// cove uses for them doesn't support setting a user agent.
+    #[document(default = "`cove/<version>`")]
+    pub user_agent: Option<String>,
+
     #[serde(default)]
     #[document(no_default)]
     pub euph: Euph,
 
+    #[serde(default)]
+    #[document(no_default)]
+    pub hooks: Hooks,
+
     #[serde(default)]
     #[document(no_default)]
     pub keys: Keys,
+
+    #[serde(default)]
+    #[document(no_default)]
+    pub notify: Notify,
+
+    /// Named searches shown in the search screen.
+    ///
+    /// Each saved search shows how many of its matches haven't been seen yet.
+    /// Selecting it lists all of its matches.
+    #[serde(default)]
+    #[document(metavar = "name")]
+    pub searches: HashMap<String, SavedSearch>,
+
+    #[serde(default)]
+    #[document(no_default)]
+    pub seen_marker: SeenMarker,
+
+    /// Text snippets for the message editor, for example `";shrug" =
+    /// '¯\_(ツ)_/¯'`.
+    ///
+    /// When the text before the end of the editor is the name of a snippet,
+    /// pressing the snippet key replaces the name with the snippet. Otherwise,
+    /// a popup listing all snippets is shown. Snippets may span multiple
+    /// lines.
+    #[serde(default)]
+    #[document(metavar = "name")]
+    pub snippets: HashMap<String, String>,
+
+    #[serde(default)]
+    #[document(no_default)]
+    pub theme: Theme,
+
+    #[serde(default)]
+    #[document(no_default)]
+    pub translate: Translate,
+
+    /// Webhooks that new messages are sent to.
+    ///
+    /// Only messages received while connected to a room are sent, not
+    /// messages from the room's history. If multiple filters are set, a
+    /// message must match all of them to be sent.
+    #[serde(default)]
+    #[document(metavar = "name")]
+    pub webhooks: HashMap<String, Webhook>,
+}
+
+fn default_scrolloff() -> u16 {
+    2
+}
+
+fn default_clock_skew_warning() -> u64 {
+    60
 }
 
 impl Config {
     pub fn load(path: &Path) -> Result<Self, Error> {
         Ok(match fs::read_to_string(path) {
             Ok(content) => toml::from_str(&content)?,
-            Err(err) if err.kind() == ErrorKind::NotFound => Self::default(),
+            // Parsing an empty config applies the same defaults as a config
+            // file that leaves every option unset.
+            Err(err) if err.kind() == ErrorKind::NotFound => toml::from_str("")?,
             Err(err) => Err(err)?,
         })
     }
 
-    pub fn euph_room(&self, domain: &str, name: &str) -> EuphRoom {
+    /// The options of a room. Rooms not listed in the config file use the
+    /// options of their template instead, if it exists.
+    pub fn euph_room(&self, domain: &str, name: &str, template: Option<&str>) -> EuphRoom {
         if let Some(server) = self.euph.servers.get(domain) {
             if let Some(room) = server.rooms.get(name) {
                 return room.clone();
             }
         }
+        if let Some(room) = template.and_then(|t| self.euph.templates.get(t)) {
+            return room.clone();
+        }
         EuphRoom::default()
     }
 
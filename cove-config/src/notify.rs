@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::doc::Document;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Document)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyOn {
+    Mention,
+    Reply,
+    All,
+}
+
+#[derive(Debug, Deserialize, Document)]
+pub struct Notify {
+    /// Command to run when a new message arrives in a room you aren't looking
+    /// at, for example to show a desktop notification via `notify-send`.
+    ///
+    /// A room counts as looked at while it is open and you aren't away. The
+    /// command is executed like a hook command (see `hooks.on_join`) with the
+    /// `COVE_HOOK` environment variable set to `notify`. In addition to the
+    /// usual environment variables, `COVE_SENDER` contains the nick of the
+    /// message's sender, `COVE_CONTENT` the message's content,
+    /// `COVE_MESSAGE_ID` the message's id and `COVE_REASON` why the command
+    /// is run (`mention`, `reply` or `message`).
+    ///
+    /// If not set, no command is run.
+    pub command: Option<String>,
+
+    /// Which messages `notify.command` is run for.
+    ///
+    /// `"mention"` is for messages mentioning your current nick.
+    ///
+    /// `"reply"` is for replies to your own messages and messages in threads
+    /// you subscribed to. Replies mentioning your nick count as mentions.
+    ///
+    /// `"all"` is for all messages by other people.
+    #[serde(default = "default_on")]
+    pub on: Vec<NotifyOn>,
+}
+
+fn default_on() -> Vec<NotifyOn> {
+    vec![NotifyOn::Mention, NotifyOn::Reply]
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self {
+            command: None,
+            on: default_on(),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use serde::Deserialize;
+
+use crate::doc::{Doc, Document};
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// A time of the week during which a room should be connected, written like
+/// `mon-fri 09:00-18:00`. See the `schedule` room option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ConnectWindow {
+    /// Days the window starts on, starting with Monday.
+    days: [bool; 7],
+    /// Minutes since midnight.
+    start: u16,
+    /// Minutes since midnight.
+    end: u16,
+}
+
+impl ConnectWindow {
+    /// Whether the window contains a time, given as the day of the week
+    /// starting with Monday as 0 and the minutes since midnight.
+    pub fn contains(&self, day: usize, minute: u16) -> bool {
+        let yesterday = (day + 6) % 7;
+        if self.start <= self.end {
+            self.days[day] && self.start <= minute && minute < self.end
+        } else {
+            (self.days[day] && self.start <= minute) || (self.days[yesterday] && minute < self.end)
+        }
+    }
+
+    fn parse_day(day: &str) -> Option<usize> {
+        DAYS.iter().position(|d| d.eq_ignore_ascii_case(day))
+    }
+
+    fn parse_days(text: &str) -> Option<[bool; 7]> {
+        let mut days = [false; 7];
+        for part in text.split(',') {
+            let (first, last) = part.split_once('-').unwrap_or((part, part));
+            let first = Self::parse_day(first.trim())?;
+            let last = Self::parse_day(last.trim())?;
+            // Ranges like sat-mon wrap around the end of the week
+            let mut day = first;
+            loop {
+                days[day] = true;
+                if day == last {
+                    break;
+                }
+                day = (day + 1) % 7;
+            }
+        }
+        Some(days)
+    }
+
+    fn parse_time(text: &str) -> Option<u16> {
+        let (hour, minute) = text.split_once(':')?;
+        let hour = hour.parse::<u16>().ok()?;
+        let minute = minute.parse::<u16>().ok()?;
+        let time = hour.checked_mul(60)?.checked_add(minute)?;
+        (minute < 60 && time <= MINUTES_PER_DAY).then_some(time)
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (days, times) = match text.rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (Self::parse_days(days.trim())?, times),
+            None => ([true; 7], text),
+        };
+        let (start, end) = times.split_once('-')?;
+        let start = Self::parse_time(start)?;
+        let end = Self::parse_time(end)?;
+        (start != end).then_some(Self { days, start, end })
+    }
+}
+
+impl TryFrom<String> for ConnectWindow {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value).ok_or_else(|| {
+            let example = "mon-fri 09:00-18:00";
+            format!("invalid connect window {value:?}, expected something like {example:?}")
+        })
+    }
+}
+
+impl Document for ConnectWindow {
+    fn doc() -> Doc {
+        let mut doc = Doc::default();
+        doc.value_info.required = Some(true);
+        doc.value_info.r#type = Some("connect window".to_string());
+        doc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConnectWindow;
+
+    fn window(text: &str) -> ConnectWindow {
+        ConnectWindow::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parse_days_and_times() {
+        let w = window("mon-fri 09:00-18:00");
+        assert_eq!(w.days, [true, true, true, true, true, false, false]);
+        assert_eq!(w.start, 9 * 60);
+        assert_eq!(w.end, 18 * 60);
+    }
+
+    #[test]
+    fn parse_without_days_means_every_day() {
+        assert_eq!(window("08:30-09:15").days, [true; 7]);
+    }
+
+    #[test]
+    fn parse_day_lists_and_wrapping_ranges() {
+        let w = window("Sat-mon, wed 10:00-11:00");
+        assert_eq!(w.days, [true, false, true, false, false, true, true]);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_windows() {
+        for text in [
+            "",
+            "mon",
+            "mon 09:00",
+            "funday 09:00-10:00",
+            "mon 09:60-10:00",
+            "mon 09:00-24:01",
+            "mon 9-10",
+            "mon 10:00-10:00",
+        ] {
+            assert_eq!(ConnectWindow::parse(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn contains_within_day() {
+        let w = window("mon-fri 09:00-18:00");
+        assert!(w.contains(0, 9 * 60));
+        assert!(w.contains(4, 18 * 60 - 1));
+        assert!(!w.contains(0, 18 * 60));
+        assert!(!w.contains(0, 9 * 60 - 1));
+        assert!(!w.contains(5, 12 * 60));
+    }
+
+    #[test]
+    fn contains_past_midnight() {
+        let w = window("fri 22:00-02:00");
+        assert!(w.contains(4, 23 * 60));
+        assert!(w.contains(5, 60));
+        assert!(!w.contains(5, 23 * 60));
+        assert!(!w.contains(4, 60));
+        assert!(!w.contains(5, 2 * 60));
+    }
+
+    #[test]
+    fn contains_until_end_of_day() {
+        let w = window("sun 23:00-24:00");
+        assert!(w.contains(6, 24 * 60 - 1));
+        assert!(!w.contains(0, 0));
+    }
+}
@@ -0,0 +1,22 @@
+use serde::Deserialize;
+
+use crate::doc::Document;
+
+#[derive(Debug, Clone, Deserialize, Document)]
+pub struct SavedSearch {
+    /// Words that must all appear in a message for it to match, ignoring case.
+    ///
+    /// A word like `from:nick` instead only matches messages sent by someone
+    /// with that nick.
+    pub query: String,
+
+    /// Only match messages from rooms on this domain.
+    pub domain: Option<String>,
+
+    /// Only match messages from rooms with this name (without the leading
+    /// `&`).
+    pub room: Option<String>,
+
+    /// Only match messages sent during the last this many days.
+    pub days: Option<u64>,
+}
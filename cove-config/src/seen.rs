@@ -0,0 +1,58 @@
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+use unicode_width::UnicodeWidthStr;
+
+use crate::doc::Document;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum SeenMarkerColor {
+    #[default]
+    Green,
+    Red,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+/// How unseen messages are marked in the chat.
+#[derive(Debug, Default, Deserialize, Document)]
+pub struct SeenMarker {
+    /// Whether to hide the column of unseen message markers entirely.
+    #[serde(default)]
+    pub hide: bool,
+
+    /// Text that marks unseen messages. It must be exactly one column wide.
+    #[serde(default, deserialize_with = "deserialize_glyph")]
+    #[document(default = "`\"*\"`")]
+    pub glyph: Option<String>,
+
+    /// Background color of the marker.
+    ///
+    /// This option only has an effect if `theme_mode` is `"color"`.
+    #[serde(default)]
+    pub color: SeenMarkerColor,
+}
+
+/// The marker column is one column wide, so wider glyphs would shift the
+/// messages next to them out of line.
+fn deserialize_glyph<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let glyph = String::deserialize(deserializer)?;
+    if glyph.width() != 1 {
+        return Err(D::Error::custom(format!(
+            "glyph {glyph:?} is not exactly one column wide"
+        )));
+    }
+    Ok(Some(glyph))
+}
+
+impl SeenMarker {
+    /// The glyph that marks unseen messages, see [`Self::glyph`].
+    pub fn glyph_ref(&self) -> &str {
+        self.glyph.as_deref().unwrap_or("*")
+    }
+}
@@ -0,0 +1,402 @@
+use crossterm::style::{Color, Stylize};
+use serde::{Deserialize, Serialize};
+use toss::Style;
+
+use crate::doc::{Doc, Document};
+use crate::{SeenMarkerColor, ThemeMode};
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Document)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemePreset {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// Built-in styles of a [`ThemePreset`].
+struct PresetStyles {
+    cursor: Style,
+    time: Style,
+    indent: Style,
+    info: Style,
+    faint: Style,
+    background: Style,
+    room: Style,
+}
+
+impl ThemePreset {
+    fn styles(self) -> PresetStyles {
+        match self {
+            Self::Dark => PresetStyles {
+                cursor: Style::new().black().on_white(),
+                time: Style::new().grey(),
+                indent: Style::new().dark_grey(),
+                info: Style::new().grey(),
+                faint: Style::new().dark_grey(),
+                background: Style::new().on_dark_grey(),
+                room: Style::new().bold().blue(),
+            },
+            Self::Light => PresetStyles {
+                cursor: Style::new().white().on_dark_grey(),
+                time: Style::new().dark_grey(),
+                indent: Style::new().grey(),
+                info: Style::new().dark_grey(),
+                faint: Style::new().grey(),
+                background: Style::new().on_grey(),
+                room: Style::new().bold().dark_blue(),
+            },
+        }
+    }
+}
+
+/// A style written as space-separated words like `"bold blue"`, see
+/// `theme.preset`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ThemeStyle(Style);
+
+impl ThemeStyle {
+    fn parse_color(name: &str) -> Option<Color> {
+        if let Some(hex) = name.strip_prefix('#') {
+            if hex.len() != 6 {
+                return None;
+            }
+            let rgb = u32::from_str_radix(hex, 16).ok()?;
+            let [_, r, g, b] = rgb.to_be_bytes();
+            return Some(Color::Rgb { r, g, b });
+        }
+
+        Some(match name {
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "white" => Color::White,
+            "grey" => Color::Grey,
+            "dark_red" => Color::DarkRed,
+            "dark_green" => Color::DarkGreen,
+            "dark_yellow" => Color::DarkYellow,
+            "dark_blue" => Color::DarkBlue,
+            "dark_magenta" => Color::DarkMagenta,
+            "dark_cyan" => Color::DarkCyan,
+            "dark_grey" => Color::DarkGrey,
+            _ => return None,
+        })
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut style = Style::new();
+        for word in text.split_whitespace() {
+            style = match word {
+                "bold" => style.bold(),
+                "dim" => style.dim(),
+                "italic" => style.italic(),
+                "underlined" => style.underlined(),
+                "reverse" => style.reverse(),
+                "crossed_out" => style.crossed_out(),
+                _ => {
+                    let (on, name) = match word.strip_prefix("on_") {
+                        Some(name) => (true, name),
+                        None => (false, word),
+                    };
+                    let color = Self::parse_color(name)
+                        .ok_or_else(|| format!("unknown word {word:?} in style {text:?}"))?;
+                    if on {
+                        style.on(color)
+                    } else {
+                        style.with(color)
+                    }
+                }
+            };
+        }
+        Ok(Self(style))
+    }
+}
+
+impl TryFrom<String> for ThemeStyle {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl Document for ThemeStyle {
+    fn doc() -> Doc {
+        let mut doc = Doc::default();
+        doc.value_info.required = Some(true);
+        doc.value_info.r#type = Some("style".to_string());
+        doc
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Document)]
+pub struct Theme {
+    /// Which built-in styles the other `theme` options override.
+    ///
+    /// `"dark"` is meant for terminals with a dark background, `"light"` for
+    /// terminals with a light background.
+    ///
+    /// The other options are styles written as space-separated words, for
+    /// example `"bold blue"` or `"black on_white"`. Available attributes are
+    /// `bold`, `dim`, `italic`, `underlined`, `reverse` and `crossed_out`.
+    /// Colors are `black`, `red`, `green`, `yellow`, `blue`, `magenta`,
+    /// `cyan`, `white`, their `dark_` variants like `dark_red`, `grey`,
+    /// `dark_grey` or `#rrggbb`. Background colors are prefixed with `on_`.
+    ///
+    /// In the chat and the rooms list, the theme is only used if `theme_mode`
+    /// is `"color"`.
+    #[serde(default)]
+    pub preset: ThemePreset,
+
+    /// Style of the cursor in the chat and of selected entries in lists.
+    pub cursor: Option<ThemeStyle>,
+
+    /// Style of timestamps in the chat.
+    pub time: Option<ThemeStyle>,
+
+    /// Style of the indentation lines in the chat.
+    pub indent: Option<ThemeStyle>,
+
+    /// Style of secondary information like hints, room domains, empty list
+    /// placeholders and the room status bar.
+    pub info: Option<ThemeStyle>,
+
+    /// Style of less important text in the chat, like placeholders for
+    /// missing messages and messages that are still being sent.
+    pub faint: Option<ThemeStyle>,
+
+    /// Style of highlighted areas like your own messages, the reply preview
+    /// and the breadcrumb above the chat.
+    pub background: Option<ThemeStyle>,
+
+    /// Style of your own nick in the chat and in the message editor.
+    ///
+    /// If not set, your nick is colored like everyone else's.
+    pub own_nick: Option<ThemeStyle>,
+
+    /// Style of other people's nicks in the chat.
+    ///
+    /// If not set, every nick gets its own color derived from the nick, like
+    /// in the web client.
+    pub other_nicks: Option<ThemeStyle>,
+
+    /// Style of room names in the rooms list.
+    pub room: Option<ThemeStyle>,
+
+    /// Style of the borders of popups.
+    pub popup_border: Option<ThemeStyle>,
+}
+
+impl Theme {
+    fn style(&self, style: Option<ThemeStyle>, preset: fn(PresetStyles) -> Style) -> Style {
+        match style {
+            Some(ThemeStyle(style)) => style,
+            None => preset(self.preset.styles()),
+        }
+    }
+
+    pub fn cursor(&self) -> Style {
+        self.style(self.cursor, |p| p.cursor)
+    }
+
+    pub fn time(&self) -> Style {
+        self.style(self.time, |p| p.time)
+    }
+
+    pub fn indent(&self) -> Style {
+        self.style(self.indent, |p| p.indent)
+    }
+
+    pub fn info(&self) -> Style {
+        self.style(self.info, |p| p.info)
+    }
+
+    pub fn faint(&self) -> Style {
+        self.style(self.faint, |p| p.faint)
+    }
+
+    pub fn background(&self) -> Style {
+        self.style(self.background, |p| p.background)
+    }
+
+    pub fn own_nick(&self) -> Option<Style> {
+        self.own_nick.map(|ThemeStyle(style)| style)
+    }
+
+    pub fn other_nicks(&self) -> Option<Style> {
+        self.other_nicks.map(|ThemeStyle(style)| style)
+    }
+
+    pub fn room(&self) -> Style {
+        self.style(self.room, |p| p.room)
+    }
+
+    pub fn popup_border(&self) -> Style {
+        self.popup_border
+            .map_or(Style::new(), |ThemeStyle(style)| style)
+    }
+}
+
+/// Styles of the chat, depending on the [`ThemeMode`].
+///
+/// Only [`ThemeMode::Color`] uses the theme. The other modes fall back to
+/// attributes like bold and reverse.
+impl Theme {
+    pub fn placeholder(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => self.faint(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new().italic(),
+        }
+    }
+
+    pub fn highlight(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => self.cursor(),
+            ThemeMode::HighContrast => Style::new().bold().reverse(),
+            ThemeMode::Mono => Style::new().reverse(),
+        }
+    }
+
+    pub fn seen_marker(&self, mode: ThemeMode, color: SeenMarkerColor) -> Style {
+        match mode {
+            ThemeMode::Color => {
+                let style = Style::new().black();
+                match color {
+                    SeenMarkerColor::Green => style.on_green(),
+                    SeenMarkerColor::Red => style.on_red(),
+                    SeenMarkerColor::Yellow => style.on_yellow(),
+                    SeenMarkerColor::Blue => style.on_blue(),
+                    SeenMarkerColor::Magenta => style.on_magenta(),
+                    SeenMarkerColor::Cyan => style.on_cyan(),
+                    SeenMarkerColor::White => style.on_white(),
+                }
+            }
+            ThemeMode::HighContrast => Style::new().bold().reverse(),
+            ThemeMode::Mono => Style::new().reverse(),
+        }
+    }
+
+    pub fn own_marker(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => Style::new().cyan(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new().bold(),
+        }
+    }
+
+    pub fn reply_marker(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => Style::new().bold().yellow(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new().bold(),
+        }
+    }
+
+    pub fn own_background(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => self.background(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new().underlined(),
+        }
+    }
+
+    pub fn reply_preview(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => self.background(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new(),
+        }
+    }
+
+    pub fn msg_time(&self, mode: ThemeMode, highlighted: bool) -> Style {
+        match (mode, highlighted) {
+            (_, true) => self.highlight(mode),
+            (ThemeMode::Color, false) => self.time(),
+            (ThemeMode::HighContrast | ThemeMode::Mono, false) => Style::new(),
+        }
+    }
+
+    pub fn msg_indent(&self, mode: ThemeMode, highlighted: bool) -> Style {
+        match (mode, highlighted) {
+            (_, true) => self.highlight(mode),
+            (ThemeMode::Color, false) => self.indent(),
+            (ThemeMode::HighContrast | ThemeMode::Mono, false) => Style::new(),
+        }
+    }
+
+    pub fn msg_info(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => self.faint().italic(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new().italic(),
+        }
+    }
+
+    /// Style of decoded or translated message content.
+    pub fn decoded(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => Style::new().green(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new().italic(),
+        }
+    }
+
+    pub fn editor_highlight(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => Style::new().black().on_cyan(),
+            ThemeMode::HighContrast => Style::new().bold().reverse(),
+            ThemeMode::Mono => Style::new().reverse(),
+        }
+    }
+
+    pub fn pseudo_highlight(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => Style::new().black().on_yellow(),
+            ThemeMode::HighContrast => Style::new().bold().underlined().reverse(),
+            ThemeMode::Mono => Style::new().underlined().reverse(),
+        }
+    }
+
+    pub fn sending(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => self.faint(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new(),
+        }
+    }
+
+    pub fn sent(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => Style::new().green(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new(),
+        }
+    }
+
+    pub fn send_failed(&self, mode: ThemeMode) -> Style {
+        match mode {
+            ThemeMode::Color => Style::new().bold().red(),
+            ThemeMode::HighContrast | ThemeMode::Mono => Style::new().bold(),
+        }
+    }
+
+    /// Style replacing the colors of a nick, if any.
+    pub fn plain_nick(&self, mode: ThemeMode) -> Option<Style> {
+        match mode {
+            ThemeMode::Mono => Some(Style::new().bold()),
+            ThemeMode::Color | ThemeMode::HighContrast => None,
+        }
+    }
+
+    /// Like [`Self::plain_nick`], but for the user's own nick.
+    pub fn own_nick_in(&self, mode: ThemeMode) -> Option<Style> {
+        match mode {
+            ThemeMode::Color => self.own_nick(),
+            ThemeMode::HighContrast | ThemeMode::Mono => self.plain_nick(mode),
+        }
+    }
+
+    /// Like [`Self::plain_nick`], but for other people's nicks.
+    pub fn other_nick_in(&self, mode: ThemeMode) -> Option<Style> {
+        match mode {
+            ThemeMode::Color => self.other_nicks(),
+            ThemeMode::HighContrast | ThemeMode::Mono => self.plain_nick(mode),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use serde::Deserialize;
+
+use crate::doc::Document;
+
+#[derive(Debug, Default, Deserialize, Document)]
+pub struct Translate {
+    /// Command to translate messages with.
+    ///
+    /// The command is executed via `sh -c` (or `cmd /C` on Windows). It
+    /// receives the message's content via stdin and must print the translation
+    /// to stdout. The target language is available in the `COVE_LANGUAGE`
+    /// environment variable.
+    ///
+    /// If both this option and `translate.url` are set, this option is used.
+    pub command: Option<String>,
+
+    /// URL of a [LibreTranslate](https://libretranslate.com/) compatible API
+    /// endpoint to translate messages with, e.g.
+    /// `"https://libretranslate.com/translate"`.
+    pub url: Option<String>,
+
+    /// API key to send along with requests to `translate.url`.
+    pub api_key: Option<String>,
+
+    /// Language to translate messages into, as a two-letter language code.
+    #[document(default = "`\"en\"`")]
+    pub language: Option<String>,
+}
+
+impl Translate {
+    pub fn language_ref(&self) -> &str {
+        self.language.as_deref().unwrap_or("en")
+    }
+}
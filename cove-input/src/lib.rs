@@ -100,4 +100,34 @@ impl<'a> InputEvent<'a> {
 
         content
     }
+
+    /// See [`suspend_process`].
+    pub fn suspend_process(&mut self) -> io::Result<()> {
+        suspend_process(self.terminal, &self.crossterm_lock)
+    }
+}
+
+/// Hand the terminal back to the shell and stop the process, just like
+/// pressing ctrl+z would outside of raw mode. Once the process is continued,
+/// the terminal is taken over again.
+#[cfg(unix)]
+pub fn suspend_process(terminal: &mut Terminal, crossterm_lock: &FairMutex<()>) -> io::Result<()> {
+    let guard = crossterm_lock.lock();
+    terminal.suspend()?;
+    // SIGTSTP may be handled by the application to end up here, so raising it
+    // would not stop the process. This stops it the way SIGTSTP usually does.
+    let result = signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP);
+    terminal.unsuspend()?;
+    drop(guard);
+
+    result
+}
+
+/// Suspending processes is not supported on this platform.
+#[cfg(not(unix))]
+pub fn suspend_process(
+    _terminal: &mut Terminal,
+    _crossterm_lock: &FairMutex<()>,
+) -> io::Result<()> {
+    Ok(())
 }
@@ -0,0 +1,154 @@
+//! Finding out why the vault is slow.
+//!
+//! The slowest statements are remembered so their query plans can be
+//! inspected later via [`crate::Vault::diagnose`]. Statistics for the query
+//! planner are also refreshed regularly while many messages are added.
+
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::{Connection, OptionalExtension};
+use vault::Action;
+
+/// Statements taking at least this long are remembered.
+const SLOW_QUERY: Duration = Duration::from_millis(100);
+
+/// How many of the slowest statements are remembered.
+const SLOW_QUERIES_AMOUNT: usize = 10;
+
+/// After how many added messages the statistics are refreshed.
+const ANALYZE_AFTER_MSGS: usize = 50_000;
+
+/// How many rows of each index are looked at when refreshing statistics, so
+/// refreshing is fast even for very large vaults.
+const ANALYSIS_LIMIT: usize = 1000;
+
+static SLOW_QUERIES: Mutex<Vec<(String, Duration)>> = Mutex::new(vec![]);
+static MSGS_SINCE_ANALYZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Called by SQLite after every statement.
+pub(crate) fn profile(sql: &str, duration: Duration) {
+    if duration < SLOW_QUERY {
+        return;
+    }
+
+    let mut queries = SLOW_QUERIES.lock().unwrap();
+    if let Some((_, slowest)) = queries.iter_mut().find(|(s, _)| s == sql) {
+        *slowest = (*slowest).max(duration);
+    } else {
+        queries.push((sql.to_string(), duration));
+    }
+    queries.sort_unstable_by_key(|(_, duration)| Reverse(*duration));
+    queries.truncate(SLOW_QUERIES_AMOUNT);
+}
+
+/// Refresh the statistics once enough messages were added since the last
+/// time.
+pub(crate) fn msgs_added(conn: &Connection, amount: usize) -> rusqlite::Result<()> {
+    let total = MSGS_SINCE_ANALYZE.fetch_add(amount, Ordering::Relaxed) + amount;
+    if total < ANALYZE_AFTER_MSGS {
+        return Ok(());
+    }
+
+    MSGS_SINCE_ANALYZE.store(0, Ordering::Relaxed);
+    conn.execute_batch(&format!(
+        "
+        PRAGMA analysis_limit = {ANALYSIS_LIMIT};
+        ANALYZE;
+        PRAGMA analysis_limit = 0;
+        "
+    ))
+}
+
+#[derive(Debug, Clone)]
+pub struct SlowQuery {
+    pub sql: String,
+    pub duration: Duration,
+    /// One line per step of the query plan.
+    pub plan: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    pub slow_queries: Vec<SlowQuery>,
+    /// Human-readable hints about what could be improved.
+    pub hints: Vec<String>,
+}
+
+pub(crate) struct DiagnoseAction;
+
+impl Action for DiagnoseAction {
+    type Output = Diagnostics;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let mut diagnostics = Diagnostics::default();
+
+        let analyzed = conn
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sqlite_stat1'",
+                [],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !analyzed {
+            let hint = "The vault has no statistics for the query planner, `cove gc` creates them";
+            diagnostics.hints.push(hint.to_string());
+        } else if MSGS_SINCE_ANALYZE.load(Ordering::Relaxed) >= ANALYZE_AFTER_MSGS / 2 {
+            let hint = "The statistics for the query planner may be stale, `cove gc` updates them";
+            diagnostics.hints.push(hint.to_string());
+        }
+
+        let queries = SLOW_QUERIES.lock().unwrap().clone();
+        for (sql, duration) in queries {
+            // Statements that can't be explained, like pragmas, are skipped.
+            let Ok(plan) = explain(conn, &sql) else {
+                continue;
+            };
+
+            for step in &plan {
+                if let Some(table) = full_scan(step) {
+                    let hint =
+                        format!("A slow query scans all of {table}, an index may be missing");
+                    if !diagnostics.hints.contains(&hint) {
+                        diagnostics.hints.push(hint);
+                    }
+                }
+            }
+
+            diagnostics.slow_queries.push(SlowQuery {
+                sql,
+                duration,
+                plan,
+            });
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+fn explain(conn: &Connection, sql: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {sql}"))?;
+
+    // The statement's parameters are left unbound since they don't matter for
+    // the plan.
+    let mut rows = stmt.raw_query();
+    let mut plan = vec![];
+    while let Some(row) = rows.next()? {
+        plan.push(row.get(3)?);
+    }
+    Ok(plan)
+}
+
+/// The table a step of a query plan scans without using any index, if any.
+fn full_scan(step: &str) -> Option<&str> {
+    let table = step.strip_prefix("SCAN ")?;
+    if table.contains(' ') || !table.starts_with("euph_") {
+        // Scans using an index or of virtual tables and subqueries
+        return None;
+    }
+    Some(table)
+}
@@ -0,0 +1,2588 @@
+use std::str::FromStr;
+use std::{fmt, mem};
+
+use cookie::{Cookie, CookieJar};
+use euphoxide::api::{Message, MessageId, SessionId, SessionView, Snowflake, Time, UserId};
+use rusqlite::types::{FromSql, FromSqlError, ToSqlOutput, Value, ValueRef};
+use rusqlite::{named_params, params, Connection, OptionalExtension, Row, ToSql, Transaction};
+use time::OffsetDateTime;
+use tz::TimeZone;
+use vault::Action;
+
+use crate::diagnostics;
+
+/// Wrapper for [`Snowflake`] that implements useful rusqlite traits.
+struct WSnowflake(Snowflake);
+
+impl ToSql for WSnowflake {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        self.0 .0.to_sql()
+    }
+}
+
+impl FromSql for WSnowflake {
+    fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
+        u64::column_result(value).map(|v| Self(Snowflake(v)))
+    }
+}
+
+/// Wrapper for [`Time`] that implements useful rusqlite traits.
+struct WTime(Time);
+
+impl ToSql for WTime {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let timestamp = self.0 .0.unix_timestamp();
+        Ok(ToSqlOutput::Owned(Value::Integer(timestamp)))
+    }
+}
+
+impl FromSql for WTime {
+    fn column_result(value: ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        let timestamp = i64::column_result(value)?;
+        Ok(Self(Time(
+            OffsetDateTime::from_unix_timestamp(timestamp).expect("timestamp in range"),
+        )))
+    }
+}
+
+/// A short summary of a message, containing only the information needed to
+/// display it in a chat view.
+#[derive(Debug, Clone)]
+pub struct SmallMessage {
+    pub id: MessageId,
+    pub parent: Option<MessageId>,
+    pub time: Time,
+    pub time_zone: &'static TimeZone,
+    pub nick: String,
+    pub content: String,
+    pub seen: bool,
+    /// The id of the sender, which stays the same across nick changes.
+    pub user_id: UserId,
+    /// Whether a local note is attached to the message.
+    pub has_note: bool,
+}
+
+/// Traffic received from a room during one hour.
+#[derive(Debug, Clone, Copy)]
+pub struct RoomStats {
+    /// Start of the hour.
+    pub hour: Time,
+    pub events: u64,
+}
+
+/// Messages sent in a room during one day, in UTC.
+#[derive(Debug, Clone, Copy)]
+pub struct RoomActivity {
+    /// Start of the day.
+    pub day: Time,
+    pub msgs: u64,
+}
+
+/// A change to whether a message was seen, recorded so it can be applied on
+/// other machines as well.
+#[derive(Debug, Clone)]
+pub struct SeenChange {
+    pub room: RoomIdentifier,
+    pub id: MessageId,
+    pub seen: bool,
+    /// When the change was made.
+    pub time: Time,
+}
+
+/// What kind of change was made to the messages of a room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsgChangeKind {
+    Added,
+    Seen,
+    Deleted,
+    Note,
+}
+
+/// A change to the messages in the vault, see [`super::Vault::subscribe`].
+#[derive(Debug, Clone)]
+pub struct MsgChange {
+    /// The room whose messages changed, or `None` if the messages of any room
+    /// may have changed.
+    pub room: Option<RoomIdentifier>,
+    pub kind: MsgChangeKind,
+}
+
+/// A message that is sent once its time has come.
+#[derive(Debug, Clone)]
+pub struct ScheduledMsg {
+    pub id: i64,
+    pub parent: Option<MessageId>,
+    pub time: Time,
+    pub content: String,
+}
+
+/// Marks the start of a matching term in [`SearchResult::snippet`].
+pub const SNIPPET_START: char = '\u{2}';
+/// Marks the end of a matching term in [`SearchResult::snippet`].
+pub const SNIPPET_END: char = '\u{3}';
+
+/// A message found via [`EuphVault::search`].
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub room: RoomIdentifier,
+    pub id: MessageId,
+    pub time: Time,
+    pub nick: String,
+    /// The part of the message's content around the match. Matching terms are
+    /// surrounded by [`SNIPPET_START`] and [`SNIPPET_END`].
+    pub snippet: String,
+}
+
+/// Restricts which messages [`EuphVault::search`] may return.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    pub domain: Option<String>,
+    pub room: Option<String>,
+    /// Only messages sent at or after this time.
+    pub since: Option<Time>,
+    /// Only messages sent by someone with this nick. Nicks are compared like
+    /// in mentions, see [`normalize_nick`].
+    pub nick: Option<String>,
+}
+
+/// Whether a character can be part of a mention.
+pub fn nick_char(ch: char) -> bool {
+    // Closely following the heim mention regex:
+    // https://github.com/euphoria-io/heim/blob/978c921063e6b06012fc8d16d9fbf1b3a0be1191/client/lib/stores/chat.js#L14-L15
+    // `>` has been experimentally confirmed to delimit mentions as well.
+    match ch {
+        ',' | '.' | '!' | '?' | ';' | '&' | '<' | '>' | '\'' | '"' => false,
+        _ => !ch.is_whitespace(),
+    }
+}
+
+/// Normalize a nick the way heim does when comparing mentions: Whitespace is
+/// removed and the result is lowercased.
+pub fn normalize_nick(nick: &str) -> String {
+    nick.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// All nicks mentioned in the content, without the leading `@`.
+pub fn mentioned_nicks(content: &str) -> impl Iterator<Item = &str> {
+    content.match_indices('@').filter_map(|(idx, _)| {
+        let possible = content[..idx]
+            .chars()
+            .next_back()
+            .map_or(true, |c| !c.is_alphanumeric());
+        let rest = &content[idx + 1..];
+        let len = rest
+            .char_indices()
+            .find(|(_, c)| !nick_char(*c))
+            .map_or(rest.len(), |(i, _)| i);
+        (possible && len > 0).then(|| &rest[..len])
+    })
+}
+
+/// Whether the content contains a mention of the nick.
+///
+/// Like heim, this ignores whitespace in the nick and compares
+/// case-insensitively.
+pub fn mentions(content: &str, nick: &str) -> bool {
+    let nick = normalize_nick(nick);
+    if nick.is_empty() {
+        return false;
+    }
+
+    mentioned_nicks(content).any(|mention| mention.to_lowercase() == nick)
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RoomIdentifier {
+    pub domain: String,
+    pub name: String,
+}
+
+impl fmt::Debug for RoomIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "&{}@{}", self.name, self.domain)
+    }
+}
+
+impl RoomIdentifier {
+    pub fn new(domain: String, name: String) -> Self {
+        Self { domain, name }
+    }
+}
+
+///////////////
+// EuphVault //
+///////////////
+
+#[derive(Debug, Clone)]
+pub struct EuphVault {
+    vault: super::Vault,
+}
+
+impl EuphVault {
+    pub(crate) fn new(vault: super::Vault) -> Self {
+        Self { vault }
+    }
+
+    pub fn vault(&self) -> &super::Vault {
+        &self.vault
+    }
+
+    pub fn room(&self, room: RoomIdentifier) -> EuphRoomVault {
+        EuphRoomVault {
+            vault: self.clone(),
+            room,
+        }
+    }
+}
+
+/// Whether an action's output indicates that messages were changed. Actions
+/// returning `()` are assumed to always change something.
+trait Changed {
+    fn changed(&self) -> bool;
+}
+
+impl Changed for () {
+    fn changed(&self) -> bool {
+        true
+    }
+}
+
+impl Changed for bool {
+    fn changed(&self) -> bool {
+        *self
+    }
+}
+
+/// Actions followed by `=> kind` notify subscribers of the vault once they
+/// succeed and changed something, see [`Changed`] and
+/// [`super::Vault::subscribe`].
+macro_rules! euph_vault_actions {
+    ( $(
+        $struct:ident : $fn:ident ( $( $arg:ident : $arg_ty:ty ),* ) -> $res:ty $( => $kind:ident )? ;
+    )* ) => {
+        $(
+            struct $struct {
+                $( $arg: $arg_ty, )*
+            }
+        )*
+
+        impl EuphVault {
+            $(
+                pub async fn $fn(&self, $( $arg: $arg_ty, )* ) -> Result<$res, vault::tokio::Error<rusqlite::Error>> {
+                    let result = self.vault.tokio_vault.execute($struct { $( $arg, )* }).await;
+                    $(
+                        if result.as_ref().is_ok_and(Changed::changed) {
+                            self.vault.notify(None, MsgChangeKind::$kind);
+                        }
+                    )?
+                    result
+                }
+            )*
+        }
+    };
+}
+
+euph_vault_actions! {
+    GetCookies : cookies(domain: String) -> CookieJar;
+    SetCookies : set_cookies(domain: String, cookies: CookieJar) -> ();
+    ClearCookies : clear_cookies(domain: Option<String>) -> ();
+    GetRooms : rooms() -> Vec<RoomIdentifier>;
+    GetTotalUnseenMsgsCount : total_unseen_msgs_count() -> usize;
+    SetSeenBetween : set_seen_between(start: Time, end: Time, seen: bool) -> bool => Seen;
+    Search : search(query: String, filter: SearchFilter, limit: usize) -> Vec<SearchResult>;
+    SearchUnseenCount : search_unseen_count(query: String, filter: SearchFilter) -> usize;
+    EnableSeenLog : enable_seen_log() -> ();
+    TakeSeenLog : take_seen_log() -> Vec<SeenChange>;
+    ApplySeenChanges : apply_seen_changes(changes: Vec<SeenChange>) -> bool => Seen;
+    PurgeDeletedContent : purge_deleted_content(before: Time) -> ();
+    GetRoomTemplates : room_templates() -> Vec<(RoomIdentifier, String)>;
+}
+
+impl Action for GetCookies {
+    type Output = CookieJar;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let cookies = conn
+            .prepare(
+                "
+                SELECT cookie
+                FROM euph_cookies
+                WHERE domain = ?
+                ",
+            )?
+            .query_map([self.domain], |row| {
+                let cookie_str: String = row.get(0)?;
+                Ok(Cookie::from_str(&cookie_str).expect("cookie in db is valid"))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut cookie_jar = CookieJar::new();
+        for cookie in cookies {
+            cookie_jar.add_original(cookie);
+        }
+        Ok(cookie_jar)
+    }
+}
+
+impl Action for SetCookies {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let tx = conn.transaction()?;
+
+        // Since euphoria sets all cookies on every response, we can just delete
+        // all previous cookies.
+        tx.execute(
+            "
+            DELETE FROM euph_cookies
+            WHERE domain = ?",
+            [&self.domain],
+        )?;
+
+        let mut insert_cookie = tx.prepare(
+            "
+            INSERT INTO euph_cookies (domain, cookie)
+            VALUES (?, ?)
+            ",
+        )?;
+        for cookie in self.cookies.iter() {
+            insert_cookie.execute(params![self.domain, format!("{cookie}")])?;
+        }
+        drop(insert_cookie);
+
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl Action for ClearCookies {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        if let Some(domain) = self.domain {
+            conn.execute("DELETE FROM euph_cookies WHERE domain = ?", [domain])?;
+        } else {
+            conn.execute_batch("DELETE FROM euph_cookies")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Action for GetRooms {
+    type Output = Vec<RoomIdentifier>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.prepare(
+            "
+                SELECT domain, room
+                FROM euph_rooms
+                ",
+        )?
+        .query_map([], |row| {
+            Ok(RoomIdentifier {
+                domain: row.get(0)?,
+                name: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()
+    }
+}
+
+impl Action for GetTotalUnseenMsgsCount {
+    type Output = usize;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.prepare(
+            "
+                SELECT COALESCE(SUM(amount), 0)
+                FROM euph_unseen_counts
+                ",
+        )?
+        .query_row([], |row| row.get(0))
+    }
+}
+
+impl Action for SetSeenBetween {
+    type Output = bool;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let changed = conn.execute(
+            "
+            UPDATE euph_msgs
+            SET seen = :seen
+            WHERE time >= :start
+            AND time <= :end
+            AND seen != :seen
+            ",
+            named_params! {
+                ":start": WTime(self.start),
+                ":end": WTime(self.end),
+                ":seen": self.seen,
+            },
+        )?;
+        Ok(changed > 0)
+    }
+}
+
+// Records every change to whether a message was seen until the vault is
+// closed. The changes can then be retrieved via `TakeSeenLog`.
+impl Action for EnableSeenLog {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.execute_batch(
+            "
+            CREATE TEMPORARY TABLE IF NOT EXISTS euph_seen_log (
+                domain TEXT NOT NULL,
+                room   TEXT NOT NULL,
+                id     INT  NOT NULL,
+                seen   INT  NOT NULL,
+                time   INT  NOT NULL
+            ) STRICT;
+
+            CREATE TEMPORARY TRIGGER IF NOT EXISTS esl_update_msg
+            AFTER UPDATE OF seen ON main.euph_msgs
+            WHEN old.seen != new.seen
+            BEGIN
+                INSERT INTO euph_seen_log (domain, room, id, seen, time)
+                VALUES (new.domain, new.room, new.id, new.seen, unixepoch());
+            END;
+            ",
+        )
+    }
+}
+
+impl Action for TakeSeenLog {
+    type Output = Vec<SeenChange>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let tx = conn.transaction()?;
+        let changes = tx
+            .prepare(
+                "
+                SELECT domain, room, id, seen, time
+                FROM euph_seen_log
+                ORDER BY rowid ASC
+                ",
+            )?
+            .query_map([], |row| {
+                Ok(SeenChange {
+                    room: RoomIdentifier::new(row.get(0)?, row.get(1)?),
+                    id: MessageId(row.get::<_, WSnowflake>(2)?.0),
+                    seen: row.get(3)?,
+                    time: row.get::<_, WTime>(4)?.0,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        tx.execute("DELETE FROM euph_seen_log", [])?;
+        tx.commit()?;
+        Ok(changes)
+    }
+}
+
+// Applies changes recorded on another machine without recording them again.
+// Changes to messages that aren't in the vault yet are kept until the messages
+// are inserted.
+impl Action for ApplySeenChanges {
+    type Output = bool;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let tx = conn.transaction()?;
+
+        let last_logged: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(rowid), 0) FROM euph_seen_log",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let mut update = tx.prepare(
+            "
+            UPDATE euph_msgs
+            SET seen = :seen
+            WHERE domain = :domain
+            AND room = :room
+            AND id = :id
+            AND seen != :seen
+            ",
+        )?;
+        let mut pending = tx.prepare(
+            "
+            INSERT INTO euph_seen_pending (domain, room, id, seen, time)
+            SELECT :domain, :room, :id, :seen, :time
+            WHERE NOT EXISTS(
+                SELECT 1
+                FROM euph_msgs
+                WHERE domain = :domain
+                AND room = :room
+                AND id = :id
+            )
+            ON CONFLICT (domain, room, id) DO UPDATE
+            SET seen = excluded.seen, time = excluded.time
+            WHERE excluded.time >= time
+            ",
+        )?;
+        let mut changed = 0;
+        for change in self.changes {
+            changed += update.execute(named_params! {
+                ":domain": change.room.domain,
+                ":room": change.room.name,
+                ":id": WSnowflake(change.id.0),
+                ":seen": change.seen,
+            })?;
+            pending.execute(named_params! {
+                ":domain": change.room.domain,
+                ":room": change.room.name,
+                ":id": WSnowflake(change.id.0),
+                ":seen": change.seen,
+                ":time": WTime(change.time),
+            })?;
+        }
+        drop(update);
+        drop(pending);
+
+        tx.execute("DELETE FROM euph_seen_log WHERE rowid > ?", [last_logged])?;
+        tx.commit()?;
+        Ok(changed > 0)
+    }
+}
+
+impl Action for PurgeDeletedContent {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.execute(
+            "
+            UPDATE euph_msgs
+            SET deleted_content = NULL
+            WHERE deleted_content IS NOT NULL
+            AND deleted < ?
+            ",
+            [WTime(self.before)],
+        )?;
+        Ok(())
+    }
+}
+
+impl Action for GetRoomTemplates {
+    type Output = Vec<(RoomIdentifier, String)>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.prepare(
+            "
+            SELECT domain, room, template
+            FROM euph_room_templates
+            ",
+        )?
+        .query_map([], |row| {
+            let room = RoomIdentifier {
+                domain: row.get(0)?,
+                name: row.get(1)?,
+            };
+            Ok((room, row.get(2)?))
+        })?
+        .collect::<rusqlite::Result<_>>()
+    }
+}
+
+/// Turn user input into a full text search query matching all messages that
+/// contain every word, without interpreting any special query syntax.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn search_result(row: &Row<'_>) -> rusqlite::Result<SearchResult> {
+    Ok(SearchResult {
+        room: RoomIdentifier::new(row.get(0)?, row.get(1)?),
+        id: MessageId(row.get::<_, WSnowflake>(2)?.0),
+        time: row.get::<_, WTime>(3)?.0,
+        nick: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+        snippet: row.get(5)?,
+    })
+}
+
+// Without any words to search for, all messages matching the filter are
+// returned, as long as it filters by nick. Scanning all messages of all rooms
+// would be too slow otherwise.
+impl Action for Search {
+    type Output = Vec<SearchResult>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let query = fts_query(&self.query);
+        let nick = self.filter.nick.as_deref().map(normalize_nick);
+        if query.is_empty() && nick.is_none() {
+            return Ok(vec![]);
+        }
+
+        if query.is_empty() {
+            return conn
+                .prepare(
+                    "
+                    SELECT m.domain, m.room, m.id, m.time, m.name, substr(m.content, 1, 100)
+                    FROM euph_msgs AS m
+                    WHERE normalize_nick(m.name) = :nick
+                    AND (:domain IS NULL OR m.domain = :domain)
+                    AND (:room IS NULL OR m.room = :room)
+                    AND (:since IS NULL OR m.time >= :since)
+                    ORDER BY m.time DESC
+                    LIMIT :limit
+                    ",
+                )?
+                .query_map(
+                    named_params! {
+                        ":domain": self.filter.domain,
+                        ":room": self.filter.room,
+                        ":since": self.filter.since.map(WTime),
+                        ":nick": nick,
+                        ":limit": self.limit,
+                    },
+                    search_result,
+                )?
+                .collect();
+        }
+
+        let results = conn
+            .prepare(
+                "
+                SELECT
+                    m.domain, m.room, m.id, m.time, m.name,
+                    snippet(euph_msgs_fts, 0, :start, :end, '…', 16)
+                FROM euph_msgs_fts
+                JOIN euph_msgs AS m ON m.rowid = euph_msgs_fts.rowid
+                WHERE euph_msgs_fts MATCH :query
+                AND (:domain IS NULL OR m.domain = :domain)
+                AND (:room IS NULL OR m.room = :room)
+                AND (:since IS NULL OR m.time >= :since)
+                AND (:nick IS NULL OR normalize_nick(m.name) = :nick)
+                ORDER BY m.time DESC
+                LIMIT :limit
+                ",
+            )?
+            .query_map(
+                named_params! {
+                    ":start": SNIPPET_START.to_string(),
+                    ":end": SNIPPET_END.to_string(),
+                    ":query": query,
+                    ":domain": self.filter.domain,
+                    ":room": self.filter.room,
+                    ":since": self.filter.since.map(WTime),
+                    ":nick": nick,
+                    ":limit": self.limit,
+                },
+                search_result,
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(results)
+    }
+}
+
+// Counts the same messages as `Search`, but only unseen ones.
+impl Action for SearchUnseenCount {
+    type Output = usize;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let query = fts_query(&self.query);
+        let nick = self.filter.nick.as_deref().map(normalize_nick);
+        if query.is_empty() && nick.is_none() {
+            return Ok(0);
+        }
+
+        if query.is_empty() {
+            return conn.query_row(
+                "
+                SELECT COUNT(*)
+                FROM euph_msgs AS m
+                WHERE normalize_nick(m.name) = :nick
+                AND NOT m.seen
+                AND (:domain IS NULL OR m.domain = :domain)
+                AND (:room IS NULL OR m.room = :room)
+                AND (:since IS NULL OR m.time >= :since)
+                ",
+                named_params! {
+                    ":domain": self.filter.domain,
+                    ":room": self.filter.room,
+                    ":since": self.filter.since.map(WTime),
+                    ":nick": nick,
+                },
+                |row| row.get(0),
+            );
+        }
+
+        conn.query_row(
+            "
+            SELECT COUNT(*)
+            FROM euph_msgs_fts
+            JOIN euph_msgs AS m ON m.rowid = euph_msgs_fts.rowid
+            WHERE euph_msgs_fts MATCH :query
+            AND NOT m.seen
+            AND (:domain IS NULL OR m.domain = :domain)
+            AND (:room IS NULL OR m.room = :room)
+            AND (:since IS NULL OR m.time >= :since)
+            AND (:nick IS NULL OR normalize_nick(m.name) = :nick)
+            ",
+            named_params! {
+                ":query": query,
+                ":domain": self.filter.domain,
+                ":room": self.filter.room,
+                ":since": self.filter.since.map(WTime),
+                ":nick": nick,
+            },
+            |row| row.get(0),
+        )
+    }
+}
+
+///////////////////
+// EuphRoomVault //
+///////////////////
+
+#[derive(Debug, Clone)]
+pub struct EuphRoomVault {
+    vault: EuphVault,
+    room: RoomIdentifier,
+}
+
+impl EuphRoomVault {
+    pub fn vault(&self) -> &EuphVault {
+        &self.vault
+    }
+
+    pub fn room(&self) -> &RoomIdentifier {
+        &self.room
+    }
+}
+
+/// Like [`euph_vault_actions`], but notifying subscribers only about the room.
+macro_rules! euph_room_vault_actions {
+    ( $(
+        $struct:ident : $fn:ident ( $( $arg:ident : $arg_ty:ty ),* ) -> $res:ty $( => $kind:ident )? ;
+    )* ) => {
+        $(
+            struct $struct {
+                room: RoomIdentifier,
+                #[allow(unused)]
+                time_zone: &'static TimeZone,
+                $( $arg: $arg_ty, )*
+            }
+        )*
+
+        impl EuphRoomVault {
+            $(
+                pub async fn $fn(&self, $( $arg: $arg_ty, )* ) -> Result<$res, vault::tokio::Error<rusqlite::Error>> {
+                    let result = self.vault.vault.tokio_vault.execute($struct {
+                        room: self.room.clone(),
+                        time_zone: self.vault.vault.time_zone,
+                        $( $arg, )*
+                    }).await;
+                    $(
+                        if result.as_ref().is_ok_and(Changed::changed) {
+                            self.vault.vault.notify(Some(self.room.clone()), MsgChangeKind::$kind);
+                        }
+                    )?
+                    result
+                }
+            )*
+        }
+    };
+}
+
+euph_room_vault_actions! {
+    // Room
+    Join : join(time: Time) -> ();
+    Delete : delete() -> () => Deleted;
+    SetTemplate : set_template(template: Option<String>) -> ();
+    AddStats : add_stats(time: Time, events: u64) -> ();
+    GetStats : stats(since: Time) -> Vec<RoomStats>;
+    GetActivity : activity(since: Time) -> Vec<RoomActivity>;
+    GetSubscribed : subscribed(id: MessageId) -> bool;
+    SetSubscribed : set_subscribed(id: MessageId, subscribed: bool) -> ();
+    GetInSubscribedThread : in_subscribed_thread(parent: MessageId) -> bool;
+
+    // Message
+    AddMsg : add_msg(msg: Box<Message>, prev_msg_id: Option<MessageId>, own_user_id: Option<UserId>) -> () => Added;
+    AddMsgs : add_msgs(msgs: Vec<Message>, next_msg_id: Option<MessageId>, own_user_id: Option<UserId>) -> () => Added;
+    GetLastSpan : last_span() -> Option<(Option<MessageId>, Option<MessageId>)>;
+    GetPath : path(id: MessageId) -> Vec<MessageId>;
+    GetMsg : msg(id: MessageId) -> Option<SmallMessage>;
+    GetFullMsg : full_msg(id: MessageId) -> Option<Message>;
+    GetDeletedContent : deleted_content(id: MessageId) -> Option<String>;
+    GetTree : tree(root_id: MessageId) -> Vec<SmallMessage>;
+    GetUnseenThreads : unseen_threads() -> Vec<(MessageId, SmallMessage)>;
+    GetPinned : pinned(nick: Option<String>, prefix: Option<String>, amount: usize) -> Vec<SmallMessage>;
+    GetFirstRootId : first_root_id() -> Option<MessageId>;
+    GetLastRootId : last_root_id() -> Option<MessageId>;
+    GetPrevRootId : prev_root_id(root_id: MessageId) -> Option<MessageId>;
+    GetNextRootId : next_root_id(root_id: MessageId) -> Option<MessageId>;
+    GetOldestMsgId : oldest_msg_id() -> Option<MessageId>;
+    GetNewestMsgId : newest_msg_id() -> Option<MessageId>;
+    GetOlderMsgId : older_msg_id(id: MessageId) -> Option<MessageId>;
+    GetNewerMsgId : newer_msg_id(id: MessageId) -> Option<MessageId>;
+    GetOldestUnseenMsgId : oldest_unseen_msg_id() -> Option<MessageId>;
+    GetOldestUnseenMsgIdSince : oldest_unseen_msg_id_since(time: Time) -> Option<MessageId>;
+    GetNewestUnseenMsgId : newest_unseen_msg_id() -> Option<MessageId>;
+    GetOlderUnseenMsgId : older_unseen_msg_id(id: MessageId) -> Option<MessageId>;
+    GetNewerUnseenMsgId : newer_unseen_msg_id(id: MessageId) -> Option<MessageId>;
+    GetUnseenMsgsCount : unseen_msgs_count() -> usize;
+    GetUnseenMentionsCount : unseen_mentions_count(nick: String, user_id: UserId) -> usize;
+    SetSeen : set_seen(id: MessageId, seen: bool) -> bool => Seen;
+    SetManySeen : set_many_seen(ids: Vec<MessageId>, seen: bool) -> bool => Seen;
+    SetOlderSeen : set_older_seen(id: MessageId, seen: bool) -> bool => Seen;
+    SetTreeSeen : set_tree_seen(root_id: MessageId, seen: bool) -> bool => Seen;
+    GetNote : note(id: MessageId) -> Option<String>;
+    SetNote : set_note(id: MessageId, note: Option<String>) -> () => Note;
+    GetDraft : draft() -> Option<String>;
+    SetDraft : set_draft(content: Option<String>) -> ();
+    GetIgnored : ignored() -> Vec<String>;
+    SetIgnored : set_ignored(who: String, ignored: bool) -> ();
+    AddScheduled : add_scheduled(parent: Option<MessageId>, time: Time, content: String) -> ();
+    GetScheduled : scheduled() -> Vec<ScheduledMsg>;
+    DeleteScheduled : delete_scheduled(id: i64) -> ();
+    GetChunkAfter : chunk_after(id: Option<MessageId>, since: Option<Time>, until: Option<Time>, amount: usize) -> Vec<Message>;
+}
+
+impl Action for Join {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.execute(
+            "
+            INSERT INTO euph_rooms (domain, room, first_joined, last_joined)
+            VALUES (:domain, :room, :time, :time)
+            ON CONFLICT (domain, room) DO UPDATE
+            SET last_joined = :time
+            ",
+            named_params! {
+                ":domain": self.room.domain,
+                ":room": self.room.name,
+                ":time": WTime(self.time),
+            },
+        )?;
+        Ok(())
+    }
+}
+
+impl Action for Delete {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "
+            INSERT INTO euph_msgs_fts (euph_msgs_fts, rowid, content)
+            SELECT 'delete', rowid, content
+            FROM euph_msgs
+            WHERE domain = ?
+            AND room = ?
+            ",
+            [&self.room.domain, &self.room.name],
+        )?;
+
+        tx.execute(
+            "
+            DELETE FROM euph_rooms
+            WHERE domain = ?
+            AND room = ?
+            ",
+            [&self.room.domain, &self.room.name],
+        )?;
+
+        tx.execute(
+            "
+            DELETE FROM euph_room_templates
+            WHERE domain = ?
+            AND room = ?
+            ",
+            [&self.room.domain, &self.room.name],
+        )?;
+
+        tx.execute(
+            "
+            DELETE FROM euph_ignored
+            WHERE domain = ?
+            AND room = ?
+            ",
+            [&self.room.domain, &self.room.name],
+        )?;
+
+        tx.execute(
+            "
+            DELETE FROM euph_seen_pending
+            WHERE domain = ?
+            AND room = ?
+            ",
+            [&self.room.domain, &self.room.name],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl Action for SetTemplate {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        match self.template {
+            Some(template) => conn.execute(
+                "
+                INSERT INTO euph_room_templates (domain, room, template)
+                VALUES (?, ?, ?)
+                ON CONFLICT (domain, room) DO UPDATE
+                SET template = excluded.template
+                ",
+                params![self.room.domain, self.room.name, template],
+            )?,
+            None => conn.execute(
+                "
+                DELETE FROM euph_room_templates
+                WHERE domain = ?
+                AND room = ?
+                ",
+                params![self.room.domain, self.room.name],
+            )?,
+        };
+        Ok(())
+    }
+}
+
+impl Action for AddStats {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let timestamp = self.time.0.unix_timestamp();
+        let hour = timestamp - timestamp.rem_euclid(60 * 60);
+
+        // Stats for rooms that were never joined are dropped.
+        conn.execute(
+            "
+            INSERT INTO euph_stats (domain, room, hour, events)
+            SELECT domain, room, :hour, :events
+            FROM euph_rooms
+            WHERE domain = :domain
+            AND room = :room
+            ON CONFLICT (domain, room, hour) DO UPDATE
+            SET events = events + excluded.events
+            ",
+            named_params! {
+                ":domain": self.room.domain,
+                ":room": self.room.name,
+                ":hour": hour,
+                ":events": self.events,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+impl Action for GetStats {
+    type Output = Vec<RoomStats>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let stats = conn
+            .prepare(
+                "
+                SELECT hour, events
+                FROM euph_stats
+                WHERE domain = ?
+                AND room = ?
+                AND hour >= ?
+                ORDER BY hour ASC
+                ",
+            )?
+            .query_map(
+                params![self.room.domain, self.room.name, WTime(self.since)],
+                |row| {
+                    Ok(RoomStats {
+                        hour: row.get::<_, WTime>(0)?.0,
+                        events: row.get(1)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(stats)
+    }
+}
+
+impl Action for GetActivity {
+    type Output = Vec<RoomActivity>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let activity = conn
+            .prepare(
+                "
+                SELECT time - time % 86400 AS day, COUNT(*)
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND time >= ?
+                GROUP BY day
+                ORDER BY day ASC
+                ",
+            )?
+            .query_map(
+                params![self.room.domain, self.room.name, WTime(self.since)],
+                |row| {
+                    Ok(RoomActivity {
+                        day: row.get::<_, WTime>(0)?.0,
+                        msgs: row.get(1)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(activity)
+    }
+}
+
+impl Action for GetSubscribed {
+    type Output = bool;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.query_row(
+            "
+            SELECT EXISTS (
+                SELECT *
+                FROM euph_subscriptions
+                WHERE domain = ?
+                AND room = ?
+                AND id = ?
+            )
+            ",
+            params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+            |row| row.get(0),
+        )
+    }
+}
+
+impl Action for SetSubscribed {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let sql = if self.subscribed {
+            "
+            INSERT OR IGNORE INTO euph_subscriptions (domain, room, id)
+            VALUES (?, ?, ?)
+            "
+        } else {
+            "
+            DELETE FROM euph_subscriptions
+            WHERE domain = ?
+            AND room = ?
+            AND id = ?
+            "
+        };
+        conn.execute(
+            sql,
+            params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+        )?;
+        Ok(())
+    }
+}
+
+impl Action for GetInSubscribedThread {
+    type Output = bool;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        // The message itself may not be in the vault yet, so its parent is
+        // passed in instead.
+        conn.query_row(
+            "
+            WITH RECURSIVE
+            path (domain, room, id) AS (
+                VALUES (:domain, :room, :id)
+            UNION
+                SELECT domain, room, parent
+                FROM euph_msgs
+                JOIN path USING (domain, room, id)
+            )
+            SELECT EXISTS (
+                SELECT *
+                FROM euph_subscriptions
+                JOIN path USING (domain, room, id)
+            )
+            ",
+            named_params! {
+                ":domain": self.room.domain,
+                ":room": self.room.name,
+                ":id": WSnowflake(self.parent.0),
+            },
+            |row| row.get(0),
+        )
+    }
+}
+
+fn insert_msgs(
+    tx: &Transaction<'_>,
+    room: &RoomIdentifier,
+    own_user_id: &Option<UserId>,
+    msgs: Vec<Message>,
+) -> rusqlite::Result<()> {
+    let mut insert_msg = tx.prepare(
+        "
+        INSERT INTO euph_msgs (
+            domain, room,
+            id, parent, previous_edit_id, time, content, encryption_key_id, edited, deleted, truncated,
+            user_id, name, server_id, server_era, session_id, is_staff, is_manager, client_address, real_client_address,
+            seen
+        )
+        VALUES (
+            :domain, :room,
+            :id, :parent, :previous_edit_id, :time, :content, :encryption_key_id, :edited, :deleted, :truncated,
+            :user_id, :name, :server_id, :server_era, :session_id, :is_staff, :is_manager, :client_address, :real_client_address,
+            COALESCE(
+                (
+                    SELECT seen
+                    FROM euph_seen_pending
+                    WHERE domain = :domain
+                    AND room = :room
+                    AND id = :id
+                ),
+                :user_id == :own_user_id OR EXISTS(
+                    SELECT 1
+                    FROM euph_rooms
+                    WHERE domain = :domain
+                    AND room = :room
+                    AND :time < first_joined
+                )
+            )
+        )
+        ON CONFLICT (domain, room, id) DO UPDATE
+        SET
+            domain = :domain,
+            room = :room,
+            id = :id,
+            parent = :parent,
+            previous_edit_id = :previous_edit_id,
+            time = :time,
+            content = :content,
+            encryption_key_id = :encryption_key_id,
+            edited = :edited,
+            deleted = :deleted,
+            truncated = :truncated,
+            -- Keep what the server removed when deleting the message until
+            -- it is purged via PurgeDeletedContent.
+            deleted_content = CASE
+                WHEN deleted IS NULL AND :deleted IS NOT NULL AND content != :content
+                THEN content
+                ELSE deleted_content
+            END,
+
+            user_id = :user_id,
+            name = :name,
+            server_id = :server_id,
+            server_era = :server_era,
+            session_id = :session_id,
+            is_staff = :is_staff,
+            is_manager = :is_manager,
+            client_address = :client_address,
+            real_client_address = :real_client_address
+        RETURNING rowid
+        "
+    )?;
+
+    let mut old_content = tx.prepare(
+        "
+        SELECT rowid, content
+        FROM euph_msgs
+        WHERE domain = ?
+        AND room = ?
+        AND id = ?
+        ",
+    )?;
+
+    let mut fts_delete = tx.prepare(
+        "
+        INSERT INTO euph_msgs_fts (euph_msgs_fts, rowid, content)
+        VALUES ('delete', ?, ?)
+        ",
+    )?;
+
+    let mut fts_insert = tx.prepare(
+        "
+        INSERT INTO euph_msgs_fts (rowid, content)
+        VALUES (?, ?)
+        ",
+    )?;
+
+    // Pending seen changes only ever concern messages that weren't in the
+    // vault, so they were already used when inserting the message above.
+    let mut delete_pending = tx.prepare(
+        "
+        DELETE FROM euph_seen_pending
+        WHERE domain = ?
+        AND room = ?
+        AND id = ?
+        ",
+    )?;
+
+    let own_user_id = own_user_id.as_ref().map(|u| &u.0);
+    for msg in msgs {
+        let old = old_content
+            .query_row(
+                params![room.domain, room.name, WSnowflake(msg.id.0)],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+
+        let content_changed = match &old {
+            Some((rowid, content)) if *content != msg.content => {
+                fts_delete.execute(params![rowid, content])?;
+                true
+            }
+            Some(_) => false,
+            None => true,
+        };
+
+        let rowid = insert_msg.query_row(
+            named_params! {
+                ":domain": room.domain,
+                ":room": room.name,
+                ":id": WSnowflake(msg.id.0),
+                ":parent": msg.parent.map(|id| WSnowflake(id.0)),
+                ":previous_edit_id": msg.previous_edit_id.map(WSnowflake),
+                ":time": WTime(msg.time),
+                ":content": msg.content,
+                ":encryption_key_id": msg.encryption_key_id,
+                ":edited": msg.edited.map(WTime),
+                ":deleted": msg.deleted.map(WTime),
+                ":truncated": msg.truncated,
+                ":user_id": msg.sender.id.0,
+                ":name": msg.sender.name,
+                ":server_id": msg.sender.server_id,
+                ":server_era": msg.sender.server_era,
+                ":session_id": msg.sender.session_id.0,
+                ":is_staff": msg.sender.is_staff,
+                ":is_manager": msg.sender.is_manager,
+                ":client_address": msg.sender.client_address,
+                ":real_client_address": msg.sender.real_client_address,
+                ":own_user_id": own_user_id, // May be NULL
+            },
+            |row| row.get::<_, i64>(0),
+        )?;
+
+        if content_changed {
+            fts_insert.execute(params![rowid, msg.content])?;
+        }
+
+        if old.is_none() {
+            delete_pending.execute(params![room.domain, room.name, WSnowflake(msg.id.0)])?;
+        }
+    }
+
+    Ok(())
+}
+
+fn add_span(
+    tx: &Transaction<'_>,
+    room: &RoomIdentifier,
+    start: Option<MessageId>,
+    end: Option<MessageId>,
+) -> rusqlite::Result<()> {
+    // Retrieve all spans for the room
+    let mut spans = tx
+        .prepare(
+            "
+            SELECT start, end
+            FROM euph_spans
+            WHERE domain = ?
+            AND room = ?
+            ",
+        )?
+        .query_map([&room.domain, &room.name], |row| {
+            let start = row.get::<_, Option<WSnowflake>>(0)?.map(|s| MessageId(s.0));
+            let end = row.get::<_, Option<WSnowflake>>(1)?.map(|s| MessageId(s.0));
+            Ok((start, end))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Add new span and sort spans lexicographically
+    spans.push((start, end));
+    spans.sort_unstable();
+
+    // Combine overlapping spans (including newly added span)
+    let mut cur_span: Option<(Option<MessageId>, Option<MessageId>)> = None;
+    let mut result = vec![];
+    for mut span in spans {
+        if let Some(cur_span) = &mut cur_span {
+            if span.0 <= cur_span.1 {
+                // Since spans are sorted lexicographically, we know that
+                // cur_span.0 <= span.0, which means that span starts inside
+                // of cur_span.
+                cur_span.1 = cur_span.1.max(span.1);
+            } else {
+                // Since span doesn't overlap cur_span, we know that no
+                // later span will overlap cur_span either. The size of
+                // cur_span is thus final.
+                mem::swap(cur_span, &mut span);
+                result.push(span);
+            }
+        } else {
+            cur_span = Some(span);
+        }
+    }
+    if let Some(cur_span) = cur_span {
+        result.push(cur_span);
+    }
+
+    // Delete all spans for the room
+    tx.execute(
+        "
+        DELETE FROM euph_spans
+        WHERE domain = ?
+        AND room = ?
+        ",
+        [&room.domain, &room.name],
+    )?;
+
+    // Re-insert combined spans for the room
+    let mut stmt = tx.prepare(
+        "
+        INSERT INTO euph_spans (domain, room, start, end)
+        VALUES (?, ?, ?, ?)
+        ",
+    )?;
+    for (start, end) in result {
+        stmt.execute(params![
+            room.domain,
+            room.name,
+            start.map(|id| WSnowflake(id.0)),
+            end.map(|id| WSnowflake(id.0))
+        ])?;
+    }
+
+    Ok(())
+}
+
+impl Action for AddMsg {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let tx = conn.transaction()?;
+
+        let end = self.msg.id;
+        insert_msgs(&tx, &self.room, &self.own_user_id, vec![*self.msg])?;
+        add_span(&tx, &self.room, self.prev_msg_id, Some(end))?;
+
+        tx.commit()?;
+        diagnostics::msgs_added(conn, 1)?;
+        Ok(())
+    }
+}
+
+impl Action for AddMsgs {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let tx = conn.transaction()?;
+        let amount = self.msgs.len();
+
+        if self.msgs.is_empty() {
+            add_span(&tx, &self.room, None, self.next_msg_id)?;
+        } else {
+            let first_msg_id = self.msgs.first().unwrap().id;
+            let last_msg_id = self.msgs.last().unwrap().id;
+
+            insert_msgs(&tx, &self.room, &self.own_user_id, self.msgs)?;
+
+            let end = self.next_msg_id.unwrap_or(last_msg_id);
+            add_span(&tx, &self.room, Some(first_msg_id), Some(end))?;
+        }
+
+        tx.commit()?;
+        diagnostics::msgs_added(conn, amount)?;
+        Ok(())
+    }
+}
+
+impl Action for GetLastSpan {
+    type Output = Option<(Option<MessageId>, Option<MessageId>)>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let span = conn
+            .prepare(
+                "
+                SELECT start, end
+                FROM euph_spans
+                WHERE domain = ?
+                AND room = ?
+                ORDER BY start DESC
+                LIMIT 1
+                ",
+            )?
+            .query_row([&self.room.domain, &self.room.name], |row| {
+                Ok((
+                    row.get::<_, Option<WSnowflake>>(0)?.map(|s| MessageId(s.0)),
+                    row.get::<_, Option<WSnowflake>>(1)?.map(|s| MessageId(s.0)),
+                ))
+            })
+            .optional()?;
+        Ok(span)
+    }
+}
+
+impl Action for GetPath {
+    type Output = Vec<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let path = conn
+            .prepare(
+                "
+                WITH RECURSIVE
+                path (domain, room, id) AS (
+                    VALUES (?, ?, ?)
+                UNION
+                    SELECT domain, room, parent
+                    FROM euph_msgs
+                    JOIN path USING (domain, room, id)
+                )
+                SELECT id
+                FROM path
+                WHERE id IS NOT NULL
+                ORDER BY id ASC
+                ",
+            )?
+            .query_map(
+                params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+                |row| row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0)),
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(path)
+    }
+}
+
+impl Action for GetMsg {
+    type Output = Option<SmallMessage>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg = conn
+            .query_row(
+                "
+                SELECT id, parent, time, name, content, seen, user_id, EXISTS (
+                    SELECT *
+                    FROM euph_notes
+                    WHERE euph_notes.domain = euph_msgs.domain
+                    AND euph_notes.room = euph_msgs.room
+                    AND euph_notes.id = euph_msgs.id
+                )
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND id = ?
+                ",
+                params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+                |row| {
+                    Ok(SmallMessage {
+                        id: MessageId(row.get::<_, WSnowflake>(0)?.0),
+                        parent: row.get::<_, Option<WSnowflake>>(1)?.map(|s| MessageId(s.0)),
+                        time: row.get::<_, WTime>(2)?.0,
+                        time_zone: self.time_zone,
+                        nick: row.get(3)?,
+                        content: row.get(4)?,
+                        seen: row.get(5)?,
+                        user_id: UserId(row.get(6)?),
+                        has_note: row.get(7)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(msg)
+    }
+}
+
+impl Action for GetFullMsg {
+    type Output = Option<Message>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let mut query = conn.prepare(
+            "
+            SELECT
+                id, parent, previous_edit_id, time, content, encryption_key_id, edited, deleted, truncated,
+                user_id, name, server_id, server_era, session_id, is_staff, is_manager, client_address, real_client_address
+            FROM euph_msgs
+            WHERE domain = ?
+            AND room = ?
+            AND id = ?
+            "
+        )?;
+
+        let msg = query
+            .query_row(
+                params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+                |row| {
+                    Ok(Message {
+                        id: MessageId(row.get::<_, WSnowflake>(0)?.0),
+                        parent: row.get::<_, Option<WSnowflake>>(1)?.map(|s| MessageId(s.0)),
+                        previous_edit_id: row.get::<_, Option<WSnowflake>>(2)?.map(|s| s.0),
+                        time: row.get::<_, WTime>(3)?.0,
+                        content: row.get(4)?,
+                        encryption_key_id: row.get(5)?,
+                        edited: row.get::<_, Option<WTime>>(6)?.map(|t| t.0),
+                        deleted: row.get::<_, Option<WTime>>(7)?.map(|t| t.0),
+                        truncated: row.get(8)?,
+                        sender: SessionView {
+                            id: UserId(row.get(9)?),
+                            name: row.get(10)?,
+                            server_id: row.get(11)?,
+                            server_era: row.get(12)?,
+                            session_id: SessionId(row.get(13)?),
+                            is_staff: row.get(14)?,
+                            is_manager: row.get(15)?,
+                            client_address: row.get(16)?,
+                            real_client_address: row.get(17)?,
+                        },
+                    })
+                },
+            )
+            .optional()?;
+        Ok(msg)
+    }
+}
+
+impl Action for GetDeletedContent {
+    type Output = Option<String>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let content = conn
+            .query_row(
+                "
+                SELECT deleted_content
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND id = ?
+                ",
+                params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(content.flatten())
+    }
+}
+
+impl Action for GetTree {
+    type Output = Vec<SmallMessage>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msgs = conn
+            .prepare(
+                "
+                WITH RECURSIVE
+                tree (domain, room, id) AS (
+                    VALUES (?, ?, ?)
+                UNION
+                    SELECT euph_msgs.domain, euph_msgs.room, euph_msgs.id
+                    FROM euph_msgs
+                    JOIN tree
+                        ON tree.domain = euph_msgs.domain
+                        AND tree.room = euph_msgs.room
+                        AND tree.id = euph_msgs.parent
+                )
+                SELECT id, parent, time, name, content, seen, user_id, EXISTS (
+                    SELECT *
+                    FROM euph_notes
+                    WHERE euph_notes.domain = euph_msgs.domain
+                    AND euph_notes.room = euph_msgs.room
+                    AND euph_notes.id = euph_msgs.id
+                )
+                FROM euph_msgs
+                JOIN tree USING (domain, room, id)
+                ORDER BY id ASC
+                ",
+            )?
+            .query_map(
+                params![self.room.domain, self.room.name, WSnowflake(self.root_id.0)],
+                |row| {
+                    Ok(SmallMessage {
+                        id: MessageId(row.get::<_, WSnowflake>(0)?.0),
+                        parent: row.get::<_, Option<WSnowflake>>(1)?.map(|s| MessageId(s.0)),
+                        time: row.get::<_, WTime>(2)?.0,
+                        time_zone: self.time_zone,
+                        nick: row.get(3)?,
+                        content: row.get(4)?,
+                        seen: row.get(5)?,
+                        user_id: UserId(row.get(6)?),
+                        has_note: row.get(7)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(msgs)
+    }
+}
+
+// All unseen messages and the roots of their threads, each paired with the id
+// of its thread's root and ordered by id. Roots that are already seen are
+// included as well.
+impl Action for GetUnseenThreads {
+    type Output = Vec<(MessageId, SmallMessage)>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msgs = conn
+            .prepare(
+                "
+                WITH RECURSIVE
+                ancestors (id, ancestor) AS (
+                    SELECT id, id
+                    FROM euph_msgs
+                    WHERE domain = :domain
+                    AND room = :room
+                    AND NOT seen
+                UNION
+                    SELECT ancestors.id, euph_msgs.parent
+                    FROM euph_msgs
+                    JOIN ancestors
+                        ON euph_msgs.domain = :domain
+                        AND euph_msgs.room = :room
+                        AND euph_msgs.id = ancestors.ancestor
+                    WHERE euph_msgs.parent IS NOT NULL
+                ),
+                roots (id, root) AS (
+                    SELECT id, min(ancestor)
+                    FROM ancestors
+                    GROUP BY id
+                ),
+                wanted (id, root) AS (
+                    SELECT id, root FROM roots
+                UNION
+                    SELECT root, root FROM roots
+                )
+                SELECT id, parent, time, name, content, seen, user_id, EXISTS (
+                    SELECT *
+                    FROM euph_notes
+                    WHERE euph_notes.domain = euph_msgs.domain
+                    AND euph_notes.room = euph_msgs.room
+                    AND euph_notes.id = euph_msgs.id
+                ), wanted.root
+                FROM euph_msgs
+                JOIN wanted USING (id)
+                WHERE domain = :domain
+                AND room = :room
+                ORDER BY id ASC
+                ",
+            )?
+            .query_map(
+                named_params! {
+                    ":domain": self.room.domain,
+                    ":room": self.room.name,
+                },
+                |row| {
+                    let msg = SmallMessage {
+                        id: MessageId(row.get::<_, WSnowflake>(0)?.0),
+                        parent: row.get::<_, Option<WSnowflake>>(1)?.map(|s| MessageId(s.0)),
+                        time: row.get::<_, WTime>(2)?.0,
+                        time_zone: self.time_zone,
+                        nick: row.get(3)?,
+                        content: row.get(4)?,
+                        seen: row.get(5)?,
+                        user_id: UserId(row.get(6)?),
+                        has_note: row.get(7)?,
+                    };
+                    let root = MessageId(row.get::<_, WSnowflake>(8)?.0);
+                    Ok((root, msg))
+                },
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(msgs)
+    }
+}
+
+impl Action for GetPinned {
+    type Output = Vec<SmallMessage>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        if self.nick.is_none() && self.prefix.is_none() {
+            return Ok(vec![]);
+        }
+
+        let msgs = conn
+            .prepare(
+                "
+                SELECT id, parent, time, name, content, seen, user_id, EXISTS (
+                    SELECT *
+                    FROM euph_notes
+                    WHERE euph_notes.domain = euph_msgs.domain
+                    AND euph_notes.room = euph_msgs.room
+                    AND euph_notes.id = euph_msgs.id
+                )
+                FROM euph_msgs
+                WHERE domain = :domain
+                AND room = :room
+                AND (:nick IS NULL OR name = :nick)
+                AND (:prefix IS NULL OR substr(content, 1, length(:prefix)) = :prefix)
+                ORDER BY id DESC
+                LIMIT :amount
+                ",
+            )?
+            .query_map(
+                named_params! {
+                    ":domain": self.room.domain,
+                    ":room": self.room.name,
+                    ":nick": self.nick,
+                    ":prefix": self.prefix,
+                    ":amount": self.amount,
+                },
+                |row| {
+                    Ok(SmallMessage {
+                        id: MessageId(row.get::<_, WSnowflake>(0)?.0),
+                        parent: row.get::<_, Option<WSnowflake>>(1)?.map(|s| MessageId(s.0)),
+                        time: row.get::<_, WTime>(2)?.0,
+                        time_zone: self.time_zone,
+                        nick: row.get(3)?,
+                        content: row.get(4)?,
+                        seen: row.get(5)?,
+                        user_id: UserId(row.get(6)?),
+                        has_note: row.get(7)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(msgs)
+    }
+}
+
+impl Action for GetFirstRootId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let root_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_trees
+                WHERE domain = ?
+                AND room = ?
+                ORDER BY id ASC
+                LIMIT 1
+                ",
+            )?
+            .query_row([&self.room.domain, &self.room.name], |row| {
+                row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0))
+            })
+            .optional()?;
+        Ok(root_id)
+    }
+}
+
+impl Action for GetLastRootId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let root_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_trees
+                WHERE domain = ?
+                AND room = ?
+                ORDER BY id DESC
+                LIMIT 1
+                ",
+            )?
+            .query_row([&self.room.domain, &self.room.name], |row| {
+                row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0))
+            })
+            .optional()?;
+        Ok(root_id)
+    }
+}
+
+impl Action for GetPrevRootId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let root_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_trees
+                WHERE domain = ?
+                AND room = ?
+                AND id < ?
+                ORDER BY id DESC
+                LIMIT 1
+                ",
+            )?
+            .query_row(
+                params![self.room.domain, self.room.name, WSnowflake(self.root_id.0)],
+                |row| row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0)),
+            )
+            .optional()?;
+        Ok(root_id)
+    }
+}
+
+impl Action for GetNextRootId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let root_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_trees
+                WHERE domain = ?
+                AND room = ?
+                AND id > ?
+                ORDER BY id ASC
+                LIMIT 1
+                ",
+            )?
+            .query_row(
+                params![self.room.domain, self.room.name, WSnowflake(self.root_id.0)],
+                |row| row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0)),
+            )
+            .optional()?;
+        Ok(root_id)
+    }
+}
+
+impl Action for GetOldestMsgId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                ORDER BY id ASC
+                LIMIT 1
+                ",
+            )?
+            .query_row([&self.room.domain, &self.room.name], |row| {
+                row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0))
+            })
+            .optional()?;
+        Ok(msg_id)
+    }
+}
+
+impl Action for GetNewestMsgId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                ORDER BY id DESC
+                LIMIT 1
+                ",
+            )?
+            .query_row([&self.room.domain, &self.room.name], |row| {
+                row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0))
+            })
+            .optional()?;
+        Ok(msg_id)
+    }
+}
+
+impl Action for GetOlderMsgId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND id < ?
+                ORDER BY id DESC
+                LIMIT 1
+                ",
+            )?
+            .query_row(
+                params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+                |row| row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0)),
+            )
+            .optional()?;
+        Ok(msg_id)
+    }
+}
+impl Action for GetNewerMsgId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND id > ?
+                ORDER BY id ASC
+                LIMIT 1
+                ",
+            )?
+            .query_row(
+                params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+                |row| row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0)),
+            )
+            .optional()?;
+        Ok(msg_id)
+    }
+}
+
+impl Action for GetOldestUnseenMsgId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND NOT seen
+                ORDER BY id ASC
+                LIMIT 1
+                ",
+            )?
+            .query_row([&self.room.domain, &self.room.name], |row| {
+                row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0))
+            })
+            .optional()?;
+        Ok(msg_id)
+    }
+}
+
+impl Action for GetOldestUnseenMsgIdSince {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND time >= ?
+                AND NOT seen
+                ORDER BY id ASC
+                LIMIT 1
+                ",
+            )?
+            .query_row(
+                params![self.room.domain, self.room.name, WTime(self.time)],
+                |row| row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0)),
+            )
+            .optional()?;
+        Ok(msg_id)
+    }
+}
+
+impl Action for GetNewestUnseenMsgId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND NOT seen
+                ORDER BY id DESC
+                LIMIT 1
+                ",
+            )?
+            .query_row([&self.room.domain, &self.room.name], |row| {
+                row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0))
+            })
+            .optional()?;
+        Ok(msg_id)
+    }
+}
+
+impl Action for GetOlderUnseenMsgId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND NOT seen
+                AND id < ?
+                ORDER BY id DESC
+                LIMIT 1
+                ",
+            )?
+            .query_row(
+                params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+                |row| row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0)),
+            )
+            .optional()?;
+        Ok(msg_id)
+    }
+}
+
+impl Action for GetNewerUnseenMsgId {
+    type Output = Option<MessageId>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msg_id = conn
+            .prepare(
+                "
+                SELECT id
+                FROM euph_msgs
+                WHERE domain = ?
+                AND room = ?
+                AND NOT seen
+                AND id > ?
+                ORDER BY id ASC
+                LIMIT 1
+                ",
+            )?
+            .query_row(
+                params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+                |row| row.get::<_, WSnowflake>(0).map(|s| MessageId(s.0)),
+            )
+            .optional()?;
+        Ok(msg_id)
+    }
+}
+
+impl Action for GetUnseenMsgsCount {
+    type Output = usize;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let amount = conn
+            .prepare(
+                "
+                SELECT amount
+                FROM euph_unseen_counts
+                WHERE domain = ?
+                AND room = ?
+                ",
+            )?
+            .query_row(params![self.room.domain, self.room.name], |row| row.get(0))
+            .optional()?
+            .unwrap_or(0);
+        Ok(amount)
+    }
+}
+
+impl Action for GetUnseenMentionsCount {
+    type Output = usize;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        // Only messages containing an @ can possibly mention anyone, and there
+        // usually aren't many of them, so the rest can be done in rust. Replies
+        // in subscribed threads and direct replies to messages sent by the user
+        // always count as mentions.
+        let amount = conn
+            .prepare(
+                "
+                WITH RECURSIVE
+                replies (id) AS (
+                    SELECT euph_msgs.id
+                    FROM euph_msgs
+                    JOIN euph_subscriptions AS s
+                        ON s.domain = euph_msgs.domain
+                        AND s.room = euph_msgs.room
+                        AND s.id = euph_msgs.parent
+                    WHERE euph_msgs.domain = :domain
+                    AND euph_msgs.room = :room
+                UNION
+                    SELECT euph_msgs.id
+                    FROM euph_msgs
+                    JOIN replies ON replies.id = euph_msgs.parent
+                    WHERE domain = :domain
+                    AND room = :room
+                )
+                SELECT content, reply
+                FROM (
+                    SELECT
+                        m.content,
+                        m.id IN replies OR EXISTS(
+                            SELECT 1
+                            FROM euph_msgs AS p
+                            WHERE p.domain = m.domain
+                            AND p.room = m.room
+                            AND p.id = m.parent
+                            AND p.user_id = :user_id
+                        ) AS reply
+                    FROM euph_msgs AS m
+                    WHERE m.domain = :domain
+                    AND m.room = :room
+                    AND NOT m.seen
+                )
+                WHERE reply OR content LIKE '%@%'
+                ",
+            )?
+            .query_map(
+                named_params! {
+                    ":domain": self.room.domain,
+                    ":room": self.room.name,
+                    ":user_id": self.user_id.0,
+                },
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?)),
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|(content, reply)| *reply || mentions(content, &self.nick))
+            .count();
+        Ok(amount)
+    }
+}
+
+impl Action for SetSeen {
+    type Output = bool;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let changed = conn.execute(
+            "
+            UPDATE euph_msgs
+            SET seen = :seen
+            WHERE domain = :domain
+            AND room = :room
+            AND id = :id
+            AND seen != :seen
+            ",
+            named_params! {
+                ":domain": self.room.domain,
+                ":room": self.room.name,
+                ":id": WSnowflake(self.id.0),
+                ":seen": self.seen,
+            },
+        )?;
+        Ok(changed > 0)
+    }
+}
+
+// Like `SetSeen`, but for many messages in a single transaction.
+impl Action for SetManySeen {
+    type Output = bool;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let tx = conn.transaction()?;
+        let mut update = tx.prepare(
+            "
+            UPDATE euph_msgs
+            SET seen = :seen
+            WHERE domain = :domain
+            AND room = :room
+            AND id = :id
+            AND seen != :seen
+            ",
+        )?;
+        let mut changed = 0;
+        for id in self.ids {
+            changed += update.execute(named_params! {
+                ":domain": self.room.domain,
+                ":room": self.room.name,
+                ":id": WSnowflake(id.0),
+                ":seen": self.seen,
+            })?;
+        }
+        drop(update);
+        tx.commit()?;
+        Ok(changed > 0)
+    }
+}
+
+impl Action for SetOlderSeen {
+    type Output = bool;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let changed = conn.execute(
+            "
+            UPDATE euph_msgs
+            SET seen = :seen
+            WHERE domain = :domain
+            AND room = :room
+            AND id <= :id
+            AND seen != :seen
+            ",
+            named_params! {
+                ":domain": self.room.domain,
+                ":room": self.room.name,
+                ":id": WSnowflake(self.id.0),
+                ":seen": self.seen,
+            },
+        )?;
+        Ok(changed > 0)
+    }
+}
+
+impl Action for SetTreeSeen {
+    type Output = bool;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let changed = conn.execute(
+            "
+            WITH RECURSIVE
+            tree (domain, room, id) AS (
+                VALUES (:domain, :room, :root_id)
+            UNION
+                SELECT euph_msgs.domain, euph_msgs.room, euph_msgs.id
+                FROM euph_msgs
+                JOIN tree
+                    ON tree.domain = euph_msgs.domain
+                    AND tree.room = euph_msgs.room
+                    AND tree.id = euph_msgs.parent
+            )
+            UPDATE euph_msgs
+            SET seen = :seen
+            WHERE domain = :domain
+            AND room = :room
+            AND id IN (SELECT id FROM tree)
+            AND seen != :seen
+            ",
+            named_params! {
+                ":domain": self.room.domain,
+                ":room": self.room.name,
+                ":root_id": WSnowflake(self.root_id.0),
+                ":seen": self.seen,
+            },
+        )?;
+        Ok(changed > 0)
+    }
+}
+
+impl Action for GetNote {
+    type Output = Option<String>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.query_row(
+            "
+            SELECT note
+            FROM euph_notes
+            WHERE domain = ?
+            AND room = ?
+            AND id = ?
+            ",
+            params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+}
+
+impl Action for SetNote {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        match self.note {
+            Some(note) => conn.execute(
+                "
+                INSERT INTO euph_notes (domain, room, id, note)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT (domain, room, id) DO UPDATE
+                SET note = excluded.note
+                ",
+                params![
+                    self.room.domain,
+                    self.room.name,
+                    WSnowflake(self.id.0),
+                    note
+                ],
+            )?,
+            None => conn.execute(
+                "
+                DELETE FROM euph_notes
+                WHERE domain = ?
+                AND room = ?
+                AND id = ?
+                ",
+                params![self.room.domain, self.room.name, WSnowflake(self.id.0)],
+            )?,
+        };
+        Ok(())
+    }
+}
+
+impl Action for GetDraft {
+    type Output = Option<String>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.query_row(
+            "
+            SELECT content
+            FROM euph_drafts
+            WHERE domain = ?
+            AND room = ?
+            ",
+            params![self.room.domain, self.room.name],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+}
+
+impl Action for SetDraft {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        match self.content {
+            Some(content) => conn.execute(
+                "
+                INSERT INTO euph_drafts (domain, room, content)
+                VALUES (?, ?, ?)
+                ON CONFLICT (domain, room) DO UPDATE
+                SET content = excluded.content
+                ",
+                params![self.room.domain, self.room.name, content],
+            )?,
+            None => conn.execute(
+                "
+                DELETE FROM euph_drafts
+                WHERE domain = ?
+                AND room = ?
+                ",
+                params![self.room.domain, self.room.name],
+            )?,
+        };
+        Ok(())
+    }
+}
+
+impl Action for GetIgnored {
+    type Output = Vec<String>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.prepare(
+            "
+            SELECT who
+            FROM euph_ignored
+            WHERE domain = ?
+            AND room = ?
+            ORDER BY who ASC
+            ",
+        )?
+        .query_map(params![self.room.domain, self.room.name], |row| row.get(0))?
+        .collect()
+    }
+}
+
+impl Action for SetIgnored {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let sql = if self.ignored {
+            "
+            INSERT OR IGNORE INTO euph_ignored (domain, room, who)
+            VALUES (?, ?, ?)
+            "
+        } else {
+            "
+            DELETE FROM euph_ignored
+            WHERE domain = ?
+            AND room = ?
+            AND who = ?
+            "
+        };
+        conn.execute(sql, params![self.room.domain, self.room.name, self.who])?;
+        Ok(())
+    }
+}
+
+impl Action for AddScheduled {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.execute(
+            "
+            INSERT INTO euph_scheduled (domain, room, parent, time, content)
+            VALUES (?, ?, ?, ?, ?)
+            ",
+            params![
+                self.room.domain,
+                self.room.name,
+                self.parent.map(|p| WSnowflake(p.0)),
+                WTime(self.time),
+                self.content,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+impl Action for GetScheduled {
+    type Output = Vec<ScheduledMsg>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        let msgs = conn
+            .prepare(
+                "
+                SELECT id, parent, time, content
+                FROM euph_scheduled
+                WHERE domain = ?
+                AND room = ?
+                ORDER BY time ASC, id ASC
+                ",
+            )?
+            .query_map(params![self.room.domain, self.room.name], |row| {
+                Ok(ScheduledMsg {
+                    id: row.get(0)?,
+                    parent: row.get::<_, Option<WSnowflake>>(1)?.map(|s| MessageId(s.0)),
+                    time: row.get::<_, WTime>(2)?.0,
+                    content: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(msgs)
+    }
+}
+
+impl Action for DeleteScheduled {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        conn.execute(
+            "
+            DELETE FROM euph_scheduled
+            WHERE domain = ?
+            AND room = ?
+            AND id = ?
+            ",
+            params![self.room.domain, self.room.name, self.id],
+        )?;
+        Ok(())
+    }
+}
+
+impl Action for GetChunkAfter {
+    type Output = Vec<Message>;
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        fn row2msg(row: &Row<'_>) -> rusqlite::Result<Message> {
+            Ok(Message {
+                id: MessageId(row.get::<_, WSnowflake>(0)?.0),
+                parent: row.get::<_, Option<WSnowflake>>(1)?.map(|s| MessageId(s.0)),
+                previous_edit_id: row.get::<_, Option<WSnowflake>>(2)?.map(|s| s.0),
+                time: row.get::<_, WTime>(3)?.0,
+                content: row.get(4)?,
+                encryption_key_id: row.get(5)?,
+                edited: row.get::<_, Option<WTime>>(6)?.map(|t| t.0),
+                deleted: row.get::<_, Option<WTime>>(7)?.map(|t| t.0),
+                truncated: row.get(8)?,
+                sender: SessionView {
+                    id: UserId(row.get(9)?),
+                    name: row.get(10)?,
+                    server_id: row.get(11)?,
+                    server_era: row.get(12)?,
+                    session_id: SessionId(row.get(13)?),
+                    is_staff: row.get(14)?,
+                    is_manager: row.get(15)?,
+                    client_address: row.get(16)?,
+                    real_client_address: row.get(17)?,
+                },
+            })
+        }
+
+        let messages = conn
+            .prepare(
+                "
+                SELECT
+                    id, parent, previous_edit_id, time, content, encryption_key_id, edited, deleted, truncated,
+                    user_id, name, server_id, server_era, session_id, is_staff, is_manager, client_address, real_client_address
+                FROM euph_msgs
+                WHERE domain = :domain
+                AND room = :room
+                AND id > coalesce(:id, -1)
+                AND (:since IS NULL OR time >= :since)
+                AND (:until IS NULL OR time < :until)
+                ORDER BY id ASC
+                LIMIT :amount
+                ",
+            )?
+            .query_map(
+                named_params! {
+                    ":domain": self.room.domain,
+                    ":room": self.room.name,
+                    ":id": self.id.map(|id| WSnowflake(id.0)),
+                    ":since": self.since.map(WTime),
+                    ":until": self.until.map(WTime),
+                    ":amount": self.amount,
+                },
+                row2msg,
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(messages)
+    }
+}
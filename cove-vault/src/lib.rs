@@ -0,0 +1,179 @@
+//! Access to cove's vault, the SQLite database cove stores its data in.
+//!
+//! A vault is opened via [`launch`] or [`launch_in_memory`], which also migrate
+//! the database to the newest schema if necessary. Data belonging to euphoria
+//! rooms can then be accessed via [`Vault::euph`] and [`EuphVault::room`].
+//!
+//! All operations are executed on a separate thread and can be awaited. While a
+//! vault is open, cove holds an exclusive lock on the database file, so a vault
+//! can't be opened while cove is running.
+
+#![forbid(unsafe_code)]
+// Rustc lint groups
+#![warn(future_incompatible)]
+#![warn(rust_2018_idioms)]
+#![warn(unused)]
+// Rustc lints
+#![warn(noop_method_call)]
+#![warn(single_use_lifetimes)]
+// Clippy lints
+#![warn(clippy::use_self)]
+
+mod diagnostics;
+mod euph;
+mod migrate;
+mod prepare;
+
+use std::fs;
+use std::path::Path;
+
+use rusqlite::functions::FunctionFlags;
+use rusqlite::Connection;
+use tokio::sync::broadcast;
+use tz::TimeZone;
+use vault::tokio::TokioVault;
+use vault::Action;
+
+pub use self::diagnostics::{Diagnostics, SlowQuery};
+pub use self::euph::{
+    mentioned_nicks, mentions, nick_char, normalize_nick, EuphRoomVault, EuphVault, MsgChange,
+    MsgChangeKind, RoomActivity, RoomIdentifier, RoomStats, ScheduledMsg, SearchFilter,
+    SearchResult, SeenChange, SmallMessage, SNIPPET_END, SNIPPET_START,
+};
+
+/// A handle to an open vault.
+///
+/// Handles can be cloned cheaply. The vault stays open until [`Vault::close`]
+/// is called on any of its handles.
+#[derive(Debug, Clone)]
+pub struct Vault {
+    tokio_vault: TokioVault,
+    time_zone: &'static TimeZone,
+    ephemeral: bool,
+    changes: broadcast::Sender<MsgChange>,
+}
+
+/// How many changes a subscriber may fall behind before missing some.
+const CHANGES_CAPACITY: usize = 1024;
+
+struct GcAction;
+
+impl Action for GcAction {
+    type Output = ();
+    type Error = rusqlite::Error;
+
+    fn run(self, conn: &mut Connection) -> Result<Self::Output, Self::Error> {
+        // Vacuuming may change the rowids the full text search index refers to.
+        conn.execute_batch(
+            "
+            ANALYZE;
+            VACUUM;
+            INSERT INTO euph_msgs_fts (euph_msgs_fts) VALUES ('rebuild');
+            ",
+        )
+    }
+}
+
+impl Vault {
+    /// Whether the vault only exists in memory.
+    pub fn ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    /// Receive a [`MsgChange`] whenever messages are added, deleted, marked
+    /// as seen or unseen or get a note via any handle to this vault.
+    pub fn subscribe(&self) -> broadcast::Receiver<MsgChange> {
+        self.changes.subscribe()
+    }
+
+    fn notify(&self, room: Option<RoomIdentifier>, kind: MsgChangeKind) {
+        // Nobody may be subscribed, which is fine.
+        let _ = self.changes.send(MsgChange { room, kind });
+    }
+
+    /// Close the vault, waiting for all pending operations to finish.
+    pub async fn close(&self) {
+        self.tokio_vault.stop().await;
+    }
+
+    /// Compact and clean up the vault.
+    pub async fn gc(&self) -> Result<(), vault::tokio::Error<rusqlite::Error>> {
+        self.tokio_vault.execute(GcAction).await
+    }
+
+    /// Explain the slowest statements executed so far and give hints about
+    /// why they may be slow.
+    pub async fn diagnose(&self) -> Result<Diagnostics, vault::tokio::Error<rusqlite::Error>> {
+        self.tokio_vault.execute(diagnostics::DiagnoseAction).await
+    }
+
+    /// The time zone that message times are shown in.
+    pub fn time_zone(&self) -> &'static TimeZone {
+        self.time_zone
+    }
+
+    /// Open a new empty vault that only exists in memory and uses the same
+    /// time zone as this vault.
+    pub fn launch_in_memory_sibling(&self) -> rusqlite::Result<Vault> {
+        launch_in_memory(self.time_zone)
+    }
+
+    /// Access the euphoria related parts of the vault.
+    pub fn euph(&self) -> EuphVault {
+        EuphVault::new(self.clone())
+    }
+}
+
+fn launch_from_connection(
+    mut conn: Connection,
+    time_zone: &'static TimeZone,
+    ephemeral: bool,
+) -> rusqlite::Result<Vault> {
+    conn.pragma_update(None, "foreign_keys", true)?;
+    conn.pragma_update(None, "trusted_schema", false)?;
+    conn.profile(Some(diagnostics::profile));
+
+    // Lets queries compare nicks the same way as `normalize_nick`.
+    conn.create_scalar_function(
+        "normalize_nick",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(ctx.get::<Option<String>>(0)?.as_deref().map(normalize_nick)),
+    )?;
+
+    let tokio_vault = TokioVault::launch_and_prepare(conn, &migrate::MIGRATIONS, prepare::prepare)?;
+    let (changes, _) = broadcast::channel(CHANGES_CAPACITY);
+    Ok(Vault {
+        tokio_vault,
+        time_zone,
+        ephemeral,
+        changes,
+    })
+}
+
+/// Open the vault at the given path, creating it if it doesn't exist yet.
+///
+/// The time zone is used for the timestamps of [`SmallMessage`]s.
+pub fn launch(path: &Path, time_zone: &'static TimeZone) -> rusqlite::Result<Vault> {
+    // If this fails, rusqlite will complain about not being able to open the db
+    // file, which saves me from adding a separate vault error type.
+    let _ = fs::create_dir_all(path.parent().expect("path to file"));
+
+    let conn = Connection::open(path)?;
+
+    // Setting locking mode before journal mode so no shared memory files
+    // (*-shm) need to be created by sqlite. Apparently, setting the journal
+    // mode is also enough to immediately acquire the exclusive lock even if the
+    // database was already using WAL.
+    // https://sqlite.org/pragma.html#pragma_locking_mode
+    conn.pragma_update(None, "locking_mode", "exclusive")?;
+    conn.pragma_update(None, "journal_mode", "wal")?;
+
+    launch_from_connection(conn, time_zone, false)
+}
+
+/// Open a new empty vault that only exists in memory.
+pub fn launch_in_memory(time_zone: &'static TimeZone) -> rusqlite::Result<Vault> {
+    let conn = Connection::open_in_memory()?;
+    launch_from_connection(conn, time_zone, true)
+}
@@ -1,7 +1,8 @@
 use rusqlite::Transaction;
 use vault::Migration;
 
-pub const MIGRATIONS: [Migration; 3] = [m1, m2, m3];
+pub const MIGRATIONS: [Migration; 14] =
+    [m1, m2, m3, m4, m5, m6, m7, m8, m9, m10, m11, m12, m13, m14];
 
 fn eprint_status(nr: usize, total: usize) {
     eprintln!("Migrating vault from {} to {} (out of {total})", nr, nr + 1);
@@ -222,3 +223,199 @@ fn m3(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()>
 
     Ok(())
 }
+
+fn m4(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+    tx.execute_batch(
+        "
+        CREATE TABLE euph_stats (
+            domain TEXT NOT NULL,
+            room   TEXT NOT NULL,
+            hour   INT  NOT NULL,
+            events INT  NOT NULL,
+
+            PRIMARY KEY (domain, room, hour),
+            FOREIGN KEY (domain, room) REFERENCES euph_rooms (domain, room)
+                ON DELETE CASCADE
+        ) STRICT;
+        ",
+    )
+}
+
+fn m5(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+    println!("  This migration might take a while.");
+
+    // The index is kept up to date manually when inserting or deleting
+    // messages instead of via triggers because untrusted schemas may not use
+    // virtual tables in triggers.
+    tx.execute_batch(
+        "
+        CREATE VIRTUAL TABLE euph_msgs_fts USING fts5 (
+            content,
+            content = 'euph_msgs'
+        );
+
+        INSERT INTO euph_msgs_fts (euph_msgs_fts) VALUES ('rebuild');
+        ",
+    )
+}
+
+fn m6(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+    tx.execute_batch(
+        "
+        CREATE TABLE euph_subscriptions (
+            domain TEXT NOT NULL,
+            room   TEXT NOT NULL,
+            id     INT  NOT NULL,
+
+            PRIMARY KEY (domain, room, id),
+            FOREIGN KEY (domain, room) REFERENCES euph_rooms (domain, room)
+                ON DELETE CASCADE
+        ) STRICT;
+        ",
+    )
+}
+
+fn m7(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+    tx.execute_batch(
+        "
+        CREATE TABLE euph_notes (
+            domain TEXT NOT NULL,
+            room   TEXT NOT NULL,
+            id     INT  NOT NULL,
+            note   TEXT NOT NULL,
+
+            PRIMARY KEY (domain, room, id),
+            FOREIGN KEY (domain, room) REFERENCES euph_rooms (domain, room)
+                ON DELETE CASCADE
+        ) STRICT;
+        ",
+    )
+}
+
+fn m8(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+    tx.execute_batch(
+        "
+        CREATE TABLE euph_drafts (
+            domain  TEXT NOT NULL,
+            room    TEXT NOT NULL,
+            content TEXT NOT NULL,
+
+            PRIMARY KEY (domain, room),
+            FOREIGN KEY (domain, room) REFERENCES euph_rooms (domain, room)
+                ON DELETE CASCADE
+        ) STRICT;
+        ",
+    )
+}
+
+fn m9(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+    tx.execute_batch(
+        "
+        CREATE TABLE euph_scheduled (
+            id      INTEGER PRIMARY KEY,
+            domain  TEXT NOT NULL,
+            room    TEXT NOT NULL,
+            parent  INT,
+            time    INT  NOT NULL,
+            content TEXT NOT NULL,
+
+            FOREIGN KEY (domain, room) REFERENCES euph_rooms (domain, room)
+                ON DELETE CASCADE
+        ) STRICT;
+
+        CREATE INDEX euph_idx_scheduled_domain_room_time
+        ON euph_scheduled (domain, room, time);
+        ",
+    )
+}
+
+fn m10(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+    println!("  This migration might take a while.");
+
+    // Most messages are usually seen, so looking up only the unseen ones via
+    // this index is much faster than via euph_idx_msgs_domain_room_id_seen.
+    tx.execute_batch(
+        "
+        CREATE INDEX euph_idx_msgs_domain_room_id_unseen
+        ON euph_msgs (domain, room, id)
+        WHERE NOT seen;
+
+        ANALYZE;
+        ",
+    )
+}
+
+fn m11(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+    tx.execute_batch(
+        "
+        ALTER TABLE euph_msgs ADD COLUMN deleted_content TEXT;
+
+        CREATE INDEX euph_idx_msgs_deleted_content
+        ON euph_msgs (deleted)
+        WHERE deleted_content IS NOT NULL;
+        ",
+    )
+}
+
+fn m12(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+
+    // Templates are chosen before connecting to a room for the first time, so
+    // the room may not be in euph_rooms yet.
+    tx.execute_batch(
+        "
+        CREATE TABLE euph_room_templates (
+            domain   TEXT NOT NULL,
+            room     TEXT NOT NULL,
+            template TEXT NOT NULL,
+
+            PRIMARY KEY (domain, room)
+        ) STRICT;
+        ",
+    )
+}
+
+fn m13(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+
+    // Like templates, users may be ignored before the room was ever joined.
+    tx.execute_batch(
+        "
+        CREATE TABLE euph_ignored (
+            domain TEXT NOT NULL,
+            room   TEXT NOT NULL,
+            who    TEXT NOT NULL,
+
+            PRIMARY KEY (domain, room, who)
+        ) STRICT;
+        ",
+    )
+}
+
+fn m14(tx: &mut Transaction<'_>, nr: usize, total: usize) -> rusqlite::Result<()> {
+    eprint_status(nr, total);
+
+    // Seen changes from other machines for messages that aren't in the vault
+    // yet. They are applied once the messages are inserted.
+    tx.execute_batch(
+        "
+        CREATE TABLE euph_seen_pending (
+            domain TEXT NOT NULL,
+            room   TEXT NOT NULL,
+            id     INT  NOT NULL,
+            seen   INT  NOT NULL,
+            time   INT  NOT NULL,
+
+            PRIMARY KEY (domain, room, id)
+        ) STRICT;
+        ",
+    )
+}
@@ -0,0 +1,82 @@
+//! Import the session of a logged-in euphoria web client.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use cookie::{Cookie, CookieJar};
+use cove_vault::EuphVault;
+
+#[derive(Debug, clap::Parser)]
+pub struct Args {
+    /// Cookies as sent by the browser, like `a=...; b=...`.
+    ///
+    /// They can be found in the browser's developer tools, for example in the
+    /// `Cookie` header of a request to the euphoria server. If they are `-` or
+    /// missing, they are read from stdin instead, which keeps them out of the
+    /// shell history and process list.
+    #[arg(conflicts_with = "file")]
+    cookies: Option<String>,
+
+    /// Browser cookie jar to import cookies from instead.
+    ///
+    /// The file must be in the Netscape `cookies.txt` format that many
+    /// browser extensions and curl use.
+    #[arg(long, short)]
+    file: Option<PathBuf>,
+
+    /// Domain of the euphoria server the cookies belong to.
+    #[arg(long, short, default_value = crate::DEFAULT_DOMAIN)]
+    domain: String,
+}
+
+fn parse_cookie_string(cookies: &str) -> anyhow::Result<Vec<Cookie<'static>>> {
+    cookies
+        .split(';')
+        .map(|cookie| cookie.trim())
+        .filter(|cookie| !cookie.is_empty())
+        .map(|cookie| Ok(Cookie::parse(cookie.to_string())?))
+        .collect()
+}
+
+/// Parse the cookies belonging to `domain` from a file in the Netscape
+/// `cookies.txt` format.
+fn parse_cookie_file(content: &str, domain: &str) -> Vec<Cookie<'static>> {
+    content
+        .lines()
+        // Cookies only sent via HTTP are prefixed like a comment.
+        .map(|line| line.strip_prefix("#HttpOnly_").unwrap_or(line))
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            let [cookie_domain, _, _, _, _, name, value] = fields[..] else {
+                return None;
+            };
+            let cookie_domain = cookie_domain.trim_start_matches('.');
+            let matches = domain == cookie_domain || domain.ends_with(&format!(".{cookie_domain}"));
+            matches.then(|| Cookie::new(name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Replaces all cookies previously stored for the domain, so importing a new
+/// session logs out of the old one.
+pub async fn import(vault: &EuphVault, args: Args) -> anyhow::Result<()> {
+    let cookies = match (args.cookies.as_deref(), &args.file) {
+        (_, Some(file)) => parse_cookie_file(&fs::read_to_string(file)?, &args.domain),
+        (Some("-") | None, None) => parse_cookie_string(&io::read_to_string(io::stdin())?)?,
+        (Some(cookies), None) => parse_cookie_string(cookies)?,
+    };
+    if cookies.is_empty() {
+        anyhow::bail!("no cookies for {} found", args.domain);
+    }
+
+    let mut jar = CookieJar::new();
+    for cookie in cookies {
+        eprintln!("Importing cookie {}", cookie.name());
+        jar.add_original(cookie);
+    }
+    vault.set_cookies(args.domain, jar).await?;
+
+    Ok(())
+}
@@ -0,0 +1,368 @@
+//! Keeping rooms connected while no other instance is using the vault.
+//!
+//! The daemon holds the vault and keeps the `autojoin` rooms connected so their
+//! history is still recorded while cove isn't running. It listens on its own
+//! unix socket next to the vault. Only one process can use the vault at a time,
+//! so every cove command that needs the vault first asks the daemon to release
+//! it, which it does by disconnecting its rooms and closing the vault. The
+//! daemon then stays idle until that instance exits, no matter how, and
+//! reconnects its rooms once it can open the vault again.
+//!
+//! Instances don't attach to the daemon as clients. They open the vault
+//! themselves, so while cove is running, the daemon's rooms are disconnected
+//! and only the rooms cove connects to are recorded.
+
+// Other platforms don't support unix sockets, so there is no daemon there.
+#![cfg_attr(not(unix), allow(unused))]
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use cove_config::Config;
+use cove_vault::{RoomIdentifier, Vault};
+use euphoxide::bot::instance::{Event, ServerConfig};
+use log::info;
+use tokio::sync::mpsc;
+use tz::TimeZone;
+
+use crate::euph;
+use crate::macros::logging_unwrap;
+use crate::{metrics, version};
+
+/// Name of the log file the daemon writes to in the data dir. It is replaced
+/// every time the daemon starts.
+pub const LOG_FILE_NAME: &str = "cove-daemon.log";
+
+/// Reply sent to a requesting instance once the vault was closed.
+const RELEASED: &str = "released";
+
+/// Reply sent to a requesting instance if the daemon doesn't hold the vault
+/// because another instance is using it.
+const NOT_HELD: &str = "not held";
+
+/// How long the daemon waits after releasing the vault before trying to open it
+/// again, so the requesting instance gets to open it first.
+const HANDOVER_GRACE: Duration = Duration::from_secs(5);
+
+/// How often the daemon tries to open the vault while another instance is
+/// using it.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a requesting instance waits for the daemon to release the vault.
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a connecting instance may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn socket_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("cove-daemon.sock")
+}
+
+/// Outcome of [`release_vault`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Release {
+    /// No daemon is running or it didn't answer properly.
+    NoDaemon,
+    /// The daemon closed the vault.
+    Released,
+    /// The daemon is running but doesn't hold the vault because another
+    /// instance is using it.
+    NotHeld,
+}
+
+/// Ask the daemon listening on the socket to release the vault.
+#[cfg(unix)]
+pub async fn release_vault(path: &Path) -> Release {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixStream;
+
+    let Ok(mut stream) = UnixStream::connect(path).await else {
+        return Release::NoDaemon;
+    };
+
+    let mut reply = String::new();
+    let request = async {
+        stream.write_all(b"release\n").await?;
+        stream.shutdown().await?;
+        stream.read_to_string(&mut reply).await
+    };
+
+    // Closing the vault may take a moment, but a stuck daemon must not keep
+    // cove from starting.
+    match tokio::time::timeout(RELEASE_TIMEOUT, request).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => {
+            log::warn!("Failed to ask daemon to release the vault: {err}");
+            return Release::NoDaemon;
+        }
+        Err(_) => {
+            log::warn!("Timed out waiting for daemon to release the vault");
+            return Release::NoDaemon;
+        }
+    }
+    match reply.trim() {
+        RELEASED => Release::Released,
+        NOT_HELD => Release::NotHeld,
+        reply => {
+            log::warn!("Unexpected reply from daemon: {reply:?}");
+            Release::NoDaemon
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn release_vault(_path: &Path) -> Release {
+    Release::NoDaemon
+}
+
+async fn connect_rooms(
+    config: &'static Config,
+    vault: &Vault,
+    event_tx: mpsc::UnboundedSender<Event>,
+) -> HashMap<RoomIdentifier, euph::Room> {
+    let templates = logging_unwrap!(vault.euph().room_templates().await)
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let ids = config
+        .euph
+        .servers
+        .iter()
+        .flat_map(|(domain, server)| {
+            server
+                .rooms
+                .keys()
+                .map(|name| RoomIdentifier::new(domain.clone(), name.clone()))
+        })
+        .chain(templates.keys().cloned())
+        .collect::<HashSet<_>>();
+    let webhooks = euph::Webhooks::new(&config.webhooks, &version::user_agent(config));
+
+    let mut servers = HashMap::new();
+    let mut rooms = HashMap::new();
+    for id in ids {
+        let template = templates.get(&id).map(|t| t as &str);
+        let room_config = config.euph_room(&id.domain, &id.name, template);
+        // Incognito rooms must not be recorded, and scheduled rooms are only
+        // connected by interactive instances.
+        if !room_config.autojoin || room_config.schedule.is_some() || room_config.incognito {
+            continue;
+        }
+
+        if !servers.contains_key(&id.domain) {
+            let cookies = logging_unwrap!(vault.euph().cookies(id.domain.clone()).await);
+            let server = ServerConfig::default()
+                .domain(id.domain.clone())
+                .cookies(Arc::new(Mutex::new(cookies)))
+                .timeout(Duration::from_secs(10));
+            servers.insert(id.domain.clone(), server);
+        }
+
+        let instance_config = servers[&id.domain]
+            .clone()
+            .room(id.name.clone())
+            .name(format!("{id:?}-daemon"))
+            .human(true)
+            .username(room_config.username.clone())
+            .force_username(room_config.force_username)
+            .password(room_config.password.clone());
+
+        let tx = event_tx.clone();
+        let room = euph::Room::new(
+            vault.euph().room(id.clone()),
+            &config.hooks,
+            webhooks.clone(),
+            instance_config,
+            config.low_bandwidth,
+            move |e| {
+                let _ = tx.send(e);
+            },
+        );
+        rooms.insert(id, room);
+    }
+
+    rooms
+}
+
+/// Open the vault after the delay, waiting for other instances to close it
+/// first.
+async fn open_vault(
+    path: &Path,
+    time_zone: &'static TimeZone,
+    delay: Duration,
+) -> anyhow::Result<Vault> {
+    tokio::time::sleep(delay).await;
+    loop {
+        // Opening the vault blocks, which must not keep the daemon from
+        // answering requesting instances in the meantime.
+        let path = path.to_path_buf();
+        let result = tokio::task::spawn_blocking(move || cove_vault::launch(&path, time_zone));
+        match result.await? {
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == rusqlite::ErrorCode::DatabaseBusy =>
+            {
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Run the daemon until it receives SIGINT or SIGTERM.
+#[cfg(unix)]
+pub async fn run(
+    config: &'static Config,
+    data_dir: &Path,
+    time_zone: &'static TimeZone,
+) -> anyhow::Result<()> {
+    use tokio::net::{UnixListener, UnixStream};
+
+    let socket = socket_path(data_dir);
+    if UnixStream::connect(&socket).await.is_ok() {
+        anyhow::bail!("a daemon is already running for this data dir");
+    }
+
+    // The socket of a daemon that did not exit cleanly may still exist. Nobody
+    // is listening on it, as checked above.
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket)?;
+
+    let (release_tx, release_rx) = mpsc::unbounded_channel();
+    let accepting = tokio::spawn(accept(listener, release_tx));
+    let result = serve(config, release_rx, &data_dir.join("vault.db"), time_zone).await;
+    accepting.abort();
+    let _ = std::fs::remove_file(&socket);
+    result
+}
+
+#[cfg(not(unix))]
+pub async fn run(
+    _config: &'static Config,
+    _data_dir: &Path,
+    _time_zone: &'static TimeZone,
+) -> anyhow::Result<()> {
+    anyhow::bail!("the daemon is only supported on unix")
+}
+
+/// Accept connections and pass on those of requesting instances. Every
+/// connection is read in its own task so a slow client can't hold up others.
+#[cfg(unix)]
+async fn accept(
+    listener: tokio::net::UnixListener,
+    release_tx: mpsc::UnboundedSender<tokio::net::UnixStream>,
+) {
+    use tokio::io::AsyncReadExt;
+
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                log::warn!("Failed to accept connection: {err}");
+                continue;
+            }
+        };
+
+        let release_tx = release_tx.clone();
+        tokio::spawn(async move {
+            let mut request = String::new();
+            let read = stream.read_to_string(&mut request);
+            let result = tokio::time::timeout(READ_TIMEOUT, read).await;
+            match result {
+                Ok(Ok(_)) if request.trim() == "release" => {
+                    let _ = release_tx.send(stream);
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => log::warn!("Failed to read request: {err}"),
+                Err(_) => log::warn!("Timed out reading request"),
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn reply(mut stream: tokio::net::UnixStream, reply: &str) {
+    use tokio::io::AsyncWriteExt;
+
+    if let Err(err) = stream.write_all(reply.as_bytes()).await {
+        log::warn!("Failed to reply to requesting instance: {err}");
+    }
+}
+
+#[cfg(unix)]
+async fn serve(
+    config: &'static Config,
+    mut release_rx: mpsc::UnboundedReceiver<tokio::net::UnixStream>,
+    vault_path: &Path,
+    time_zone: &'static TimeZone,
+) -> anyhow::Result<()> {
+    use tokio::select;
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    let mut delay = Duration::ZERO;
+    loop {
+        let opening = open_vault(vault_path, time_zone, delay);
+        tokio::pin!(opening);
+        let vault = loop {
+            select! {
+                vault = &mut opening => break vault?,
+                Some(stream) = release_rx.recv() => {
+                    // Another instance holds the vault, so there is nothing to
+                    // release. Backing off gives the requesting instance a
+                    // chance to open it once the other one is done.
+                    reply(stream, NOT_HELD).await;
+                    opening.set(open_vault(vault_path, time_zone, HANDOVER_GRACE));
+                }
+                _ = interrupt.recv() => return Ok(()),
+                _ = terminate.recv() => return Ok(()),
+            }
+        };
+
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        let mut rooms = connect_rooms(config, &vault, event_tx).await;
+        info!("Opened vault and connected {} rooms", rooms.len());
+
+        // Metrics are served by whoever holds the vault, so the requesting
+        // instance can take over the address.
+        let metrics = config
+            .metrics_addr
+            .clone()
+            .map(|addr| tokio::spawn(metrics::serve(addr, vault.euph())));
+
+        let requesting = loop {
+            select! {
+                Some(event) = event_rx.recv() => {
+                    let event_config = event.config();
+                    let id = RoomIdentifier::new(
+                        event_config.server.domain.clone(),
+                        event_config.room.clone(),
+                    );
+                    if let Some(room) = rooms.get_mut(&id) {
+                        room.handle_event(event).await;
+                    }
+                }
+                Some(stream) = release_rx.recv() => break Some(stream),
+                _ = interrupt.recv() => break None,
+                _ = terminate.recv() => break None,
+            }
+        };
+
+        // Dropping the rooms disconnects them.
+        drop(rooms);
+        if let Some(metrics) = metrics {
+            metrics.abort();
+            let _ = metrics.await;
+        }
+        vault.close().await;
+
+        let Some(stream) = requesting else {
+            return Ok(());
+        };
+        reply(stream, RELEASED).await;
+        info!("Released vault to another instance");
+        delay = HANDOVER_GRACE;
+    }
+}
@@ -0,0 +1,166 @@
+//! Generate sample data for trying out cove without connecting anywhere.
+//!
+//! The demo rooms live on a [`MockServer`] that cove connects to like to any
+//! other server.
+
+use cove_vault::{EuphVault, RoomIdentifier};
+use euphoxide::api::{Message, MessageId, SessionId, SessionView, Snowflake, Time, UserId};
+use time::{Duration, OffsetDateTime};
+
+use crate::mock_server::MockServer;
+
+pub const ROOMS: &[&str] = &["welcome", "music", "programming", "test"];
+
+const NICKS: &[&str] = &[
+    "Garmy",
+    "aurora",
+    "TumbleWeed",
+    "xyz",
+    "Bridget",
+    "coffee ☕",
+    "lurker",
+    "Pi",
+    "nils",
+];
+
+const CONTENTS: &[&str] = &[
+    "hello everyone!",
+    "hi :)",
+    "Has anyone seen the new release yet?",
+    "Not yet, what changed?",
+    "Threads are pretty neat once you get used to them",
+    "lol",
+    "I think so too",
+    "Try pressing `?` or F1 to see all key bindings",
+    "Does anyone know a good place to eat around here?",
+    "&music is where the cool people hang out",
+    "@aurora wanna join me in &programming later?",
+    "sure, why not",
+    "/me waves",
+    "This is a longer message to show how cove wraps text that does not fit onto a single \
+     line. It keeps going for a while so that even wide terminals will wrap it at least once.",
+    "https://euphoria.leet.nu/",
+    "brb",
+    "back",
+    "good night!",
+];
+
+/// A small deterministic pseudo-random number generator so the demo looks the
+/// same every time.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        // xorshift64
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+fn message(
+    id: u64,
+    parent: Option<MessageId>,
+    time: OffsetDateTime,
+    nick: &str,
+    content: &str,
+) -> Message {
+    let user_id = format!("agent:demo-{}", nick.to_lowercase().replace(' ', "-"));
+    Message {
+        id: MessageId(Snowflake(id)),
+        parent,
+        previous_edit_id: None,
+        time: Time(time),
+        content: content.to_string(),
+        encryption_key_id: None,
+        edited: None,
+        deleted: None,
+        truncated: false,
+        sender: SessionView {
+            id: UserId(user_id.clone()),
+            name: nick.to_string(),
+            server_id: "demo".to_string(),
+            server_era: "demo".to_string(),
+            session_id: SessionId(user_id),
+            is_staff: false,
+            is_manager: false,
+            client_address: None,
+            real_client_address: None,
+        },
+    }
+}
+
+fn generate_room(rng: &mut Rng, now: OffsetDateTime) -> Vec<Message> {
+    let mut msgs: Vec<Message> = vec![];
+    let mut time = now;
+    let mut next_id = 1_000_000;
+
+    let threads = 10 + rng.below(10);
+    for _ in 0..threads {
+        let thread_start = msgs.len();
+        let replies = rng.below(12);
+        for i in 0..=replies {
+            time += Duration::minutes(1 + rng.below(30) as i64);
+            let parent = if i == 0 {
+                None
+            } else {
+                // Prefer replying to recent messages to get deeper threads.
+                let candidates = &msgs[thread_start..];
+                let offset = rng.below(candidates.len().min(3));
+                Some(candidates[candidates.len() - 1 - offset].id)
+            };
+            let nick = rng.pick(NICKS);
+            let content = rng.pick(CONTENTS);
+            msgs.push(message(next_id, parent, time, nick, content));
+            next_id += 1 + rng.below(100) as u64;
+        }
+        time += Duration::minutes(30 + rng.below(180) as i64);
+    }
+
+    // Shift all messages so the newest one was sent a few minutes ago.
+    if let Some(newest) = msgs.last().map(|msg| msg.time.0) {
+        let shift = now - Duration::minutes(5) - newest;
+        for msg in &mut msgs {
+            msg.time.0 += shift;
+        }
+    }
+
+    msgs
+}
+
+/// Fill the server with generated rooms and threaded conversations, and add
+/// the rooms to the vault.
+///
+/// Messages from the last few hours are left unseen so that unseen message
+/// markers and counts are visible as well.
+pub async fn populate(vault: &EuphVault, server: &MockServer, domain: &str) -> anyhow::Result<()> {
+    let now = OffsetDateTime::now_utc();
+    let mut rng = Rng(0xc0fe_c0fe_c0fe_c0fe);
+
+    for name in ROOMS {
+        let room = vault.room(RoomIdentifier::new(domain.to_string(), name.to_string()));
+        let msgs = generate_room(&mut rng, now);
+        let first_joined = msgs
+            .iter()
+            .map(|msg| msg.time.0)
+            .max()
+            .map(|newest| newest - Duration::hours(3))
+            .unwrap_or(now);
+
+        // Messages older than the first join are marked as seen once they
+        // arrive from the server.
+        room.join(Time(first_joined)).await?;
+        server.add_msgs(name, msgs)?;
+    }
+
+    Ok(())
+}
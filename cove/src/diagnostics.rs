@@ -0,0 +1,38 @@
+//! Regularly checking why the vault may be slow and logging hints about it.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use cove_vault::Vault;
+use log::{debug, warn};
+
+use crate::macros::logging_unwrap;
+
+/// Time between two checks.
+const INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+pub async fn watch(vault: Vault) {
+    let mut logged = HashSet::new();
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        interval.tick().await;
+        let diagnostics = logging_unwrap!(vault.diagnose().await);
+
+        let mut new = false;
+        for hint in diagnostics.hints {
+            if logged.insert(hint.clone()) {
+                warn!("Vault: {hint}");
+                new = true;
+            }
+        }
+
+        // The plans are only interesting along with a hint.
+        if new {
+            for query in diagnostics.slow_queries {
+                let sql = query.sql.split_whitespace().collect::<Vec<_>>().join(" ");
+                let plan = query.plan.join("\n");
+                debug!("Slow vault query ({:.0?}): {sql}\n{plan}", query.duration);
+            }
+        }
+    }
+}
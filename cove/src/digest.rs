@@ -0,0 +1,229 @@
+//! Summarize the unseen messages of all rooms as markdown.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use cove_vault::{EuphRoomVault, EuphVault};
+use euphoxide::api::MessageId;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+
+use crate::euph::SmallMessage;
+
+const TIME_FORMAT: &[FormatItem<'_>] = format_description!("[year]-[month]-[day] [hour]:[minute]");
+
+#[derive(Debug, clap::Parser)]
+pub struct Args {
+    /// Location of the output file.
+    ///
+    /// If not set, the digest is written to stdout.
+    #[arg(long, short)]
+    out: Option<PathBuf>,
+
+    /// Mark all messages in the digest as seen afterwards.
+    #[arg(long, short)]
+    mark_seen: bool,
+}
+
+/// Unseen messages of a thread, in the order they were sent.
+struct Thread {
+    root: MessageId,
+    root_msg: Option<SmallMessage>,
+    msgs: Vec<SmallMessage>,
+}
+
+/// Collect the unseen messages of a room, grouped by thread. Threads are
+/// ordered by their oldest unseen message.
+async fn unseen_threads(vault: &EuphRoomVault) -> anyhow::Result<Vec<Thread>> {
+    let mut threads = Vec::<Thread>::new();
+    let mut roots = HashMap::new();
+
+    for (root, msg) in vault.unseen_threads().await? {
+        if msg.id == root {
+            roots.insert(root, msg.clone());
+        }
+        if msg.seen {
+            continue;
+        }
+        match threads.iter_mut().find(|thread| thread.root == root) {
+            Some(thread) => thread.msgs.push(msg),
+            None => threads.push(Thread {
+                root,
+                root_msg: None,
+                msgs: vec![msg],
+            }),
+        }
+    }
+
+    for thread in &mut threads {
+        thread.root_msg = roots.remove(&thread.root);
+    }
+
+    Ok(threads)
+}
+
+/// Escape characters that markdown would otherwise interpret, so nicks and
+/// message contents are rendered verbatim.
+fn escape_markdown(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut line_start = true;
+    let mut leading_digits = false;
+    for c in text.chars() {
+        let escape = match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '~' | '|' | '!' => true,
+            '-' | '+' | '=' => line_start,
+            '.' | ')' => leading_digits,
+            _ => false,
+        };
+        if escape {
+            result.push('\\');
+        }
+        result.push(c);
+
+        leading_digits = c.is_ascii_digit() && (line_start || leading_digits);
+        line_start = c == '\n' || (line_start && c == ' ');
+    }
+    result
+}
+
+fn write_msg<W: Write>(out: &mut W, msg: &SmallMessage) -> anyhow::Result<()> {
+    let time = msg
+        .time
+        .0
+        .format(TIME_FORMAT)
+        .expect("time can be formatted");
+    let content = escape_markdown(&msg.content);
+    let mut lines = content.lines();
+    let first = lines.next().unwrap_or_default();
+    let nick = escape_markdown(&msg.nick);
+    writeln!(out, "- {time} **[{nick}]** {first}")?;
+    for line in lines {
+        writeln!(out, "  {line}")?;
+    }
+    Ok(())
+}
+
+/// Returns the newest message that was included in the digest.
+async fn digest_room<W: Write>(
+    vault: &EuphRoomVault,
+    out: &mut W,
+) -> anyhow::Result<Option<MessageId>> {
+    let threads = unseen_threads(vault).await?;
+    if threads.is_empty() {
+        return Ok(None);
+    }
+
+    let room = vault.room();
+    let amount = threads
+        .iter()
+        .map(|thread| thread.msgs.len())
+        .sum::<usize>();
+    eprintln!(
+        "  &{} on {}: {amount} unseen messages",
+        room.name, room.domain
+    );
+    writeln!(out, "## &{} on {}", room.name, room.domain)?;
+
+    for thread in &threads {
+        writeln!(out)?;
+        match &thread.root_msg {
+            Some(root) => {
+                let first = root.content.lines().next().unwrap_or_default();
+                let first = escape_markdown(first);
+                let nick = escape_markdown(&root.nick);
+                writeln!(out, "### [{nick}] {first}")?;
+            }
+            None => writeln!(out, "### Unknown thread")?,
+        }
+        writeln!(out)?;
+        for msg in &thread.msgs {
+            write_msg(out, msg)?;
+        }
+    }
+    writeln!(out)?;
+
+    let newest = threads
+        .iter()
+        .flat_map(|thread| &thread.msgs)
+        .map(|msg| msg.id)
+        .max();
+    Ok(newest)
+}
+
+async fn write_digest<W: Write>(vault: &EuphVault, out: &mut W, args: &Args) -> anyhow::Result<()> {
+    let mut rooms = vault.rooms().await?;
+    rooms.sort_unstable();
+
+    writeln!(out, "# Unseen messages")?;
+    writeln!(out)?;
+
+    let mut empty = true;
+    for room in rooms {
+        let vault = vault.room(room);
+        let Some(newest) = digest_room(&vault, out).await? else {
+            continue;
+        };
+        empty = false;
+
+        if args.mark_seen {
+            vault.set_older_seen(newest, true).await?;
+        }
+    }
+
+    if empty {
+        eprintln!("  No unseen messages");
+        writeln!(out, "No unseen messages.")?;
+    }
+
+    Ok(())
+}
+
+pub async fn digest(vault: &EuphVault, args: Args) -> anyhow::Result<()> {
+    match &args.out {
+        Some(path) => {
+            eprintln!("Writing digest to {}", path.to_string_lossy());
+            let mut file = BufWriter::new(File::create(path)?);
+            write_digest(vault, &mut file, &args).await?;
+            file.flush()?;
+        }
+        None => {
+            eprintln!("Writing digest to stdout");
+            let mut stdout = BufWriter::new(io::stdout());
+            write_digest(vault, &mut stdout, &args).await?;
+            stdout.flush()?;
+        }
+    }
+
+    if args.mark_seen {
+        eprintln!("Marked all messages in the digest as seen");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_markdown;
+
+    #[test]
+    fn plain_text_is_unchanged() {
+        assert_eq!(escape_markdown("hello there, world"), "hello there, world");
+    }
+
+    #[test]
+    fn inline_markup_is_escaped() {
+        assert_eq!(escape_markdown("*hi* _x_ `y`"), r"\*hi\* \_x\_ \`y\`");
+        assert_eq!(escape_markdown("[a](b)"), r"\[a\](b)");
+        assert_eq!(escape_markdown(r"a\b"), r"a\\b");
+    }
+
+    #[test]
+    fn block_markup_is_escaped_only_at_line_start() {
+        assert_eq!(escape_markdown("- a-b"), r"\- a-b");
+        assert_eq!(escape_markdown("x\n  + y"), "x\n  \\+ y");
+        assert_eq!(escape_markdown("12. x 3."), r"12\. x 3.");
+        assert_eq!(escape_markdown("# a"), r"\# a");
+    }
+}
@@ -1,7 +1,17 @@
+mod hooks;
+mod notify;
+mod retention;
 mod room;
 mod small_message;
+mod store;
+mod translate;
 mod util;
+mod webhooks;
 
+pub use notify::*;
+pub use retention::*;
 pub use room::*;
 pub use small_message::*;
+pub use translate::*;
 pub use util::*;
+pub use webhooks::*;
@@ -0,0 +1,38 @@
+//! Running the external commands configured via the `hooks` config options.
+
+use std::process::Stdio;
+
+use log::{debug, warn};
+
+use crate::util;
+
+/// Run a hook command in the background.
+///
+/// The command's output is discarded so it doesn't mess up the terminal.
+pub fn run(hook: &str, command: &Option<String>, env: &[(&str, &str)]) {
+    let Some(command) = command else { return };
+
+    let mut cmd = util::shell(command);
+    cmd.env("COVE_HOOK", hook)
+        .envs(env.iter().copied())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            warn!("{hook}: failed to run hook command: {err}");
+            return;
+        }
+    };
+
+    let hook = hook.to_string();
+    tokio::task::spawn(async move {
+        match child.wait().await {
+            Ok(status) if status.success() => debug!("{hook}: hook command finished"),
+            Ok(status) => warn!("{hook}: hook command failed ({status})"),
+            Err(err) => warn!("{hook}: failed to wait for hook command: {err}"),
+        }
+    });
+}
@@ -0,0 +1,97 @@
+//! Running the command configured via `notify.command` when new messages
+//! arrive in rooms the user isn't looking at.
+
+use cove_config::{Notify, NotifyOn};
+use cove_vault::{mentions, EuphRoomVault};
+use euphoxide::api::Message;
+use euphoxide::conn::Joined;
+
+use crate::macros::logging_unwrap;
+
+use super::hooks;
+
+/// How relevant a new message by somebody else is to the user.
+///
+/// Mentions take precedence over replies, so a reply mentioning the user's
+/// nick is a mention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relevance {
+    /// The message mentions the user's current nick.
+    Mention,
+    /// The message replies to one of the user's messages or is in a thread the
+    /// user subscribed to.
+    Reply,
+    /// Any other message.
+    Message,
+}
+
+impl Relevance {
+    pub async fn of(vault: &EuphRoomVault, joined: &Joined, msg: &Message) -> Self {
+        if mentions(&msg.content, &joined.session.name) {
+            return Self::Mention;
+        }
+
+        if let Some(parent) = msg.parent {
+            let own_parent = logging_unwrap!(vault.full_msg(parent).await)
+                .is_some_and(|parent| parent.sender.id == joined.session.id);
+            if own_parent || logging_unwrap!(vault.in_subscribed_thread(parent).await) {
+                return Self::Reply;
+            }
+        }
+
+        Self::Message
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Mention => "mention",
+            Self::Reply => "reply",
+            Self::Message => "message",
+        }
+    }
+}
+
+/// Why the command should be run for a message, if at all.
+async fn reason(
+    vault: &EuphRoomVault,
+    joined: &Joined,
+    on: &[NotifyOn],
+    msg: &Message,
+) -> Option<&'static str> {
+    let relevance = Relevance::of(vault, joined, msg).await;
+    let wanted = match relevance {
+        Relevance::Mention => on.contains(&NotifyOn::Mention),
+        Relevance::Reply => on.contains(&NotifyOn::Reply),
+        Relevance::Message => false,
+    };
+    (wanted || on.contains(&NotifyOn::All)).then_some(relevance.name())
+}
+
+/// Run the command for a new message if it matches `on`.
+pub async fn notify(
+    config: &Notify,
+    on: &[NotifyOn],
+    vault: &EuphRoomVault,
+    joined: &Joined,
+    msg: &Message,
+) {
+    if config.command.is_none() || msg.sender.id == joined.session.id {
+        return;
+    }
+    let Some(reason) = reason(vault, joined, on, msg).await else {
+        return;
+    };
+
+    let room = vault.room();
+    let id = msg.id.0.to_string();
+    let env = [
+        ("COVE_DOMAIN", room.domain.as_str()),
+        ("COVE_ROOM", room.name.as_str()),
+        ("COVE_NICK", joined.session.name.as_str()),
+        ("COVE_SENDER", msg.sender.name.as_str()),
+        ("COVE_CONTENT", msg.content.as_str()),
+        ("COVE_MESSAGE_ID", id.as_str()),
+        ("COVE_REASON", reason),
+    ];
+    hooks::run("notify", &config.command, &env);
+}
@@ -0,0 +1,24 @@
+//! Purging the content of deleted messages once the `deleted_retention` config
+//! option's window has passed.
+
+use std::time::Duration;
+
+use cove_config::Config;
+use cove_vault::EuphVault;
+use euphoxide::api::Time;
+use time::OffsetDateTime;
+
+use crate::macros::logging_unwrap;
+
+/// Time between two purges.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub async fn purge_deleted_content(config: &'static Config, vault: EuphVault) {
+    let retention = time::Duration::days(config.deleted_retention.unwrap_or(0) as i64);
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let before = Time(OffsetDateTime::now_utc() - retention);
+        logging_unwrap!(vault.purge_deleted_content(before).await);
+    }
+}
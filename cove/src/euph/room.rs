@@ -1,12 +1,16 @@
 // TODO Remove rl2dev-specific code
 
+use std::collections::HashSet;
 use std::convert::Infallible;
-use std::time::Duration;
+use std::mem;
+use std::time::{Duration, Instant};
 
+use cove_config::Hooks;
+use cove_vault::EuphRoomVault;
 use euphoxide::api::packet::ParsedPacket;
 use euphoxide::api::{
-    Auth, AuthOption, Data, Log, Login, Logout, MessageId, Nick, Send, SendEvent, SendReply, Time,
-    UserId,
+    Auth, AuthOption, Data, GetMessage, GetMessageReply, Log, Login, Logout, Message, MessageId,
+    Nick, Send, SendEvent, SendReply, Time, UserId,
 };
 use euphoxide::bot::instance::{ConnSnapshot, Event, Instance, InstanceConfig};
 use euphoxide::conn::{self, ConnTx, Joined};
@@ -14,11 +18,17 @@ use log::{debug, error, info, warn};
 use tokio::select;
 use tokio::sync::oneshot;
 
+use crate::logger::THREAD_TARGET;
 use crate::macros::logging_unwrap;
-use crate::vault::EuphRoomVault;
+use crate::metrics;
+
+use super::{hooks, Relevance, Webhooks};
 
 const LOG_INTERVAL: Duration = Duration::from_secs(10);
 
+/// How often traffic stats are written to the vault.
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum State {
@@ -51,25 +61,72 @@ pub enum Error {
     NotConnected,
 }
 
+/// Traffic that hasn't been written to the vault yet.
+#[derive(Debug)]
+struct PendingStats {
+    since: Instant,
+    /// When the first of the pending events was received. The vault groups
+    /// stats by hour, so pending stats never span more than one hour.
+    received: Option<Time>,
+    events: u64,
+}
+
+impl PendingStats {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            received: None,
+            events: 0,
+        }
+    }
+}
+
+fn same_hour(a: Time, b: Time) -> bool {
+    let hour = |time: Time| time.0.unix_timestamp().div_euclid(60 * 60);
+    hour(a) == hour(b)
+}
+
 #[derive(Debug)]
 pub struct Room {
     vault: EuphRoomVault,
     ephemeral: bool,
-
-    instance: Instance,
+    /// Whether older messages are not downloaded in the background, see the
+    /// `low_bandwidth` config option.
+    low_bandwidth: bool,
+    hooks: &'static Hooks,
+    webhooks: Webhooks,
+
+    /// `None` if the room only replays packets, see [`Self::replay`].
+    instance: Option<Instance>,
     state: State,
+    /// When the last event was received from the instance.
+    last_event: Instant,
+    /// Connection attempts since the room was last joined.
+    attempts: u64,
+    pending_stats: PendingStats,
 
     /// `None` before any `snapshot-event`, then either `Some(None)` or
     /// `Some(Some(id))`. Reset whenever connection is lost.
     last_msg_id: Option<Option<MessageId>>,
 
+    /// Requested messages whose ancestors should be requested as well once
+    /// they arrive, see [`Self::get_msg_with_ancestors`].
+    ancestors_requested: HashSet<MessageId>,
+
     /// `Some` while `Self::regularly_request_logs` is running. Set to `None` to
     /// drop the sender and stop the task.
     log_request_canary: Option<oneshot::Sender<Infallible>>,
 }
 
 impl Room {
-    pub fn new<F>(vault: EuphRoomVault, instance_config: InstanceConfig, on_event: F) -> Self
+    pub fn new<F>(
+        vault: EuphRoomVault,
+        hooks: &'static Hooks,
+        webhooks: Webhooks,
+        instance_config: InstanceConfig,
+        low_bandwidth: bool,
+        on_event: F,
+    ) -> Self
     where
         F: Fn(Event) + std::marker::Send + Sync + 'static,
     {
@@ -83,75 +140,174 @@ impl Room {
         Self {
             vault,
             ephemeral,
-            instance: instance_config.build(on_event),
+            low_bandwidth,
+            hooks,
+            webhooks,
+            instance: Some(instance_config.build(on_event)),
+            state: State::Disconnected,
+            last_event: Instant::now(),
+            attempts: 0,
+            pending_stats: PendingStats::new(),
+            last_msg_id: None,
+            ancestors_requested: HashSet::new(),
+            log_request_canary: None,
+        }
+    }
+
+    /// A room that never connects and is only fed packets recorded in an
+    /// earlier session via [`Self::replay_packet`], see `--replay`.
+    pub fn replay(vault: EuphRoomVault, hooks: &'static Hooks, webhooks: Webhooks) -> Self {
+        let ephemeral = vault.vault().vault().ephemeral();
+        Self {
+            vault,
+            ephemeral,
+            low_bandwidth: false,
+            hooks,
+            webhooks,
+            instance: None,
             state: State::Disconnected,
+            last_event: Instant::now(),
+            attempts: 0,
+            pending_stats: PendingStats::new(),
             last_msg_id: None,
+            ancestors_requested: HashSet::new(),
             log_request_canary: None,
         }
     }
 
     pub fn stopped(&self) -> bool {
-        self.instance.stopped()
+        self.instance.as_ref().is_some_and(|i| i.stopped())
     }
 
-    pub fn instance(&self) -> &Instance {
-        &self.instance
+    /// `None` if the room only replays packets.
+    pub fn instance(&self) -> Option<&Instance> {
+        self.instance.as_ref()
     }
 
     pub fn state(&self) -> &State {
         &self.state
     }
 
+    /// How often the instance tried to connect since the room was last joined.
+    pub fn attempts(&self) -> u64 {
+        self.attempts
+    }
+
     fn conn_tx(&self) -> Result<&ConnTx, Error> {
         self.state.conn_tx().ok_or(Error::NotConnected)
     }
 
+    /// How long ago the server last sent anything. Only available while
+    /// connected.
+    pub fn idle_time(&self) -> Option<Duration> {
+        match self.state {
+            State::Connected(..) => Some(self.last_event.elapsed()),
+            _ => None,
+        }
+    }
+
     pub async fn handle_event(&mut self, event: Event) {
+        self.last_event = Instant::now();
+        let was_joined = self.state.joined().is_some();
+        self.apply_event(event).await;
+        match (was_joined, self.state.joined().is_some()) {
+            (false, true) => metrics::room_joined(),
+            (true, false) => metrics::room_left(),
+            _ => {}
+        }
+    }
+
+    async fn apply_event(&mut self, event: Event) {
         match event {
             Event::Connecting(_) => {
                 self.state = State::Connecting;
+                self.attempts += 1;
+                metrics::connection_attempted();
+                info!(
+                    target: THREAD_TARGET,
+                    room:? = self.vault.room();
+                    "{}: connecting (attempt {})",
+                    self.vault.room().name,
+                    self.attempts
+                );
 
                 // Juuust to make sure
                 self.last_msg_id = None;
                 self.log_request_canary = None;
             }
             Event::Connected(_, ConnSnapshot { conn_tx, state }) => {
-                if !self.ephemeral {
-                    let (tx, rx) = oneshot::channel();
-                    self.log_request_canary = Some(tx);
-                    let vault_clone = self.vault.clone();
-                    let conn_tx_clone = conn_tx.clone();
-                    debug!("{}: spawning log request task", self.instance.config().room);
-                    tokio::task::spawn(async move {
-                        select! {
-                            _ = rx => {},
-                            _ = Self::regularly_request_logs(vault_clone, conn_tx_clone) => {},
-                        }
-                    });
+                if !self.low_bandwidth {
+                    self.start_requesting_logs(&conn_tx);
                 }
 
                 self.state = State::Connected(conn_tx, state);
 
-                let cookies = &*self.instance.config().server.cookies;
-                let cookies = cookies.lock().unwrap().clone();
-                let domain = self.vault.room().domain.clone();
-                logging_unwrap!(self.vault.vault().set_cookies(domain, cookies).await);
+                if let Some(instance) = &self.instance {
+                    let cookies = instance.config().server.cookies.lock().unwrap().clone();
+                    let domain = self.vault.room().domain.clone();
+                    logging_unwrap!(self.vault.vault().set_cookies(domain, cookies).await);
+                }
             }
             Event::Packet(_, packet, ConnSnapshot { conn_tx, state }) => {
+                if let conn::State::Joined(_) = state {
+                    self.attempts = 0;
+                }
                 self.state = State::Connected(conn_tx, state);
+                self.record_stats().await;
                 self.on_packet(packet).await;
             }
             Event::Disconnected(_) => {
+                self.flush_stats().await;
+                if let State::Connected(..) = self.state {
+                    self.run_hook("on_disconnect", &self.hooks.on_disconnect, &[]);
+                }
                 self.state = State::Disconnected;
                 self.last_msg_id = None;
+                self.ancestors_requested.clear();
                 self.log_request_canary = None;
             }
             Event::Stopped(_) => {
+                self.flush_stats().await;
                 self.state = State::Stopped;
             }
         }
     }
 
+    /// Stop or resume downloading older messages in the background.
+    pub fn set_low_bandwidth(&mut self, enabled: bool) {
+        self.low_bandwidth = enabled;
+        if enabled {
+            self.log_request_canary = None;
+        } else if self.log_request_canary.is_none() {
+            if let State::Connected(conn_tx, _) = &self.state {
+                let conn_tx = conn_tx.clone();
+                self.start_requesting_logs(&conn_tx);
+            }
+        }
+    }
+
+    fn start_requesting_logs(&mut self, conn_tx: &ConnTx) {
+        if self.ephemeral {
+            return;
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.log_request_canary = Some(tx);
+        let vault_clone = self.vault.clone();
+        let conn_tx_clone = conn_tx.clone();
+        debug!(
+            room:? = self.vault.room();
+            "{}: spawning log request task",
+            self.vault.room().name
+        );
+        tokio::task::spawn(async move {
+            select! {
+                _ = rx => {},
+                _ = Self::regularly_request_logs(vault_clone, conn_tx_clone) => {},
+            }
+        });
+    }
+
     async fn regularly_request_logs(vault: EuphRoomVault, conn_tx: ConnTx) {
         // TODO Make log downloading smarter
 
@@ -192,7 +348,7 @@ impl Room {
             None => None,
         };
 
-        debug!("{:?}: requesting logs", vault.room());
+        debug!(room:? = vault.room(); "{}: requesting logs", vault.room().name);
 
         // &rl2dev's message history is broken and requesting old messages past
         // a certain point results in errors. By reducing the amount of messages
@@ -217,8 +373,67 @@ impl Room {
         }
     }
 
+    fn run_hook(&self, hook: &str, command: &Option<String>, env: &[(&str, &str)]) {
+        let room = self.vault.room();
+        let nick = self.state.joined().map(|j| j.session.name.as_str());
+        let mut env = env.to_vec();
+        env.push(("COVE_DOMAIN", room.domain.as_str()));
+        env.push(("COVE_ROOM", room.name.as_str()));
+        env.push(("COVE_NICK", nick.unwrap_or_default()));
+        hooks::run(hook, command, &env);
+    }
+
+    async fn on_send_event(&self, msg: &Message) {
+        self.webhooks.send(self.vault.room(), msg);
+
+        let Some(joined) = self.state.joined() else {
+            return;
+        };
+        if msg.sender.id == joined.session.id {
+            return;
+        }
+        if Relevance::of(&self.vault, joined, msg).await != Relevance::Mention {
+            return;
+        }
+
+        let id = msg.id.0.to_string();
+        let env = [
+            ("COVE_SENDER", msg.sender.name.as_str()),
+            ("COVE_CONTENT", msg.content.as_str()),
+            ("COVE_MESSAGE_ID", id.as_str()),
+        ];
+        self.run_hook("on_mention", &self.hooks.on_mention, &env);
+    }
+
+    async fn record_stats(&mut self) {
+        let received = Time::now();
+        if let Some(pending) = self.pending_stats.received {
+            if !same_hour(pending, received) {
+                self.flush_stats().await;
+            }
+        }
+
+        let pending = &mut self.pending_stats;
+        pending.received.get_or_insert(received);
+        pending.events += 1;
+        if pending.since.elapsed() >= STATS_INTERVAL {
+            self.flush_stats().await;
+        }
+    }
+
+    /// Write the pending traffic stats to the vault, grouped by the hour they
+    /// were received in.
+    async fn flush_stats(&mut self) {
+        let stats = mem::replace(&mut self.pending_stats, PendingStats::new());
+        let Some(received) = stats.received else {
+            return;
+        };
+        logging_unwrap!(self.vault.add_stats(received, stats.events).await);
+    }
+
     async fn on_packet(&mut self, packet: ParsedPacket) {
-        let room_name = &self.instance.config().room;
+        let room = self.vault.room();
+        let room_name = &room.name;
         let Ok(data) = &packet.content else {
             return;
         };
@@ -227,31 +442,36 @@ impl Room {
             Data::DisconnectEvent(_) => {}
             Data::HelloEvent(_) => {}
             Data::JoinEvent(d) => {
-                debug!("{room_name}: {:?} joined", d.0.name);
+                debug!(room:? = room; "{room_name}: {:?} joined", d.0.name);
             }
             Data::LoginEvent(_) => {}
             Data::LogoutEvent(_) => {}
             Data::NetworkEvent(d) => {
-                warn!("{room_name}: network event ({})", d.r#type);
+                warn!(room:? = room; "{room_name}: network event ({})", d.r#type);
             }
             Data::NickEvent(d) => {
-                debug!("{room_name}: {:?} renamed to {:?}", d.from, d.to);
+                debug!(room:? = room; "{room_name}: {:?} renamed to {:?}", d.from, d.to);
             }
             Data::EditMessageEvent(_) => {
-                info!("{room_name}: a message was edited");
+                info!(room:? = room; "{room_name}: a message was edited");
             }
             Data::PartEvent(d) => {
-                debug!("{room_name}: {:?} left", d.0.name);
+                debug!(room:? = room; "{room_name}: {:?} left", d.0.name);
             }
             Data::PingEvent(_) => {}
             Data::PmInitiateEvent(d) => {
                 // TODO Show info popup and automatically join PM room
                 info!(
+                    room:? = room;
                     "{room_name}: {:?} initiated a pm from &{}",
                     d.from_nick, d.from_room
                 );
             }
             Data::SendEvent(SendEvent(msg)) | Data::SendReply(SendReply(msg)) => {
+                if let Data::SendEvent(_) = data {
+                    self.on_send_event(msg).await;
+                }
+
                 let own_user_id = self.own_user_id();
                 if let Some(last_msg_id) = &mut self.last_msg_id {
                     logging_unwrap!(
@@ -259,11 +479,13 @@ impl Room {
                             .add_msg(Box::new(msg.clone()), *last_msg_id, own_user_id)
                             .await
                     );
+                    metrics::msgs_received(1);
                     *last_msg_id = Some(msg.id);
                 }
             }
             Data::SnapshotEvent(d) => {
-                info!("{room_name}: successfully joined");
+                info!(room:? = room; "{room_name}: successfully joined");
+                self.run_hook("on_join", &self.hooks.on_join, &[]);
                 logging_unwrap!(self.vault.join(Time::now()).await);
                 self.last_msg_id = Some(d.log.last().map(|m| m.id));
                 logging_unwrap!(
@@ -271,6 +493,25 @@ impl Room {
                         .add_msgs(d.log.clone(), None, self.own_user_id())
                         .await
                 );
+                metrics::msgs_received(d.log.len());
+            }
+            Data::GetMessageReply(GetMessageReply(msg)) => {
+                // The message was requested on its own, so only its own id is
+                // known to have no gaps around it.
+                logging_unwrap!(
+                    self.vault
+                        .add_msg(Box::new(msg.clone()), Some(msg.id), self.own_user_id())
+                        .await
+                );
+                metrics::msgs_received(1);
+
+                if self.ancestors_requested.remove(&msg.id) {
+                    if let Some(parent) = msg.parent {
+                        if logging_unwrap!(self.vault.msg(parent).await).is_none() {
+                            let _ = self.get_msg_with_ancestors(parent);
+                        }
+                    }
+                }
             }
             Data::LogReply(d) => {
                 logging_unwrap!(
@@ -278,11 +519,19 @@ impl Room {
                         .add_msgs(d.log.clone(), d.before, self.own_user_id())
                         .await
                 );
+                metrics::msgs_received(d.log.len());
             }
             _ => {}
         }
     }
 
+    /// Handle a packet recorded in an earlier session like one received from
+    /// the server, except that traffic stats aren't recorded again.
+    pub async fn replay_packet(&mut self, packet: ParsedPacket) {
+        self.last_event = Instant::now();
+        self.on_packet(packet).await;
+    }
+
     pub fn auth(&self, password: String) -> Result<(), Error> {
         self.conn_tx()?.send_only(Auth {
             r#type: AuthOption::Passcode,
@@ -298,6 +547,19 @@ impl Room {
         Ok(())
     }
 
+    pub fn get_msg(&self, id: MessageId) -> Result<(), Error> {
+        self.conn_tx()?.send_only(GetMessage { id });
+        Ok(())
+    }
+
+    /// Request a message and, one after another, all of its ancestors that are
+    /// not in the vault yet.
+    pub fn get_msg_with_ancestors(&mut self, id: MessageId) -> Result<(), Error> {
+        self.get_msg(id)?;
+        self.ancestors_requested.insert(id);
+        Ok(())
+    }
+
     pub fn nick(&self, name: String) -> Result<(), Error> {
         self.conn_tx()?.send_only(Nick { name });
         Ok(())
@@ -332,3 +594,11 @@ impl Room {
         Ok(())
     }
 }
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        if self.state.joined().is_some() {
+            metrics::room_left();
+        }
+    }
+}
@@ -1,25 +1,18 @@
 use std::mem;
 
+use cove_config::LongWords;
+use cove_vault::nick_char;
 use crossterm::style::Stylize;
-use euphoxide::api::{MessageId, Snowflake, Time};
+use euphoxide::api::{MessageId, Snowflake};
 use time::OffsetDateTime;
 use toss::{Style, Styled};
-use tz::TimeZone;
 
 use crate::store::Msg;
 use crate::ui::ChatMsg;
 
 use super::util;
 
-fn nick_char(ch: char) -> bool {
-    // Closely following the heim mention regex:
-    // https://github.com/euphoria-io/heim/blob/978c921063e6b06012fc8d16d9fbf1b3a0be1191/client/lib/stores/chat.js#L14-L15
-    // `>` has been experimentally confirmed to delimit mentions as well.
-    match ch {
-        ',' | '.' | '!' | '?' | ';' | '&' | '<' | '>' | '\'' | '"' => false,
-        _ => !ch.is_whitespace(),
-    }
-}
+pub use cove_vault::{mentions, SmallMessage};
 
 fn room_char(ch: char) -> bool {
     // Basically just \w, see also
@@ -38,6 +31,9 @@ struct Highlighter<'a> {
     content: &'a str,
     base_style: Style,
     exact: bool,
+    /// Byte offsets after which zero-width spaces are inserted, see
+    /// [`crate::util::long_word_breaks`].
+    breaks: Vec<usize>,
 
     span: Span,
     span_start: usize,
@@ -115,13 +111,27 @@ impl<'a> Highlighter<'a> {
             return;
         }
 
-        self.result =
-            mem::take(&mut self.result).then(&self.content[self.span_start..idx], self.base_style);
+        let text = self.text_with_breaks(self.span_start, idx);
+        self.result = mem::take(&mut self.result).then(text, self.base_style);
 
         self.span = Span::Nothing;
         self.span_start = idx;
     }
 
+    /// The content between two byte offsets, with zero-width spaces at the
+    /// breaks within it.
+    fn text_with_breaks(&self, start: usize, end: usize) -> String {
+        let mut result = String::with_capacity(end - start);
+        let mut last = start;
+        for &at in self.breaks.iter().filter(|at| start < **at && **at <= end) {
+            result.push_str(&self.content[last..at]);
+            result.push('\u{200b}');
+            last = at;
+        }
+        result.push_str(&self.content[last..end]);
+        result
+    }
+
     fn close_span_before_current_char(&mut self, idx: usize, char: char) {
         match self.span {
             Span::Mention if !nick_char(char) => self.close_mention(idx),
@@ -178,18 +188,24 @@ impl<'a> Highlighter<'a> {
         self.room_or_mention_possible = !char.is_alphanumeric();
     }
 
-    fn highlight(content: &'a str, base_style: Style, exact: bool) -> Styled {
+    fn highlight(content: &'a str, base_style: Style, exact: bool, breaks: bool) -> Styled {
+        let content = if exact { content } else { content.trim() };
         let mut this = Self {
-            content: if exact { content } else { content.trim() },
+            content,
             base_style,
             exact,
+            breaks: if breaks {
+                crate::util::long_word_breaks(content)
+            } else {
+                vec![]
+            },
             span: Span::Nothing,
             span_start: 0,
             room_or_mention_possible: true,
             result: Styled::default(),
         };
 
-        for (idx, char) in (if exact { content } else { content.trim() }).char_indices() {
+        for (idx, char) in content.char_indices() {
             this.step(idx, char);
         }
 
@@ -199,19 +215,10 @@ impl<'a> Highlighter<'a> {
     }
 }
 
-fn highlight_content(content: &str, base_style: Style, exact: bool) -> Styled {
-    Highlighter::highlight(content, base_style, exact)
-}
-
-#[derive(Debug, Clone)]
-pub struct SmallMessage {
-    pub id: MessageId,
-    pub parent: Option<MessageId>,
-    pub time: Time,
-    pub time_zone: &'static TimeZone,
-    pub nick: String,
-    pub content: String,
-    pub seen: bool,
+/// Highlight mentions, rooms and emoji. With `breaks`, lines may additionally
+/// be broken after punctuation in long words.
+fn highlight_content(content: &str, base_style: Style, exact: bool, breaks: bool) -> Styled {
+    Highlighter::highlight(content, base_style, exact, breaks)
 }
 
 fn as_me(content: &str) -> Option<&str> {
@@ -222,24 +229,24 @@ fn style_me() -> Style {
     Style::new().grey().italic()
 }
 
-fn styled_nick(nick: &str) -> Styled {
+fn styled_nick(nick: &str, width: Option<usize>) -> Styled {
     Styled::new_plain("[")
-        .and_then(util::style_nick(nick, Style::new()))
+        .and_then(util::style_nick_within(nick, Style::new(), width))
         .then_plain("]")
 }
 
-fn styled_nick_me(nick: &str) -> Styled {
+fn styled_nick_me(nick: &str, width: Option<usize>) -> Styled {
     let style = style_me();
-    Styled::new("*", style).and_then(util::style_nick(nick, style))
+    Styled::new("*", style).and_then(util::style_nick_within(nick, style, width))
 }
 
-fn styled_content(content: &str) -> Styled {
-    highlight_content(content.trim(), Style::new(), false)
+fn styled_content(content: &str, breaks: bool) -> Styled {
+    highlight_content(content.trim(), Style::new(), false, breaks)
 }
 
-fn styled_content_me(content: &str) -> Styled {
+fn styled_content_me(content: &str, breaks: bool) -> Styled {
     let style = style_me();
-    highlight_content(content.trim(), style, false).then("*", style)
+    highlight_content(content.trim(), style, false, breaks).then("*", style)
 }
 
 fn styled_editor_content(content: &str) -> Styled {
@@ -248,7 +255,40 @@ fn styled_editor_content(content: &str) -> Styled {
     } else {
         Style::new()
     };
-    highlight_content(content, style, true)
+    highlight_content(content, style, true, false)
+}
+
+fn styled_pseudo(
+    nick: &str,
+    content: &str,
+    breaks: bool,
+    nick_width: Option<usize>,
+) -> (Styled, Styled) {
+    if let Some(content) = as_me(content) {
+        (
+            styled_nick_me(nick, nick_width),
+            styled_content_me(content, breaks),
+        )
+    } else {
+        (
+            styled_nick(nick, nick_width),
+            styled_content(content, breaks),
+        )
+    }
+}
+
+fn styled_msg(
+    msg: &SmallMessage,
+    content: &str,
+    breaks: bool,
+    nick_width: Option<usize>,
+) -> (Styled, Styled) {
+    let (nick, content) = styled_pseudo(&msg.nick, content, breaks, nick_width);
+    if msg.has_note {
+        (nick, content.then(" [note]", Style::new().yellow()))
+    } else {
+        (nick, content)
+    }
 }
 
 impl Msg for SmallMessage {
@@ -266,29 +306,41 @@ impl Msg for SmallMessage {
         self.seen
     }
 
+    fn time(&self) -> Option<OffsetDateTime> {
+        crate::util::convert_to_time_zone(self.time_zone, self.time.0)
+    }
+
     fn last_possible_id() -> Self::Id {
         MessageId(Snowflake::MAX)
     }
 }
 
 impl ChatMsg for SmallMessage {
-    fn time(&self) -> Option<OffsetDateTime> {
-        crate::util::convert_to_time_zone(self.time_zone, self.time.0)
+    fn styled(&self) -> (Styled, Styled) {
+        styled_msg(self, &self.content, false, None)
     }
 
-    fn styled(&self) -> (Styled, Styled) {
-        Self::pseudo(&self.nick, &self.content)
+    fn styled_prepared(&self, long_words: LongWords, nick_width: Option<u16>) -> (Styled, Styled) {
+        let nick_width = nick_width.map(usize::from);
+        match long_words {
+            LongWords::Break => styled_msg(self, &self.content, false, nick_width),
+            LongWords::Punctuation => styled_msg(self, &self.content, true, nick_width),
+            LongWords::Truncate => {
+                let content = crate::util::truncate_long_words(&self.content);
+                styled_msg(self, &content, false, nick_width)
+            }
+        }
+    }
+
+    fn sent_by(&self, user_id: &str) -> bool {
+        self.user_id.0 == user_id
     }
 
     fn edit(nick: &str, content: &str) -> (Styled, Styled) {
-        (styled_nick(nick), styled_editor_content(content))
+        (styled_nick(nick, None), styled_editor_content(content))
     }
 
     fn pseudo(nick: &str, content: &str) -> (Styled, Styled) {
-        if let Some(content) = as_me(content) {
-            (styled_nick_me(nick), styled_content_me(content))
-        } else {
-            (styled_nick(nick), styled_content(content))
-        }
+        styled_pseudo(nick, content, false, None)
     }
 }
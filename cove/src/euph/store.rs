@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use cove_config::SiblingOrder;
+use cove_vault::{EuphRoomVault, MsgChangeKind};
+use euphoxide::api::MessageId;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+use crate::store::{MsgStore, Path, StoreChange, Tree};
+
+use super::SmallMessage;
+
+#[async_trait]
+impl MsgStore<SmallMessage> for EuphRoomVault {
+    type Error = vault::tokio::Error<rusqlite::Error>;
+
+    async fn path(&self, id: &MessageId) -> Result<Path<MessageId>, Self::Error> {
+        Ok(Path::new(self.path(*id).await?))
+    }
+
+    async fn msg(&self, id: &MessageId) -> Result<Option<SmallMessage>, Self::Error> {
+        self.msg(*id).await
+    }
+
+    async fn tree(
+        &self,
+        root_id: &MessageId,
+        order: SiblingOrder,
+    ) -> Result<Tree<SmallMessage>, Self::Error> {
+        Ok(Tree::new(*root_id, self.tree(*root_id).await?, order))
+    }
+
+    async fn first_root_id(&self) -> Result<Option<MessageId>, Self::Error> {
+        self.first_root_id().await
+    }
+
+    async fn last_root_id(&self) -> Result<Option<MessageId>, Self::Error> {
+        self.last_root_id().await
+    }
+
+    async fn prev_root_id(&self, root_id: &MessageId) -> Result<Option<MessageId>, Self::Error> {
+        self.prev_root_id(*root_id).await
+    }
+
+    async fn next_root_id(&self, root_id: &MessageId) -> Result<Option<MessageId>, Self::Error> {
+        self.next_root_id(*root_id).await
+    }
+
+    async fn oldest_msg_id(&self) -> Result<Option<MessageId>, Self::Error> {
+        self.oldest_msg_id().await
+    }
+
+    async fn newest_msg_id(&self) -> Result<Option<MessageId>, Self::Error> {
+        self.newest_msg_id().await
+    }
+
+    async fn older_msg_id(&self, id: &MessageId) -> Result<Option<MessageId>, Self::Error> {
+        self.older_msg_id(*id).await
+    }
+
+    async fn newer_msg_id(&self, id: &MessageId) -> Result<Option<MessageId>, Self::Error> {
+        self.newer_msg_id(*id).await
+    }
+
+    async fn oldest_unseen_msg_id(&self) -> Result<Option<MessageId>, Self::Error> {
+        self.oldest_unseen_msg_id().await
+    }
+
+    async fn newest_unseen_msg_id(&self) -> Result<Option<MessageId>, Self::Error> {
+        self.newest_unseen_msg_id().await
+    }
+
+    async fn older_unseen_msg_id(&self, id: &MessageId) -> Result<Option<MessageId>, Self::Error> {
+        self.older_unseen_msg_id(*id).await
+    }
+
+    async fn newer_unseen_msg_id(&self, id: &MessageId) -> Result<Option<MessageId>, Self::Error> {
+        self.newer_unseen_msg_id(*id).await
+    }
+
+    async fn unseen_msgs_count(&self) -> Result<usize, Self::Error> {
+        self.unseen_msgs_count().await
+    }
+
+    async fn set_seen(&self, id: &MessageId, seen: bool) -> Result<(), Self::Error> {
+        self.set_seen(*id, seen).await?;
+        Ok(())
+    }
+
+    async fn set_many_seen(&self, ids: &[MessageId], seen: bool) -> Result<(), Self::Error> {
+        self.set_many_seen(ids.to_vec(), seen).await?;
+        Ok(())
+    }
+
+    async fn set_older_seen(&self, id: &MessageId, seen: bool) -> Result<(), Self::Error> {
+        self.set_older_seen(*id, seen).await?;
+        Ok(())
+    }
+
+    async fn set_tree_seen(&self, root_id: &MessageId, seen: bool) -> Result<(), Self::Error> {
+        self.set_tree_seen(*root_id, seen).await?;
+        Ok(())
+    }
+
+    fn subscribe(&self) -> mpsc::UnboundedReceiver<StoreChange> {
+        let mut changes = self.vault().vault().subscribe();
+        let room = self.room().clone();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::task::spawn(async move {
+            loop {
+                let change = match changes.recv().await {
+                    Ok(change) if change.room.as_ref().is_some_and(|r| *r != room) => continue,
+                    Ok(change) => match change.kind {
+                        MsgChangeKind::Added => StoreChange::Added,
+                        MsgChangeKind::Seen => StoreChange::Seen,
+                        MsgChangeKind::Deleted => StoreChange::Deleted,
+                        MsgChangeKind::Note => StoreChange::Note,
+                    },
+                    // Some changes were missed, so assume the most drastic one.
+                    Err(RecvError::Lagged(_)) => StoreChange::Deleted,
+                    Err(RecvError::Closed) => break,
+                };
+                if tx.send(change).is_err() {
+                    // Nobody is listening any more.
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+}
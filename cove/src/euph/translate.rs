@@ -0,0 +1,84 @@
+//! Translating messages via the backend configured in the `translate` config
+//! section.
+
+use std::process::Stdio;
+
+use anyhow::{anyhow, bail, Context};
+use cove_config::Translate;
+use reqwest::header::USER_AGENT;
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+
+use crate::util;
+
+async fn translate_via_command(
+    command: &str,
+    language: &str,
+    text: &str,
+) -> anyhow::Result<String> {
+    let mut child = util::shell(command)
+        .env("COVE_LANGUAGE", language)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to run translation command")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).await?;
+        // Dropping stdin closes it, signalling the end of the input.
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        bail!("translation command failed ({})", output.status);
+    }
+
+    let translation = String::from_utf8(output.stdout)?;
+    Ok(translation.trim_end().to_string())
+}
+
+async fn translate_via_api(
+    url: &str,
+    user_agent: &str,
+    api_key: Option<&str>,
+    language: &str,
+    text: &str,
+) -> anyhow::Result<String> {
+    let mut body = json!({
+        "q": text,
+        "source": "auto",
+        "target": language,
+        "format": "text",
+    });
+    if let Some(api_key) = api_key {
+        body["api_key"] = api_key.into();
+    }
+
+    let response: Value = reqwest::Client::new()
+        .post(url)
+        .header(USER_AGENT, user_agent)
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    response["translatedText"]
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("translation missing from response"))
+}
+
+pub async fn translate(config: &Translate, user_agent: &str, text: &str) -> anyhow::Result<String> {
+    let language = config.language_ref();
+    if let Some(command) = &config.command {
+        translate_via_command(command, language, text).await
+    } else if let Some(url) = &config.url {
+        let api_key = config.api_key.as_deref();
+        translate_via_api(url, user_agent, api_key, language, text).await
+    } else {
+        bail!("no translator configured")
+    }
+}
@@ -1,4 +1,10 @@
+use std::iter;
+
+use cove_config::Config;
+use cove_vault::{mentioned_nicks, normalize_nick};
 use crossterm::style::{Color, Stylize};
+use euphoxide::api::{SessionType, UserId};
+use euphoxide::conn::Joined;
 use euphoxide::Emoji;
 use once_cell::sync::Lazy;
 use toss::{Style, Styled};
@@ -37,9 +43,13 @@ fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (u8, u8, u8) {
     ((r * 256.0) as u8, (g * 256.0) as u8, (b * 256.0) as u8)
 }
 
+/// The hue the web client uses for a nick, in degrees.
+pub fn nick_hue(nick: &str) -> f32 {
+    euphoxide::nick::hue(&EMOJI, nick) as f32
+}
+
 pub fn nick_color(nick: &str) -> (u8, u8, u8) {
-    let hue = euphoxide::nick::hue(&EMOJI, nick) as f32;
-    hsl_to_rgb(hue, 1.0, 0.72)
+    hsl_to_rgb(nick_hue(nick), 1.0, 0.72)
 }
 
 pub fn nick_style(nick: &str, base: Style) -> Style {
@@ -51,6 +61,142 @@ pub fn style_nick(nick: &str, base: Style) -> Styled {
     Styled::new(EMOJI.replace(nick), nick_style(nick, base))
 }
 
+/// Like [`style_nick`], but shortened to at most `width` columns if set.
+pub fn style_nick_within(nick: &str, base: Style, width: Option<usize>) -> Styled {
+    let text = EMOJI.replace(nick);
+    let text = match width {
+        Some(width) => crate::util::truncate_to_width(&text, width),
+        None => text.to_string(),
+    };
+    Styled::new(text, nick_style(nick, base))
+}
+
 pub fn style_nick_exact(nick: &str, base: Style) -> Styled {
     Styled::new(nick, nick_style(nick, base))
 }
+
+/// Which kinds of sessions to leave out when listing the sessions in a room.
+#[derive(Debug, Clone)]
+pub struct SessionFilter {
+    pub hide_bots: bool,
+    pub hide_lurkers: bool,
+    /// Nicks and user ids ignored via `/ignore`.
+    pub ignored: Vec<String>,
+}
+
+impl SessionFilter {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            hide_bots: config.hide_bots,
+            hide_lurkers: config.hide_lurkers,
+            ignored: vec![],
+        }
+    }
+
+    pub fn shows(&self, user_id: &UserId, name: &str) -> bool {
+        let bot = user_id.session_type() == Some(SessionType::Bot);
+        let lurker = name.is_empty();
+        let ignored = self
+            .ignored
+            .iter()
+            .any(|who| *who == user_id.0 || (!lurker && who == name));
+        !(self.hide_bots && bot) && !(self.hide_lurkers && lurker) && !ignored
+    }
+}
+
+/// Count the people, bots, lurkers and nurkers in a room. Sessions hidden by
+/// the filter are not counted, except for our own session.
+pub fn format_pbln(joined: &Joined, filter: &SessionFilter) -> String {
+    let mut p = 0_usize;
+    let mut b = 0_usize;
+    let mut l = 0_usize;
+    let mut n = 0_usize;
+
+    let sessions = joined
+        .listing
+        .values()
+        .map(|s| (s.id(), s.name()))
+        .filter(|(user_id, name)| filter.shows(user_id, name))
+        .chain(iter::once((
+            &joined.session.id,
+            &joined.session.name as &str,
+        )));
+    for (user_id, name) in sessions {
+        match user_id.session_type() {
+            Some(SessionType::Bot) if name.is_empty() => n += 1,
+            Some(SessionType::Bot) => b += 1,
+            _ if name.is_empty() => l += 1,
+            _ => p += 1,
+        }
+    }
+
+    // There must always be either one p, b, l or n since we're including
+    // ourselves.
+    let mut result = vec![];
+    if p > 0 {
+        result.push(format!("{p}p"));
+    }
+    if b > 0 {
+        result.push(format!("{b}b"));
+    }
+    if l > 0 {
+        result.push(format!("{l}l"));
+    }
+    if n > 0 {
+        result.push(format!("{n}n"));
+    }
+    result.join(" ")
+}
+
+/// Levenshtein distance between two strings, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Mentions in the content that don't match anyone currently in the room.
+///
+/// Each unknown mention is returned along with the most similar nick in the
+/// room (in mention form), if there is one that is similar enough.
+pub fn unknown_mentions(content: &str, joined: &Joined) -> Vec<(String, Option<String>)> {
+    let nicks = joined
+        .listing
+        .values()
+        .map(|s| s.name())
+        .chain(iter::once(&joined.session.name as &str))
+        .map(normalize_nick)
+        .filter(|nick| !nick.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut result: Vec<(String, Option<String>)> = vec![];
+    for mention in mentioned_nicks(content) {
+        let normalized = mention.to_lowercase();
+        if nicks.contains(&normalized) || result.iter().any(|(m, _)| m == mention) {
+            continue;
+        }
+
+        let max_distance = (normalized.chars().count() / 3).max(1);
+        let suggestion = joined
+            .listing
+            .values()
+            .map(|s| s.name())
+            .filter(|nick| !nick.is_empty())
+            .map(|nick| (edit_distance(&normalize_nick(nick), &normalized), nick))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, nick)| nick.chars().filter(|c| !c.is_whitespace()).collect());
+
+        result.push((mention.to_string(), suggestion));
+    }
+    result
+}
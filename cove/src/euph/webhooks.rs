@@ -0,0 +1,137 @@
+//! Sending new messages to the webhooks configured via the `webhooks` config
+//! option.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use cove_config::Webhook;
+use cove_vault::RoomIdentifier;
+use euphoxide::api::Message;
+use log::{debug, warn};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+/// How long to wait for further messages before sending a batch.
+const BATCH_DELAY: Duration = Duration::from_secs(2);
+const BATCH_SIZE: usize = 100;
+const RETRIES: u32 = 4;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+fn matches(webhook: &Webhook, room: &RoomIdentifier, msg: &Message) -> bool {
+    let domain = webhook.domain.as_ref().map_or(true, |d| *d == room.domain);
+    let name = webhook.room.as_ref().map_or(true, |r| *r == room.name);
+    let sender = webhook
+        .sender
+        .as_ref()
+        .map_or(true, |s| *s == msg.sender.name);
+    let keyword = webhook.keyword.as_ref().map_or(true, |k| {
+        msg.content.to_lowercase().contains(&k.to_lowercase())
+    });
+    domain && name && sender && keyword
+}
+
+async fn post(client: &reqwest::Client, url: &str, body: &Value) -> reqwest::Result<()> {
+    client
+        .post(url)
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+async fn post_with_retries(client: &reqwest::Client, name: &str, url: &str, messages: Vec<Value>) {
+    let amount = messages.len();
+    let body = json!({ "messages": messages });
+
+    let mut delay = RETRY_DELAY;
+    for attempt in 0..=RETRIES {
+        match post(client, url, &body).await {
+            Ok(()) => {
+                debug!("webhook {name}: sent {amount} messages");
+                return;
+            }
+            Err(err) if attempt < RETRIES => {
+                debug!("webhook {name}: request failed, retrying in {delay:?}: {err}");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(err) => warn!("webhook {name}: dropping {amount} messages: {err}"),
+        }
+    }
+}
+
+async fn run(
+    client: reqwest::Client,
+    name: String,
+    url: String,
+    mut rx: mpsc::UnboundedReceiver<Value>,
+) {
+    while let Some(first) = rx.recv().await {
+        let mut messages = vec![first];
+
+        // Collect messages arriving shortly after the first one.
+        let deadline = tokio::time::Instant::now() + BATCH_DELAY;
+        while messages.len() < BATCH_SIZE {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(msg)) => messages.push(msg),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        post_with_retries(&client, &name, &url, messages).await;
+    }
+}
+
+/// A cheaply cloneable handle for sending messages to all configured webhooks.
+#[derive(Debug, Clone, Default)]
+pub struct Webhooks {
+    webhooks: Arc<Vec<(&'static Webhook, mpsc::UnboundedSender<Value>)>>,
+}
+
+impl Webhooks {
+    /// Spawn one background task per webhook.
+    ///
+    /// If the http client can't be initialized, for example because the root
+    /// certificates can't be loaded, the webhooks are disabled.
+    pub fn new(webhooks: &'static HashMap<String, Webhook>, user_agent: &str) -> Self {
+        if webhooks.is_empty() {
+            return Self::default();
+        }
+
+        let client = match reqwest::Client::builder().user_agent(user_agent).build() {
+            Ok(client) => client,
+            Err(err) => {
+                warn!("Failed to initialize http client, webhooks are disabled: {err}");
+                return Self::default();
+            }
+        };
+
+        let webhooks = webhooks
+            .iter()
+            .map(|(name, webhook)| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                let url = webhook.url.clone();
+                tokio::task::spawn(run(client.clone(), name.clone(), url, rx));
+                (webhook, tx)
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            webhooks: Arc::new(webhooks),
+        }
+    }
+
+    pub fn send(&self, room: &RoomIdentifier, msg: &Message) {
+        for (webhook, tx) in self.webhooks.iter() {
+            if matches(webhook, room, msg) {
+                let _ = tx.send(json!({
+                    "domain": room.domain,
+                    "room": room.name,
+                    "message": msg,
+                }));
+            }
+        }
+    }
+}
@@ -1,12 +1,22 @@
 //! Export logs from the vault to plain text files.
 
 mod json;
+mod manifest;
 mod text;
 
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
 
-use crate::vault::{EuphRoomVault, EuphVault, RoomIdentifier};
+use cove_vault::{EuphRoomVault, EuphVault, RoomIdentifier};
+use euphoxide::api::Time;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
+
+use self::manifest::{HashingWriter, Manifest, MsgHashes};
+
+pub use self::manifest::{verify, VerifyArgs};
 
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
 pub enum Format {
@@ -70,17 +80,62 @@ pub struct Args {
     #[arg(long, short, default_value_t = Into::into("%r.%e"))]
     #[arg(verbatim_doc_comment)]
     out: String,
+
+    /// Write a manifest with hashes of the exported files to this file.
+    ///
+    /// For the json formats, the manifest also contains a hash of every
+    /// message. Exports can then be checked for modifications and missing
+    /// messages via the `verify-export` command.
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+
+    /// Only export messages sent at or after this time.
+    ///
+    /// Either a date like `2024-01-31` or a time like `2024-01-31T12:00:00Z`.
+    /// Dates are interpreted as midnight UTC.
+    #[arg(long, value_parser = parse_time)]
+    since: Option<Time>,
+
+    /// Only export messages sent before this time.
+    ///
+    /// Uses the same format as `--since`.
+    #[arg(long, value_parser = parse_time)]
+    until: Option<Time>,
+}
+
+fn parse_time(s: &str) -> Result<Time, time::error::Parse> {
+    if let Ok(time) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Ok(Time(time));
+    }
+    let date = Date::parse(s, format_description!("[year]-[month]-[day]"))?;
+    Ok(Time(date.midnight().assume_utc()))
+}
+
+/// The time range of the messages to export.
+#[derive(Debug, Clone, Copy)]
+struct TimeRange {
+    since: Option<Time>,
+    until: Option<Time>,
+}
+
+impl TimeRange {
+    fn contains(&self, time: Time) -> bool {
+        self.since.map_or(true, |since| time.0 >= since.0)
+            && self.until.map_or(true, |until| time.0 < until.0)
+    }
 }
 
 async fn export_room<W: Write>(
     vault: &EuphRoomVault,
     out: &mut W,
     format: Format,
+    range: TimeRange,
+    hashes: Option<&mut MsgHashes>,
 ) -> anyhow::Result<()> {
     match format {
-        Format::Text => text::export(vault, out).await?,
-        Format::Json => json::export(vault, out).await?,
-        Format::JsonLines => json::export_lines(vault, out).await?,
+        Format::Text => text::export(vault, out, range).await?,
+        Format::Json => json::export(vault, out, range, hashes).await?,
+        Format::JsonLines => json::export_lines(vault, out, range, hashes).await?,
     }
     Ok(())
 }
@@ -89,6 +144,13 @@ pub async fn export(vault: &EuphVault, mut args: Args) -> anyhow::Result<()> {
     if args.out.ends_with('/') {
         args.out.push_str("%r.%e");
     }
+    if args.manifest.is_some() && args.out == "-" {
+        anyhow::bail!("can't write a manifest when exporting to stdout");
+    }
+    let range = TimeRange {
+        since: args.since,
+        until: args.until,
+    };
 
     let rooms = if args.all {
         let mut rooms = vault
@@ -109,23 +171,35 @@ pub async fn export(vault: &EuphVault, mut args: Args) -> anyhow::Result<()> {
         eprintln!("No rooms to export");
     }
 
+    let mut manifest = Manifest::default();
     for room in rooms {
+        let id = RoomIdentifier::new(args.domain.clone(), room);
         if args.out == "-" {
-            eprintln!("Exporting &{room} as {} to stdout", args.format.name());
-            let vault = vault.room(RoomIdentifier::new(args.domain.clone(), room));
+            eprintln!("Exporting &{} as {} to stdout", id.name, args.format.name());
+            let vault = vault.room(id);
             let mut stdout = BufWriter::new(io::stdout());
-            export_room(&vault, &mut stdout, args.format).await?;
+            export_room(&vault, &mut stdout, args.format, range, None).await?;
             stdout.flush()?;
         } else {
-            let out = format_out(&args.out, &room, args.format);
-            eprintln!("Exporting &{room} as {} to {out}", args.format.name());
-            let vault = vault.room(RoomIdentifier::new(args.domain.clone(), room));
-            let mut file = BufWriter::new(File::create(out)?);
-            export_room(&vault, &mut file, args.format).await?;
+            let out = format_out(&args.out, &id.name, args.format);
+            eprintln!("Exporting &{} as {} to {out}", id.name, args.format.name());
+            let vault = vault.room(id.clone());
+            let mut file = HashingWriter::new(BufWriter::new(File::create(&out)?));
+            let mut hashes = match args.format {
+                Format::Json | Format::JsonLines if args.manifest.is_some() => Some(vec![]),
+                _ => None,
+            };
+            export_room(&vault, &mut file, args.format, range, hashes.as_mut()).await?;
             file.flush()?;
+            manifest.add(&out, &id, args.format, file.hash(), hashes);
         }
     }
 
+    if let Some(path) = &args.manifest {
+        eprintln!("Writing manifest to {}", path.to_string_lossy());
+        manifest.write(path)?;
+    }
+
     Ok(())
 }
 
@@ -1,16 +1,26 @@
 use std::io::Write;
 
-use crate::vault::EuphRoomVault;
+use cove_vault::EuphRoomVault;
+
+use super::manifest::{self, MsgHashes};
+use super::TimeRange;
 
 const CHUNK_SIZE: usize = 10000;
 
-pub async fn export<W: Write>(vault: &EuphRoomVault, file: &mut W) -> anyhow::Result<()> {
+pub async fn export<W: Write>(
+    vault: &EuphRoomVault,
+    file: &mut W,
+    range: TimeRange,
+    mut hashes: Option<&mut MsgHashes>,
+) -> anyhow::Result<()> {
     write!(file, "[")?;
 
     let mut total = 0;
     let mut last_msg_id = None;
     loop {
-        let messages = vault.chunk_after(last_msg_id, CHUNK_SIZE).await?;
+        let messages = vault
+            .chunk_after(last_msg_id, range.since, range.until, CHUNK_SIZE)
+            .await?;
         last_msg_id = Some(match messages.last() {
             Some(last_msg) => last_msg.id,
             None => break, // No more messages, export finished
@@ -22,7 +32,11 @@ pub async fn export<W: Write>(vault: &EuphRoomVault, file: &mut W) -> anyhow::Re
             } else {
                 writeln!(file, ",")?;
             }
-            serde_json::to_writer(&mut *file, &message)?; // Fancy reborrow! :D
+            let json = serde_json::to_vec(&message)?;
+            file.write_all(&json)?;
+            if let Some(hashes) = &mut hashes {
+                hashes.push((message.id.0.to_string(), manifest::hash(&json)));
+            }
             total += 1;
         }
 
@@ -37,18 +51,29 @@ pub async fn export<W: Write>(vault: &EuphRoomVault, file: &mut W) -> anyhow::Re
     Ok(())
 }
 
-pub async fn export_lines<W: Write>(vault: &EuphRoomVault, file: &mut W) -> anyhow::Result<()> {
+pub async fn export_lines<W: Write>(
+    vault: &EuphRoomVault,
+    file: &mut W,
+    range: TimeRange,
+    mut hashes: Option<&mut MsgHashes>,
+) -> anyhow::Result<()> {
     let mut total = 0;
     let mut last_msg_id = None;
     loop {
-        let messages = vault.chunk_after(last_msg_id, CHUNK_SIZE).await?;
+        let messages = vault
+            .chunk_after(last_msg_id, range.since, range.until, CHUNK_SIZE)
+            .await?;
         last_msg_id = Some(match messages.last() {
             Some(last_msg) => last_msg.id,
             None => break, // No more messages, export finished
         });
 
         for message in messages {
-            serde_json::to_writer(&mut *file, &message)?; // Fancy reborrow! :D
+            let json = serde_json::to_vec(&message)?;
+            file.write_all(&json)?;
+            if let Some(hashes) = &mut hashes {
+                hashes.push((message.id.0.to_string(), manifest::hash(&json)));
+            }
             writeln!(file)?;
             total += 1;
         }
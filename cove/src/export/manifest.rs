@@ -0,0 +1,190 @@
+//! Manifests listing the SHA-256 hashes of exported files and messages, so
+//! exports can be verified later.
+//!
+//! Messages are hashed as serialized in the json and json lines formats. Since
+//! the text format doesn't contain all of a message's fields, only the hashes of
+//! its files are recorded.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use cove_vault::RoomIdentifier;
+use euphoxide::api::Message;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use super::Format;
+
+const VERSION: u64 = 1;
+
+/// Hashes everything written through it.
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    pub fn hash(&self) -> String {
+        format!("{:x}", self.hasher.clone().finalize())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Ids and hashes of the messages of a file, in the order they were written.
+pub type MsgHashes = Vec<(String, String)>;
+
+pub fn hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+#[derive(Default)]
+pub struct Manifest {
+    files: Vec<Value>,
+}
+
+impl Manifest {
+    pub fn add(
+        &mut self,
+        path: &str,
+        room: &RoomIdentifier,
+        format: Format,
+        hash: String,
+        msgs: Option<MsgHashes>,
+    ) {
+        let mut file = json!({
+            "path": path,
+            "domain": room.domain,
+            "room": room.name,
+            "format": format.name(),
+            "sha256": hash,
+        });
+        if let Some(msgs) = msgs {
+            file["messages"] = json!(msgs);
+        }
+        self.files.push(file);
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let manifest = json!({
+            "version": VERSION,
+            "files": self.files,
+        });
+        fs::write(path, serde_json::to_string_pretty(&manifest)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct VerifyArgs {
+    /// Manifest written via `export --manifest`.
+    ///
+    /// Relative paths of exported files are interpreted relative to the
+    /// current directory, like when exporting.
+    manifest: PathBuf,
+}
+
+fn parse_msgs(format: &str, content: &[u8]) -> anyhow::Result<Vec<Message>> {
+    Ok(match format {
+        "json" => serde_json::from_slice(content)?,
+        _ => content
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Compare the messages of a file against their hashes in the manifest.
+fn verify_msgs(format: &str, content: &[u8], expected: &[Value]) -> Vec<String> {
+    let msgs = match parse_msgs(format, content) {
+        Ok(msgs) => msgs,
+        Err(err) => return vec![format!("can't be parsed: {err}")],
+    };
+
+    let mut expected = expected
+        .iter()
+        .filter_map(|entry| Some((entry[0].as_str()?, entry[1].as_str()?)))
+        .collect::<HashMap<_, _>>();
+
+    let mut problems = vec![];
+    for msg in msgs {
+        let id = msg.id.0.to_string();
+        let Ok(json) = serde_json::to_vec(&msg) else {
+            continue;
+        };
+        match expected.remove(&id as &str) {
+            Some(expected) if expected == hash(&json) => {}
+            Some(_) => problems.push(format!("message {id} was modified")),
+            None => problems.push(format!("message {id} was added")),
+        }
+    }
+    if !expected.is_empty() {
+        problems.push(format!("{} messages are missing", expected.len()));
+    }
+    problems
+}
+
+fn verify_file(file: &Value) -> Vec<String> {
+    let path = file["path"].as_str().unwrap_or_default();
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(err) => return vec![format!("can't be read: {err}")],
+    };
+
+    if Some(&hash(&content) as &str) == file["sha256"].as_str() {
+        return vec![];
+    }
+
+    let mut problems = vec!["file hash doesn't match".to_string()];
+    // The messages are only checked to find out what exactly changed.
+    if let Some(msgs) = file["messages"].as_array() {
+        let format = file["format"].as_str().unwrap_or_default();
+        problems.extend(verify_msgs(format, &content, msgs));
+    }
+    problems
+}
+
+pub fn verify(args: VerifyArgs) -> anyhow::Result<()> {
+    let manifest: Value = serde_json::from_slice(&fs::read(&args.manifest)?)?;
+    if manifest["version"].as_u64() != Some(VERSION) {
+        anyhow::bail!("unsupported manifest version");
+    }
+
+    let files = manifest["files"].as_array().cloned().unwrap_or_default();
+    let mut failed = 0;
+    for file in &files {
+        let path = file["path"].as_str().unwrap_or_default();
+        let problems = verify_file(file);
+        if problems.is_empty() {
+            eprintln!("{path}: ok");
+        } else {
+            failed += 1;
+            eprintln!("{path}: {}", problems.join(", "));
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} files failed verification", files.len());
+    }
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use std::io::Write;
 
+use cove_config::SiblingOrder;
+use cove_vault::EuphRoomVault;
 use euphoxide::api::MessageId;
 use time::format_description::FormatItem;
 use time::macros::format_description;
@@ -7,20 +9,32 @@ use unicode_width::UnicodeWidthStr;
 
 use crate::euph::SmallMessage;
 use crate::store::Tree;
-use crate::vault::EuphRoomVault;
+
+use super::TimeRange;
 
 const TIME_FORMAT: &[FormatItem<'_>] =
     format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
 const TIME_EMPTY: &str = "                   ";
 
-pub async fn export<W: Write>(vault: &EuphRoomVault, out: &mut W) -> anyhow::Result<()> {
+/// Threads are exported in full if any of their messages is in the range.
+pub async fn export<W: Write>(
+    vault: &EuphRoomVault,
+    out: &mut W,
+    range: TimeRange,
+) -> anyhow::Result<()> {
     let mut exported_trees = 0;
     let mut exported_msgs = 0;
     let mut root_id = vault.first_root_id().await?;
     while let Some(some_root_id) = root_id {
-        let tree = vault.tree(some_root_id).await?;
-        write_tree(out, &tree, some_root_id, 0)?;
+        let msgs = vault.tree(some_root_id).await?;
         root_id = vault.next_root_id(some_root_id).await?;
+        if !msgs.iter().any(|msg| range.contains(msg.time)) {
+            continue;
+        }
+
+        // Exports shouldn't depend on how the chat is configured to look.
+        let tree = Tree::new(some_root_id, msgs, SiblingOrder::Id);
+        write_tree(out, &tree, some_root_id, 0)?;
 
         exported_trees += 1;
         exported_msgs += tree.len();
@@ -40,17 +54,19 @@ fn write_tree<W: Write>(
     id: MessageId,
     indent: usize,
 ) -> anyhow::Result<()> {
-    let indent_string = "| ".repeat(indent);
+    // Iterative so that very deep threads can't overflow the stack.
+    let mut stack = vec![(id, indent)];
+    while let Some((id, indent)) = stack.pop() {
+        let indent_string = "| ".repeat(indent);
 
-    if let Some(msg) = tree.msg(&id) {
-        write_msg(out, &indent_string, msg)?;
-    } else {
-        write_placeholder(out, &indent_string)?;
-    }
+        if let Some(msg) = tree.msg(&id) {
+            write_msg(out, &indent_string, msg)?;
+        } else {
+            write_placeholder(out, &indent_string)?;
+        }
 
-    if let Some(children) = tree.children(&id) {
-        for child in children {
-            write_tree(out, tree, *child, indent + 1)?;
+        if let Some(children) = tree.children(&id) {
+            stack.extend(children.iter().rev().map(|child| (*child, indent + 1)));
         }
     }
 
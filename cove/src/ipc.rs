@@ -0,0 +1,135 @@
+//! Communication between cove instances using the same data directory.
+//!
+//! Only one instance can use a vault at a time. The instance holding the vault
+//! listens on a unix socket next to it. Instances started later hand the room
+//! they were asked to open over to that instance and exit instead of failing to
+//! open the vault.
+
+// Other platforms don't support unix sockets, so there is nothing to do there.
+#![cfg_attr(not(unix), allow(unused))]
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use cove_vault::RoomIdentifier;
+use euphoxide::api::{MessageId, Snowflake};
+use tokio::sync::mpsc;
+
+use crate::ui::StartRoom;
+
+/// How long another instance may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn socket_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("cove.sock")
+}
+
+/// One line each for the domain, the room name and optionally the message id.
+/// An empty request only checks whether an instance is running.
+fn encode(start: Option<&StartRoom>) -> String {
+    let Some(start) = start else {
+        return String::new();
+    };
+
+    let mut result = format!("{}\n{}\n", start.room.domain, start.room.name);
+    if let Some(msg) = start.msg {
+        result.push_str(&format!("{}\n", msg.0));
+    }
+    result
+}
+
+fn decode(text: &str) -> Option<StartRoom> {
+    let mut lines = text.lines();
+    let domain = lines.next()?.to_string();
+    let name = lines.next()?.to_string();
+    let msg = match lines.next() {
+        Some(msg) => Some(MessageId(msg.parse::<Snowflake>().ok()?)),
+        None => None,
+    };
+
+    Some(StartRoom {
+        room: RoomIdentifier::new(domain, name),
+        msg,
+    })
+}
+
+/// Hand a room over to the instance listening on the socket, if there is one.
+/// Returns whether such an instance exists.
+#[cfg(unix)]
+pub async fn hand_off(path: &Path, start: Option<&StartRoom>) -> bool {
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::UnixStream;
+
+    let Ok(mut stream) = UnixStream::connect(path).await else {
+        return false;
+    };
+
+    if let Err(err) = stream.write_all(encode(start).as_bytes()).await {
+        log::warn!("Failed to hand room over to running instance: {err}");
+    }
+    true
+}
+
+#[cfg(not(unix))]
+pub async fn hand_off(_path: &Path, _start: Option<&StartRoom>) -> bool {
+    false
+}
+
+/// Listen for rooms handed over by other instances until the receiving end of
+/// the channel is dropped.
+#[cfg(unix)]
+pub async fn listen(path: PathBuf, open_tx: mpsc::UnboundedSender<StartRoom>) {
+    use tokio::io::AsyncReadExt;
+    use tokio::net::UnixListener;
+
+    // The socket of an instance that did not exit cleanly may still exist. We
+    // hold the vault, so nobody can be listening on it any more.
+    let _ = std::fs::remove_file(&path);
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::warn!("Failed to listen on {}: {err}", path.to_string_lossy());
+            return;
+        }
+    };
+
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                log::warn!("Failed to accept connection from other instance: {err}");
+                continue;
+            }
+        };
+        if open_tx.is_closed() {
+            return;
+        }
+
+        // A client that never finishes its request must not keep others from
+        // handing rooms over.
+        let open_tx = open_tx.clone();
+        tokio::spawn(async move {
+            let mut text = String::new();
+            let read = stream.read_to_string(&mut text);
+            match tokio::time::timeout(READ_TIMEOUT, read).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => {
+                    log::warn!("Failed to read request from other instance: {err}");
+                    return;
+                }
+                Err(_) => {
+                    log::warn!("Timed out reading request from other instance");
+                    return;
+                }
+            }
+
+            if let Some(start) = decode(&text) {
+                let _ = open_tx.send(start);
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn listen(_path: PathBuf, _open_tx: mpsc::UnboundedSender<StartRoom>) {}
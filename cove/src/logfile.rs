@@ -0,0 +1,157 @@
+//! Printing the log file that cove writes to its data dir while running.
+
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context;
+use log::Level;
+
+/// Name of the log file in the data dir. It is replaced every time cove
+/// starts.
+pub const FILE_NAME: &str = "cove.log";
+
+/// How often the log file is checked for new messages while following it.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, clap::Parser)]
+pub struct Args {
+    /// Only print messages at least this severe.
+    #[arg(long, short, default_value = "trace")]
+    level: Level,
+
+    /// Keep printing messages as they are logged.
+    #[arg(long, short)]
+    follow: bool,
+
+    /// How many of the most recent messages to print.
+    #[arg(long, short = 'n', default_value_t = 10)]
+    lines: usize,
+}
+
+/// Lines of a message after the first are indented, see
+/// [`crate::logger::LogMsg`].
+fn is_continuation(line: &str) -> bool {
+    line.starts_with(' ')
+}
+
+/// The first line of a message contains its time, level and content.
+fn level(line: &str) -> Option<Level> {
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Decides which lines to print, line by line.
+struct Filter {
+    min: Level,
+    /// Whether the lines of the current message are printed.
+    printing: bool,
+}
+
+impl Filter {
+    fn prints(&mut self, line: &str) -> bool {
+        if !is_continuation(line) {
+            self.printing = level(line).is_some_and(|l| l <= self.min);
+        }
+        self.printing
+    }
+}
+
+/// The length of the part of `bytes` up to and including the last newline.
+/// Messages may still be in the process of being written.
+fn complete_len(bytes: &[u8]) -> usize {
+    bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1)
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &Metadata, _b: &Metadata) -> bool {
+    // Without a way to tell files apart, only truncated files are noticed.
+    true
+}
+
+/// Whether the log file at `path` is no longer the open `file`, or was
+/// truncated to less than what was already read from it.
+///
+/// Cove truncates the log file when it starts, but the file may also have been
+/// deleted and created anew in the meantime.
+fn was_replaced(file: &File, path: &Path, offset: u64) -> io::Result<bool> {
+    let open = file.metadata()?;
+    let current = fs::metadata(path)?;
+    Ok(!same_file(&open, &current) || current.len() < offset)
+}
+
+/// Read the complete lines written to `file` since `offset` and advance
+/// `offset` past them.
+fn read_new(file: &mut File, offset: &mut u64) -> io::Result<String> {
+    let mut bytes = vec![];
+    file.seek(SeekFrom::Start(*offset))?;
+    file.read_to_end(&mut bytes)?;
+    let len = complete_len(&bytes);
+    *offset += len as u64;
+    Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+}
+
+pub async fn tail(path: &Path, args: Args) -> anyhow::Result<()> {
+    let context = || format!("failed to read log file {}", path.to_string_lossy());
+    let mut file = File::open(path).with_context(context)?;
+    let mut offset = 0;
+    let text = read_new(&mut file, &mut offset).with_context(context)?;
+
+    let mut filter = Filter {
+        min: args.level,
+        printing: false,
+    };
+
+    // Group the printed lines by message so the amount of messages can be
+    // limited.
+    let mut msgs: Vec<Vec<&str>> = vec![];
+    for line in text.lines() {
+        if !filter.prints(line) {
+            continue;
+        }
+        match msgs.last_mut() {
+            Some(msg) if is_continuation(line) => msg.push(line),
+            _ => msgs.push(vec![line]),
+        }
+    }
+    for msg in &msgs[msgs.len().saturating_sub(args.lines)..] {
+        for line in msg {
+            println!("{line}");
+        }
+    }
+
+    if !args.follow {
+        return Ok(());
+    }
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        // The file may not exist for a moment while it is being replaced.
+        match was_replaced(&file, path, offset) {
+            Ok(false) => {}
+            Ok(true) => {
+                let Ok(new_file) = File::open(path) else {
+                    continue;
+                };
+                eprintln!("Log file was replaced, cove was probably restarted");
+                file = new_file;
+                offset = 0;
+            }
+            Err(_) => continue,
+        }
+
+        let new = read_new(&mut file, &mut offset).with_context(context)?;
+        for line in new.lines() {
+            if filter.prints(line) {
+                println!("{line}");
+            }
+        }
+    }
+}
@@ -1,26 +1,59 @@
-use std::convert::Infallible;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::vec;
 
-use async_trait::async_trait;
 use crossterm::style::Stylize;
+use log::kv::Key;
 use log::{Level, LevelFilter, Log};
 use parking_lot::Mutex;
+use time::format_description::FormatItem;
+use time::macros::format_description;
 use time::OffsetDateTime;
 use tokio::sync::mpsc;
 use toss::{Style, Styled};
 
-use crate::store::{Msg, MsgStore, Path, Tree};
+use crate::store::{MemoryMsg, MemoryStore, Msg};
 use crate::ui::ChatMsg;
 
+/// Target of log messages that start a new thread. All following messages
+/// with the same `room` key are shown as replies to it.
+///
+/// Messages about a room pass its [`cove_vault::RoomIdentifier`] as the
+/// `room` key, for example `info!(room:? = vault.room(); "...")`.
+pub const THREAD_TARGET: &str = "cove::thread";
+
+/// Key of log messages naming the room they are about.
+const ROOM_KEY: &str = "room";
+
+/// How many threads are remembered at most. When a new thread starts and
+/// there are already this many, the oldest one is forgotten.
+const MAX_THREADS: usize = 256;
+
+const FILE_TIME_FORMAT: &[FormatItem<'_>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z");
+
 #[derive(Debug, Clone)]
 pub struct LogMsg {
     id: usize,
+    parent: Option<usize>,
     time: OffsetDateTime,
     level: Level,
     content: String,
 }
 
+impl LogMsg {
+    /// The message as written to the log file. Lines after the first are
+    /// indented so they can be told apart from other messages.
+    fn file_entry(&self) -> String {
+        let time = self.time.format(FILE_TIME_FORMAT).unwrap_or_default();
+        let content = self.content.replace('\n', "\n    ");
+        format!("{time} {:<5} {content}\n", self.level)
+    }
+}
+
 impl Msg for LogMsg {
     type Id = usize;
 
@@ -29,23 +62,29 @@ impl Msg for LogMsg {
     }
 
     fn parent(&self) -> Option<Self::Id> {
-        None
+        self.parent
     }
 
     fn seen(&self) -> bool {
         true
     }
 
+    fn time(&self) -> Option<OffsetDateTime> {
+        Some(self.time)
+    }
+
     fn last_possible_id() -> Self::Id {
         Self::Id::MAX
     }
 }
 
-impl ChatMsg for LogMsg {
-    fn time(&self) -> Option<OffsetDateTime> {
-        Some(self.time)
+impl MemoryMsg for LogMsg {
+    fn set_seen(&mut self, _seen: bool) {
+        // Log messages are always seen.
     }
+}
 
+impl ChatMsg for LogMsg {
     fn styled(&self) -> (Styled, Styled) {
         let nick_style = match self.level {
             Level::Error => Style::new().bold().red(),
@@ -55,7 +94,12 @@ impl ChatMsg for LogMsg {
             Level::Trace => Style::new().bold().magenta(),
         };
         let nick = Styled::new(format!("{}", self.level), nick_style);
-        let content = Styled::new_plain(&self.content);
+        let content_style = match self.level {
+            Level::Error => Style::new().red(),
+            Level::Warn => Style::new().yellow(),
+            Level::Info | Level::Debug | Level::Trace => Style::new(),
+        };
+        let content = Styled::new(&self.content, content_style);
         (nick, content)
     }
 
@@ -70,14 +114,13 @@ impl ChatMsg for LogMsg {
 
 /// Prints all error messages when dropped.
 pub struct LoggerGuard {
-    messages: Arc<Mutex<Vec<LogMsg>>>,
+    msgs: MemoryStore<LogMsg>,
 }
 
 impl Drop for LoggerGuard {
     fn drop(&mut self) {
-        let guard = self.messages.lock();
         let mut error_encountered = false;
-        for msg in &*guard {
+        for msg in self.msgs.msgs() {
             if msg.level == Level::Error {
                 if !error_encountered {
                     eprintln!();
@@ -96,92 +139,12 @@ impl Drop for LoggerGuard {
 #[derive(Debug, Clone)]
 pub struct Logger {
     event_tx: mpsc::UnboundedSender<()>,
-    messages: Arc<Mutex<Vec<LogMsg>>>,
-}
-
-#[async_trait]
-impl MsgStore<LogMsg> for Logger {
-    type Error = Infallible;
-
-    async fn path(&self, id: &usize) -> Result<Path<usize>, Self::Error> {
-        Ok(Path::new(vec![*id]))
-    }
-
-    async fn msg(&self, id: &usize) -> Result<Option<LogMsg>, Self::Error> {
-        Ok(self.messages.lock().get(*id).cloned())
-    }
-
-    async fn tree(&self, root_id: &usize) -> Result<Tree<LogMsg>, Self::Error> {
-        let msgs = self
-            .messages
-            .lock()
-            .get(*root_id)
-            .map(|msg| vec![msg.clone()])
-            .unwrap_or_default();
-        Ok(Tree::new(*root_id, msgs))
-    }
-
-    async fn first_root_id(&self) -> Result<Option<usize>, Self::Error> {
-        let empty = self.messages.lock().is_empty();
-        Ok(Some(0).filter(|_| !empty))
-    }
-
-    async fn last_root_id(&self) -> Result<Option<usize>, Self::Error> {
-        Ok(self.messages.lock().len().checked_sub(1))
-    }
-
-    async fn prev_root_id(&self, root_id: &usize) -> Result<Option<usize>, Self::Error> {
-        Ok(root_id.checked_sub(1))
-    }
-
-    async fn next_root_id(&self, root_id: &usize) -> Result<Option<usize>, Self::Error> {
-        let len = self.messages.lock().len();
-        Ok(root_id.checked_add(1).filter(|t| *t < len))
-    }
-
-    async fn oldest_msg_id(&self) -> Result<Option<usize>, Self::Error> {
-        self.first_root_id().await
-    }
-
-    async fn newest_msg_id(&self) -> Result<Option<usize>, Self::Error> {
-        self.last_root_id().await
-    }
-
-    async fn older_msg_id(&self, id: &usize) -> Result<Option<usize>, Self::Error> {
-        self.prev_root_id(id).await
-    }
-
-    async fn newer_msg_id(&self, id: &usize) -> Result<Option<usize>, Self::Error> {
-        self.next_root_id(id).await
-    }
-
-    async fn oldest_unseen_msg_id(&self) -> Result<Option<usize>, Self::Error> {
-        Ok(None)
-    }
-
-    async fn newest_unseen_msg_id(&self) -> Result<Option<usize>, Self::Error> {
-        Ok(None)
-    }
-
-    async fn older_unseen_msg_id(&self, _id: &usize) -> Result<Option<usize>, Self::Error> {
-        Ok(None)
-    }
-
-    async fn newer_unseen_msg_id(&self, _id: &usize) -> Result<Option<usize>, Self::Error> {
-        Ok(None)
-    }
-
-    async fn unseen_msgs_count(&self) -> Result<usize, Self::Error> {
-        Ok(0)
-    }
-
-    async fn set_seen(&self, _id: &usize, _seen: bool) -> Result<(), Self::Error> {
-        Ok(())
-    }
-
-    async fn set_older_seen(&self, _id: &usize, _seen: bool) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    next_id: Arc<AtomicUsize>,
+    msgs: MemoryStore<LogMsg>,
+    /// The message that started the current thread of each room.
+    threads: Arc<Mutex<HashMap<String, usize>>>,
+    /// See [`Self::log_to_file`].
+    file: Arc<Mutex<Option<File>>>,
 }
 
 impl Log for Logger {
@@ -206,14 +169,43 @@ impl Log for Logger {
             return;
         }
 
-        let mut guard = self.messages.lock();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let args = record.args().to_string();
+        let starts_thread = record.target() == THREAD_TARGET;
+
+        let room = record
+            .key_values()
+            .get(Key::from_str(ROOM_KEY))
+            .map(|room| room.to_string());
+
+        let parent = match room {
+            Some(room) if starts_thread => {
+                self.start_thread(room, id);
+                None
+            }
+            Some(room) => self.threads.lock().get(&room).copied(),
+            None => None,
+        };
+
+        let target = match record.module_path() {
+            Some(module) if starts_thread => module,
+            _ => record.target(),
+        };
+
         let msg = LogMsg {
-            id: guard.len(),
+            id,
+            parent,
             time: OffsetDateTime::now_utc(),
             level: record.level(),
-            content: format!("<{}> {}", record.target(), record.args()),
+            content: format!("<{target}> {args}"),
         };
-        guard.push(msg);
+
+        if let Some(file) = &mut *self.file.lock() {
+            // There is nowhere to report the error to.
+            let _ = file.write_all(msg.file_entry().as_bytes());
+        }
+
+        self.msgs.add(msg);
 
         let _ = self.event_tx.send(());
     }
@@ -222,14 +214,31 @@ impl Log for Logger {
 }
 
 impl Logger {
+    fn start_thread(&self, room: String, id: usize) {
+        let mut threads = self.threads.lock();
+        if threads.len() >= MAX_THREADS && !threads.contains_key(&room) {
+            let oldest = threads
+                .iter()
+                .min_by_key(|(_, id)| **id)
+                .map(|(room, _)| room.clone());
+            if let Some(oldest) = oldest {
+                threads.remove(&oldest);
+            }
+        }
+        threads.insert(room, id);
+    }
+
     pub fn init(verbose: bool) -> (Self, LoggerGuard, mpsc::UnboundedReceiver<()>) {
         let (event_tx, event_rx) = mpsc::unbounded_channel();
         let logger = Self {
             event_tx,
-            messages: Arc::new(Mutex::new(Vec::new())),
+            next_id: Arc::new(AtomicUsize::new(0)),
+            msgs: MemoryStore::new(),
+            threads: Arc::new(Mutex::new(HashMap::new())),
+            file: Arc::new(Mutex::new(None)),
         };
         let guard = LoggerGuard {
-            messages: logger.messages.clone(),
+            msgs: logger.msgs.clone(),
         };
 
         log::set_max_level(if verbose {
@@ -242,4 +251,21 @@ impl Logger {
 
         (logger, guard, event_rx)
     }
+
+    /// Write all messages logged so far and from now on to a file, replacing
+    /// its previous contents.
+    pub fn log_to_file(&self, path: &Path) -> io::Result<()> {
+        let mut file_guard = self.file.lock();
+        let mut file = File::create(path)?;
+        for msg in self.msgs.msgs() {
+            file.write_all(msg.file_entry().as_bytes())?;
+        }
+        *file_guard = Some(file);
+        Ok(())
+    }
+
+    /// The messages logged so far, to be shown in a chat.
+    pub fn msgs(&self) -> MemoryStore<LogMsg> {
+        self.msgs.clone()
+    }
 }
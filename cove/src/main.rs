@@ -12,40 +12,67 @@
 // TODO Enable warn(unreachable_pub)?
 // TODO Remove unnecessary Debug impls and compare compile times
 // TODO Time zones other than UTC
-// TODO Invoke external notification command?
 
+mod cookies;
+mod daemon;
+#[cfg(feature = "mock-server")]
+mod demo;
+mod diagnostics;
+mod digest;
 mod euph;
 mod export;
+mod ipc;
+mod logfile;
 mod logger;
 mod macros;
+mod metrics;
+#[cfg(any(test, feature = "mock-server"))]
+mod mock_server;
+mod startup;
 mod store;
+mod sync;
 mod ui;
 mod util;
-mod vault;
 mod version;
 
+#[cfg(feature = "mock-server")]
+use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
 
 use anyhow::Context;
 use clap::Parser;
 use cove_config::doc::Document;
 use cove_config::Config;
+use cove_vault::{RoomIdentifier, Vault};
 use directories::{BaseDirs, ProjectDirs};
-use log::info;
+use euphoxide::api::{MessageId, Snowflake};
+use log::{info, warn};
 use tokio::sync::mpsc;
 use toss::Terminal;
 
 use crate::logger::Logger;
-use crate::ui::Ui;
-use crate::vault::Vault;
+use crate::ui::{Recording, StartRoom, Ui};
 use crate::version::{NAME, VERSION};
 
 #[derive(Debug, clap::Parser)]
 enum Command {
     /// Run the client interactively (default).
     Run,
+    /// Keep `autojoin` rooms connected in the background.
+    ///
+    /// The daemon records the history of the rooms while cove isn't running.
+    /// Starting cove or any other command using the vault asks the daemon to
+    /// release the vault, so it disconnects its rooms and waits for that
+    /// instance to exit before reconnecting them.
+    Daemon,
     /// Export room logs as plain text files.
     Export(export::Args),
+    /// Check exported files for modifications using the manifest written via
+    /// `export --manifest`.
+    VerifyExport(export::VerifyArgs),
+    /// Print all unseen messages as markdown, grouped by room and thread.
+    Digest(digest::Args),
     /// Compact and clean up vault.
     Gc,
     /// Clear euphoria session cookies.
@@ -54,8 +81,40 @@ enum Command {
         #[arg(long, short)]
         domain: Option<String>,
     },
+    /// Import the session cookies of a logged-in euphoria web client.
+    ///
+    /// Any cookies previously stored for the domain are replaced.
+    ImportSession(cookies::Args),
+    /// Print the log of the running or last interactive session.
+    Log(logfile::Args),
     /// Print config documentation as markdown.
     HelpConfig,
+    /// Try out cove using generated rooms and messages.
+    ///
+    /// The rooms are served by a local mock server, see `mock-server`, so
+    /// messages can be sent and are received like in any other room. Implies
+    /// `--ephemeral` and only connects to the demo rooms.
+    #[cfg(feature = "mock-server")]
+    Demo,
+    /// Run a minimal euphoria server keeping its rooms in memory.
+    #[cfg(feature = "mock-server")]
+    MockServer {
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: std::net::SocketAddr,
+        /// Make all rooms private, with this as their passcode.
+        #[arg(long)]
+        password: Option<String>,
+        /// Accept wss:// instead of plain ws:// connections.
+        ///
+        /// The server uses a self-signed certificate for `localhost`, whose
+        /// path is printed on startup. Cove only connects to rooms via wss://
+        /// and trusts the certificate if the `SSL_CERT_FILE` environment
+        /// variable points to it. Rooms are then found at the domain
+        /// `localhost:<port>`.
+        #[arg(long)]
+        tls: bool,
+    },
 }
 
 impl Default for Command {
@@ -96,6 +155,39 @@ struct Args {
     #[arg(long, short)]
     measure_widths: bool,
 
+    /// Record terminal events, packets from euphoria servers and ticks to a
+    /// file.
+    ///
+    /// Changes to the vault are not recorded, so a replay only reproduces the
+    /// original session if it starts from the same vault and terminal size.
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay a session previously recorded via `--record`.
+    ///
+    /// Events are replayed as fast as possible, then the session continues
+    /// normally. Rooms don't connect to their servers. Implies `--offline`.
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Print how long the individual steps of starting cove took.
+    ///
+    /// The steps are printed when cove exits. This helps find out why cove
+    /// starts slowly, for example with a very large vault.
+    #[arg(long)]
+    startup_trace: bool,
+
+    /// Open a room right away instead of showing the rooms list.
+    ///
+    /// The room is specified as `&room` or `&room@domain`. If no domain is
+    /// given, `euphoria.leet.nu` is used.
+    #[arg(long, conflicts_with = "link")]
+    room: Option<String>,
+
+    /// Open the room (and optionally the message) that a link of the form
+    /// `euphoria://room/<room>[/<message id>]` points to.
+    link: Option<String>,
+
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -113,6 +205,50 @@ fn data_dir(config: &Config, dirs: &ProjectDirs) -> PathBuf {
         .unwrap_or_else(|| dirs.data_dir().to_path_buf())
 }
 
+const DEFAULT_DOMAIN: &str = "euphoria.leet.nu";
+
+fn parse_room(room: &str) -> anyhow::Result<RoomIdentifier> {
+    let room = room.strip_prefix('&').unwrap_or(room);
+    let (name, domain) = room.split_once('@').unwrap_or((room, DEFAULT_DOMAIN));
+    if name.is_empty() || domain.is_empty() {
+        anyhow::bail!("invalid room: {room:?}");
+    }
+    Ok(RoomIdentifier::new(domain.to_string(), name.to_string()))
+}
+
+fn parse_link(link: &str) -> anyhow::Result<StartRoom> {
+    let path = link
+        .strip_prefix("euphoria://room/")
+        .with_context(|| format!("unsupported link: {link:?}"))?
+        .trim_end_matches('/');
+    let (name, msg) = match path.split_once('/') {
+        Some((name, msg)) => (name, Some(msg)),
+        None => (path, None),
+    };
+
+    let room = parse_room(name)?;
+    let msg = match msg {
+        Some(msg) => {
+            let id = msg
+                .parse::<Snowflake>()
+                .map_err(|_| anyhow::anyhow!("invalid message id: {msg:?}"))?;
+            Some(MessageId(id))
+        }
+        None => None,
+    };
+
+    Ok(StartRoom { room, msg })
+}
+
+fn start_room(args: &Args) -> anyhow::Result<Option<StartRoom>> {
+    if let Some(room) = &args.room {
+        let room = parse_room(room)?;
+        return Ok(Some(StartRoom { room, msg: None }));
+    }
+
+    args.link.as_deref().map(parse_link).transpose()
+}
+
 fn update_config_with_args(config: &mut Config, args: &Args) {
     if let Some(data_dir) = args.data_dir.clone() {
         // The data dir specified via args_data_dir is relative to the current
@@ -127,29 +263,64 @@ fn update_config_with_args(config: &mut Config, args: &Args) {
 
     config.ephemeral |= args.ephemeral;
     config.measure_widths |= args.measure_widths;
-    config.offline |= args.offline;
+    // Messages arriving during a replay would make it diverge from the
+    // recorded session even sooner.
+    config.offline |= args.offline || args.replay.is_some();
 }
 
-fn open_vault(config: &Config, dirs: &ProjectDirs) -> anyhow::Result<Vault> {
+/// Open the vault, asking the daemon to release it first if one is running.
+async fn open_vault(config: &Config, dirs: &ProjectDirs) -> anyhow::Result<Vault> {
     let time_zone =
         util::load_time_zone(config.time_zone_ref()).context("failed to load time zone")?;
     let time_zone = Box::leak(Box::new(time_zone));
 
     let vault = if config.ephemeral {
-        vault::launch_in_memory(time_zone)?
+        cove_vault::launch_in_memory(time_zone)?
     } else {
         let data_dir = data_dir(config, dirs);
         eprintln!("Data dir:    {}", data_dir.to_string_lossy());
-        vault::launch(&data_dir.join("vault.db"), time_zone)?
+        let release = daemon::release_vault(&daemon::socket_path(&data_dir)).await;
+        if release == daemon::Release::Released {
+            info!("Daemon released the vault, it will reconnect its rooms once cove exits");
+        }
+        match cove_vault::launch(&data_dir.join("vault.db"), time_zone) {
+            Ok(vault) => vault,
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == rusqlite::ErrorCode::DatabaseBusy =>
+            {
+                if release == daemon::Release::NotHeld {
+                    anyhow::bail!("the vault is in use by another instance of cove")
+                }
+                anyhow::bail!(
+                    "the vault is in use, is another instance of cove or its daemon running?"
+                )
+            }
+            Err(err) => return Err(err.into()),
+        }
     };
 
     Ok(vault)
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
+    // Rooms only trust the demo's mock server if its certificate is the only
+    // root certificate. The environment must not be modified once other
+    // threads are running, so this has to happen before the runtime starts.
+    #[cfg(feature = "mock-server")]
+    if matches!(args.command, Some(Command::Demo)) {
+        std::env::set_var("SSL_CERT_FILE", mock_server::certificate_file()?);
+    }
+
+    tokio::runtime::Runtime::new()?.block_on(async_main(args))
+}
+
+async fn async_main(args: Args) -> anyhow::Result<()> {
+    if args.startup_trace {
+        startup::enable();
+    }
+
     let (logger, logger_guard, logger_rx) = Logger::init(args.verbose);
     let dirs = ProjectDirs::from("de", "plugh", "cove").expect("failed to find config directory");
 
@@ -161,13 +332,36 @@ async fn main() -> anyhow::Result<()> {
     let mut config = Config::load(&config_path)?;
     update_config_with_args(&mut config, &args);
     let config = Box::leak(Box::new(config));
+    startup::step("load config");
+
+    let start = start_room(&args)?;
 
     match args.command.unwrap_or_default() {
-        Command::Run => run(logger, logger_rx, config, &dirs).await?,
+        Command::Run => {
+            let recording = Recording::new(args.record.as_deref(), args.replay.as_deref())
+                .context("failed to set up recording")?;
+            run(logger, logger_rx, config, &dirs, recording, start).await?
+        }
+        Command::Daemon => daemon(logger, config, &dirs).await?,
         Command::Export(args) => export(config, &dirs, args).await?,
+        Command::VerifyExport(args) => export::verify(args)?,
+        Command::Digest(args) => digest(config, &dirs, args).await?,
         Command::Gc => gc(config, &dirs).await?,
         Command::ClearCookies { domain } => clear_cookies(config, &dirs, domain).await?,
+        Command::ImportSession(args) => import_session(config, &dirs, args).await?,
+        Command::Log(args) => tail_log(config, &dirs, args).await?,
         Command::HelpConfig => help_config(),
+        #[cfg(feature = "mock-server")]
+        Command::Demo => {
+            config.ephemeral = true;
+            demo(logger, logger_rx, config).await?
+        }
+        #[cfg(feature = "mock-server")]
+        Command::MockServer {
+            addr,
+            password,
+            tls,
+        } => mock_server(addr, password, tls).await?,
     }
 
     // Print all logged errors. This should always happen, even if cove panics,
@@ -175,6 +369,8 @@ async fn main() -> anyhow::Result<()> {
     // this, it is not implemented via a normal function call.
     drop(logger_guard);
 
+    startup::print();
+
     eprintln!("Goodbye!");
     Ok(())
 }
@@ -184,26 +380,167 @@ async fn run(
     logger_rx: mpsc::UnboundedReceiver<()>,
     config: &'static Config,
     dirs: &ProjectDirs,
+    recording: Recording,
+    start: Option<StartRoom>,
 ) -> anyhow::Result<()> {
     info!("Welcome to {NAME} {VERSION}",);
 
-    let vault = open_vault(config, dirs)?;
+    let socket = (!config.ephemeral).then(|| ipc::socket_path(&data_dir(config, dirs)));
+    if let Some(socket) = &socket {
+        if ipc::hand_off(socket, start.as_ref()).await {
+            eprintln!("Another instance of cove is already using this data dir.");
+            if start.is_some() {
+                eprintln!("The room was opened in that instance instead.");
+            }
+            return Ok(());
+        }
+    }
+    startup::step("check for other instances");
+
+    let vault = open_vault(config, dirs).await?;
+    startup::step("open and migrate vault");
+
+    if !config.ephemeral {
+        let path = data_dir(config, dirs).join(logfile::FILE_NAME);
+        if let Err(err) = logger.log_to_file(&path) {
+            warn!("Failed to open log file {}: {err}", path.to_string_lossy());
+        }
+    }
+
+    let sync = match &config.sync_dir {
+        Some(dir) if !config.ephemeral => {
+            let data_dir = data_dir(config, dirs);
+            let sync = sync::launch(vault.euph(), dir.clone(), &data_dir).await;
+            Some(sync.context("failed to set up sync dir")?)
+        }
+        _ => None,
+    };
+    startup::step("set up sync dir");
+
+    let (open_tx, open_rx) = mpsc::unbounded_channel();
+    if let Some(start) = start {
+        let _ = open_tx.send(start);
+    }
+    if let Some(socket) = socket.clone() {
+        tokio::spawn(ipc::listen(socket, open_tx));
+    }
+    tokio::spawn(diagnostics::watch(vault.clone()));
+    tokio::spawn(euph::purge_deleted_content(config, vault.euph()));
+    if let Some(addr) = config.metrics_addr.clone() {
+        tokio::spawn(metrics::serve(addr, vault.euph()));
+    }
+
+    let mut terminal = Terminal::new()?;
+    terminal.set_measuring(config.measure_widths);
+    startup::step("set up terminal");
+    Ui::run(
+        config,
+        &mut terminal,
+        vault.clone(),
+        logger,
+        logger_rx,
+        recording,
+        open_rx,
+    )
+    .await?;
+    drop(terminal);
+
+    if let Some(socket) = &socket {
+        let _ = fs::remove_file(socket);
+    }
+
+    if let Some(sync) = sync {
+        sync.stop().await;
+    }
+
+    vault.close().await;
+    Ok(())
+}
+
+#[cfg(feature = "mock-server")]
+async fn demo(
+    logger: Logger,
+    logger_rx: mpsc::UnboundedReceiver<()>,
+    config: &'static mut Config,
+) -> anyhow::Result<()> {
+    let time_zone =
+        util::load_time_zone(config.time_zone_ref()).context("failed to load time zone")?;
+    let time_zone = Box::leak(Box::new(time_zone));
+    let vault = cove_vault::launch_in_memory(time_zone)?;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let domain = format!("localhost:{}", listener.local_addr()?.port());
+    let server = mock_server::MockServer::new(None);
+    demo::populate(&vault.euph(), &server, &domain).await?;
+    tokio::spawn(server.serve(listener, Some(mock_server::tls_acceptor()?)));
+
+    // Only the demo rooms are connected to, whatever the config file says.
+    let rooms = demo::ROOMS
+        .iter()
+        .map(|name| {
+            let room = cove_config::EuphRoom {
+                autojoin: true,
+                ..Default::default()
+            };
+            (name.to_string(), room)
+        })
+        .collect();
+    let server = cove_config::EuphServer {
+        rooms,
+        ..Default::default()
+    };
+    config.euph.servers = HashMap::from([(domain, server)]);
+    config.euph.templates.clear();
+    config.offline = false;
+    let config = &*config;
+
+    // Other instances can't hand rooms over to the demo
+    let (_, open_rx) = mpsc::unbounded_channel();
 
     let mut terminal = Terminal::new()?;
     terminal.set_measuring(config.measure_widths);
-    Ui::run(config, &mut terminal, vault.clone(), logger, logger_rx).await?;
+    Ui::run(
+        config,
+        &mut terminal,
+        vault.clone(),
+        logger,
+        logger_rx,
+        Recording::Off,
+        open_rx,
+    )
+    .await?;
     drop(terminal);
 
     vault.close().await;
     Ok(())
 }
 
+async fn daemon(logger: Logger, config: &'static Config, dirs: &ProjectDirs) -> anyhow::Result<()> {
+    if config.ephemeral {
+        anyhow::bail!("the daemon can't run in ephemeral mode");
+    }
+
+    let time_zone =
+        util::load_time_zone(config.time_zone_ref()).context("failed to load time zone")?;
+    let time_zone = Box::leak(Box::new(time_zone));
+
+    let data_dir = data_dir(config, dirs);
+    eprintln!("Data dir:    {}", data_dir.to_string_lossy());
+    let path = data_dir.join(daemon::LOG_FILE_NAME);
+    if let Err(err) = logger.log_to_file(&path) {
+        warn!("Failed to open log file {}: {err}", path.to_string_lossy());
+    }
+
+    info!("Starting {NAME} {VERSION} daemon");
+    daemon::run(config, &data_dir, time_zone).await
+}
+
 async fn export(
     config: &'static Config,
     dirs: &ProjectDirs,
     args: export::Args,
 ) -> anyhow::Result<()> {
-    let vault = open_vault(config, dirs)?;
+    let vault = open_vault(config, dirs).await?;
 
     export::export(&vault.euph(), args).await?;
 
@@ -211,8 +548,26 @@ async fn export(
     Ok(())
 }
 
+async fn digest(
+    config: &'static Config,
+    dirs: &ProjectDirs,
+    args: digest::Args,
+) -> anyhow::Result<()> {
+    let vault = open_vault(config, dirs).await?;
+
+    digest::digest(&vault.euph(), args).await?;
+
+    vault.close().await;
+    Ok(())
+}
+
+async fn tail_log(config: &Config, dirs: &ProjectDirs, args: logfile::Args) -> anyhow::Result<()> {
+    let path = data_dir(config, dirs).join(logfile::FILE_NAME);
+    logfile::tail(&path, args).await
+}
+
 async fn gc(config: &'static Config, dirs: &ProjectDirs) -> anyhow::Result<()> {
-    let vault = open_vault(config, dirs)?;
+    let vault = open_vault(config, dirs).await?;
 
     eprintln!("Cleaning up and compacting vault");
     eprintln!("This may take a while...");
@@ -227,7 +582,7 @@ async fn clear_cookies(
     dirs: &ProjectDirs,
     domain: Option<String>,
 ) -> anyhow::Result<()> {
-    let vault = open_vault(config, dirs)?;
+    let vault = open_vault(config, dirs).await?;
 
     eprintln!("Clearing cookies");
     vault.euph().clear_cookies(domain).await?;
@@ -236,6 +591,42 @@ async fn clear_cookies(
     Ok(())
 }
 
+async fn import_session(
+    config: &'static Config,
+    dirs: &ProjectDirs,
+    args: cookies::Args,
+) -> anyhow::Result<()> {
+    let vault = open_vault(config, dirs).await?;
+
+    eprintln!("Importing cookies");
+    cookies::import(&vault.euph(), args).await?;
+
+    vault.close().await;
+    Ok(())
+}
+
 fn help_config() {
     print!("{}", Config::doc().as_markdown());
 }
+
+#[cfg(feature = "mock-server")]
+async fn mock_server(
+    addr: std::net::SocketAddr,
+    password: Option<String>,
+    tls: bool,
+) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let tls = if tls {
+        let path = mock_server::certificate_file()?;
+        eprintln!("Certificate: {}", path.to_string_lossy());
+        eprintln!("Listening on wss://{}", listener.local_addr()?);
+        Some(mock_server::tls_acceptor()?)
+    } else {
+        eprintln!("Listening on ws://{}", listener.local_addr()?);
+        None
+    };
+    mock_server::MockServer::new(password)
+        .serve(listener, tls)
+        .await;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+//! Metrics about the running instance, served via HTTP in the Prometheus text
+//! format, see the `metrics_addr` config option.
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use cove_vault::EuphVault;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+static ROOMS_JOINED: AtomicU64 = AtomicU64::new(0);
+static CONNECTION_ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+static MSGS_RECEIVED: AtomicU64 = AtomicU64::new(0);
+
+/// Requests larger than this are not read completely.
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+pub fn room_joined() {
+    ROOMS_JOINED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn room_left() {
+    ROOMS_JOINED.fetch_sub(1, Ordering::Relaxed);
+}
+
+pub fn connection_attempted() {
+    CONNECTION_ATTEMPTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Messages were received from a server, either live or via a log request.
+pub fn msgs_received(amount: usize) {
+    MSGS_RECEIVED.fetch_add(amount as u64, Ordering::Relaxed);
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl ToString) {
+    let value = value.to_string();
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+async fn render(vault: &EuphVault) -> String {
+    let mut out = String::new();
+
+    metric(
+        &mut out,
+        "cove_rooms_joined",
+        "gauge",
+        "Rooms currently joined.",
+        ROOMS_JOINED.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "cove_connection_attempts_total",
+        "counter",
+        "Connection attempts to rooms, including reconnects.",
+        CONNECTION_ATTEMPTS.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "cove_messages_received_total",
+        "counter",
+        "Messages received from servers and stored in the vault.",
+        MSGS_RECEIVED.load(Ordering::Relaxed),
+    );
+
+    // Requests to the vault are queued, so this includes the time spent
+    // waiting for other requests.
+    let start = Instant::now();
+    if vault.rooms().await.is_ok() {
+        metric(
+            &mut out,
+            "cove_vault_latency_seconds",
+            "gauge",
+            "How long the vault took to answer a simple request.",
+            start.elapsed().as_secs_f64(),
+        );
+    }
+
+    out
+}
+
+async fn respond(mut stream: TcpStream, vault: &EuphVault) -> std::io::Result<()> {
+    // Every request is answered with the metrics, so the request only needs to
+    // be read until the end of its headers.
+    let mut request = vec![];
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_LEN {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let body = render(vault).await;
+    let response = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await
+}
+
+pub async fn serve(addr: String, vault: EuphVault) {
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::warn!("Failed to serve metrics on {addr}: {err}");
+            return;
+        }
+    };
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                log::warn!("Failed to accept metrics request: {err}");
+                continue;
+            }
+        };
+
+        let vault = vault.clone();
+        tokio::spawn(async move {
+            if let Err(err) = respond(stream, &vault).await {
+                log::debug!("Failed to answer metrics request: {err}");
+            }
+        });
+    }
+}
@@ -0,0 +1,713 @@
+//! A minimal in-process euphoria server for tests and demos.
+//!
+//! It speaks just enough of the protocol for a client to join rooms and chat:
+//! `hello-event`, `snapshot-event`, `bounce-event` and the `auth`, `log`,
+//! `nick`, `ping` and `send` commands. Rooms are created when the first client
+//! connects and only live in memory.
+//!
+//! Cove always connects to rooms via `wss://`, so for cove itself to reach the
+//! server, it has to be served with [`tls_acceptor`] and cove has to be started
+//! with `SSL_CERT_FILE` pointing to [`certificate_file`]. Rooms on the server
+//! then use `localhost:<port>` as their domain.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::{env, fs, io, process};
+
+use euphoxide::api::Message;
+use futures_util::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::WebSocketStream;
+
+/// Maximum amount of messages in a `snapshot-event` or `log-reply`.
+const LOG_AMOUNT: usize = 100;
+
+/// Self-signed certificate for `localhost`, valid until 2120. It is only meant
+/// for connecting to the mock server, so its key is no secret.
+const CERTIFICATE: &str = include_str!("mock_server/localhost.crt");
+const PRIVATE_KEY: &str = include_str!("mock_server/localhost.key");
+
+/// Accepts `wss://` connections to `localhost` using the built-in certificate.
+pub fn tls_acceptor() -> anyhow::Result<TlsAcceptor> {
+    let certs = rustls_pemfile::certs(&mut CERTIFICATE.as_bytes()).collect::<Result<_, _>>()?;
+    let key = rustls_pemfile::private_key(&mut PRIVATE_KEY.as_bytes())?
+        .ok_or_else(|| anyhow::anyhow!("no private key"))?;
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Write the built-in certificate to a temporary file and return its path.
+pub fn certificate_file() -> io::Result<PathBuf> {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    if let Some(path) = PATH.get() {
+        return Ok(path.clone());
+    }
+
+    let path = env::temp_dir().join(format!("cove-mock-server-{}.crt", process::id()));
+    fs::write(&path, CERTIFICATE)?;
+    Ok(PATH.get_or_init(|| path).clone())
+}
+
+/// An event of a room and the session that caused it, which doesn't receive
+/// the event itself.
+#[derive(Clone)]
+struct RoomEvent {
+    session_id: String,
+    packet: String,
+}
+
+struct Room {
+    /// Messages ordered by their id.
+    log: Vec<Value>,
+    /// Session views of the sessions that finished joining, by session id.
+    listing: HashMap<String, Value>,
+    events: broadcast::Sender<RoomEvent>,
+}
+
+impl Room {
+    fn new() -> Self {
+        Self {
+            log: vec![],
+            listing: HashMap::new(),
+            events: broadcast::channel(LOG_AMOUNT).0,
+        }
+    }
+
+    fn broadcast(&self, session_id: &str, packet: &Value) {
+        let _ = self.events.send(RoomEvent {
+            session_id: session_id.to_string(),
+            packet: packet.to_string(),
+        });
+    }
+}
+
+struct State {
+    password: Option<String>,
+    rooms: HashMap<String, Room>,
+    /// Used for session ids and message ids alike.
+    next_id: u64,
+}
+
+impl State {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+#[derive(Clone)]
+pub struct MockServer {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockServer {
+    /// If a password is set, all rooms are private and clients have to
+    /// authenticate using it as passcode before joining.
+    pub fn new(password: Option<String>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                password,
+                rooms: HashMap::new(),
+                next_id: 0,
+            })),
+        }
+    }
+
+    /// Add messages to a room's log, creating the room if necessary.
+    ///
+    /// Messages sent afterwards get ids higher than any of these.
+    pub fn add_msgs(&self, room: &str, msgs: Vec<Message>) -> serde_json::Result<()> {
+        let mut guard = self.state.lock();
+        let max_id = msgs.iter().map(|msg| msg.id.0 .0).max().unwrap_or(0);
+        guard.next_id = guard.next_id.max(max_id);
+
+        let room = guard
+            .rooms
+            .entry(room.to_string())
+            .or_insert_with(Room::new);
+        for msg in msgs {
+            room.log.push(serde_json::to_value(msg)?);
+        }
+        // Snowflakes of the same length sort like the numbers they encode.
+        room.log
+            .sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+        Ok(())
+    }
+
+    /// Accept connections until the task is aborted. If `tls` is set, clients
+    /// have to connect via `wss://`, otherwise via `ws://`.
+    pub async fn serve(self, listener: TcpListener, tls: Option<TlsAcceptor>) {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    log::warn!("Failed to accept connection: {err}");
+                    continue;
+                }
+            };
+
+            let server = self.clone();
+            let tls = tls.clone();
+            tokio::spawn(async move {
+                let result = match tls {
+                    Some(tls) => match tls.accept(stream).await {
+                        Ok(stream) => server.handle(stream).await,
+                        Err(err) => Err(err.into()),
+                    },
+                    None => server.handle(stream).await,
+                };
+                if let Err(err) = result {
+                    log::debug!("Connection closed: {err}");
+                }
+            });
+        }
+    }
+
+    async fn handle<S>(self, stream: S) -> anyhow::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut path = None;
+        let callback = |request: &Request, response: Response| {
+            path = Some(request.uri().path().to_string());
+            Ok::<_, ErrorResponse>(response)
+        };
+        let ws = tokio_tungstenite::accept_hdr_async(stream, callback).await?;
+        let Some(room) = path.as_deref().and_then(room_name) else {
+            anyhow::bail!("not a room url");
+        };
+
+        let mut session = Session::new(self.state, room);
+        let result = session.run(ws).await;
+        session.part();
+        result
+    }
+}
+
+/// Extract the room name from a path like `/room/<name>/ws`.
+fn room_name(path: &str) -> Option<String> {
+    let name = path.strip_prefix("/room/")?.strip_suffix("/ws")?;
+    if name.is_empty() || name.contains('/') {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Ids are snowflakes, which are encoded in base 36 and padded to 13 digits.
+fn snowflake(id: u64) -> String {
+    let mut digits = vec![];
+    let mut rest = id;
+    loop {
+        digits.push(std::char::from_digit((rest % 36) as u32, 36).unwrap());
+        rest /= 36;
+        if rest == 0 {
+            break;
+        }
+    }
+    digits.resize(13.max(digits.len()), '0');
+    digits.into_iter().rev().collect()
+}
+
+fn packet(kind: &str, id: Option<&Value>, data: Value) -> Value {
+    let mut packet = json!({ "type": kind, "data": data });
+    if let Some(id) = id {
+        packet["id"] = id.clone();
+    }
+    packet
+}
+
+fn error_reply(kind: &str, id: Option<&Value>, error: &str) -> Value {
+    let mut packet = json!({ "type": format!("{kind}-reply"), "error": error });
+    if let Some(id) = id {
+        packet["id"] = id.clone();
+    }
+    packet
+}
+
+struct Session {
+    state: Arc<Mutex<State>>,
+    room: String,
+    session_id: String,
+    nick: String,
+    authenticated: bool,
+    joined: bool,
+}
+
+impl Session {
+    fn new(state: Arc<Mutex<State>>, room: String) -> Self {
+        let (session_id, authenticated) = {
+            let mut guard = state.lock();
+            let id = snowflake(guard.next_id());
+            (id, guard.password.is_none())
+        };
+
+        Self {
+            state,
+            room,
+            session_id,
+            nick: String::new(),
+            authenticated,
+            joined: false,
+        }
+    }
+
+    fn view(&self) -> Value {
+        json!({
+            "id": format!("agent:{}", self.session_id),
+            "name": self.nick,
+            "server_id": "mock",
+            "server_era": "mock",
+            "session_id": self.session_id,
+        })
+    }
+
+    async fn send<S>(&self, ws: &mut WebSocketStream<S>, packet: Value) -> anyhow::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        ws.send(WsMessage::Text(packet.to_string())).await?;
+        Ok(())
+    }
+
+    async fn run<S>(&mut self, mut ws: WebSocketStream<S>) -> anyhow::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (private, mut events) = {
+            let mut guard = self.state.lock();
+            let private = guard.password.is_some();
+            let room = guard
+                .rooms
+                .entry(self.room.clone())
+                .or_insert_with(Room::new);
+            (private, room.events.subscribe())
+        };
+
+        let hello = json!({
+            "id": format!("agent:{}", self.session_id),
+            "session": self.view(),
+            "account_has_access": false,
+            "room_is_private": private,
+            "version": "mock",
+        });
+        self.send(&mut ws, packet("hello-event", None, hello))
+            .await?;
+
+        if self.authenticated {
+            self.join(&mut ws).await?;
+        } else {
+            let bounce = json!({
+                "reason": "authentication required",
+                "auth_options": ["passcode"],
+            });
+            self.send(&mut ws, packet("bounce-event", None, bounce))
+                .await?;
+        }
+
+        loop {
+            tokio::select! {
+                msg = ws.next() => {
+                    let text = match msg {
+                        Some(Ok(WsMessage::Text(text))) => text,
+                        Some(Ok(WsMessage::Close(_))) | None => return Ok(()),
+                        Some(Ok(_)) => continue,
+                        Some(Err(err)) => return Err(err.into()),
+                    };
+                    let command: Value = serde_json::from_str(&text)?;
+                    for reply in self.on_command(&command) {
+                        self.send(&mut ws, reply).await?;
+                    }
+                    if self.authenticated && !self.joined {
+                        self.join(&mut ws).await?;
+                    }
+                }
+                event = events.recv() => {
+                    let event = match event {
+                        Ok(event) => event,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                    };
+                    if self.joined && event.session_id != self.session_id {
+                        ws.send(WsMessage::Text(event.packet)).await?;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn join<S>(&mut self, ws: &mut WebSocketStream<S>) -> anyhow::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let snapshot = {
+            let mut guard = self.state.lock();
+            let room = guard.rooms.get_mut(&self.room).expect("room exists");
+            let listing = room.listing.values().cloned().collect::<Vec<_>>();
+            let log_start = room.log.len().saturating_sub(LOG_AMOUNT);
+            let log = room.log[log_start..].to_vec();
+
+            room.listing.insert(self.session_id.clone(), self.view());
+            room.broadcast(&self.session_id, &packet("join-event", None, self.view()));
+
+            json!({
+                "identity": format!("agent:{}", self.session_id),
+                "session_id": self.session_id,
+                "version": "mock",
+                "listing": listing,
+                "log": log,
+            })
+        };
+
+        self.joined = true;
+        self.send(ws, packet("snapshot-event", None, snapshot))
+            .await
+    }
+
+    fn part(&self) {
+        if !self.joined {
+            return;
+        }
+        let mut guard = self.state.lock();
+        if let Some(room) = guard.rooms.get_mut(&self.room) {
+            room.listing.remove(&self.session_id);
+            room.broadcast(&self.session_id, &packet("part-event", None, self.view()));
+        }
+    }
+
+    /// Handle a command packet and return the packets to send back.
+    fn on_command(&mut self, command: &Value) -> Vec<Value> {
+        let id = command.get("id");
+        let data = &command["data"];
+        let Some(kind) = command["type"].as_str() else {
+            return vec![];
+        };
+
+        match kind {
+            "ping" => vec![packet("ping-reply", id, json!({ "time": data["time"] }))],
+            "auth" => vec![self.on_auth(id, data)],
+            _ if !self.joined => vec![error_reply(kind, id, "not joined")],
+            "nick" => vec![self.on_nick(id, data)],
+            "send" => vec![self.on_send(id, data)],
+            "log" => vec![self.on_log(id, data)],
+            _ => vec![error_reply(kind, id, "unknown command")],
+        }
+    }
+
+    fn on_auth(&mut self, id: Option<&Value>, data: &Value) -> Value {
+        if self.authenticated {
+            return error_reply("auth", id, "already joined");
+        }
+
+        let guard = self.state.lock();
+        if data["passcode"].as_str() == guard.password.as_deref() {
+            self.authenticated = true;
+            packet("auth-reply", id, json!({ "success": true }))
+        } else {
+            let data = json!({ "success": false, "reason": "authentication failed" });
+            packet("auth-reply", id, data)
+        }
+    }
+
+    fn on_nick(&mut self, id: Option<&Value>, data: &Value) -> Value {
+        let Some(name) = data["name"].as_str() else {
+            return error_reply("nick", id, "name required");
+        };
+
+        let from = std::mem::replace(&mut self.nick, name.to_string());
+        let data = json!({
+            "session_id": self.session_id,
+            "id": format!("agent:{}", self.session_id),
+            "from": from,
+            "to": self.nick,
+        });
+
+        let mut guard = self.state.lock();
+        let room = guard.rooms.get_mut(&self.room).expect("room exists");
+        room.listing.insert(self.session_id.clone(), self.view());
+        room.broadcast(&self.session_id, &packet("nick-event", None, data.clone()));
+        packet("nick-reply", id, data)
+    }
+
+    fn on_send(&mut self, id: Option<&Value>, data: &Value) -> Value {
+        let Some(content) = data["content"].as_str() else {
+            return error_reply("send", id, "content required");
+        };
+
+        let mut guard = self.state.lock();
+        let msg = json!({
+            "id": snowflake(guard.next_id()),
+            "parent": data.get("parent"),
+            "time": OffsetDateTime::now_utc().unix_timestamp(),
+            "sender": self.view(),
+            "content": content,
+        });
+
+        let room = guard.rooms.get_mut(&self.room).expect("room exists");
+        room.log.push(msg.clone());
+        room.broadcast(&self.session_id, &packet("send-event", None, msg.clone()));
+        packet("send-reply", id, msg)
+    }
+
+    fn on_log(&mut self, id: Option<&Value>, data: &Value) -> Value {
+        let amount = data["n"].as_u64().unwrap_or(0) as usize;
+        let amount = amount.min(LOG_AMOUNT);
+        let before = data["before"].as_str();
+
+        let guard = self.state.lock();
+        let room = guard.rooms.get(&self.room).expect("room exists");
+        // Snowflakes of the same length sort like the numbers they encode.
+        let end = match before {
+            Some(before) => room
+                .log
+                .partition_point(|msg| msg["id"].as_str() < Some(before)),
+            None => room.log.len(),
+        };
+        let log = room.log[end.saturating_sub(amount)..end].to_vec();
+
+        let mut data = json!({ "log": log });
+        if let Some(before) = before {
+            data["before"] = json!(before);
+        }
+        packet("log-reply", id, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::env;
+    use std::process::Command;
+    use std::time::Duration;
+
+    use cove_config::Hooks;
+    use euphoxide::api::packet::ParsedPacket;
+    use euphoxide::api::{Data, Message};
+    use euphoxide::bot::instance::{Event, ServerConfig};
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::{json, Value};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+    use tz::TimeZone;
+
+    use crate::euph::{self, Webhooks};
+
+    use super::{certificate_file, snowflake, tls_acceptor, MockServer};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    async fn start(password: Option<&str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = MockServer::new(password.map(|p| p.to_string()));
+        tokio::spawn(server.serve(listener, None));
+        format!("ws://{addr}/room/test/ws")
+    }
+
+    async fn connect(url: &str) -> Client {
+        tokio_tungstenite::connect_async(url).await.unwrap().0
+    }
+
+    async fn recv(client: &mut Client) -> Value {
+        loop {
+            if let WsMessage::Text(text) = client.next().await.unwrap().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    async fn send(client: &mut Client, kind: &str, data: Value) -> Value {
+        let command = json!({ "id": "1", "type": kind, "data": data });
+        let command = WsMessage::Text(command.to_string());
+        client.send(command).await.unwrap();
+        recv(client).await
+    }
+
+    #[test]
+    fn snowflakes_are_padded() {
+        assert_eq!(snowflake(0), "0000000000000");
+        assert_eq!(snowflake(35), "000000000000z");
+        assert_eq!(snowflake(36), "0000000000010");
+    }
+
+    #[tokio::test]
+    async fn messages_are_logged_and_broadcast() {
+        let url = start(None).await;
+
+        let mut alice = connect(&url).await;
+        assert_eq!(recv(&mut alice).await["type"], "hello-event");
+        assert_eq!(recv(&mut alice).await["type"], "snapshot-event");
+        let reply = send(&mut alice, "nick", json!({ "name": "alice" })).await;
+        assert_eq!(reply["data"]["to"], "alice");
+
+        let mut bob = connect(&url).await;
+        assert_eq!(recv(&mut bob).await["type"], "hello-event");
+        let snapshot = recv(&mut bob).await;
+        assert_eq!(snapshot["data"]["listing"][0]["name"], "alice");
+        assert_eq!(recv(&mut alice).await["type"], "join-event");
+
+        let reply = send(&mut alice, "send", json!({ "content": "hi" })).await;
+        assert_eq!(reply["type"], "send-reply");
+        assert_eq!(reply["id"], "1");
+        let event = recv(&mut bob).await;
+        assert_eq!(event["type"], "send-event");
+        assert_eq!(event["data"]["sender"]["name"], "alice");
+        assert_eq!(event["data"]["content"], "hi");
+
+        let reply = send(&mut bob, "log", json!({ "n": 10 })).await;
+        assert_eq!(reply["data"]["log"][0]["content"], "hi");
+        let before = reply["data"]["log"][0]["id"].clone();
+        let reply = send(&mut bob, "log", json!({ "n": 10, "before": before })).await;
+        assert_eq!(reply["data"]["log"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn private_rooms_require_passcode() {
+        let url = start(Some("hunter2")).await;
+
+        let mut client = connect(&url).await;
+        let hello = recv(&mut client).await;
+        assert_eq!(hello["data"]["room_is_private"], true);
+        assert_eq!(recv(&mut client).await["type"], "bounce-event");
+
+        let reply = send(&mut client, "send", json!({ "content": "hi" })).await;
+        assert_eq!(reply["error"], "not joined");
+
+        let reply = send(&mut client, "auth", json!({ "passcode": "wrong" })).await;
+        assert_eq!(reply["data"]["success"], false);
+
+        let reply = send(&mut client, "auth", json!({ "passcode": "hunter2" })).await;
+        assert_eq!(reply["data"]["success"], true);
+        assert_eq!(recv(&mut client).await["type"], "snapshot-event");
+    }
+
+    fn msg(id: u64, content: &str) -> Message {
+        let sender = json!({
+            "id": "agent:seed",
+            "name": "seed",
+            "server_id": "mock",
+            "server_era": "mock",
+            "session_id": "seed",
+        });
+        let msg = json!({
+            "id": snowflake(id),
+            "time": 1_700_000_000 + id,
+            "sender": sender,
+            "content": content,
+        });
+        serde_json::from_value(msg).unwrap()
+    }
+
+    /// Handle the room's events until one of them is a packet matching `until`.
+    async fn handle_until(
+        room: &mut euph::Room,
+        events: &mut mpsc::UnboundedReceiver<Event>,
+        until: impl Fn(&ParsedPacket) -> bool,
+    ) {
+        let handle = async {
+            loop {
+                let event = events.recv().await.unwrap();
+                let done = matches!(&event, Event::Packet(_, packet, _) if until(packet));
+                room.handle_event(event).await;
+                if done {
+                    break;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), handle)
+            .await
+            .expect("room did not receive packet in time");
+    }
+
+    /// Rooms only trust the mock server's certificate if `SSL_CERT_FILE` points
+    /// to it. Setting it would affect all other tests in this process, so the
+    /// room test runs in a child process instead.
+    #[test]
+    fn room_joins_sends_and_fetches_logs() {
+        let status = Command::new(env::current_exe().unwrap())
+            .args(["mock_server::tests::room_in_child", "--exact", "--ignored"])
+            .env("SSL_CERT_FILE", certificate_file().unwrap())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[tokio::test]
+    #[ignore = "only run by room_joins_sends_and_fetches_logs"]
+    async fn room_in_child() {
+        if env::var_os("SSL_CERT_FILE").is_none() {
+            return;
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let domain = format!("localhost:{}", listener.local_addr().unwrap().port());
+        let server = MockServer::new(None);
+        let seeded = (1..=150).map(|id| msg(id, "seeded")).collect();
+        server.add_msgs("test", seeded).unwrap();
+        tokio::spawn(server.serve(listener, Some(tls_acceptor().unwrap())));
+
+        let time_zone = Box::leak(Box::new(TimeZone::utc()));
+        let vault = cove_vault::launch_in_memory(time_zone).unwrap();
+        let vault = vault.euph().room(cove_vault::RoomIdentifier::new(
+            domain.clone(),
+            "test".to_string(),
+        ));
+
+        let (tx, mut events) = mpsc::unbounded_channel();
+        let instance_config = ServerConfig::default()
+            .domain(domain)
+            .timeout(Duration::from_secs(10))
+            .room("test".to_string())
+            .name("test".to_string())
+            .human(true);
+        let mut room = euph::Room::new(
+            vault.clone(),
+            Box::leak(Box::new(Hooks::default())),
+            Webhooks::new(Box::leak(Box::new(HashMap::new())), "cove"),
+            instance_config,
+            false,
+            move |e| {
+                let _ = tx.send(e);
+            },
+        );
+
+        // The snapshot only contains the newest messages
+        handle_until(&mut room, &mut events, |p| {
+            matches!(p.content, Ok(Data::SnapshotEvent(_)))
+        })
+        .await;
+        assert!(room.state().joined().is_some());
+        assert_eq!(vault.newest_msg_id().await.unwrap(), Some(msg(150, "").id));
+        assert_ne!(vault.oldest_msg_id().await.unwrap(), Some(msg(1, "").id));
+
+        room.log().unwrap();
+        handle_until(&mut room, &mut events, |p| {
+            matches!(p.content, Ok(Data::LogReply(_)))
+        })
+        .await;
+        assert_eq!(vault.oldest_msg_id().await.unwrap(), Some(msg(1, "").id));
+
+        let reply = room.send(None, "hello".to_string()).unwrap();
+        handle_until(&mut room, &mut events, |p| {
+            matches!(p.content, Ok(Data::SendReply(_)))
+        })
+        .await;
+        let id = reply.await.unwrap();
+        let sent = vault.msg(id).await.unwrap().unwrap();
+        assert_eq!(sent.content, "hello");
+        assert_eq!(vault.newest_msg_id().await.unwrap(), Some(id));
+    }
+}
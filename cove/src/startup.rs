@@ -0,0 +1,64 @@
+//! Measuring how long the individual steps of starting cove take, see the
+//! `--startup-trace` flag.
+
+use std::time::{Duration, Instant};
+
+use log::info;
+use parking_lot::Mutex;
+
+struct Trace {
+    start: Instant,
+    last: Instant,
+    steps: Vec<(&'static str, Duration)>,
+    /// Whether the first frame was rendered, after which nothing is recorded.
+    done: bool,
+}
+
+static TRACE: Mutex<Option<Trace>> = parking_lot::const_mutex(None);
+
+/// Start recording steps. Until this is called, [`step`] does nothing.
+pub fn enable() {
+    let now = Instant::now();
+    *TRACE.lock() = Some(Trace {
+        start: now,
+        last: now,
+        steps: vec![],
+        done: false,
+    });
+}
+
+/// Record that a step finished. It is considered to have started when the
+/// previous step finished.
+pub fn step(name: &'static str) {
+    if let Some(trace) = TRACE.lock().as_mut() {
+        if !trace.done {
+            let now = Instant::now();
+            trace.steps.push((name, now - trace.last));
+            trace.last = now;
+        }
+    }
+}
+
+/// Record the first render, which concludes the startup.
+pub fn first_render() {
+    step("first render");
+    if let Some(trace) = TRACE.lock().as_mut() {
+        if !trace.done {
+            trace.done = true;
+            info!("Startup took {:.0?}", trace.last - trace.start);
+        }
+    }
+}
+
+/// Print all recorded steps, if recording was enabled.
+pub fn print() {
+    let Some(trace) = TRACE.lock().take() else {
+        return;
+    };
+
+    eprintln!("Startup trace:");
+    for (name, duration) in &trace.steps {
+        eprintln!("  {name:<24} {duration:>10.1?}");
+    }
+    eprintln!("  {:<24} {:>10.1?}", "total", trace.last - trace.start);
+}
@@ -1,15 +1,23 @@
-use std::collections::HashMap;
+mod memory;
+
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::vec;
 
 use async_trait::async_trait;
+use cove_config::SiblingOrder;
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+
+pub use self::memory::{MemoryMsg, MemoryStore};
 
 pub trait Msg {
     type Id: Clone + Debug + Hash + Eq + Ord;
     fn id(&self) -> Self::Id;
     fn parent(&self) -> Option<Self::Id>;
     fn seen(&self) -> bool;
+    fn time(&self) -> Option<OffsetDateTime>;
 
     fn last_possible_id() -> Self::Id;
 }
@@ -49,28 +57,61 @@ pub struct Tree<M: Msg> {
     root: M::Id,
     msgs: HashMap<M::Id, M>,
     children: HashMap<M::Id, Vec<M::Id>>,
+    /// Parents that are not loaded but have loaded children. They are shown as
+    /// placeholders directly below the root.
+    missing: HashSet<M::Id>,
 }
 
 impl<M: Msg> Tree<M> {
-    pub fn new(root: M::Id, msgs: Vec<M>) -> Self {
+    pub fn new(root: M::Id, msgs: Vec<M>, order: SiblingOrder) -> Self {
         let msgs: HashMap<M::Id, M> = msgs.into_iter().map(|m| (m.id(), m)).collect();
 
         let mut children: HashMap<M::Id, Vec<M::Id>> = HashMap::new();
         for msg in msgs.values() {
             children.entry(msg.id()).or_default();
+            // A root with a parent would form a cycle if the parent is part of
+            // its own tree, and traversing the tree would never end. Every
+            // other cycle is unreachable from the root.
+            if msg.id() == root {
+                continue;
+            }
             if let Some(parent) = msg.parent() {
                 children.entry(parent).or_default().push(msg.id());
             }
         }
 
+        // Without a placeholder, messages whose parent is not loaded would be
+        // unreachable from the root.
+        let mut missing = HashSet::new();
+        for msg in msgs.values() {
+            let Some(parent) = msg.parent() else {
+                continue;
+            };
+            if msg.id() == root || parent == root || msgs.contains_key(&parent) {
+                continue;
+            }
+            if missing.insert(parent.clone()) {
+                children.entry(root.clone()).or_default().push(parent);
+            }
+        }
+
         for list in children.values_mut() {
-            list.sort_unstable();
+            match order {
+                SiblingOrder::Id => list.sort_unstable(),
+                SiblingOrder::Time => {
+                    list.sort_unstable_by_key(|id| {
+                        (msgs.get(id).and_then(|m| m.time()), id.clone())
+                    });
+                }
+                SiblingOrder::NewestFirst => list.sort_unstable_by(|a, b| b.cmp(a)),
+            }
         }
 
         Self {
             root,
             msgs,
             children,
+            missing,
         }
     }
 
@@ -86,7 +127,18 @@ impl<M: Msg> Tree<M> {
         self.msgs.get(id)
     }
 
+    /// Ids of all messages in the tree, in no particular order.
+    pub fn ids(&self) -> impl Iterator<Item = &M::Id> {
+        self.msgs.keys()
+    }
+
     pub fn parent(&self, id: &M::Id) -> Option<M::Id> {
+        if *id == self.root {
+            return None;
+        }
+        if self.missing.contains(id) {
+            return Some(self.root.clone());
+        }
         self.msg(id).and_then(|m| m.parent())
     }
 
@@ -95,10 +147,13 @@ impl<M: Msg> Tree<M> {
     }
 
     pub fn subtree_size(&self, id: &M::Id) -> usize {
-        let children = self.children(id).unwrap_or_default();
-        let mut result = children.len();
-        for child in children {
-            result += self.subtree_size(child);
+        // Iterative so that very deep threads can't overflow the stack.
+        let mut result = 0;
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let children = self.children(id).unwrap_or_default();
+            result += children.len();
+            stack.extend(children);
         }
         result
     }
@@ -130,12 +185,25 @@ impl<M: Msg> Tree<M> {
     }
 }
 
+/// A change to the messages of a store, see [`MsgStore::subscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreChange {
+    /// New messages were added or existing ones updated.
+    Added,
+    /// Messages were marked as seen or unseen.
+    Seen,
+    /// All messages were deleted.
+    Deleted,
+    /// A local note was attached to or removed from a message.
+    Note,
+}
+
 #[async_trait]
 pub trait MsgStore<M: Msg> {
     type Error;
     async fn path(&self, id: &M::Id) -> Result<Path<M::Id>, Self::Error>;
     async fn msg(&self, id: &M::Id) -> Result<Option<M>, Self::Error>;
-    async fn tree(&self, root_id: &M::Id) -> Result<Tree<M>, Self::Error>;
+    async fn tree(&self, root_id: &M::Id, order: SiblingOrder) -> Result<Tree<M>, Self::Error>;
     async fn first_root_id(&self) -> Result<Option<M::Id>, Self::Error>;
     async fn last_root_id(&self) -> Result<Option<M::Id>, Self::Error>;
     async fn prev_root_id(&self, root_id: &M::Id) -> Result<Option<M::Id>, Self::Error>;
@@ -150,5 +218,11 @@ pub trait MsgStore<M: Msg> {
     async fn newer_unseen_msg_id(&self, id: &M::Id) -> Result<Option<M::Id>, Self::Error>;
     async fn unseen_msgs_count(&self) -> Result<usize, Self::Error>;
     async fn set_seen(&self, id: &M::Id, seen: bool) -> Result<(), Self::Error>;
+    async fn set_many_seen(&self, ids: &[M::Id], seen: bool) -> Result<(), Self::Error>;
     async fn set_older_seen(&self, id: &M::Id, seen: bool) -> Result<(), Self::Error>;
+    async fn set_tree_seen(&self, root_id: &M::Id, seen: bool) -> Result<(), Self::Error>;
+
+    /// Receive all changes made to the messages from now on, no matter via
+    /// which handle to the store they were made.
+    fn subscribe(&self) -> mpsc::UnboundedReceiver<StoreChange>;
 }
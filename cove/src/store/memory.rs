@@ -0,0 +1,442 @@
+//! A message store keeping all of its messages in memory.
+//!
+//! Unlike the vault, it can hold any kind of message, which makes it useful
+//! for showing arbitrary data in a chat, like the log.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::Infallible;
+use std::hash::Hash;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use cove_config::SiblingOrder;
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
+
+use super::{Msg, MsgStore, Path, StoreChange, Tree};
+
+/// A message that can be held by a [`MemoryStore`].
+pub trait MemoryMsg: Msg + Clone {
+    /// Called when the message is marked as seen or unseen.
+    fn set_seen(&mut self, seen: bool);
+}
+
+/// Which messages are tree roots and which are the children of which. Derived
+/// from the messages and cached until they change.
+#[derive(Debug)]
+struct Index<I> {
+    roots: BTreeSet<I>,
+    children: HashMap<I, Vec<I>>,
+}
+
+impl<I: Clone + Ord + Hash> Index<I> {
+    fn new<M: Msg<Id = I>>(msgs: &BTreeMap<I, M>) -> Self {
+        // Like in the vault, a message without parent is a root, and so is a
+        // parent that is not loaded.
+        let mut roots = BTreeSet::new();
+        let mut children: HashMap<I, Vec<I>> = HashMap::new();
+        for msg in msgs.values() {
+            let Some(parent) = msg.parent() else {
+                roots.insert(msg.id());
+                continue;
+            };
+            if !msgs.contains_key(&parent) {
+                roots.insert(parent.clone());
+            }
+            children.entry(parent).or_default().push(msg.id());
+        }
+        Self { roots, children }
+    }
+}
+
+#[derive(Debug)]
+struct Inner<M: Msg> {
+    msgs: BTreeMap<M::Id, M>,
+    index: Option<Index<M::Id>>,
+    subscribers: Vec<mpsc::UnboundedSender<StoreChange>>,
+}
+
+impl<M: MemoryMsg> Inner<M> {
+    fn notify(&mut self, change: StoreChange) {
+        self.subscribers.retain(|tx| tx.send(change).is_ok());
+    }
+
+    fn roots(&mut self) -> &BTreeSet<M::Id> {
+        &self
+            .index
+            .get_or_insert_with(|| Index::new(&self.msgs))
+            .roots
+    }
+
+    fn path(&self, id: &M::Id) -> Vec<M::Id> {
+        let mut path = vec![id.clone()];
+        let mut visited = HashSet::from([id.clone()]);
+        let mut current = id.clone();
+        while let Some(parent) = self.msgs.get(&current).and_then(|m| m.parent()) {
+            // Messages forming a cycle have no root, so stop anywhere.
+            if !visited.insert(parent.clone()) {
+                break;
+            }
+            path.push(parent.clone());
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    fn tree(&mut self, root_id: &M::Id) -> Vec<M> {
+        let index = self.index.get_or_insert_with(|| Index::new(&self.msgs));
+        let mut result = vec![];
+        let mut visited = HashSet::new();
+        let mut stack = vec![root_id.clone()];
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id.clone()) {
+                continue;
+            }
+            if let Some(msg) = self.msgs.get(&id) {
+                result.push(msg.clone());
+            }
+            if let Some(children) = index.children.get(&id) {
+                stack.extend(children.iter().cloned());
+            }
+        }
+        result
+    }
+}
+
+/// Messages are ordered by their id, so ids should increase over time.
+#[derive(Debug, Clone)]
+pub struct MemoryStore<M: Msg> {
+    inner: Arc<Mutex<Inner<M>>>,
+}
+
+impl<M: MemoryMsg> MemoryStore<M> {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                msgs: BTreeMap::new(),
+                index: None,
+                subscribers: vec![],
+            })),
+        }
+    }
+
+    /// Add a message, replacing the message with the same id if there is one.
+    pub fn add(&self, msg: M) {
+        let mut inner = self.inner.lock();
+        inner.msgs.insert(msg.id(), msg);
+        inner.index = None;
+        inner.notify(StoreChange::Added);
+    }
+
+    /// All messages, ordered by their id.
+    pub fn msgs(&self) -> Vec<M> {
+        self.inner.lock().msgs.values().cloned().collect()
+    }
+}
+
+impl<M: MemoryMsg> Default for MemoryStore<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<M> MsgStore<M> for MemoryStore<M>
+where
+    M: MemoryMsg + Send + Sync,
+    M::Id: Send + Sync,
+{
+    type Error = Infallible;
+
+    async fn path(&self, id: &M::Id) -> Result<Path<M::Id>, Self::Error> {
+        Ok(Path::new(self.inner.lock().path(id)))
+    }
+
+    async fn msg(&self, id: &M::Id) -> Result<Option<M>, Self::Error> {
+        Ok(self.inner.lock().msgs.get(id).cloned())
+    }
+
+    async fn tree(&self, root_id: &M::Id, order: SiblingOrder) -> Result<Tree<M>, Self::Error> {
+        let msgs = self.inner.lock().tree(root_id);
+        Ok(Tree::new(root_id.clone(), msgs, order))
+    }
+
+    async fn first_root_id(&self) -> Result<Option<M::Id>, Self::Error> {
+        Ok(self.inner.lock().roots().first().cloned())
+    }
+
+    async fn last_root_id(&self) -> Result<Option<M::Id>, Self::Error> {
+        Ok(self.inner.lock().roots().last().cloned())
+    }
+
+    async fn prev_root_id(&self, root_id: &M::Id) -> Result<Option<M::Id>, Self::Error> {
+        let mut inner = self.inner.lock();
+        Ok(inner.roots().range(..root_id).next_back().cloned())
+    }
+
+    async fn next_root_id(&self, root_id: &M::Id) -> Result<Option<M::Id>, Self::Error> {
+        let mut inner = self.inner.lock();
+        let range = (Bound::Excluded(root_id), Bound::Unbounded);
+        Ok(inner.roots().range(range).next().cloned())
+    }
+
+    async fn oldest_msg_id(&self) -> Result<Option<M::Id>, Self::Error> {
+        Ok(self.inner.lock().msgs.keys().next().cloned())
+    }
+
+    async fn newest_msg_id(&self) -> Result<Option<M::Id>, Self::Error> {
+        Ok(self.inner.lock().msgs.keys().next_back().cloned())
+    }
+
+    async fn older_msg_id(&self, id: &M::Id) -> Result<Option<M::Id>, Self::Error> {
+        let inner = self.inner.lock();
+        Ok(inner.msgs.range(..id).next_back().map(|(id, _)| id.clone()))
+    }
+
+    async fn newer_msg_id(&self, id: &M::Id) -> Result<Option<M::Id>, Self::Error> {
+        let inner = self.inner.lock();
+        let range = (Bound::Excluded(id), Bound::Unbounded);
+        Ok(inner.msgs.range(range).next().map(|(id, _)| id.clone()))
+    }
+
+    async fn oldest_unseen_msg_id(&self) -> Result<Option<M::Id>, Self::Error> {
+        let inner = self.inner.lock();
+        Ok(inner.msgs.values().find(|m| !m.seen()).map(|m| m.id()))
+    }
+
+    async fn newest_unseen_msg_id(&self) -> Result<Option<M::Id>, Self::Error> {
+        let inner = self.inner.lock();
+        Ok(inner
+            .msgs
+            .values()
+            .rev()
+            .find(|m| !m.seen())
+            .map(|m| m.id()))
+    }
+
+    async fn older_unseen_msg_id(&self, id: &M::Id) -> Result<Option<M::Id>, Self::Error> {
+        let inner = self.inner.lock();
+        let mut older = inner.msgs.range(..id).rev();
+        Ok(older.find(|(_, m)| !m.seen()).map(|(id, _)| id.clone()))
+    }
+
+    async fn newer_unseen_msg_id(&self, id: &M::Id) -> Result<Option<M::Id>, Self::Error> {
+        let inner = self.inner.lock();
+        let mut newer = inner.msgs.range((Bound::Excluded(id), Bound::Unbounded));
+        Ok(newer.find(|(_, m)| !m.seen()).map(|(id, _)| id.clone()))
+    }
+
+    async fn unseen_msgs_count(&self) -> Result<usize, Self::Error> {
+        Ok(self
+            .inner
+            .lock()
+            .msgs
+            .values()
+            .filter(|m| !m.seen())
+            .count())
+    }
+
+    async fn set_seen(&self, id: &M::Id, seen: bool) -> Result<(), Self::Error> {
+        let mut inner = self.inner.lock();
+        if let Some(msg) = inner.msgs.get_mut(id).filter(|msg| msg.seen() != seen) {
+            msg.set_seen(seen);
+            inner.notify(StoreChange::Seen);
+        }
+        Ok(())
+    }
+
+    async fn set_many_seen(&self, ids: &[M::Id], seen: bool) -> Result<(), Self::Error> {
+        let mut inner = self.inner.lock();
+        let mut changed = false;
+        for id in ids {
+            if let Some(msg) = inner.msgs.get_mut(id) {
+                changed |= msg.seen() != seen;
+                msg.set_seen(seen);
+            }
+        }
+        if changed {
+            inner.notify(StoreChange::Seen);
+        }
+        Ok(())
+    }
+
+    async fn set_older_seen(&self, id: &M::Id, seen: bool) -> Result<(), Self::Error> {
+        let mut inner = self.inner.lock();
+        let mut changed = false;
+        for (_, msg) in inner.msgs.range_mut(..=id) {
+            changed |= msg.seen() != seen;
+            msg.set_seen(seen);
+        }
+        if changed {
+            inner.notify(StoreChange::Seen);
+        }
+        Ok(())
+    }
+
+    async fn set_tree_seen(&self, root_id: &M::Id, seen: bool) -> Result<(), Self::Error> {
+        let mut inner = self.inner.lock();
+        let ids = inner
+            .tree(root_id)
+            .into_iter()
+            .map(|msg| msg.id())
+            .collect::<Vec<_>>();
+        let mut changed = false;
+        for id in ids {
+            if let Some(msg) = inner.msgs.get_mut(&id) {
+                changed |= msg.seen() != seen;
+                msg.set_seen(seen);
+            }
+        }
+        if changed {
+            inner.notify(StoreChange::Seen);
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self) -> mpsc::UnboundedReceiver<StoreChange> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.inner.lock().subscribers.push(tx);
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::{MemoryMsg, MemoryStore};
+    use crate::store::{Msg, MsgStore, StoreChange};
+
+    #[derive(Debug, Clone)]
+    struct TestMsg {
+        id: u64,
+        parent: Option<u64>,
+        seen: bool,
+    }
+
+    impl Msg for TestMsg {
+        type Id = u64;
+
+        fn id(&self) -> Self::Id {
+            self.id
+        }
+
+        fn parent(&self) -> Option<Self::Id> {
+            self.parent
+        }
+
+        fn seen(&self) -> bool {
+            self.seen
+        }
+
+        fn time(&self) -> Option<OffsetDateTime> {
+            None
+        }
+
+        fn last_possible_id() -> Self::Id {
+            u64::MAX
+        }
+    }
+
+    impl MemoryMsg for TestMsg {
+        fn set_seen(&mut self, seen: bool) {
+            self.seen = seen;
+        }
+    }
+
+    /// Threads rooted at 1 and 4, with 2 replying to 1 and 3 replying to 2,
+    /// and a thread rooted at 5, which is not in the store but replied to by 6.
+    fn store() -> MemoryStore<TestMsg> {
+        let store = MemoryStore::new();
+        for (id, parent) in [
+            (1, None),
+            (2, Some(1)),
+            (3, Some(2)),
+            (4, None),
+            (6, Some(5)),
+        ] {
+            store.add(TestMsg {
+                id,
+                parent,
+                seen: false,
+            });
+        }
+        store
+    }
+
+    #[tokio::test]
+    async fn roots_include_missing_parents() {
+        let store = store();
+        assert_eq!(store.first_root_id().await, Ok(Some(1)));
+        assert_eq!(store.last_root_id().await, Ok(Some(5)));
+        assert_eq!(store.next_root_id(&1).await, Ok(Some(4)));
+        assert_eq!(store.next_root_id(&4).await, Ok(Some(5)));
+        assert_eq!(store.prev_root_id(&4).await, Ok(Some(1)));
+        assert_eq!(store.prev_root_id(&1).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn paths_and_trees_follow_parents() {
+        let store = store();
+        let path = store.path(&3).await.unwrap();
+        assert_eq!(path.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+        let path = store.path(&6).await.unwrap();
+        assert_eq!(path.into_iter().collect::<Vec<_>>(), vec![5, 6]);
+
+        let tree = store.tree(&1, SiblingOrder::Id).await.unwrap();
+        let mut ids = tree.ids().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn msgs_are_ordered_by_id() {
+        let store = store();
+        assert_eq!(store.oldest_msg_id().await, Ok(Some(1)));
+        assert_eq!(store.newest_msg_id().await, Ok(Some(6)));
+        assert_eq!(store.newer_msg_id(&4).await, Ok(Some(6)));
+        assert_eq!(store.older_msg_id(&4).await, Ok(Some(3)));
+        assert_eq!(store.older_msg_id(&1).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn seen_msgs_are_skipped() {
+        let store = store();
+        store.set_older_seen(&2, true).await.unwrap();
+        store.set_seen(&4, true).await.unwrap();
+        assert_eq!(store.unseen_msgs_count().await, Ok(2));
+        assert_eq!(store.oldest_unseen_msg_id().await, Ok(Some(3)));
+        assert_eq!(store.newest_unseen_msg_id().await, Ok(Some(6)));
+        assert_eq!(store.newer_unseen_msg_id(&3).await, Ok(Some(6)));
+        assert_eq!(store.older_unseen_msg_id(&6).await, Ok(Some(3)));
+
+        store.set_tree_seen(&1, true).await.unwrap();
+        assert_eq!(store.unseen_msgs_count().await, Ok(1));
+
+        store.set_many_seen(&[3, 6], false).await.unwrap();
+        assert_eq!(store.unseen_msgs_count().await, Ok(2));
+        assert_eq!(store.oldest_unseen_msg_id().await, Ok(Some(3)));
+    }
+
+    #[tokio::test]
+    async fn subscribers_are_notified_of_changes() {
+        let store = store();
+        let mut changes = store.subscribe();
+
+        store.set_seen(&1, true).await.unwrap();
+        assert_eq!(changes.try_recv(), Ok(StoreChange::Seen));
+
+        // Nothing changes, so nobody is notified.
+        store.set_seen(&1, true).await.unwrap();
+        assert!(changes.try_recv().is_err());
+
+        store.add(TestMsg {
+            id: 7,
+            parent: None,
+            seen: false,
+        });
+        assert_eq!(changes.try_recv(), Ok(StoreChange::Added));
+    }
+}
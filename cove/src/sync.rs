@@ -0,0 +1,359 @@
+//! Sharing which messages were seen between multiple machines via files in a
+//! synced directory, see the `sync_dir` config option.
+//!
+//! Every machine appends the changes it makes to its own file in the directory
+//! and regularly applies the changes other machines appended to their files
+//! since it last looked. When its file grows too large, a machine replaces it
+//! by a compacted file under a new name, which the other machines then read
+//! from the start.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cove_vault::{EuphVault, RoomIdentifier, SeenChange};
+use euphoxide::api::{MessageId, Snowflake, Time};
+use log::warn;
+use time::OffsetDateTime;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::macros::logging_unwrap;
+
+/// Time between two syncs.
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Own files smaller than this are never compacted.
+const COMPACT_MIN_LEN: u64 = 64 * 1024;
+
+const EXTENSION: &str = "seen";
+
+/// One line per room, seen state and time, containing the domain, room name,
+/// whether the messages were seen, the unix timestamp of the change and the ids
+/// of all messages changed together.
+fn encode(changes: &[SeenChange]) -> String {
+    let mut text = String::new();
+    let mut prev = None::<&SeenChange>;
+    for change in changes {
+        let same_group = prev.is_some_and(|prev| {
+            prev.room == change.room && prev.seen == change.seen && prev.time.0 == change.time.0
+        });
+        if !same_group {
+            if prev.is_some() {
+                text.push('\n');
+            }
+            let seen = u8::from(change.seen);
+            let time = change.time.0.unix_timestamp();
+            let room = &change.room;
+            text.push_str(&format!("{} {} {seen} {time}", room.domain, room.name));
+        }
+        text.push_str(&format!(" {}", change.id.0));
+        prev = Some(change);
+    }
+    if prev.is_some() {
+        text.push('\n');
+    }
+    text
+}
+
+fn decode(line: &str) -> Option<Vec<SeenChange>> {
+    let mut parts = line.split(' ');
+    let domain = parts.next()?;
+    let name = parts.next()?;
+    let seen = match parts.next()? {
+        "0" => false,
+        "1" => true,
+        _ => return None,
+    };
+    let time = parts.next()?.parse::<i64>().ok()?;
+    let time = Time(OffsetDateTime::from_unix_timestamp(time).ok()?);
+    parts
+        .map(|id| {
+            Some(SeenChange {
+                room: RoomIdentifier::new(domain.to_string(), name.to_string()),
+                id: MessageId(id.parse::<Snowflake>().ok()?),
+                seen,
+                time,
+            })
+        })
+        .collect()
+}
+
+/// The name identifying this machine in the sync directory. It is generated
+/// once and then stored in the data directory.
+fn machine_id(data_dir: &Path) -> io::Result<String> {
+    let path = data_dir.join("sync-id");
+    if let Ok(id) = fs::read_to_string(&path) {
+        let id = id.trim();
+        if !id.is_empty() {
+            return Ok(id.to_string());
+        }
+    }
+
+    let id = format!("{:x}{:x}", now_nanos(), process::id());
+    fs::create_dir_all(data_dir)?;
+    fs::write(&path, &id)?;
+    Ok(id)
+}
+
+fn now_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+/// Read the complete lines of a file that follow `offset`. Returns the new
+/// offset to continue from next time.
+fn read_from(path: &Path, mut offset: u64) -> io::Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    if offset > file.metadata()?.len() {
+        // The file was replaced, so start over.
+        offset = 0;
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    let mut new = vec![];
+    file.read_to_end(&mut new)?;
+
+    // Lines may still be in the process of being written or synced, so only
+    // complete lines are applied.
+    let complete = new.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+    let text = String::from_utf8_lossy(&new[..complete]).into_owned();
+    Ok((text, offset + complete as u64))
+}
+
+type MsgKey = (RoomIdentifier, MessageId);
+
+struct SeenSync {
+    vault: EuphVault,
+    dir: PathBuf,
+    machine_id: String,
+    own_file: PathBuf,
+    /// Size of the own file right after it was last compacted.
+    compacted_len: u64,
+    /// The latest change made on this machine to each message. Used to
+    /// compact the own file and to ignore older changes of other machines.
+    own_changes: HashMap<MsgKey, SeenChange>,
+    /// Where the offsets are remembered across restarts.
+    offsets_file: PathBuf,
+    /// How many bytes of each other machine's file were already applied.
+    offsets: HashMap<String, u64>,
+}
+
+impl SeenSync {
+    fn new(vault: EuphVault, dir: PathBuf, data_dir: &Path) -> io::Result<Self> {
+        let machine_id = machine_id(data_dir)?;
+        let offsets_file = data_dir.join("sync-offsets");
+
+        let offsets = fs::read_to_string(&offsets_file)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.rsplit_once(' '))
+            .filter_map(|(name, offset)| Some((name.to_string(), offset.parse().ok()?)))
+            .collect();
+
+        let mut own_files = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if Self::is_own_file(&machine_id, &path) {
+                own_files.push(path);
+            }
+        }
+        own_files.sort_unstable();
+
+        // If there are multiple files, a compaction was interrupted. The
+        // latest file is complete, so it wins.
+        let own_file = match own_files.pop() {
+            Some(path) => path,
+            None => Self::new_own_file(&dir, &machine_id),
+        };
+        for path in own_files {
+            fs::remove_file(path)?;
+        }
+
+        let mut own_changes = HashMap::new();
+        if own_file.exists() {
+            let (text, _) = read_from(&own_file, 0)?;
+            for change in text.lines().filter_map(decode).flatten() {
+                own_changes.insert((change.room.clone(), change.id), change);
+            }
+        }
+        let compacted_len = own_file.metadata().map_or(0, |m| m.len());
+
+        Ok(Self {
+            vault,
+            dir,
+            machine_id,
+            own_file,
+            compacted_len,
+            own_changes,
+            offsets_file,
+            offsets,
+        })
+    }
+
+    /// Own files are named after the machine id and the time they were
+    /// created, so a compacted file can replace an older one.
+    fn new_own_file(dir: &Path, machine_id: &str) -> PathBuf {
+        dir.join(format!("{machine_id}-{:032x}.{EXTENSION}", now_nanos()))
+    }
+
+    fn is_own_file(machine_id: &str, path: &Path) -> bool {
+        path.extension().and_then(|e| e.to_str()) == Some(EXTENSION)
+            && path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.strip_prefix(machine_id))
+                .is_some_and(|s| s.starts_with('-'))
+    }
+
+    fn write_changes(&mut self, changes: &[SeenChange]) -> io::Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        for change in changes {
+            let key = (change.room.clone(), change.id);
+            self.own_changes.insert(key, change.clone());
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.own_file)?;
+        file.write_all(encode(changes).as_bytes())?;
+
+        let len = file.metadata()?.len();
+        if len > COMPACT_MIN_LEN.max(2 * self.compacted_len) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Replace the own file by a new one containing only the latest change to
+    /// each message.
+    fn compact(&mut self) -> io::Result<()> {
+        let mut changes = self.own_changes.values().cloned().collect::<Vec<_>>();
+        changes.sort_unstable_by(|a, b| {
+            (&a.room, a.seen, a.time.0, a.id).cmp(&(&b.room, b.seen, b.time.0, b.id))
+        });
+
+        let new_file = Self::new_own_file(&self.dir, &self.machine_id);
+        let text = encode(&changes);
+        fs::write(&new_file, &text)?;
+        fs::remove_file(&self.own_file)?;
+
+        self.own_file = new_file;
+        self.compacted_len = text.len() as u64;
+        Ok(())
+    }
+
+    /// Read the changes other machines made since the last call, ordered by
+    /// when they were made. Changes older than the latest change made to the
+    /// same message on this machine are left out.
+    fn read_changes(&mut self) -> io::Result<Vec<SeenChange>> {
+        let mut changes = vec![];
+        let mut offsets = HashMap::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            if Self::is_own_file(&self.machine_id, &path) {
+                continue;
+            }
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+
+            let offset = self.offsets.get(name).copied().unwrap_or(0);
+            let (text, offset) = read_from(&path, offset)?;
+            changes.extend(text.lines().filter_map(decode).flatten());
+            offsets.insert(name.to_string(), offset);
+        }
+
+        // Files that no longer exist were replaced by compacted ones.
+        self.offsets = offsets;
+
+        changes.retain(|change| {
+            let key = (change.room.clone(), change.id);
+            match self.own_changes.get(&key) {
+                Some(own) => own.time.0 < change.time.0,
+                None => true,
+            }
+        });
+        changes.sort_by_key(|change| change.time.0);
+
+        Ok(changes)
+    }
+
+    fn write_offsets(&self) -> io::Result<()> {
+        let text = self
+            .offsets
+            .iter()
+            .map(|(name, offset)| format!("{name} {offset}\n"))
+            .collect::<String>();
+        fs::write(&self.offsets_file, text)
+    }
+
+    async fn sync(&mut self) {
+        let changes = logging_unwrap!(self.vault.take_seen_log().await);
+        if let Err(err) = self.write_changes(&changes) {
+            warn!("Failed to write seen messages to sync dir: {err}");
+        }
+
+        let changes = match self.read_changes() {
+            Ok(changes) => changes,
+            Err(err) => {
+                warn!("Failed to read seen messages from sync dir: {err}");
+                return;
+            }
+        };
+        if !changes.is_empty() {
+            logging_unwrap!(self.vault.apply_seen_changes(changes).await);
+        }
+        if let Err(err) = self.write_offsets() {
+            warn!("Failed to remember sync progress: {err}");
+        }
+    }
+}
+
+/// Handle to the task syncing seen messages in the background.
+pub struct SyncTask {
+    stop_tx: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl SyncTask {
+    /// Sync one last time, then stop syncing.
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(());
+        let _ = self.task.await;
+    }
+}
+
+pub async fn launch(vault: EuphVault, dir: PathBuf, data_dir: &Path) -> io::Result<SyncTask> {
+    fs::create_dir_all(&dir)?;
+    let mut sync = SeenSync::new(vault.clone(), dir, data_dir)?;
+    logging_unwrap!(vault.enable_seen_log().await);
+
+    let (stop_tx, mut stop_rx) = oneshot::channel();
+    let task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SYNC_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => sync.sync().await,
+                _ = &mut stop_rx => {
+                    sync.sync().await;
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(SyncTask { stop_tx, task })
+}
@@ -1,37 +1,70 @@
 mod chat;
+mod clipboard;
 mod euph;
 mod key_bindings;
+mod quit;
+mod recording;
 mod rooms;
+#[cfg(all(test, unix))]
+mod tests;
 mod util;
+mod wakeup;
 mod widgets;
 
 use std::convert::Infallible;
-use std::io;
 use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
+use std::{future, io};
 
 use cove_config::Config;
 use cove_input::InputEvent;
+use cove_vault::{RoomIdentifier, Vault};
+use crossterm::style::Stylize;
+use euphoxide::api::packet::ParsedPacket;
 use parking_lot::FairMutex;
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use tokio::task;
-use toss::widgets::BoxedAsync;
-use toss::{Terminal, WidgetExt};
+use toss::widgets::{BoxedAsync, Text};
+use toss::{Size, Style, Styled, Terminal, Widget, WidgetExt};
 
 use crate::logger::{LogMsg, Logger};
 use crate::macros::logging_unwrap;
+use crate::startup;
+use crate::store::MemoryStore;
 use crate::util::InfallibleExt;
-use crate::vault::Vault;
 
 pub use self::chat::ChatMsg;
 use self::chat::ChatState;
+pub use self::recording::Recording;
+use self::recording::{Recorder, Replay};
 use self::rooms::Rooms;
+pub use self::rooms::StartRoom;
+use self::wakeup::Wakeup;
 use self::widgets::ListState;
 
 /// Time to spend batch processing events before redrawing the screen.
 const EVENT_PROCESSING_TIME: Duration = Duration::from_millis(1000 / 15); // 15 fps
 
+/// Time between two [`UiEvent::Tick`]s, which are used for periodic checks.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time without input or anything new to show after which the UI is considered
+/// idle. While idle, the terminal is polled less often and redraws are limited
+/// to one per [`IDLE_REDRAW_INTERVAL`].
+const IDLE_AFTER: Duration = Duration::from_secs(30);
+
+/// Minimum time between two redraws while the UI is idle.
+const IDLE_REDRAW_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Smallest terminal width the UI is drawn at. Below this width, only a
+/// warning is shown.
+const MIN_WIDTH: u16 = 40;
+
+/// Smallest terminal height the UI is drawn at. Below this height, only a
+/// warning is shown.
+const MIN_HEIGHT: u16 = 10;
+
 /// Error for anything that can go wrong while rendering.
 #[derive(Debug, thiserror::Error)]
 pub enum UiError {
@@ -52,6 +85,18 @@ pub enum UiEvent {
     LogChanged,
     Term(crossterm::event::Event),
     Euph(euphoxide::bot::instance::Event),
+    /// A packet recorded in an earlier session, see `--replay`.
+    Replayed(RoomIdentifier, ParsedPacket),
+    Translated,
+    DirectoryLoaded,
+    StoreChanged,
+    Wakeup(Wakeup),
+    Tick,
+    Open(StartRoom),
+    /// Another process asked us to stop via SIGTSTP.
+    Suspend,
+    /// The process was continued after being stopped via SIGCONT.
+    Continued,
 }
 
 enum EventHandleResult {
@@ -73,14 +118,20 @@ pub struct Ui {
     mode: Mode,
 
     rooms: Rooms,
-    log_chat: ChatState<LogMsg, Logger>,
+    log_chat: ChatState<LogMsg, MemoryStore<LogMsg>>,
 
     key_bindings_visible: bool,
     key_bindings_list: ListState<Infallible>,
+
+    /// What would be lost by quitting, while asking for confirmation.
+    quit_warnings: Option<Vec<String>>,
+
+    recorder: Option<Recorder>,
 }
 
 impl Ui {
     const POLL_DURATION: Duration = Duration::from_millis(100);
+    const IDLE_POLL_DURATION: Duration = Duration::from_secs(1);
 
     pub async fn run(
         config: &'static Config,
@@ -88,10 +139,18 @@ impl Ui {
         vault: Vault,
         logger: Logger,
         logger_rx: UnboundedReceiver<()>,
+        recording: Recording,
+        open_rx: UnboundedReceiver<StartRoom>,
     ) -> anyhow::Result<()> {
         let (event_tx, event_rx) = mpsc::unbounded_channel();
         let crossterm_lock = Arc::new(FairMutex::new(()));
 
+        let (recorder, replay) = match recording {
+            Recording::Off => (None, None),
+            Recording::Record(recorder) => (Some(recorder), None),
+            Recording::Replay(replay) => (None, Some(replay)),
+        };
+
         // Prepare and start crossterm event polling task
         let weak_crossterm_lock = Arc::downgrade(&crossterm_lock);
         let event_tx_clone = event_tx.clone();
@@ -108,19 +167,69 @@ impl Ui {
         //
         // On the other hand, if the crossterm_event_task stops for any reason,
         // the rest of the UI is also shut down and the client stops.
-        let mut ui = Self {
+        //
+        // While replaying, the recorded ticks take the place of live ones.
+        let replaying = replay.is_some();
+        let mut ui = Self::new(config, vault, logger.msgs(), event_tx.clone(), replaying).await;
+        ui.recorder = recorder;
+        tokio::select! {
+            e = ui.run_main(terminal, event_rx, crossterm_lock, replay) => e?,
+            _ = Self::update_on_log_event(logger_rx, &event_tx) => (),
+            _ = wakeup::watch(&event_tx) => (),
+            _ = Self::send_ticks(&event_tx), if !replaying => (),
+            _ = Self::forward_open_requests(open_rx, &event_tx) => (),
+            _ = Self::forward_signals(&event_tx) => (),
+            e = crossterm_event_task => e??,
+        }
+        Ok(())
+    }
+
+    async fn new(
+        config: &'static Config,
+        vault: Vault,
+        log_msgs: MemoryStore<LogMsg>,
+        event_tx: UnboundedSender<UiEvent>,
+        replaying: bool,
+    ) -> Self {
+        Self {
             config,
             event_tx: event_tx.clone(),
             mode: Mode::Main,
-            rooms: Rooms::new(config, vault, event_tx.clone()).await,
-            log_chat: ChatState::new(logger),
+            rooms: Rooms::new(config, vault, event_tx, replaying).await,
+            log_chat: ChatState::new(config, log_msgs),
             key_bindings_visible: false,
             key_bindings_list: ListState::new(),
-        };
-        tokio::select! {
-            e = ui.run_main(terminal, event_rx, crossterm_lock) => e?,
-            _ = Self::update_on_log_event(logger_rx, &event_tx) => (),
-            e = crossterm_event_task => e??,
+            quit_warnings: None,
+            recorder: None,
+        }
+    }
+
+    /// Run the UI on scripted events instead of terminal input, server packets
+    /// and ticks, for snapshot tests.
+    ///
+    /// The UI is drawn once at the start and again after every event, just
+    /// like it would be if the events were handled one by one. Returns once
+    /// all events are handled or the UI stops.
+    #[cfg(test)]
+    pub async fn run_scripted(
+        config: &'static Config,
+        terminal: &mut Terminal,
+        vault: Vault,
+        events: Vec<UiEvent>,
+    ) -> Result<(), UiError> {
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        let crossterm_lock = Arc::new(FairMutex::new(()));
+        let mut ui = Self::new(config, vault, MemoryStore::new(), event_tx, false).await;
+
+        let (width, height) = crossterm::terminal::size()?;
+        let size = Size::new(width, height);
+        terminal.present_async_widget(ui.widget(size).await).await?;
+        for event in events {
+            let result = ui.handle_event(terminal, &crossterm_lock, event).await;
+            if let EventHandleResult::Stop = result {
+                break;
+            }
+            terminal.present_async_widget(ui.widget(size).await).await?;
         }
         Ok(())
     }
@@ -129,12 +238,21 @@ impl Ui {
         tx: UnboundedSender<UiEvent>,
         lock: Weak<FairMutex<()>>,
     ) -> io::Result<()> {
+        let mut last_input = Instant::now();
         loop {
             let Some(lock) = lock.upgrade() else {
                 return Ok(());
             };
             let _guard = lock.lock();
-            if crossterm::event::poll(Self::POLL_DURATION)? {
+            // Polling returns as soon as there is input, so polling longer
+            // only delays others waiting for the lock.
+            let duration = if last_input.elapsed() < IDLE_AFTER {
+                Self::POLL_DURATION
+            } else {
+                Self::IDLE_POLL_DURATION
+            };
+            if crossterm::event::poll(duration)? {
+                last_input = Instant::now();
                 let event = crossterm::event::read()?;
                 if tx.send(UiEvent::Term(event)).is_err() {
                     return Ok(());
@@ -157,27 +275,98 @@ impl Ui {
         }
     }
 
+    async fn forward_open_requests(
+        mut open_rx: UnboundedReceiver<StartRoom>,
+        event_tx: &UnboundedSender<UiEvent>,
+    ) {
+        while let Some(start) = open_rx.recv().await {
+            if event_tx.send(UiEvent::Open(start)).is_err() {
+                return;
+            }
+        }
+
+        // No more rooms will be handed over, but the UI should keep running.
+        future::pending().await
+    }
+
+    /// Handle SIGTSTP like ctrl+z so the terminal is handed back to the shell
+    /// before stopping, and notice SIGCONT since the shell may have changed the
+    /// terminal while the process was stopped.
+    #[cfg(unix)]
+    async fn forward_signals(event_tx: &UnboundedSender<UiEvent>) {
+        use signal_hook::consts::{SIGCONT, SIGTSTP};
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let signals = signal(SignalKind::from_raw(SIGTSTP))
+            .and_then(|tstp| Ok((tstp, signal(SignalKind::from_raw(SIGCONT))?)));
+        let (mut tstp, mut cont) = match signals {
+            Ok(signals) => signals,
+            Err(err) => {
+                log::warn!("Failed to listen for suspend signals: {err}");
+                return future::pending().await;
+            }
+        };
+
+        loop {
+            let event = tokio::select! {
+                _ = tstp.recv() => UiEvent::Suspend,
+                _ = cont.recv() => UiEvent::Continued,
+            };
+            if event_tx.send(event).is_err() {
+                return;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    async fn forward_signals(_event_tx: &UnboundedSender<UiEvent>) {
+        future::pending().await
+    }
+
+    async fn send_ticks(event_tx: &UnboundedSender<UiEvent>) {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if event_tx.send(UiEvent::Tick).is_err() {
+                return;
+            }
+        }
+    }
+
     async fn run_main(
         &mut self,
         terminal: &mut Terminal,
         mut event_rx: UnboundedReceiver<UiEvent>,
         crossterm_lock: Arc<FairMutex<()>>,
+        mut replay: Option<Replay>,
     ) -> Result<(), UiError> {
+        // Replayed events are handled before any live ones, in the batches
+        // they were originally handled in and without waiting in between.
+        if let Some(replay) = &mut replay {
+            self.redraw(terminal, &crossterm_lock).await?;
+            while let Some(batch) = replay.next_batch(EVENT_PROCESSING_TIME) {
+                for event in batch {
+                    let result = self.handle_event(terminal, &crossterm_lock, event).await;
+                    if let EventHandleResult::Stop = result {
+                        return Ok(());
+                    }
+                }
+                self.redraw(terminal, &crossterm_lock).await?;
+            }
+        }
+
         let mut redraw = true;
+        let mut last_activity = Instant::now();
+        let mut last_redraw = Instant::now();
 
         loop {
-            // Redraw if necessary
-            if redraw {
+            // Redraw if necessary. Since ticks arrive every second, a postponed
+            // redraw still happens soon enough.
+            let idle = last_activity.elapsed() >= IDLE_AFTER;
+            if redraw && (!idle || last_redraw.elapsed() >= IDLE_REDRAW_INTERVAL) {
                 redraw = false;
-                terminal.present_async_widget(self.widget().await).await?;
-
-                if terminal.measuring_required() {
-                    let _guard = crossterm_lock.lock();
-                    terminal.measure_widths()?;
-                    if self.event_tx.send(UiEvent::GraphemeWidthsChanged).is_err() {
-                        return Ok(());
-                    }
-                }
+                last_redraw = Instant::now();
+                self.redraw(terminal, &crossterm_lock).await?;
             }
 
             // Handle events (in batches)
@@ -186,12 +375,32 @@ impl Ui {
             };
             let end_time = Instant::now() + EVENT_PROCESSING_TIME;
             loop {
-                match self.handle_event(terminal, &crossterm_lock, event).await {
+                // Relayout right away instead of drawing outdated frames
+                let resized = matches!(event, UiEvent::Term(crossterm::event::Event::Resize(_, _)));
+                let input = matches!(event, UiEvent::Term(_));
+                let news = matches!(
+                    event,
+                    UiEvent::Euph(_)
+                        | UiEvent::Open(_)
+                        | UiEvent::Wakeup(_)
+                        | UiEvent::StoreChanged
+                        | UiEvent::Tick
+                );
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.record(&event)?;
+                }
+                let result = self.handle_event(terminal, &crossterm_lock, event).await;
+                // Events that don't change what's shown, like pings or ticks
+                // without anything to update, don't count as activity.
+                if input || (news && matches!(result, EventHandleResult::Redraw)) {
+                    last_activity = Instant::now();
+                }
+                match result {
                     EventHandleResult::Redraw => redraw = true,
                     EventHandleResult::Continue => {}
                     EventHandleResult::Stop => return Ok(()),
                 }
-                if Instant::now() >= end_time {
+                if resized || Instant::now() >= end_time {
                     break;
                 }
                 event = match event_rx.try_recv() {
@@ -203,13 +412,41 @@ impl Ui {
         }
     }
 
-    async fn widget(&mut self) -> BoxedAsync<'_, UiError> {
+    async fn redraw(
+        &mut self,
+        terminal: &mut Terminal,
+        crossterm_lock: &Arc<FairMutex<()>>,
+    ) -> Result<(), UiError> {
+        let (width, height) = crossterm::terminal::size()?;
+        let size = Size::new(width, height);
+        terminal
+            .present_async_widget(self.widget(size).await)
+            .await?;
+        startup::first_render();
+
+        if terminal.measuring_required() {
+            let _guard = crossterm_lock.lock();
+            terminal.measure_widths()?;
+            // If nobody is listening any more, the UI is about to stop anyway.
+            let _ = self.event_tx.send(UiEvent::GraphemeWidthsChanged);
+        }
+        Ok(())
+    }
+
+    async fn widget(&mut self, size: Size) -> BoxedAsync<'_, UiError> {
+        if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+            return Self::too_small_widget(size).desync().boxed_async();
+        }
+
         let widget = match self.mode {
             Mode::Main => self.rooms.widget().await,
-            Mode::Log => self.log_chat.widget(String::new(), true),
+            Mode::Log => self.log_chat.widget(String::new(), None, true),
         };
 
-        if self.key_bindings_visible {
+        if let Some(warnings) = &self.quit_warnings {
+            let popup = quit::widget(&self.config.theme, warnings, &self.config.keys);
+            popup.desync().above(widget).boxed_async()
+        } else if self.key_bindings_visible {
             let popup = key_bindings::widget(&mut self.key_bindings_list, self.config);
             popup.desync().above(widget).boxed_async()
         } else {
@@ -217,6 +454,15 @@ impl Ui {
         }
     }
 
+    fn too_small_widget(size: Size) -> impl Widget<UiError> {
+        let need = format!(
+            "\nneed {MIN_WIDTH}x{MIN_HEIGHT}, have {}x{}",
+            size.width, size.height
+        );
+        let text = Styled::new("Terminal too small", Style::new().bold().yellow()).then_plain(need);
+        Text::new(text).float().with_center()
+    }
+
     async fn handle_event(
         &mut self,
         terminal: &mut Terminal,
@@ -225,6 +471,9 @@ impl Ui {
     ) -> EventHandleResult {
         match event {
             UiEvent::GraphemeWidthsChanged => EventHandleResult::Redraw,
+            UiEvent::Translated => EventHandleResult::Redraw,
+            UiEvent::DirectoryLoaded => EventHandleResult::Redraw,
+            UiEvent::StoreChanged => EventHandleResult::Redraw,
             UiEvent::LogChanged if self.mode == Mode::Log => EventHandleResult::Redraw,
             UiEvent::LogChanged => EventHandleResult::Continue,
             UiEvent::Term(crossterm::event::Event::Resize(_, _)) => EventHandleResult::Redraw,
@@ -232,6 +481,43 @@ impl Ui {
                 self.handle_term_event(terminal, crossterm_lock.clone(), event)
                     .await
             }
+            UiEvent::Tick => {
+                self.rooms.mark_seen().await;
+                self.rooms.save_drafts().await;
+                self.rooms.send_scheduled().await;
+                let reconnected = self.rooms.reconnect_stalled_rooms().await;
+                let scheduled = self.rooms.apply_schedules().await;
+                let toasts_expired = self.rooms.expire_toasts();
+                if self.rooms.send_cooldown_changed() || reconnected || scheduled || toasts_expired
+                {
+                    EventHandleResult::Redraw
+                } else {
+                    EventHandleResult::Continue
+                }
+            }
+            UiEvent::Wakeup(wakeup) => {
+                self.rooms.handle_wakeup(wakeup).await;
+                EventHandleResult::Redraw
+            }
+            UiEvent::Open(start) => {
+                self.mode = Mode::Main;
+                self.rooms.open(start).await;
+                EventHandleResult::Redraw
+            }
+            UiEvent::Suspend => {
+                if let Err(err) = cove_input::suspend_process(terminal, crossterm_lock) {
+                    log::warn!("failed to suspend: {err}");
+                }
+                EventHandleResult::Redraw
+            }
+            UiEvent::Continued => {
+                // The shell may have reset the terminal while we were stopped.
+                let _guard = crossterm_lock.lock();
+                if let Err(err) = terminal.suspend().and_then(|()| terminal.unsuspend()) {
+                    log::warn!("failed to take over terminal again: {err}");
+                }
+                EventHandleResult::Redraw
+            }
             UiEvent::Euph(event) => {
                 if self.rooms.handle_euph_event(event).await {
                     EventHandleResult::Redraw
@@ -239,6 +525,13 @@ impl Ui {
                     EventHandleResult::Continue
                 }
             }
+            UiEvent::Replayed(room, packet) => {
+                if self.rooms.handle_replayed_packet(room, packet).await {
+                    EventHandleResult::Redraw
+                } else {
+                    EventHandleResult::Continue
+                }
+            }
         }
     }
 
@@ -248,10 +541,33 @@ impl Ui {
         crossterm_lock: Arc<FairMutex<()>>,
         event: crossterm::event::Event,
     ) -> EventHandleResult {
+        if let crossterm::event::Event::Key(_) = event {
+            self.rooms.activity();
+        }
+
         let mut event = InputEvent::new(event, terminal, crossterm_lock);
         let keys = &self.config.keys;
 
+        // The quit confirmation overrides all other bindings if visible
+        if self.quit_warnings.is_some() {
+            if event.matches(&keys.general.exit) || event.matches(&keys.general.confirm) {
+                return EventHandleResult::Stop;
+            }
+            if event.matches(&keys.general.abort) {
+                self.quit_warnings = None;
+                return EventHandleResult::Redraw;
+            }
+            return EventHandleResult::Continue;
+        }
+
         if event.matches(&keys.general.exit) {
+            if self.config.confirm_quit {
+                let warnings = self.rooms.quit_warnings();
+                if !warnings.is_empty() {
+                    self.quit_warnings = Some(warnings);
+                    return EventHandleResult::Redraw;
+                }
+            }
             return EventHandleResult::Stop;
         }
 
@@ -268,11 +584,28 @@ impl Ui {
             return EventHandleResult::Continue;
         }
 
+        if event.matches(&keys.general.suspend) {
+            if let Err(err) = event.suspend_process() {
+                log::warn!("failed to suspend: {err}");
+            }
+            return EventHandleResult::Redraw;
+        }
+
         if event.matches(&keys.general.help) {
             self.key_bindings_visible = true;
             return EventHandleResult::Redraw;
         }
 
+        if event.matches(&keys.general.away) {
+            self.rooms.toggle_away();
+            return EventHandleResult::Redraw;
+        }
+
+        if event.matches(&keys.general.low_bandwidth) {
+            self.rooms.toggle_low_bandwidth();
+            return EventHandleResult::Redraw;
+        }
+
         match self.mode {
             Mode::Main => {
                 if event.matches(&keys.general.log) {
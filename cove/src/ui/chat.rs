@@ -4,11 +4,12 @@ mod renderer;
 mod tree;
 mod widgets;
 
-use cove_config::Keys;
+use cove_config::{Composer, Config, CursorAfterSend, Keys, LongWords};
 use cove_input::InputEvent;
+use crossterm::style::Stylize;
 use time::OffsetDateTime;
-use toss::widgets::{BoxedAsync, EditorState};
-use toss::{Styled, WidgetExt};
+use toss::widgets::{BoxedAsync, EditorState, Join2, Text};
+use toss::{Style, Styled, Widget, WidgetExt, WidthDb};
 
 use crate::store::{Msg, MsgStore};
 use crate::util;
@@ -16,11 +17,27 @@ use crate::util;
 use self::cursor::Cursor;
 use self::tree::TreeViewState;
 
-use super::UiError;
+use super::{key_bindings, UiError};
 
 pub trait ChatMsg {
-    fn time(&self) -> Option<OffsetDateTime>;
     fn styled(&self) -> (Styled, Styled);
+
+    /// Like [`Self::styled`], but with long words in the content prepared for
+    /// wrapping according to the `long_words` config option and the nick
+    /// shortened according to the `nick_width` config option.
+    fn styled_prepared(
+        &self,
+        _long_words: LongWords,
+        _nick_width: Option<u16>,
+    ) -> (Styled, Styled) {
+        self.styled()
+    }
+
+    /// Whether the message was sent by the user with the given id.
+    fn sent_by(&self, _user_id: &str) -> bool {
+        false
+    }
+
     fn edit(nick: &str, content: &str) -> (Styled, Styled);
     fn pseudo(nick: &str, content: &str) -> (Styled, Styled);
 }
@@ -29,26 +46,68 @@ pub enum Mode {
     Tree,
 }
 
+/// A translation of a message, shown below the message's content.
+pub enum Translation {
+    Pending,
+    Done(String),
+    Failed,
+}
+
+/// Delivery state of a message sent by the user, shown next to its time.
+#[derive(Clone, Copy)]
+pub enum Delivery {
+    /// The message was sent and is waiting for a reply from the server.
+    Sending,
+    /// The server acknowledged the message and assigned it an id.
+    Sent,
+    /// Sending the message failed and its content is still in the editor.
+    Failed,
+}
+
+/// Where the message under the cursor is located in its thread.
+pub struct ThreadPosition {
+    /// One-based position of the message among its siblings and the amount of
+    /// siblings including itself, if the message is a reply.
+    pub sibling: Option<(usize, usize)>,
+    /// Amount of direct and indirect replies to the message.
+    pub replies: usize,
+}
+
 pub struct ChatState<M: Msg, S: MsgStore<M>> {
+    config: &'static Config,
     store: S,
 
     cursor: Cursor<M::Id>,
     editor: EditorState,
     caesar: i8,
+    /// Whether to hide everything except the messages themselves.
+    zen: bool,
+    /// Whether to keep the cursor at the bottom so that new messages stay
+    /// visible as they arrive.
+    follow: bool,
 
     mode: Mode,
     tree: TreeViewState<M, S>,
+
+    /// Summary of the message being replied to, shown above the chat or the
+    /// composer bar while composing a reply.
+    reply_preview: Option<(M::Id, Styled)>,
 }
 
 impl<M: Msg, S: MsgStore<M> + Clone> ChatState<M, S> {
-    pub fn new(store: S) -> Self {
+    pub fn new(config: &'static Config, store: S) -> Self {
         Self {
+            config,
             cursor: Cursor::Bottom,
             editor: EditorState::new(),
             caesar: 0,
+            zen: false,
+            follow: false,
 
             mode: Mode::Tree,
-            tree: TreeViewState::new(store.clone()),
+            tree: TreeViewState::new(config, store.clone()),
+
+            reply_preview: None,
 
             store,
         }
@@ -60,7 +119,30 @@ impl<M: Msg, S: MsgStore<M>> ChatState<M, S> {
         &self.store
     }
 
-    pub fn widget(&mut self, nick: String, focused: bool) -> BoxedAsync<'_, UiError>
+    pub fn zen(&self) -> bool {
+        self.zen
+    }
+
+    pub fn following(&self) -> bool {
+        self.follow
+    }
+
+    /// A new message from somebody else arrived.
+    pub fn msg_arrived(&mut self) {
+        self.tree.msg_arrived();
+    }
+
+    /// See [`TreeViewState::mark_seen`].
+    pub async fn mark_seen(&mut self) -> Result<(), S::Error> {
+        self.tree.mark_seen().await
+    }
+
+    pub fn widget(
+        &mut self,
+        nick: String,
+        user_id: Option<String>,
+        focused: bool,
+    ) -> BoxedAsync<'_, UiError>
     where
         M: ChatMsg + Send + Sync,
         M::Id: Send + Sync,
@@ -68,20 +150,115 @@ impl<M: Msg, S: MsgStore<M>> ChatState<M, S> {
         S::Error: Send,
         UiError: From<S::Error>,
     {
-        match self.mode {
+        let replying_to = match &self.cursor {
+            Cursor::Editor { parent, .. } => parent.as_ref(),
+            _ => None,
+        };
+        let reply_preview = self
+            .reply_preview
+            .as_ref()
+            .filter(|(id, _)| Some(id) == replying_to)
+            .map(|(_, preview)| preview.clone());
+
+        // With the composer bar, the preview is shown above the editor instead
+        let (reply_preview, composer_preview) = match self.config.composer {
+            Composer::Inline => (reply_preview, None),
+            Composer::Bottom => (None, reply_preview),
+        };
+
+        let chat = match self.mode {
             Mode::Tree => self
                 .tree
                 .widget(
                     &mut self.cursor,
                     &mut self.editor,
                     nick,
+                    user_id,
                     focused,
                     self.caesar,
+                    self.zen,
+                    composer_preview,
                 )
                 .boxed_async(),
+        };
+
+        match reply_preview {
+            Some(preview) => Join2::vertical(
+                reply_preview_widget(preview)
+                    .desync()
+                    .segment()
+                    .with_fixed(true),
+                chat.segment(),
+            )
+            .boxed_async(),
+            None => chat,
         }
     }
 
+    async fn update_reply_preview(&mut self, keys: &Keys) -> Result<(), S::Error>
+    where
+        M: ChatMsg + Send + Sync,
+        M::Id: Send + Sync,
+        S: Send + Sync,
+    {
+        let Cursor::Editor {
+            parent: Some(parent),
+            ..
+        } = &self.cursor
+        else {
+            return Ok(());
+        };
+
+        if let Some((id, _)) = &self.reply_preview {
+            if id == parent {
+                return Ok(());
+            }
+        }
+
+        let preview = match self.store.msg(parent).await? {
+            Some(msg) => {
+                let (nick, content) = msg.styled();
+                let first_line = content.text().lines().next().unwrap_or_default();
+                let preview = Styled::new("Replying to ", self.config.theme.info())
+                    .and_then(nick)
+                    .then_plain(format!(": {first_line}"));
+                match msg.time().and_then(|time| self.stale_reply_age(time)) {
+                    Some(age) => {
+                        let style = Style::new().yellow();
+                        let binding = &keys.editor.action.new_thread;
+                        Styled::new(format!("This message is {age} old, press "), style)
+                            .and_then(key_bindings::format_binding(binding))
+                            .then(" to start a new thread instead\n", style)
+                            .and_then(preview)
+                    }
+                    None => preview,
+                }
+            }
+            None => Styled::new(
+                "Replying to a message that is not loaded",
+                self.config.theme.info(),
+            ),
+        };
+        self.reply_preview = Some((parent.clone(), preview));
+
+        Ok(())
+    }
+
+    /// How old a message is, if it is too old to reply to according to the
+    /// `stale_reply_after` config option.
+    fn stale_reply_age(&self, time: OffsetDateTime) -> Option<String> {
+        let hours = self.config.stale_reply_after?;
+        let age = OffsetDateTime::now_utc() - time;
+        if age.whole_hours() < hours as i64 {
+            return None;
+        }
+
+        Some(match age.whole_hours() {
+            h if h < 48 => format!("{h} hours"),
+            _ => format!("{} days", age.whole_days()),
+        })
+    }
+
     pub async fn handle_input_event(
         &mut self,
         event: &mut InputEvent<'_>,
@@ -94,6 +271,14 @@ impl<M: Msg, S: MsgStore<M>> ChatState<M, S> {
         S: Send + Sync,
         S::Error: Send,
     {
+        if let Cursor::Editor { parent, .. } = &mut self.cursor {
+            if parent.is_some() && event.matches(&keys.editor.action.new_thread) {
+                *parent = None;
+                self.update_reply_preview(keys).await?;
+                return Ok(Reaction::Handled);
+            }
+        }
+
         let reaction = match self.mode {
             Mode::Tree => {
                 self.tree
@@ -108,6 +293,13 @@ impl<M: Msg, S: MsgStore<M>> ChatState<M, S> {
             }
         };
 
+        self.update_reply_preview(keys).await?;
+
+        // Moving the cursor away from the bottom stops following new messages
+        if let Cursor::Msg(_) = self.cursor {
+            self.follow = false;
+        }
+
         Ok(match reaction {
             Reaction::Composed { parent, content } if self.caesar != 0 => {
                 let content = util::caesar(&content, self.caesar);
@@ -124,10 +316,160 @@ impl<M: Msg, S: MsgStore<M>> ChatState<M, S> {
                 Reaction::Handled
             }
 
+            Reaction::NotHandled if event.matches(&keys.tree.action.follow) => {
+                self.follow = !self.follow;
+                if self.follow {
+                    self.cursor = Cursor::Bottom;
+                }
+                Reaction::Handled
+            }
+
+            Reaction::NotHandled if event.matches(&keys.tree.action.expand) => match &self.cursor {
+                Cursor::Msg(id) => Reaction::FetchMsg(id.clone()),
+                _ => Reaction::NotHandled,
+            },
+
+            Reaction::NotHandled if event.matches(&keys.tree.action.load_ancestors) => {
+                let Cursor::Msg(id) = &self.cursor else {
+                    return Ok(Reaction::Handled);
+                };
+                // Everything above the root of the thread is missing.
+                let root = self.store.path(id).await?.into_first();
+                if self.store.msg(&root).await?.is_some() {
+                    return Ok(Reaction::Handled);
+                }
+                Reaction::FetchAncestors(root)
+            }
+
+            Reaction::NotHandled if event.matches(&keys.tree.action.zen) => {
+                self.zen = !self.zen;
+                Reaction::Handled
+            }
+
             reaction => reaction,
         })
     }
 
+    /// Describe the cursor position in words, for use with screen readers.
+    pub async fn cursor_description(&self) -> Result<Styled, S::Error>
+    where
+        M: ChatMsg + Send + Sync,
+        M::Id: Send + Sync,
+        S: Send + Sync,
+    {
+        Ok(match &self.cursor {
+            Cursor::Bottom => Styled::new_plain("at the bottom"),
+            Cursor::Msg(id) => match self.store.msg(id).await? {
+                Some(msg) => {
+                    let (nick, content) = msg.styled();
+                    let first_line = content.text().lines().next().unwrap_or_default();
+                    Styled::new_plain("on message by ")
+                        .and_then(nick)
+                        .then_plain(format!(": {first_line}"))
+                }
+                None => Styled::new_plain("on a message that is not loaded"),
+            },
+            Cursor::Editor { parent: None, .. } => Styled::new_plain("writing a new thread"),
+            Cursor::Editor {
+                parent: Some(parent),
+                ..
+            } => match self.store.msg(parent).await? {
+                Some(msg) => Styled::new_plain("writing a reply to ").and_then(msg.styled().0),
+                None => Styled::new_plain("writing a reply"),
+            },
+            Cursor::Pseudo { .. } => Styled::new_plain("sending a message"),
+        })
+    }
+
+    /// Where the message under the cursor is located in its thread, if the
+    /// cursor is on a message.
+    pub async fn thread_position(&self) -> Result<Option<ThreadPosition>, S::Error> {
+        let Cursor::Msg(id) = &self.cursor else {
+            return Ok(None);
+        };
+
+        let root = self.store.path(id).await?.into_first();
+        let tree = self.store.tree(&root, self.config.sibling_order).await?;
+        let sibling = tree.siblings(id).and_then(|siblings| {
+            let index = siblings.iter().position(|s| s == id)?;
+            Some((index + 1, siblings.len()))
+        });
+
+        Ok(Some(ThreadPosition {
+            sibling,
+            replies: tree.subtree_size(id),
+        }))
+    }
+
+    /// The editor's content, if the user is currently composing a message.
+    pub fn composing(&self) -> Option<&str> {
+        match self.cursor {
+            Cursor::Editor { .. } => Some(self.editor.text()),
+            _ => None,
+        }
+    }
+
+    /// Replace the editor's content, if the user is currently composing a
+    /// message.
+    pub fn set_composing(&mut self, widthdb: &mut WidthDb, text: String) {
+        if let Cursor::Editor { .. } = self.cursor {
+            self.editor.set_text(widthdb, text);
+        }
+    }
+
+    /// Start composing a message with the given text below `parent`, unless
+    /// the user is already composing a message or the editor still contains
+    /// text. Returns whether the text was restored.
+    pub fn restore_composing(
+        &mut self,
+        widthdb: &mut WidthDb,
+        parent: Option<M::Id>,
+        text: String,
+    ) -> bool {
+        if let Cursor::Editor { .. } = self.cursor {
+            return false;
+        }
+        if !self.editor.text().is_empty() {
+            return false;
+        }
+
+        self.cursor = Cursor::Editor {
+            coming_from: self.cursor().cloned(),
+            parent,
+        };
+        self.editor.set_text(widthdb, text);
+        true
+    }
+
+    /// Insert text at the editor's cursor, if the user is currently composing a
+    /// message.
+    pub fn insert_composing(&mut self, widthdb: &mut WidthDb, text: &str) {
+        if let Cursor::Editor { .. } = self.cursor {
+            for c in text.chars() {
+                self.editor.insert_char(widthdb, c);
+            }
+        }
+    }
+
+    /// Move the cursor to a message, unless the user is currently composing a
+    /// message.
+    pub fn move_cursor_to(&mut self, id: M::Id) {
+        if let Cursor::Bottom | Cursor::Msg(_) = self.cursor {
+            self.cursor = Cursor::Msg(id);
+            self.follow = false;
+        }
+    }
+
+    pub fn set_translation(&mut self, id: M::Id, translation: Translation) {
+        self.tree.set_translation(id, translation);
+    }
+
+    /// Show or hide the translation of a message. Returns `false` if there is
+    /// no translation to toggle and a new one should be requested instead.
+    pub fn toggle_translation(&mut self, id: &M::Id) -> bool {
+        self.tree.toggle_translation(id)
+    }
+
     pub fn cursor(&self) -> Option<&M::Id> {
         match &self.cursor {
             Cursor::Msg(id) => Some(id),
@@ -137,15 +479,54 @@ impl<M: Msg, S: MsgStore<M>> ChatState<M, S> {
 
     /// A [`Reaction::Composed`] message was sent successfully.
     pub fn send_successful(&mut self, id: M::Id) {
-        if let Cursor::Pseudo { .. } = &self.cursor {
+        if let Cursor::Pseudo { coming_from, .. } = &self.cursor {
             self.tree.send_successful(&id);
-            self.cursor = Cursor::Msg(id);
+            self.cursor = match self.config.cursor_after_send {
+                _ if self.follow => Cursor::Bottom,
+                CursorAfterSend::Sent => Cursor::Msg(id),
+                CursorAfterSend::Previous => match coming_from {
+                    Some(id) => Cursor::Msg(id.clone()),
+                    None => Cursor::Bottom,
+                },
+                CursorAfterSend::Bottom => Cursor::Bottom,
+            };
             self.editor.clear();
         }
     }
 
+    /// A [`Reaction::Composed`] message was interpreted as a command instead of
+    /// being sent. If the command failed, the editor is reopened so the message
+    /// can be corrected.
+    pub fn command_executed(&mut self, success: bool) {
+        if let Cursor::Pseudo {
+            coming_from,
+            parent,
+        } = &self.cursor
+        {
+            self.cursor = if success {
+                self.editor.clear();
+                match coming_from {
+                    Some(id) => Cursor::Msg(id.clone()),
+                    None => Cursor::Bottom,
+                }
+            } else {
+                Cursor::Editor {
+                    coming_from: coming_from.clone(),
+                    parent: parent.clone(),
+                }
+            };
+        }
+    }
+
+    /// Show in the editor how many seconds are left until the next message
+    /// may be sent, or nothing if it may be sent right away.
+    pub fn set_send_cooldown(&mut self, cooldown: Option<u64>) {
+        self.tree.set_send_cooldown(cooldown);
+    }
+
     /// A [`Reaction::Composed`] message failed to be sent.
     pub fn send_failed(&mut self) {
+        self.tree.send_failed();
         if let Cursor::Pseudo { coming_from, .. } = &self.cursor {
             self.cursor = match coming_from {
                 Some(id) => Cursor::Msg(id.clone()),
@@ -155,6 +536,16 @@ impl<M: Msg, S: MsgStore<M>> ChatState<M, S> {
     }
 }
 
+fn reply_preview_widget(preview: Styled) -> impl Widget<UiError> {
+    // One line for the message, plus one for the stale reply warning, if any
+    let lines = preview.text().lines().count() as u16;
+    Text::new(preview)
+        .resize()
+        .with_max_height(lines)
+        .padding()
+        .with_horizontal(1)
+}
+
 pub enum Reaction<M: Msg> {
     NotHandled,
     Handled,
@@ -162,6 +553,11 @@ pub enum Reaction<M: Msg> {
         parent: Option<M::Id>,
         content: String,
     },
+    /// The message is not loaded and should be requested from the server.
+    FetchMsg(M::Id),
+    /// The message is not loaded and should be requested from the server,
+    /// followed by all of its ancestors that are not loaded either.
+    FetchAncestors(M::Id),
 }
 
 impl<M: Msg> Reaction<M> {
@@ -3,6 +3,8 @@
 use std::collections::HashSet;
 use std::hash::Hash;
 
+use cove_config::SiblingOrder;
+
 use crate::store::{Msg, MsgStore, Tree};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -37,6 +39,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
     /// Always stays at the same level of indentation.
     async fn find_prev_sibling<M, S>(
         store: &S,
+        order: SiblingOrder,
         tree: &mut Tree<M>,
         id: &mut Id,
     ) -> Result<bool, S::Error>
@@ -51,7 +54,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
             // We're at the root of our tree, so we need to move to the root of
             // the previous tree.
             if let Some(prev_root_id) = store.prev_root_id(tree.root()).await? {
-                *tree = store.tree(&prev_root_id).await?;
+                *tree = store.tree(&prev_root_id, order).await?;
                 *id = prev_root_id;
                 true
             } else {
@@ -68,6 +71,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
     /// Always stays at the same level of indentation.
     async fn find_next_sibling<M, S>(
         store: &S,
+        order: SiblingOrder,
         tree: &mut Tree<M>,
         id: &mut Id,
     ) -> Result<bool, S::Error>
@@ -82,7 +86,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
             // We're at the root of our tree, so we need to move to the root of
             // the next tree.
             if let Some(next_root_id) = store.next_root_id(tree.root()).await? {
-                *tree = store.tree(&next_root_id).await?;
+                *tree = store.tree(&next_root_id, order).await?;
                 *id = next_root_id;
                 true
             } else {
@@ -129,6 +133,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
     /// Move to the message above, or don't move if this is not possible.
     async fn find_above_msg_in_tree<M, S>(
         store: &S,
+        order: SiblingOrder,
         folded: &HashSet<Id>,
         tree: &mut Tree<M>,
         id: &mut Id,
@@ -139,7 +144,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
     {
         // Move to previous sibling, then to its last child
         // If not possible, move to parent
-        let moved = if Self::find_prev_sibling(store, tree, id).await? {
+        let moved = if Self::find_prev_sibling(store, order, tree, id).await? {
             while Self::find_last_child_in_tree(folded, tree, id) {}
             true
         } else {
@@ -151,6 +156,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
     /// Move to the next message, or don't move if this is not possible.
     async fn find_below_msg_in_tree<M, S>(
         store: &S,
+        order: SiblingOrder,
         folded: &HashSet<Id>,
         tree: &mut Tree<M>,
         id: &mut Id,
@@ -163,7 +169,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
             return Ok(true);
         }
 
-        if Self::find_next_sibling(store, tree, id).await? {
+        if Self::find_next_sibling(store, order, tree, id).await? {
             return Ok(true);
         }
 
@@ -171,7 +177,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
         // can be found.
         let mut tmp_id = id.clone();
         while Self::find_parent(tree, &mut tmp_id) {
-            if Self::find_next_sibling(store, tree, &mut tmp_id).await? {
+            if Self::find_next_sibling(store, order, tree, &mut tmp_id).await? {
                 *id = tmp_id;
                 return Ok(true);
             }
@@ -324,7 +330,11 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
         Ok(())
     }
 
-    pub async fn move_to_prev_sibling<M, S>(&mut self, store: &S) -> Result<(), S::Error>
+    pub async fn move_to_prev_sibling<M, S>(
+        &mut self,
+        store: &S,
+        order: SiblingOrder,
+    ) -> Result<(), S::Error>
     where
         M: Msg<Id = Id>,
         S: MsgStore<M>,
@@ -337,8 +347,8 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
             }
             Self::Msg(msg) => {
                 let path = store.path(msg).await?;
-                let mut tree = store.tree(path.first()).await?;
-                Self::find_prev_sibling(store, &mut tree, msg).await?;
+                let mut tree = store.tree(path.first(), order).await?;
+                Self::find_prev_sibling(store, order, &mut tree, msg).await?;
             }
             Self::Editor { .. } => {}
             Self::Pseudo {
@@ -346,7 +356,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
                 ..
             } => {
                 let path = store.path(parent).await?;
-                let tree = store.tree(path.first()).await?;
+                let tree = store.tree(path.first(), order).await?;
                 if let Some(children) = tree.children(parent) {
                     if let Some(last_child) = children.last() {
                         *self = Self::Msg(last_child.clone());
@@ -357,7 +367,11 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
         Ok(())
     }
 
-    pub async fn move_to_next_sibling<M, S>(&mut self, store: &S) -> Result<(), S::Error>
+    pub async fn move_to_next_sibling<M, S>(
+        &mut self,
+        store: &S,
+        order: SiblingOrder,
+    ) -> Result<(), S::Error>
     where
         M: Msg<Id = Id>,
         S: MsgStore<M>,
@@ -365,8 +379,8 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
         match self {
             Self::Msg(msg) => {
                 let path = store.path(msg).await?;
-                let mut tree = store.tree(path.first()).await?;
-                if !Self::find_next_sibling(store, &mut tree, msg).await?
+                let mut tree = store.tree(path.first(), order).await?;
+                if !Self::find_next_sibling(store, order, &mut tree, msg).await?
                     && tree.parent(msg).is_none()
                 {
                     *self = Self::Bottom;
@@ -383,6 +397,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
     pub async fn move_up_in_tree<M, S>(
         &mut self,
         store: &S,
+        order: SiblingOrder,
         folded: &HashSet<Id>,
     ) -> Result<(), S::Error>
     where
@@ -392,7 +407,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
         match self {
             Self::Bottom | Self::Pseudo { parent: None, .. } => {
                 if let Some(last_root_id) = store.last_root_id().await? {
-                    let tree = store.tree(&last_root_id).await?;
+                    let tree = store.tree(&last_root_id, order).await?;
                     let mut id = last_root_id;
                     while Self::find_last_child_in_tree(folded, &tree, &mut id) {}
                     *self = Self::Msg(id);
@@ -400,15 +415,15 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
             }
             Self::Msg(msg) => {
                 let path = store.path(msg).await?;
-                let mut tree = store.tree(path.first()).await?;
-                Self::find_above_msg_in_tree(store, folded, &mut tree, msg).await?;
+                let mut tree = store.tree(path.first(), order).await?;
+                Self::find_above_msg_in_tree(store, order, folded, &mut tree, msg).await?;
             }
             Self::Editor { .. } => {}
             Self::Pseudo {
                 parent: Some(parent),
                 ..
             } => {
-                let tree = store.tree(parent).await?;
+                let tree = store.tree(parent, order).await?;
                 let mut id = parent.clone();
                 while Self::find_last_child_in_tree(folded, &tree, &mut id) {}
                 *self = Self::Msg(id);
@@ -420,6 +435,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
     pub async fn move_down_in_tree<M, S>(
         &mut self,
         store: &S,
+        order: SiblingOrder,
         folded: &HashSet<Id>,
     ) -> Result<(), S::Error>
     where
@@ -429,8 +445,8 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
         match self {
             Self::Msg(msg) => {
                 let path = store.path(msg).await?;
-                let mut tree = store.tree(path.first()).await?;
-                if !Self::find_below_msg_in_tree(store, folded, &mut tree, msg).await? {
+                let mut tree = store.tree(path.first(), order).await?;
+                if !Self::find_below_msg_in_tree(store, order, folded, &mut tree, msg).await? {
                     *self = Self::Bottom;
                 }
             }
@@ -441,11 +457,11 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
                 parent: Some(parent),
                 ..
             } => {
-                let mut tree = store.tree(parent).await?;
+                let mut tree = store.tree(parent, order).await?;
                 let mut id = parent.clone();
                 while Self::find_last_child_in_tree(folded, &tree, &mut id) {}
                 // Now we're at the previous message
-                if Self::find_below_msg_in_tree(store, folded, &mut tree, &mut id).await? {
+                if Self::find_below_msg_in_tree(store, order, folded, &mut tree, &mut id).await? {
                     *self = Self::Msg(id);
                 } else {
                     *self = Self::Bottom;
@@ -461,6 +477,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
     pub async fn parent_for_normal_tree_reply<M, S>(
         &self,
         store: &S,
+        order: SiblingOrder,
     ) -> Result<Option<Option<M::Id>>, S::Error>
     where
         M: Msg<Id = Id>,
@@ -470,7 +487,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
             Self::Bottom => Some(None),
             Self::Msg(id) => {
                 let path = store.path(id).await?;
-                let tree = store.tree(path.first()).await?;
+                let tree = store.tree(path.first(), order).await?;
 
                 Some(Some(if tree.next_sibling(id).is_some() {
                     // A reply to a message that has further siblings should be
@@ -499,6 +516,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
     pub async fn parent_for_alternate_tree_reply<M, S>(
         &self,
         store: &S,
+        order: SiblingOrder,
     ) -> Result<Option<Option<M::Id>>, S::Error>
     where
         M: Msg<Id = Id>,
@@ -508,7 +526,7 @@ impl<Id: Clone + Eq + Hash> Cursor<Id> {
             Self::Bottom => Some(None),
             Self::Msg(id) => {
                 let path = store.path(id).await?;
-                let tree = store.tree(path.first()).await?;
+                let tree = store.tree(path.first(), order).await?;
 
                 Some(Some(if tree.next_sibling(id).is_none() {
                     // The opposite of replying normally
@@ -30,12 +30,14 @@ where
 }
 
 /// The renderer's visible area, reduced by its scrolloff at the top and bottom.
+/// Like in vim, the scrolloff is limited to less than half the visible area.
 fn scroll_area<Id, R>(r: &R) -> Range<i32>
 where
     R: Renderer<Id>,
 {
     let range = visible_area(r);
-    let scrolloff = r.scrolloff();
+    let max_scrolloff = ((range.bottom - range.top - 1) / 2).max(0);
+    let scrolloff = r.scrolloff().min(max_scrolloff);
     let top = range.top + scrolloff;
     let bottom = top.max(range.bottom - scrolloff);
     Range::new(top, bottom)
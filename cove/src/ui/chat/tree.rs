@@ -4,48 +4,202 @@
 
 mod renderer;
 mod scroll;
+#[cfg(test)]
+mod tests;
 mod widgets;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::mem;
+use std::slice;
 
 use async_trait::async_trait;
-use cove_config::Keys;
+use cove_config::{Composer, Config, Keys, MarkSeen, Theme};
 use cove_input::InputEvent;
-use toss::widgets::EditorState;
-use toss::{AsyncWidget, Frame, Pos, Size, WidgetExt, WidthDb};
+use crossterm::style::Stylize;
+use tokio::sync::mpsc;
+use toss::widgets::{EditorState, Predrawn, Resize, Text};
+use toss::{AsyncWidget, Frame, Pos, Size, Styled, Widget, WidgetExt, WidthDb};
 
-use crate::store::{Msg, MsgStore};
-use crate::ui::{util, ChatMsg, UiError};
+use crate::store::{Msg, MsgStore, Path, StoreChange};
+use crate::ui::{key_bindings, util, ChatMsg, UiError};
 use crate::util::InfallibleExt;
 
 use self::renderer::{TreeContext, TreeRenderer};
 
 use super::cursor::Cursor;
-use super::Reaction;
+use super::{Reaction, Translation};
+
+/// Maximum width of the messages in zen mode.
+const ZEN_WIDTH: u16 = 100;
+
+/// How many ancestors of the cursor message the breadcrumb shows at most.
+const BREADCRUMB_ANCESTORS: usize = 3;
+
+/// Maximum length of the content snippets in the breadcrumb, in characters.
+const BREADCRUMB_SNIPPET_LEN: usize = 30;
+
+/// How many styled messages are cached before the cache is cleared.
+const STYLED_CACHE_SIZE: usize = 1000;
+
+/// Styled nick and content of messages by their id and whether they are
+/// expanded, see [`TreeViewState::invalidate_styled`].
+type StyledCache<Id> = HashMap<(Id, bool), (Styled, Styled)>;
+
+/// The breadcrumb of a cursor message, see [`TreeViewState::update_breadcrumb`].
+struct Breadcrumb<Id> {
+    cursor: Id,
+    path: Path<Id>,
+    /// Ancestors of the cursor message that were scrolled out of view.
+    hidden: Vec<Id>,
+    styled: Option<Styled>,
+}
 
 pub struct TreeViewState<M: Msg, S: MsgStore<M>> {
+    config: &'static Config,
     store: S,
 
     last_size: Size,
     last_nick: String,
+    last_user_id: Option<String>,
+    last_zen: bool,
     last_cursor: Cursor<M::Id>,
     last_cursor_top: i32,
     last_visible_msgs: Vec<M::Id>,
+    /// The subset of [`Self::last_visible_msgs`] that was not seen yet.
+    last_visible_unseen: Vec<M::Id>,
+    /// Messages to mark as seen according to the `mark_seen` config option,
+    /// see [`Self::mark_seen`].
+    pending_seen: Vec<M::Id>,
 
     folded: HashSet<M::Id>,
+    /// Messages whose long words are shown in full.
+    expanded: HashSet<M::Id>,
+    translations: HashMap<M::Id, Translation>,
+    hidden_translations: HashMap<M::Id, Translation>,
+
+    /// Messages sent during this session that were acknowledged by the server.
+    sent: HashSet<M::Id>,
+    /// Whether sending the last composed message failed.
+    send_failed: bool,
+    /// Seconds until the next message may be sent, shown in the editor.
+    send_cooldown: Option<u64>,
+    /// Amount of messages that arrived since the newest message was last
+    /// visible.
+    new_below: usize,
+
+    /// Styling messages is comparatively expensive, so messages are only
+    /// styled again after they changed.
+    styled: StyledCache<M::Id>,
+    breadcrumb: Option<Breadcrumb<M::Id>>,
+    store_changes: mpsc::UnboundedReceiver<StoreChange>,
 }
 
 impl<M: Msg, S: MsgStore<M>> TreeViewState<M, S> {
-    pub fn new(store: S) -> Self {
+    pub fn new(config: &'static Config, store: S) -> Self {
+        let store_changes = store.subscribe();
         Self {
+            config,
             store,
             last_size: Size::ZERO,
             last_nick: String::new(),
+            last_user_id: None,
+            last_zen: false,
             last_cursor: Cursor::Bottom,
             last_cursor_top: 0,
             last_visible_msgs: vec![],
+            last_visible_unseen: vec![],
+            pending_seen: vec![],
             folded: HashSet::new(),
+            expanded: HashSet::new(),
+            translations: HashMap::new(),
+            hidden_translations: HashMap::new(),
+            sent: HashSet::new(),
+            send_failed: false,
+            send_cooldown: None,
+            new_below: 0,
+            styled: HashMap::new(),
+            breadcrumb: None,
+            store_changes,
+        }
+    }
+
+    /// Forget the styling of all messages and the breadcrumb if any message
+    /// may have changed since it was styled. Whether a message was seen is not
+    /// part of its styling.
+    fn invalidate_styled(&mut self) {
+        let mut changed = false;
+        while let Ok(change) = self.store_changes.try_recv() {
+            changed |= change != StoreChange::Seen;
+        }
+        if changed {
+            self.breadcrumb = None;
+        }
+        if changed || self.styled.len() > STYLED_CACHE_SIZE {
+            self.styled.clear();
+        }
+    }
+
+    /// Mark the messages as seen that were visible during the last redraw,
+    /// according to the `mark_seen` config option.
+    ///
+    /// This happens separately from drawing so a redraw never waits for the
+    /// store. The new seen status then only becomes visible on the next
+    /// redraw, which also gives the user a chance to notice which messages
+    /// were new.
+    pub async fn mark_seen(&mut self) -> Result<(), S::Error> {
+        if self.pending_seen.is_empty() {
+            return Ok(());
+        }
+        let ids = mem::take(&mut self.pending_seen);
+        self.store.set_many_seen(&ids, true).await
+    }
+
+    pub fn set_translation(&mut self, id: M::Id, translation: Translation) {
+        self.hidden_translations.remove(&id);
+        self.translations.insert(id, translation);
+    }
+
+    /// Show or hide an existing translation. Returns `false` if there is no
+    /// translation to toggle, including failed translations.
+    pub fn toggle_translation(&mut self, id: &M::Id) -> bool {
+        if let Some(translation) = self.translations.remove(id) {
+            if let Translation::Failed = translation {
+                return false;
+            }
+            self.hidden_translations.insert(id.clone(), translation);
+            true
+        } else if let Some(translation) = self.hidden_translations.remove(id) {
+            self.translations.insert(id.clone(), translation);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Mark the thread the cursor is in as seen, then move to the oldest unseen
+    /// message, unfolding its thread if necessary. Repeating this reads through
+    /// all unseen messages thread by thread.
+    async fn move_to_next_unseen_thread(
+        &mut self,
+        cursor: &mut Cursor<M::Id>,
+    ) -> Result<(), S::Error> {
+        if let Cursor::Msg(id) = cursor {
+            let root = self.store.path(id).await?.into_first();
+            self.store.set_tree_seen(&root, true).await?;
+        }
+
+        match self.store.oldest_unseen_msg_id().await? {
+            Some(id) => {
+                for segment in self.store.path(&id).await?.parent_segments() {
+                    self.folded.remove(segment);
+                }
+                *cursor = Cursor::Msg(id);
+            }
+            None => *cursor = Cursor::Bottom,
         }
+
+        Ok(())
     }
 
     async fn handle_movement_input_event(
@@ -62,14 +216,19 @@ impl<M: Msg, S: MsgStore<M>> TreeViewState<M, S> {
         S::Error: Send,
     {
         let chat_height: i32 = (event.frame().size().height - 3).into();
+        let order = self.config.sibling_order;
 
         // Basic cursor movement
         if event.matches(&keys.cursor.up) {
-            cursor.move_up_in_tree(&self.store, &self.folded).await?;
+            cursor
+                .move_up_in_tree(&self.store, order, &self.folded)
+                .await?;
             return Ok(true);
         }
         if event.matches(&keys.cursor.down) {
-            cursor.move_down_in_tree(&self.store, &self.folded).await?;
+            cursor
+                .move_down_in_tree(&self.store, order, &self.folded)
+                .await?;
             return Ok(true);
         }
         if event.matches(&keys.cursor.to_top) {
@@ -83,11 +242,11 @@ impl<M: Msg, S: MsgStore<M>> TreeViewState<M, S> {
 
         // Tree cursor movement
         if event.matches(&keys.tree.cursor.to_above_sibling) {
-            cursor.move_to_prev_sibling(&self.store).await?;
+            cursor.move_to_prev_sibling(&self.store, order).await?;
             return Ok(true);
         }
         if event.matches(&keys.tree.cursor.to_below_sibling) {
-            cursor.move_to_next_sibling(&self.store).await?;
+            cursor.move_to_next_sibling(&self.store, order).await?;
             return Ok(true);
         }
         if event.matches(&keys.tree.cursor.to_parent) {
@@ -114,6 +273,10 @@ impl<M: Msg, S: MsgStore<M>> TreeViewState<M, S> {
             cursor.move_to_newer_unseen_msg(&self.store).await?;
             return Ok(true);
         }
+        if event.matches(&keys.tree.cursor.to_next_unseen_thread) {
+            self.move_to_next_unseen_thread(cursor).await?;
+            return Ok(true);
+        }
 
         // Scrolling
         if event.matches(&keys.scroll.up_line) {
@@ -171,9 +334,23 @@ impl<M: Msg, S: MsgStore<M>> TreeViewState<M, S> {
             return Ok(true);
         }
 
+        if event.matches(&keys.tree.action.expand) {
+            if let Some(id) = id {
+                // Messages that are not loaded can't be expanded, they must be
+                // fetched instead.
+                if self.store.msg(id).await?.is_none() {
+                    return Ok(false);
+                }
+                if !self.expanded.remove(id) {
+                    self.expanded.insert(id.clone());
+                }
+            }
+            return Ok(true);
+        }
+
         if event.matches(&keys.tree.action.toggle_seen) {
             if let Some(id) = id {
-                if let Some(msg) = self.store.tree(id).await?.msg(id) {
+                if let Some(msg) = self.store.msg(id).await? {
                     self.store.set_seen(id, !msg.seen()).await?;
                 }
             }
@@ -181,9 +358,9 @@ impl<M: Msg, S: MsgStore<M>> TreeViewState<M, S> {
         }
 
         if event.matches(&keys.tree.action.mark_visible_seen) {
-            for id in &self.last_visible_msgs {
-                self.store.set_seen(id, true).await?;
-            }
+            self.store
+                .set_many_seen(&self.last_visible_msgs, true)
+                .await?;
             return Ok(true);
         }
 
@@ -208,8 +385,13 @@ impl<M: Msg, S: MsgStore<M>> TreeViewState<M, S> {
         cursor: &mut Cursor<M::Id>,
         id: Option<M::Id>,
     ) -> Result<bool, S::Error> {
+        let order = self.config.sibling_order;
+
         if event.matches(&keys.tree.action.reply) {
-            if let Some(parent) = cursor.parent_for_normal_tree_reply(&self.store).await? {
+            if let Some(parent) = cursor
+                .parent_for_normal_tree_reply(&self.store, order)
+                .await?
+            {
                 *cursor = Cursor::Editor {
                     coming_from: id,
                     parent,
@@ -219,7 +401,10 @@ impl<M: Msg, S: MsgStore<M>> TreeViewState<M, S> {
         }
 
         if event.matches(&keys.tree.action.reply_alternate) {
-            if let Some(parent) = cursor.parent_for_alternate_tree_reply(&self.store).await? {
+            if let Some(parent) = cursor
+                .parent_for_alternate_tree_reply(&self.store, order)
+                .await?
+            {
                 *cursor = Cursor::Editor {
                     coming_from: id,
                     parent,
@@ -375,26 +560,149 @@ impl<M: Msg, S: MsgStore<M>> TreeViewState<M, S> {
     }
 
     pub fn send_successful(&mut self, id: &M::Id) {
+        self.sent.insert(id.clone());
+        self.send_failed = false;
         if let Cursor::Pseudo { .. } = self.last_cursor {
             self.last_cursor = Cursor::Msg(id.clone());
         }
     }
 
+    pub fn send_failed(&mut self) {
+        self.send_failed = true;
+    }
+
+    pub fn set_send_cooldown(&mut self, cooldown: Option<u64>) {
+        self.send_cooldown = cooldown;
+    }
+
+    /// A new message from somebody else arrived.
+    pub fn msg_arrived(&mut self) {
+        self.new_below += 1;
+    }
+
+    /// Whether a breadcrumb was shown for the cursor message during the last
+    /// redraw, in which case a row is reserved for it.
+    fn has_breadcrumb(&self, cursor: &Cursor<M::Id>) -> bool {
+        match (cursor, &self.breadcrumb) {
+            (Cursor::Msg(id), Some(breadcrumb)) => {
+                breadcrumb.cursor == *id && breadcrumb.styled.is_some()
+            }
+            _ => false,
+        }
+    }
+
+    /// Update the closest ancestors of the cursor message that were scrolled
+    /// out of view. The breadcrumb is only styled again if they changed.
+    async fn update_breadcrumb(&mut self) -> Result<(), S::Error>
+    where
+        M: ChatMsg,
+    {
+        let Cursor::Msg(id) = &self.last_cursor else {
+            self.breadcrumb = None;
+            return Ok(());
+        };
+
+        let path = match self.breadcrumb.take() {
+            Some(breadcrumb) if breadcrumb.cursor == *id => {
+                let hidden = self.hidden_ancestors(&breadcrumb.path);
+                if hidden == breadcrumb.hidden {
+                    self.breadcrumb = Some(breadcrumb);
+                    return Ok(());
+                }
+                breadcrumb.path
+            }
+            _ => self.store.path(id).await?,
+        };
+
+        let hidden = self.hidden_ancestors(&path);
+        let styled = self.style_breadcrumb(&path, &hidden).await?;
+        self.breadcrumb = Some(Breadcrumb {
+            cursor: id.clone(),
+            path,
+            hidden,
+            styled,
+        });
+        Ok(())
+    }
+
+    fn hidden_ancestors(&self, path: &Path<M::Id>) -> Vec<M::Id> {
+        path.parent_segments()
+            .filter(|id| !self.last_visible_msgs.contains(*id))
+            .cloned()
+            .collect()
+    }
+
+    /// The hidden ancestors of a message, if any, as a single line.
+    async fn style_breadcrumb(
+        &self,
+        path: &Path<M::Id>,
+        hidden: &[M::Id],
+    ) -> Result<Option<Styled>, S::Error>
+    where
+        M: ChatMsg,
+    {
+        if hidden.is_empty() {
+            return Ok(None);
+        }
+
+        let tree = self
+            .store
+            .tree(path.first(), self.config.sibling_order)
+            .await?;
+        let style = self.config.theme.info();
+        let skipped = hidden.len().saturating_sub(BREADCRUMB_ANCESTORS);
+        let mut result = Styled::new("↑ ", style);
+        if skipped > 0 {
+            result = result.then("… › ", style);
+        }
+        for (i, id) in hidden.iter().skip(skipped).enumerate() {
+            if i > 0 {
+                result = result.then(" › ", style);
+            }
+            let Some(msg) = tree.msg(id) else {
+                result = result.then("…", style);
+                continue;
+            };
+            let (nick, content) = msg.styled();
+            let content = content.text().lines().next().unwrap_or_default();
+            let mut snippet = content
+                .chars()
+                .take(BREADCRUMB_SNIPPET_LEN)
+                .collect::<String>();
+            if snippet.len() < content.len() {
+                snippet.push('…');
+            }
+            result = result
+                .then("[", style)
+                .and_then(nick)
+                .then("] ", style)
+                .then(snippet, style);
+        }
+
+        Ok(Some(result))
+    }
+
     pub fn widget<'a>(
         &'a mut self,
         cursor: &'a mut Cursor<M::Id>,
         editor: &'a mut EditorState,
         nick: String,
+        user_id: Option<String>,
         focused: bool,
         caesar: i8,
+        zen: bool,
+        reply_preview: Option<Styled>,
     ) -> TreeView<'a, M, S> {
         TreeView {
             state: self,
             cursor,
             editor,
             nick,
+            user_id,
             focused,
             caesar,
+            zen,
+            reply_preview,
         }
     }
 }
@@ -406,8 +714,13 @@ pub struct TreeView<'a, M: Msg, S: MsgStore<M>> {
     editor: &'a mut EditorState,
 
     nick: String,
+    user_id: Option<String>,
     focused: bool,
     caesar: i8,
+    zen: bool,
+    /// Shown above the editor in the composer bar, see the `composer` config
+    /// option.
+    reply_preview: Option<Styled>,
 }
 
 #[async_trait]
@@ -429,21 +742,73 @@ where
     }
 
     async fn draw(self, frame: &mut Frame) -> Result<(), UiError> {
-        let size = frame.size();
+        // In zen mode, the messages are centered with margins on both sides.
+        let full_size = frame.size();
+        let width = if self.zen {
+            full_size.width.min(ZEN_WIDTH)
+        } else {
+            full_size.width
+        };
+        frame.push(
+            Pos::new(((full_size.width - width) / 2).into(), 0),
+            Size::new(width, full_size.height),
+        );
+        let mut size = frame.size();
 
-        let context = TreeContext {
+        // A breadcrumb shown for the cursor message during the last redraw
+        // most likely stays, so the messages are laid out below it.
+        let top = u16::from(self.state.has_breadcrumb(self.cursor) && size.height > 1);
+        size.height -= top;
+
+        let mut context = TreeContext {
             size,
             nick: self.nick.clone(),
+            user_id: self.user_id.clone(),
             focused: self.focused,
             caesar: self.caesar,
+            composer: self.state.config.composer,
+            zen: self.zen,
+            seen_marker: &self.state.config.seen_marker,
+            long_words: self.state.config.long_words,
+            nick_width: self.state.config.nick_width,
+            own_messages: self.state.config.own_messages,
+            scrolloff: self.state.config.scrolloff,
+            theme_mode: self.state.config.theme_mode,
+            theme: &self.state.config.theme,
+            screen_reader: self.state.config.screen_reader,
+            scrollback: self.state.config.scrollback,
+            sibling_order: self.state.config.sibling_order,
+            send_failed: self.state.send_failed,
+            send_cooldown: self.state.send_cooldown,
             last_cursor: self.state.last_cursor.clone(),
             last_cursor_top: self.state.last_cursor_top,
         };
 
+        // The composer bar takes up space at the bottom, so the messages are
+        // laid out in the remaining space above it.
+        let composer = match (context.composer, &*self.cursor) {
+            (Composer::Bottom, Cursor::Editor { .. }) => {
+                let editor = &mut *self.editor;
+                let widget = widgets::composer::<M>(&context, self.reply_preview, editor);
+                let widget = Resize::new(widget).with_max_width(size.width);
+                let predrawn = Predrawn::new(widget, frame.widthdb()).infallible();
+                let height = predrawn.size().height.min(size.height.saturating_sub(1));
+                size.height -= height;
+                context.size = size;
+                Some((predrawn, height))
+            }
+            _ => None,
+        };
+
+        self.state.invalidate_styled();
         let mut renderer = TreeRenderer::new(
             context,
             &self.state.store,
             &mut self.state.folded,
+            &self.state.translations,
+            &self.state.sent,
+            &self.state.expanded,
+            &mut self.state.styled,
             self.cursor,
             self.editor,
             frame.widthdb(),
@@ -453,19 +818,103 @@ where
 
         self.state.last_size = size;
         self.state.last_nick = self.nick;
+        self.state.last_user_id = self.user_id;
+        self.state.last_zen = self.zen;
         renderer.update_render_info(
             &mut self.state.last_cursor,
             &mut self.state.last_cursor_top,
             &mut self.state.last_visible_msgs,
+            &mut self.state.last_visible_unseen,
         );
 
         for (range, block) in renderer.into_visible_blocks() {
             let widget = block.into_widget();
-            frame.push(Pos::new(0, range.top), widget.size());
+            frame.push(Pos::new(0, range.top + i32::from(top)), widget.size());
             widget.desync().draw(frame).await.infallible();
             frame.pop();
         }
 
+        if let Some((predrawn, height)) = composer {
+            frame.push(
+                Pos::new(0, i32::from(size.height + top)),
+                Size::new(size.width, height),
+            );
+            predrawn.draw(frame).infallible();
+            frame.pop();
+        }
+
+        self.state.update_breadcrumb().await?;
+        if let Some(Breadcrumb {
+            styled: Some(styled),
+            ..
+        }) = &self.state.breadcrumb
+        {
+            frame.push(Pos::new(0, 0), Size::new(size.width, 1));
+            breadcrumb_widget(&self.state.config.theme, styled.clone())
+                .draw(frame)
+                .infallible();
+            frame.pop();
+        }
+
+        let newest_visible = match self.state.store.newest_msg_id().await? {
+            Some(id) => self.state.last_visible_msgs.contains(&id),
+            None => true,
+        };
+        if newest_visible || matches!(self.cursor, Cursor::Bottom) {
+            self.state.new_below = 0;
+        } else if self.state.new_below > 0 {
+            frame.push(
+                Pos::new(0, i32::from(size.height + top) - 1),
+                Size::new(size.width, 1),
+            );
+            new_below_widget(self.state.config, self.state.new_below)
+                .draw(frame)
+                .infallible();
+            frame.pop();
+        }
+        frame.pop();
+
+        // Only messages that aren't seen yet are marked, since every change to
+        // the store causes another redraw.
+        let unseen = &self.state.last_visible_unseen;
+        let newly_seen = match self.state.config.mark_seen {
+            MarkSeen::Explicit => &[][..],
+            MarkSeen::Cursor => match &self.state.last_cursor {
+                Cursor::Msg(id) if unseen.contains(id) => slice::from_ref(id),
+                _ => &[],
+            },
+            MarkSeen::Render => unseen,
+        };
+        for id in newly_seen {
+            if !self.state.pending_seen.contains(id) {
+                self.state.pending_seen.push(id.clone());
+            }
+        }
+
         Ok(())
     }
 }
+
+/// Keeps the context of the cursor message visible when its ancestors are
+/// scrolled out of view.
+fn breadcrumb_widget(theme: &Theme, breadcrumb: Styled) -> impl Widget<Infallible> {
+    Text::new(breadcrumb)
+        .with_wrap(false)
+        .background()
+        .with_style(theme.background())
+}
+
+/// Points out messages that arrived below the visible part of the chat.
+fn new_below_widget(config: &Config, amount: usize) -> impl Widget<Infallible> {
+    let style = config.theme.cursor().bold();
+    let text = if amount == 1 {
+        " ↓ 1 new message, press ".to_string()
+    } else {
+        format!(" ↓ {amount} new messages, press ")
+    };
+    let text = Styled::new(text, style)
+        .and_then(key_bindings::format_binding(&config.keys.cursor.to_bottom))
+        .then(" ", style);
+
+    Text::new(text).with_wrap(false).float().with_center_h()
+}
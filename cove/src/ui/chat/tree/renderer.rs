@@ -1,20 +1,22 @@
 //! A [`Renderer`] for message trees.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 
 use async_trait::async_trait;
+use cove_config::{Composer, LongWords, OwnMessages, SeenMarker, SiblingOrder, Theme, ThemeMode};
 use toss::widgets::{EditorState, Empty, Predrawn, Resize};
-use toss::{Size, Widget, WidthDb};
+use toss::{Size, Styled, Widget, WidthDb};
 
 use crate::store::{Msg, MsgStore, Tree};
 use crate::ui::chat::blocks::{Block, Blocks, Range};
 use crate::ui::chat::cursor::Cursor;
 use crate::ui::chat::renderer::{self, overlaps, Renderer};
+use crate::ui::chat::{Delivery, Translation};
 use crate::ui::ChatMsg;
 use crate::util::InfallibleExt;
 
-use super::widgets;
+use super::{widgets, StyledCache};
 
 /// When rendering messages as full trees, special ids and zero-height messages
 /// are used for robust scrolling behaviour.
@@ -30,6 +32,10 @@ pub enum TreeBlockId<Id> {
     /// rendered. It is used for positioning [`Cursor::Editor`] and
     /// [`Cursor::Pseudo`].
     After(Id),
+    /// Messages of a tree that are not laid out because the tree is larger
+    /// than the `scrollback` config option allows are replaced by a single
+    /// block with the id of the first of these messages.
+    Unloaded(Id),
 }
 
 impl<Id: Clone> TreeBlockId<Id> {
@@ -53,13 +59,13 @@ impl<Id: Clone> TreeBlockId<Id> {
     pub fn any_id(&self) -> Option<&Id> {
         match self {
             Self::Bottom => None,
-            Self::Msg(id) | Self::After(id) => Some(id),
+            Self::Msg(id) | Self::After(id) | Self::Unloaded(id) => Some(id),
         }
     }
 
     pub fn msg_id(&self) -> Option<&Id> {
         match self {
-            Self::Bottom | Self::After(_) => None,
+            Self::Bottom | Self::After(_) | Self::Unloaded(_) => None,
             Self::Msg(id) => Some(id),
         }
     }
@@ -68,11 +74,40 @@ impl<Id: Clone> TreeBlockId<Id> {
 type TreeBlock<Id> = Block<TreeBlockId<Id>>;
 type TreeBlocks<Id> = Blocks<TreeBlockId<Id>>;
 
+/// Which messages of a tree are laid out when the tree has more messages than
+/// the `scrollback` config option allows.
+enum Anchor<'a, Id> {
+    /// The oldest messages, for trees below the blocks laid out so far.
+    Top,
+    /// The newest messages, for trees above the blocks laid out so far.
+    Bottom,
+    /// The messages around a specific block.
+    Block(&'a TreeBlockId<Id>),
+}
+
 pub struct TreeContext<Id> {
     pub size: Size,
     pub nick: String,
+    /// The id of the user's current session, used to recognize their own
+    /// messages regardless of the nick they were sent with.
+    pub user_id: Option<String>,
     pub focused: bool,
     pub caesar: i8,
+    pub composer: Composer,
+    /// Hide seen markers and timestamps.
+    pub zen: bool,
+    pub seen_marker: &'static SeenMarker,
+    pub long_words: LongWords,
+    pub nick_width: Option<u16>,
+    pub own_messages: OwnMessages,
+    pub scrolloff: u16,
+    pub theme_mode: ThemeMode,
+    pub theme: &'static Theme,
+    pub screen_reader: bool,
+    pub scrollback: Option<usize>,
+    pub sibling_order: SiblingOrder,
+    pub send_failed: bool,
+    pub send_cooldown: Option<u64>,
     pub last_cursor: Cursor<Id>,
     pub last_cursor_top: i32,
 }
@@ -82,6 +117,10 @@ pub struct TreeRenderer<'a, M: Msg, S: MsgStore<M>> {
 
     store: &'a S,
     folded: &'a mut HashSet<M::Id>,
+    translations: &'a HashMap<M::Id, Translation>,
+    sent: &'a HashSet<M::Id>,
+    expanded: &'a HashSet<M::Id>,
+    styled: &'a mut StyledCache<M::Id>,
     cursor: &'a mut Cursor<M::Id>,
     editor: &'a mut EditorState,
     widthdb: &'a mut WidthDb,
@@ -94,6 +133,8 @@ pub struct TreeRenderer<'a, M: Msg, S: MsgStore<M>> {
     bottom_root_id: Option<M::Id>,
 
     blocks: TreeBlocks<M::Id>,
+    /// Messages in the blocks that are not seen yet.
+    unseen: HashSet<M::Id>,
 }
 
 impl<'a, M, S> TreeRenderer<'a, M, S>
@@ -105,10 +146,15 @@ where
 {
     /// You must call [`Self::prepare_blocks_for_drawing`] immediately after
     /// calling this function.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         context: TreeContext<M::Id>,
         store: &'a S,
         folded: &'a mut HashSet<M::Id>,
+        translations: &'a HashMap<M::Id, Translation>,
+        sent: &'a HashSet<M::Id>,
+        expanded: &'a HashSet<M::Id>,
+        styled: &'a mut StyledCache<M::Id>,
         cursor: &'a mut Cursor<M::Id>,
         editor: &'a mut EditorState,
         widthdb: &'a mut WidthDb,
@@ -117,12 +163,17 @@ where
             context,
             store,
             folded,
+            translations,
+            sent,
+            expanded,
+            styled,
             cursor,
             editor,
             widthdb,
             top_root_id: None,
             bottom_root_id: None,
             blocks: Blocks::new(0),
+            unseen: HashSet::new(),
         }
     }
 
@@ -144,17 +195,18 @@ where
     }
 
     fn editor_block(&mut self, indent: usize, parent: Option<&M::Id>) -> TreeBlock<M::Id> {
+        // The editor is drawn in the composer bar instead, but the position of
+        // the new message should still be scrolled into view.
+        if self.context.composer == Composer::Bottom {
+            return self.zero_height_block(parent);
+        }
+
         let id = match parent {
             Some(parent) => TreeBlockId::After(parent.clone()),
             None => TreeBlockId::Bottom,
         };
 
-        let widget = widgets::editor::<M>(
-            indent,
-            &self.context.nick,
-            self.context.focused,
-            self.editor,
-        );
+        let widget = widgets::editor::<M>(&self.context, indent, self.editor);
         let widget = Self::predraw(widget, self.context.size, self.widthdb);
         let mut block = Block::new(id, widget, false);
 
@@ -172,18 +224,34 @@ where
             None => TreeBlockId::Bottom,
         };
 
-        let widget = widgets::pseudo::<M>(indent, &self.context.nick, self.editor);
+        let widget = widgets::pseudo::<M>(&self.context, indent, self.editor);
         let widget = Self::predraw(widget, self.context.size, self.widthdb);
         Block::new(id, widget, false)
     }
 
+    /// Whether a message counts as the user's own, see the `own_messages`
+    /// config option.
+    fn own(&self, msg: &M) -> bool {
+        self.sent.contains(&msg.id())
+            || self
+                .context
+                .user_id
+                .as_deref()
+                .is_some_and(|id| msg.sent_by(id))
+    }
+
     fn message_block(
         &mut self,
         indent: usize,
         msg: &M,
+        reply_to: Option<Styled>,
+        reply_to_own: bool,
         folded_info: Option<usize>,
     ) -> TreeBlock<M::Id> {
         let msg_id = msg.id();
+        if !msg.seen() {
+            self.unseen.insert(msg_id.clone());
+        }
 
         let highlighted = match self.cursor {
             Cursor::Msg(id) => *id == msg_id,
@@ -191,7 +259,35 @@ where
         };
         let highlighted = highlighted && self.context.focused;
 
-        let widget = widgets::msg(highlighted, indent, msg, self.context.caesar, folded_info);
+        let expanded = self.expanded.contains(&msg_id);
+        let (nick, content) = self
+            .styled
+            .entry((msg_id.clone(), expanded))
+            .or_insert_with(|| {
+                if expanded {
+                    msg.styled_prepared(LongWords::Break, None)
+                } else {
+                    msg.styled_prepared(self.context.long_words, self.context.nick_width)
+                }
+            })
+            .clone();
+
+        let widget = widgets::msg(
+            &self.context,
+            highlighted,
+            indent,
+            widgets::MsgParts {
+                msg,
+                own: self.own(msg),
+                reply_to_own,
+                nick,
+                content,
+                reply_to,
+                translation: self.translations.get(&msg_id),
+                delivery: self.sent.contains(&msg_id).then_some(Delivery::Sent),
+                folded_info,
+            },
+        );
         let widget = Self::predraw(widget, self.context.size, self.widthdb);
         Block::new(TreeBlockId::Msg(msg_id), widget, true)
     }
@@ -208,11 +304,29 @@ where
         };
         let highlighted = highlighted && self.context.focused;
 
-        let widget = widgets::msg_placeholder(highlighted, indent, folded_info);
+        let widget = widgets::msg_placeholder(&self.context, highlighted, indent, folded_info);
         let widget = Self::predraw(widget, self.context.size, self.widthdb);
         Block::new(TreeBlockId::Msg(msg_id.clone()), widget, true)
     }
 
+    fn unloaded_block(
+        &mut self,
+        tree: &Tree<M>,
+        msg_id: &M::Id,
+        amount: usize,
+    ) -> TreeBlock<M::Id> {
+        let mut indent = 0;
+        let mut id = msg_id.clone();
+        while let Some(parent_id) = tree.parent(&id) {
+            indent += 1;
+            id = parent_id;
+        }
+
+        let widget = widgets::unloaded(&self.context, indent, amount);
+        let widget = Self::predraw(widget, self.context.size, self.widthdb);
+        Block::new(TreeBlockId::Unloaded(msg_id.clone()), widget, false)
+    }
+
     fn layout_bottom(&mut self) -> TreeBlocks<M::Id> {
         let mut blocks = Blocks::new(0);
 
@@ -225,39 +339,103 @@ where
         blocks
     }
 
-    fn layout_subtree(
+    /// Ids of the messages of a tree in the order they are laid out in. The
+    /// children of folded messages are not laid out.
+    fn layout_order(&self, tree: &Tree<M>) -> Vec<M::Id> {
+        let mut order = vec![];
+        let mut stack = vec![tree.root().clone()];
+        while let Some(id) = stack.pop() {
+            if !self.folded.contains(&id) {
+                if let Some(children) = tree.children(&id) {
+                    stack.extend(children.iter().rev().cloned());
+                }
+            }
+            order.push(id);
+        }
+        order
+    }
+
+    /// Amount of messages below a message that are laid out, see
+    /// [`Self::layout_order`].
+    fn laid_out_subtree_size(&self, tree: &Tree<M>, msg_id: &M::Id) -> usize {
+        let mut result = 0;
+        let mut stack = vec![msg_id];
+        while let Some(id) = stack.pop() {
+            if self.folded.contains(id) {
+                continue;
+            }
+            let children = tree.children(id).unwrap_or_default();
+            result += children.len();
+            stack.extend(children);
+        }
+        result
+    }
+
+    /// The range of indices into the [`Self::layout_order`] of a tree whose
+    /// messages are laid out. Messages outside this range are replaced by an
+    /// [`TreeBlockId::Unloaded`] block and only laid out once the cursor gets
+    /// closer to them.
+    fn layout_window(
+        &self,
+        tree: &Tree<M>,
+        order: &[M::Id],
+        anchor: Anchor<'_, M::Id>,
+    ) -> (usize, usize) {
+        let len = order.len();
+        let Some(scrollback) = self.context.scrollback.map(|s| s.max(1)) else {
+            return (0, len);
+        };
+        if len <= scrollback {
+            return (0, len);
+        }
+
+        let position = |id: &M::Id| order.iter().position(|o| o == id).unwrap_or(0);
+        let center = match anchor {
+            Anchor::Top => 0,
+            Anchor::Bottom => len,
+            Anchor::Block(TreeBlockId::Msg(id)) => position(id),
+            // The block after a message is only laid out if the last message
+            // of its subtree is.
+            Anchor::Block(TreeBlockId::After(id)) => {
+                position(id) + self.laid_out_subtree_size(tree, id)
+            }
+            Anchor::Block(TreeBlockId::Bottom | TreeBlockId::Unloaded(_)) => 0,
+        };
+
+        let start = center.saturating_sub(scrollback / 2).min(len - scrollback);
+        (start, start + scrollback)
+    }
+
+    fn layout_message(
         &mut self,
         tree: &Tree<M>,
         indent: usize,
         msg_id: &M::Id,
-        blocks: &mut TreeBlocks<M::Id>,
-    ) {
-        let folded = self.folded.contains(msg_id);
+        folded: bool,
+    ) -> TreeBlock<M::Id> {
         let folded_info = if folded {
             Some(tree.subtree_size(msg_id)).filter(|s| *s > 0)
         } else {
             None
         };
 
-        // Message itself
-        let block = if let Some(msg) = tree.msg(msg_id) {
-            self.message_block(indent, msg, folded_info)
+        if let Some(msg) = tree.msg(msg_id) {
+            let parent = tree.parent(msg_id).and_then(|parent| tree.msg(&parent));
+            let reply_to_own = !self.own(msg) && parent.is_some_and(|parent| self.own(parent));
+            let reply_to = if self.context.screen_reader {
+                parent.map(|parent| parent.styled().0)
+            } else {
+                None
+            };
+            self.message_block(indent, msg, reply_to, reply_to_own, folded_info)
         } else {
             self.message_placeholder_block(indent, msg_id, folded_info)
-        };
-        blocks.push_bottom(block);
-
-        // Children, recursively
-        if !folded {
-            if let Some(children) = tree.children(msg_id) {
-                for child in children {
-                    self.layout_subtree(tree, indent + 1, child, blocks);
-                }
-            }
         }
+    }
 
-        // After message (zero-height block, editor, or placeholder)
-        let block = match self.cursor {
+    /// Zero-height block, editor, or placeholder after a message's children.
+    fn layout_after_message(&mut self, indent: usize, msg_id: &M::Id) -> TreeBlock<M::Id> {
+        match self.cursor {
             Cursor::Editor {
                 parent: Some(id), ..
             } if id == msg_id => self.editor_block(indent + 1, Some(msg_id)),
@@ -267,13 +445,66 @@ where
             } if id == msg_id => self.pseudo_block(indent + 1, Some(msg_id)),
 
             _ => self.zero_height_block(Some(msg_id)),
-        };
-        blocks.push_bottom(block);
+        }
+    }
+
+    fn layout_subtree(
+        &mut self,
+        tree: &Tree<M>,
+        indent: usize,
+        msg_id: &M::Id,
+        window: (usize, usize),
+        blocks: &mut TreeBlocks<M::Id>,
+    ) {
+        let in_window = |index: usize| window.0 <= index && index < window.1;
+        // Index of the next message in the layout order
+        let mut index = 0;
+
+        // Iterative so that very deep threads can't overflow the stack. The
+        // flag says whether the message's children were already laid out.
+        let mut stack = vec![(msg_id.clone(), indent, false)];
+        while let Some((id, indent, children_done)) = stack.pop() {
+            if children_done {
+                // The block after the message belongs to the last message of
+                // its subtree, which may be the message itself.
+                if in_window(index - 1) {
+                    let block = self.layout_after_message(indent, &id);
+                    blocks.push_bottom(block);
+                }
+                continue;
+            }
+
+            let folded = self.folded.contains(&id);
+            if in_window(index) {
+                let block = self.layout_message(tree, indent, &id, folded);
+                blocks.push_bottom(block);
+            }
+            index += 1;
+
+            stack.push((id.clone(), indent, true));
+            if !folded {
+                if let Some(children) = tree.children(&id) {
+                    for child in children.iter().rev() {
+                        stack.push((child.clone(), indent + 1, false));
+                    }
+                }
+            }
+        }
     }
 
-    fn layout_tree(&mut self, tree: Tree<M>) -> TreeBlocks<M::Id> {
+    fn layout_tree(&mut self, tree: Tree<M>, anchor: Anchor<'_, M::Id>) -> TreeBlocks<M::Id> {
+        let order = self.layout_order(&tree);
+        let window = self.layout_window(&tree, &order, anchor);
+
         let mut blocks = Blocks::new(0);
-        self.layout_subtree(&tree, 0, tree.root(), &mut blocks);
+        if window.0 > 0 {
+            blocks.push_bottom(self.unloaded_block(&tree, &order[0], window.0));
+        }
+        self.layout_subtree(&tree, 0, tree.root(), window, &mut blocks);
+        if window.1 < order.len() {
+            let amount = order.len() - window.1;
+            blocks.push_bottom(self.unloaded_block(&tree, &order[window.1], amount));
+        }
         blocks
     }
 
@@ -297,7 +528,7 @@ where
         self.bottom_root_id = root_id.clone();
 
         let blocks = if let Some(root_id) = root_id {
-            let tree = self.store.tree(root_id).await?;
+            let tree = self.store.tree(root_id, self.context.sibling_order).await?;
 
             // To ensure the cursor block will be rendered, all its parents must
             // be unfolded.
@@ -309,7 +540,7 @@ where
                 }
             }
 
-            self.layout_tree(tree)
+            self.layout_tree(tree, Anchor::Block(cursor_id))
         } else {
             self.layout_bottom()
         };
@@ -407,6 +638,7 @@ where
         last_cursor: &mut Cursor<M::Id>,
         last_cursor_top: &mut i32,
         last_visible_msgs: &mut Vec<M::Id>,
+        last_visible_unseen: &mut Vec<M::Id>,
     ) {
         *last_cursor = self.cursor.clone();
 
@@ -421,7 +653,12 @@ where
             .filter(|(range, _)| overlaps(area, *range))
             .filter_map(|(_, block)| block.id().msg_id())
             .cloned()
-            .collect()
+            .collect();
+        *last_visible_unseen = last_visible_msgs
+            .iter()
+            .filter(|id| self.unseen.contains(*id))
+            .cloned()
+            .collect();
     }
 
     pub fn into_visible_blocks(
@@ -449,7 +686,7 @@ where
     }
 
     fn scrolloff(&self) -> i32 {
-        2 // TODO Make configurable
+        self.context.scrolloff.into()
     }
 
     fn blocks(&self) -> &TreeBlocks<M::Id> {
@@ -472,8 +709,11 @@ where
         };
 
         if let Some(prev_root_id) = prev_root_id {
-            let tree = self.store.tree(&prev_root_id).await?;
-            let blocks = self.layout_tree(tree);
+            let tree = self
+                .store
+                .tree(&prev_root_id, self.context.sibling_order)
+                .await?;
+            let blocks = self.layout_tree(tree, Anchor::Bottom);
             self.blocks.append_top(blocks);
             self.top_root_id = Some(prev_root_id);
         } else {
@@ -491,8 +731,11 @@ where
 
         let next_root_id = self.store.next_root_id(bottom_root_id).await?;
         if let Some(next_root_id) = next_root_id {
-            let tree = self.store.tree(&next_root_id).await?;
-            let blocks = self.layout_tree(tree);
+            let tree = self
+                .store
+                .tree(&next_root_id, self.context.sibling_order)
+                .await?;
+            let blocks = self.layout_tree(tree, Anchor::Top);
             self.blocks.append_bottom(blocks);
             self.bottom_root_id = Some(next_root_id);
         } else {
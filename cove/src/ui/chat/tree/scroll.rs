@@ -15,12 +15,27 @@ where
     S: MsgStore<M> + Send + Sync,
     S::Error: Send,
 {
-    fn last_context(&self) -> TreeContext<M::Id> {
+    pub(super) fn last_context(&self) -> TreeContext<M::Id> {
         TreeContext {
             size: self.last_size,
             nick: self.last_nick.clone(),
+            user_id: self.last_user_id.clone(),
             focused: true,
             caesar: 0,
+            composer: self.config.composer,
+            zen: self.last_zen,
+            seen_marker: &self.config.seen_marker,
+            long_words: self.config.long_words,
+            nick_width: self.config.nick_width,
+            own_messages: self.config.own_messages,
+            scrolloff: self.config.scrolloff,
+            theme_mode: self.config.theme_mode,
+            theme: &self.config.theme,
+            screen_reader: self.config.screen_reader,
+            scrollback: self.config.scrollback,
+            sibling_order: self.config.sibling_order,
+            send_failed: self.send_failed,
+            send_cooldown: self.send_cooldown,
             last_cursor: self.last_cursor.clone(),
             last_cursor_top: self.last_cursor_top,
         }
@@ -38,6 +53,10 @@ where
             context,
             &self.store,
             &mut self.folded,
+            &self.translations,
+            &self.sent,
+            &self.expanded,
+            &mut self.styled,
             cursor,
             editor,
             widthdb,
@@ -50,6 +69,7 @@ where
             &mut self.last_cursor,
             &mut self.last_cursor_top,
             &mut self.last_visible_msgs,
+            &mut self.last_visible_unseen,
         );
         Ok(())
     }
@@ -65,6 +85,10 @@ where
             context,
             &self.store,
             &mut self.folded,
+            &self.translations,
+            &self.sent,
+            &self.expanded,
+            &mut self.styled,
             cursor,
             editor,
             widthdb,
@@ -77,6 +101,7 @@ where
             &mut self.last_cursor,
             &mut self.last_cursor_top,
             &mut self.last_visible_msgs,
+            &mut self.last_visible_unseen,
         );
         Ok(())
     }
@@ -0,0 +1,320 @@
+//! Headless snapshots of the tree view.
+//!
+//! The [`Harness`] lays out a [`MemoryStore`] the same way
+//! [`super::TreeView`] does when drawing and turns the visible blocks into one
+//! line of text each, so cursor movement, folding and scrolling can be tested
+//! without a terminal.
+
+use cove_config::Config;
+use time::OffsetDateTime;
+use toss::widgets::EditorState;
+use toss::{Size, Styled, WidthDb};
+
+use crate::store::{MemoryMsg, MemoryStore, Msg, MsgStore};
+use crate::ui::chat::cursor::Cursor;
+use crate::ui::ChatMsg;
+use crate::util::InfallibleExt;
+
+use super::renderer::{TreeBlockId, TreeRenderer};
+use super::TreeViewState;
+
+#[derive(Debug, Clone)]
+struct TestMsg {
+    id: u64,
+    parent: Option<u64>,
+    nick: &'static str,
+    content: &'static str,
+    seen: bool,
+}
+
+impl Msg for TestMsg {
+    type Id = u64;
+
+    fn id(&self) -> Self::Id {
+        self.id
+    }
+
+    fn parent(&self) -> Option<Self::Id> {
+        self.parent
+    }
+
+    fn seen(&self) -> bool {
+        self.seen
+    }
+
+    fn time(&self) -> Option<OffsetDateTime> {
+        None
+    }
+
+    fn last_possible_id() -> Self::Id {
+        u64::MAX
+    }
+}
+
+impl MemoryMsg for TestMsg {
+    fn set_seen(&mut self, seen: bool) {
+        self.seen = seen;
+    }
+}
+
+impl ChatMsg for TestMsg {
+    fn styled(&self) -> (Styled, Styled) {
+        Self::pseudo(self.nick, self.content)
+    }
+
+    fn edit(nick: &str, content: &str) -> (Styled, Styled) {
+        Self::pseudo(nick, content)
+    }
+
+    fn pseudo(nick: &str, content: &str) -> (Styled, Styled) {
+        (Styled::new_plain(nick), Styled::new_plain(content))
+    }
+}
+
+struct Harness {
+    state: TreeViewState<TestMsg, MemoryStore<TestMsg>>,
+    cursor: Cursor<u64>,
+    editor: EditorState,
+    widthdb: WidthDb,
+    size: Size,
+}
+
+impl Harness {
+    /// Messages are given as `(id, parent, nick, content)`.
+    fn new(
+        config: serde_json::Value,
+        height: u16,
+        msgs: &[(u64, Option<u64>, &'static str, &'static str)],
+    ) -> Self {
+        let config: Config = serde_json::from_value(config).unwrap();
+        let config = Box::leak(Box::new(config));
+
+        let store = MemoryStore::new();
+        for &(id, parent, nick, content) in msgs {
+            store.add(TestMsg {
+                id,
+                parent,
+                nick,
+                content,
+                seen: false,
+            });
+        }
+
+        Self {
+            state: TreeViewState::new(config, store),
+            cursor: Cursor::Bottom,
+            editor: EditorState::new(),
+            widthdb: WidthDb::default(),
+            size: Size::new(80, height),
+        }
+    }
+
+    async fn up(&mut self) {
+        let state = &self.state;
+        let order = state.config.sibling_order;
+        let result = self
+            .cursor
+            .move_up_in_tree(&state.store, order, &state.folded);
+        result.await.infallible();
+    }
+
+    async fn down(&mut self) {
+        let state = &self.state;
+        let order = state.config.sibling_order;
+        let result = self
+            .cursor
+            .move_down_in_tree(&state.store, order, &state.folded);
+        result.await.infallible();
+    }
+
+    fn fold(&mut self, id: u64) {
+        self.state.folded.insert(id);
+    }
+
+    async fn scroll(&mut self, delta: i32) {
+        let state = &mut self.state;
+        let result = state.scroll_by(&mut self.cursor, &mut self.editor, &mut self.widthdb, delta);
+        result.await.infallible();
+    }
+
+    /// Lay out the messages like [`super::TreeView`] does and describe every
+    /// visible block as `<top> <cursor> <indent><message>`. Zero-height
+    /// blocks are left out.
+    async fn render(&mut self) -> Vec<String> {
+        let state = &mut self.state;
+        state.last_size = self.size;
+        state.invalidate_styled();
+
+        let mut renderer = TreeRenderer::new(
+            state.last_context(),
+            &state.store,
+            &mut state.folded,
+            &state.translations,
+            &state.sent,
+            &state.expanded,
+            &mut state.styled,
+            &mut self.cursor,
+            &mut self.editor,
+            &mut self.widthdb,
+        );
+        renderer.prepare_blocks_for_drawing().await.infallible();
+        renderer.update_render_info(
+            &mut state.last_cursor,
+            &mut state.last_cursor_top,
+            &mut state.last_visible_msgs,
+            &mut state.last_visible_unseen,
+        );
+        let blocks = renderer.into_visible_blocks().collect::<Vec<_>>();
+
+        let cursor = TreeBlockId::from_cursor(&self.cursor);
+        let mut lines = vec![];
+        for (range, block) in blocks {
+            if range.top == range.bottom {
+                continue;
+            }
+            let (prefix, id) = match block.id() {
+                TreeBlockId::Msg(id) => ("", id),
+                TreeBlockId::Unloaded(id) => ("(unloaded) ", id),
+                TreeBlockId::Bottom | TreeBlockId::After(_) => continue,
+            };
+
+            let marker = if *block.id() == cursor { '>' } else { ' ' };
+            let depth = state
+                .store
+                .path(id)
+                .await
+                .infallible()
+                .parent_segments()
+                .count();
+            let indent = "  ".repeat(depth);
+            let msg = state.store.msg(id).await.infallible().unwrap();
+            lines.push(format!(
+                "{:>2} {marker} {indent}{prefix}[{}] {}",
+                range.top, msg.nick, msg.content
+            ));
+        }
+        lines
+    }
+}
+
+#[tokio::test]
+async fn cursor_moves_through_threads() {
+    let mut h = Harness::new(
+        serde_json::json!({}),
+        10,
+        &[
+            (1, None, "alice", "hi"),
+            (2, Some(1), "bob", "hello"),
+            (3, None, "carol", "hey"),
+        ],
+    );
+
+    assert_eq!(
+        h.render().await,
+        [" 7   [alice] hi", " 8     [bob] hello", " 9   [carol] hey"]
+    );
+
+    h.up().await;
+    h.up().await;
+    assert_eq!(
+        h.render().await,
+        [" 7   [alice] hi", " 8 >   [bob] hello", " 9   [carol] hey"]
+    );
+
+    h.down().await;
+    h.down().await;
+    assert_eq!(
+        h.render().await,
+        [" 7   [alice] hi", " 8     [bob] hello", " 9   [carol] hey"]
+    );
+}
+
+#[tokio::test]
+async fn folded_children_are_hidden() {
+    let mut h = Harness::new(
+        serde_json::json!({}),
+        10,
+        &[
+            (1, None, "alice", "hi"),
+            (2, Some(1), "bob", "hello"),
+            (3, None, "carol", "hey"),
+        ],
+    );
+
+    h.fold(1);
+    h.up().await;
+    h.up().await;
+    assert_eq!(h.render().await, [" 8 > [alice] hi", " 9   [carol] hey"]);
+}
+
+#[tokio::test]
+async fn scrolling_moves_cursor_into_view() {
+    let mut h = Harness::new(
+        serde_json::json!({}),
+        5,
+        &[
+            (1, None, "a", "1"),
+            (2, None, "b", "2"),
+            (3, None, "c", "3"),
+            (4, None, "d", "4"),
+            (5, None, "e", "5"),
+            (6, None, "f", "6"),
+        ],
+    );
+
+    assert_eq!(
+        h.render().await,
+        [
+            " 0   [b] 2",
+            " 1   [c] 3",
+            " 2   [d] 4",
+            " 3   [e] 5",
+            " 4   [f] 6",
+        ]
+    );
+
+    h.scroll(2).await;
+    assert_eq!(
+        h.render().await,
+        [
+            " 0   [a] 1",
+            " 1   [b] 2",
+            " 2 > [c] 3",
+            " 3   [d] 4",
+            " 4   [e] 5",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn scrollback_limits_laid_out_messages() {
+    let mut h = Harness::new(
+        serde_json::json!({ "scrollback": 2 }),
+        10,
+        &[
+            (1, None, "a", "1"),
+            (2, Some(1), "b", "2"),
+            (3, Some(1), "c", "3"),
+            (4, Some(1), "d", "4"),
+            (5, Some(1), "e", "5"),
+        ],
+    );
+
+    assert_eq!(
+        h.render().await,
+        [" 7   (unloaded) [a] 1", " 8     [d] 4", " 9     [e] 5",]
+    );
+
+    h.up().await;
+    h.up().await;
+    h.up().await;
+    assert_eq!(
+        h.render().await,
+        [
+            " 6   (unloaded) [a] 1",
+            " 7     [b] 2",
+            " 8 >   [c] 3",
+            " 9     (unloaded) [d] 4",
+        ]
+    );
+}
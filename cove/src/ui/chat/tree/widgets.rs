@@ -1,84 +1,226 @@
 use std::convert::Infallible;
 
-use crossterm::style::Stylize;
-use toss::widgets::{Boxed, EditorState, Join2, Join4, Join5, Text};
-use toss::{Style, Styled, WidgetExt};
+use cove_config::{OwnMessages, Theme, ThemeMode};
+use time::OffsetDateTime;
+use toss::widgets::{Boxed, EditorState, Join2, Join4, Join5, Resize, Text};
+use toss::{Style, Styled, Widget, WidgetExt};
 
 use crate::store::Msg;
-use crate::ui::chat::widgets::{Indent, Seen, Time};
+use crate::ui::chat::widgets::{DeliveryIndicator, Indent, Seen, Time};
+use crate::ui::chat::{Delivery, Translation};
 use crate::ui::ChatMsg;
 use crate::util;
 
+use super::renderer::TreeContext;
+
 pub const PLACEHOLDER: &str = "[...]";
 
-pub fn style_placeholder() -> Style {
-    Style::new().dark_grey()
+/// Shown in the seen marker column next to the user's own messages.
+const OWN_MARKER: &str = "▎";
+
+/// Shown in the seen marker column next to direct replies to the user's own
+/// messages, instead of the seen marker.
+const REPLY_MARKER: &str = "↳";
+
+fn style_delivery(theme: &Theme, mode: ThemeMode, delivery: Option<Delivery>) -> Style {
+    match delivery {
+        None => Style::new(),
+        Some(Delivery::Sending) => theme.sending(mode),
+        Some(Delivery::Sent) => theme.sent(mode),
+        Some(Delivery::Failed) => theme.send_failed(mode),
+    }
+}
+
+fn indent<Id>(context: &TreeContext<Id>, level: usize, style: Style) -> Indent {
+    Indent::new(level, style).with_plain(context.screen_reader)
 }
 
-fn style_time(highlighted: bool) -> Style {
-    if highlighted {
-        Style::new().black().on_white()
+fn seen_marker<Id>(context: &TreeContext<Id>, seen: bool) -> Seen {
+    let config = context.seen_marker;
+    if context.zen || config.hide {
+        Seen::hidden()
     } else {
-        Style::new().grey()
+        let style = context.theme.seen_marker(context.theme_mode, config.color);
+        Seen::new(seen, config.glyph_ref(), style)
     }
 }
 
-fn style_indent(highlighted: bool) -> Style {
-    if highlighted {
-        Style::new().black().on_white()
+/// Like [`seen_marker`], but marking replies to the user's own messages and,
+/// if configured, the user's own messages.
+fn msg_seen_marker<Id>(
+    context: &TreeContext<Id>,
+    seen: bool,
+    own: bool,
+    reply_to_own: bool,
+) -> Seen {
+    let config = context.seen_marker;
+    let theme = context.theme;
+    let mode = context.theme_mode;
+    if context.zen || config.hide {
+        Seen::hidden()
+    } else if reply_to_own {
+        let style = if seen {
+            theme.reply_marker(mode)
+        } else {
+            theme.seen_marker(mode, config.color)
+        };
+        Seen::new(false, REPLY_MARKER, style)
+    } else if own && seen && context.own_messages == OwnMessages::Marker {
+        Seen::new(false, OWN_MARKER, theme.own_marker(mode))
     } else {
-        Style::new().dark_grey()
+        seen_marker(context, seen)
     }
 }
 
-fn style_caesar() -> Style {
-    Style::new().green()
+fn restyle_nick(nick: Styled, style: Option<Style>) -> Styled {
+    match style {
+        Some(style) => Styled::new(nick.text().to_string(), style),
+        None => nick,
+    }
+}
+
+/// Remove the colors from a nick in mono mode.
+fn uncolored_nick<Id>(context: &TreeContext<Id>, nick: Styled) -> Styled {
+    restyle_nick(nick, context.theme.plain_nick(context.theme_mode))
+}
+
+/// Restyle the user's own nick according to the `theme.own_nick` option.
+fn own_nick<Id>(context: &TreeContext<Id>, nick: Styled) -> Styled {
+    restyle_nick(nick, context.theme.own_nick_in(context.theme_mode))
+}
+
+/// Restyle other people's nicks according to the `theme.other_nicks` option.
+fn other_nick<Id>(context: &TreeContext<Id>, nick: Styled) -> Styled {
+    restyle_nick(nick, context.theme.other_nick_in(context.theme_mode))
 }
 
-fn style_info() -> Style {
-    Style::new().italic().dark_grey()
+fn time_column<Id>(context: &TreeContext<Id>, time: Option<OffsetDateTime>, style: Style) -> Time {
+    if context.zen {
+        Time::hidden()
+    } else {
+        Time::new(time, style)
+    }
 }
 
-fn style_editor_highlight() -> Style {
-    Style::new().black().on_cyan()
+/// The time column, followed by the delivery state of the user's own messages
+/// in the single column separating the time from the message.
+fn time_and_delivery<Id>(
+    context: &TreeContext<Id>,
+    time: Option<OffsetDateTime>,
+    style: Style,
+    delivery: Option<Delivery>,
+) -> impl Widget<Infallible> {
+    let theme = context.theme;
+    let mode = context.theme_mode;
+    let delivery = DeliveryIndicator::new(delivery, style_delivery(theme, mode, delivery));
+    Join2::horizontal(
+        time_column(context, time, style)
+            .padding()
+            .with_stretch(true)
+            .segment()
+            .with_fixed(true),
+        Resize::new(delivery)
+            .with_min_width(1)
+            .with_max_width(1)
+            .background()
+            .with_style(style)
+            .segment()
+            .with_fixed(true),
+    )
 }
 
-fn style_pseudo_highlight() -> Style {
-    Style::new().black().on_yellow()
+/// Everything about a single message that [`msg`] renders.
+pub struct MsgParts<'a, M> {
+    pub msg: &'a M,
+    pub own: bool,
+    pub reply_to_own: bool,
+    pub nick: Styled,
+    pub content: Styled,
+    pub reply_to: Option<Styled>,
+    pub translation: Option<&'a Translation>,
+    pub delivery: Option<Delivery>,
+    pub folded_info: Option<usize>,
 }
 
 pub fn msg<M: Msg + ChatMsg>(
+    context: &TreeContext<M::Id>,
     highlighted: bool,
-    indent: usize,
-    msg: &M,
-    caesar: i8,
-    folded_info: Option<usize>,
+    indent_level: usize,
+    parts: MsgParts<'_, M>,
 ) -> Boxed<'static, Infallible> {
-    let (nick, mut content) = msg.styled();
+    let MsgParts {
+        msg,
+        own,
+        reply_to_own,
+        nick,
+        mut content,
+        reply_to,
+        translation,
+        delivery,
+        folded_info,
+    } = parts;
+
+    let theme = context.theme;
+    let mode = context.theme_mode;
+    let caesar = context.caesar;
+
+    let nick = if own {
+        own_nick(context, nick)
+    } else {
+        other_nick(context, nick)
+    };
+
+    if let Some(parent_nick) = reply_to {
+        content = Styled::new("reply to ", theme.msg_info(mode))
+            .and_then(uncolored_nick(context, parent_nick))
+            .then_plain(": ")
+            .and_then(content);
+    }
 
     if caesar != 0 {
         // Apply caesar in inverse because we're decoding
         let rotated = util::caesar(content.text(), -caesar);
         content = content
             .then_plain("\n")
-            .then(format!("{rotated} [rot{caesar}]"), style_caesar());
+            .then(format!("{rotated} [rot{caesar}]"), theme.decoded(mode));
+    }
+
+    if let Some(translation) = translation {
+        content = content.then_plain("\n");
+        content = match translation {
+            Translation::Pending => content.then("[translating...]", theme.msg_info(mode)),
+            Translation::Done(text) => {
+                content.then(format!("{text} [translated]"), theme.decoded(mode))
+            }
+            Translation::Failed => content.then("[translation failed]", theme.msg_info(mode)),
+        };
     }
 
     if let Some(amount) = folded_info {
         content = content
             .then_plain("\n")
-            .then(format!("[{amount} more]"), style_info());
+            .then(format!("[{amount} more]"), theme.msg_info(mode));
     }
 
+    let background = if own && context.own_messages == OwnMessages::Background {
+        theme.own_background(mode)
+    } else {
+        Style::new()
+    };
+
     Join5::horizontal(
-        Seen::new(msg.seen()).segment().with_fixed(true),
-        Time::new(msg.time(), style_time(highlighted))
-            .padding()
-            .with_right(1)
-            .with_stretch(true)
+        msg_seen_marker(context, msg.seen(), own, reply_to_own)
             .segment()
             .with_fixed(true),
-        Indent::new(indent, style_indent(highlighted))
+        time_and_delivery(
+            context,
+            msg.time(),
+            theme.msg_time(mode, highlighted),
+            delivery,
+        )
+        .segment()
+        .with_fixed(true),
+        indent(context, indent_level, theme.msg_indent(mode, highlighted))
             .segment()
             .with_fixed(true),
         Join2::vertical(
@@ -87,39 +229,72 @@ pub fn msg<M: Msg + ChatMsg>(
                 .with_right(1)
                 .segment()
                 .with_fixed(true),
-            Indent::new(1, style_indent(false)).segment(),
+            indent(context, 1, theme.msg_indent(mode, false)).segment(),
         )
+        .background()
+        .with_style(background)
         .segment()
         .with_fixed(true),
         // TODO Minimum content width
         // TODO Minimizing and maximizing messages
-        Text::new(content).segment(),
+        Text::new(content)
+            .background()
+            .with_style(background)
+            .segment(),
     )
     .boxed()
 }
 
-pub fn msg_placeholder(
+pub fn msg_placeholder<Id>(
+    context: &TreeContext<Id>,
     highlighted: bool,
-    indent: usize,
+    indent_level: usize,
     folded_info: Option<usize>,
 ) -> Boxed<'static, Infallible> {
-    let mut content = Styled::new(PLACEHOLDER, style_placeholder());
+    let theme = context.theme;
+    let mode = context.theme_mode;
+    let mut content = Styled::new(PLACEHOLDER, theme.placeholder(mode));
 
     if let Some(amount) = folded_info {
         content = content
             .then_plain("\n")
-            .then(format!("[{amount} more]"), style_info());
+            .then(format!("[{amount} more]"), theme.msg_info(mode));
     }
 
     Join4::horizontal(
-        Seen::new(true).segment().with_fixed(true),
-        Time::new(None, style_time(highlighted))
+        seen_marker(context, true).segment().with_fixed(true),
+        time_column(context, None, theme.msg_time(mode, highlighted))
+            .padding()
+            .with_right(1)
+            .with_stretch(true)
+            .segment()
+            .with_fixed(true),
+        indent(context, indent_level, theme.msg_indent(mode, highlighted))
+            .segment()
+            .with_fixed(true),
+        Text::new(content).segment(),
+    )
+    .boxed()
+}
+
+pub fn unloaded<Id>(
+    context: &TreeContext<Id>,
+    indent_level: usize,
+    amount: usize,
+) -> Boxed<'static, Infallible> {
+    let theme = context.theme;
+    let mode = context.theme_mode;
+    let content = Styled::new(format!("[{amount} more not shown]"), theme.msg_info(mode));
+
+    Join4::horizontal(
+        seen_marker(context, true).segment().with_fixed(true),
+        Time::new(None, theme.msg_time(mode, false))
             .padding()
             .with_right(1)
             .with_stretch(true)
             .segment()
             .with_fixed(true),
-        Indent::new(indent, style_indent(highlighted))
+        indent(context, indent_level, theme.msg_indent(mode, false))
             .segment()
             .with_fixed(true),
         Text::new(content).segment(),
@@ -127,27 +302,38 @@ pub fn msg_placeholder(
     .boxed()
 }
 
-pub fn editor<'a, M: ChatMsg>(
-    indent: usize,
-    nick: &str,
-    focus: bool,
+pub fn editor<'a, M: Msg + ChatMsg>(
+    context: &TreeContext<M::Id>,
+    indent_level: usize,
     editor: &'a mut EditorState,
 ) -> Boxed<'a, Infallible> {
-    let (nick, content) = M::edit(nick, editor.text());
+    let theme = context.theme;
+    let mode = context.theme_mode;
+    let (nick, content) = M::edit(&context.nick, editor.text());
+    let nick = own_nick(context, nick);
     let editor = editor
         .widget()
         .with_highlight(|_| content)
-        .with_focus(focus);
+        .with_focus(context.focused);
+
+    let style = theme.editor_highlight(mode);
+    let delivery = context.send_failed.then_some(Delivery::Failed);
+    let time = match context.send_cooldown {
+        Some(secs) if !context.zen => Time::countdown(secs, style),
+        _ => time_column(context, None, style),
+    };
 
     Join5::horizontal(
-        Seen::new(true).segment().with_fixed(true),
-        Time::new(None, style_editor_highlight())
-            .padding()
-            .with_right(1)
-            .with_stretch(true)
-            .segment()
-            .with_fixed(true),
-        Indent::new(indent, style_editor_highlight())
+        seen_marker(context, true).segment().with_fixed(true),
+        Join2::horizontal(
+            time.segment().with_fixed(true),
+            DeliveryIndicator::new(delivery, style_delivery(theme, mode, delivery))
+                .segment()
+                .with_fixed(true),
+        )
+        .segment()
+        .with_fixed(true),
+        indent(context, indent_level, theme.editor_highlight(mode))
             .segment()
             .with_fixed(true),
         Join2::vertical(
@@ -156,7 +342,7 @@ pub fn editor<'a, M: ChatMsg>(
                 .with_right(1)
                 .segment()
                 .with_fixed(true),
-            Indent::new(1, style_indent(false)).segment(),
+            indent(context, 1, theme.msg_indent(mode, false)).segment(),
         )
         .segment()
         .with_fixed(true),
@@ -165,22 +351,53 @@ pub fn editor<'a, M: ChatMsg>(
     .boxed()
 }
 
-pub fn pseudo<'a, M: ChatMsg>(
-    indent: usize,
-    nick: &str,
+/// The editor in the composer bar at the bottom of the room, with the message
+/// being replied to shown above it.
+pub fn composer<'a, M: Msg + ChatMsg>(
+    context: &TreeContext<M::Id>,
+    reply_preview: Option<Styled>,
     editor: &'a mut EditorState,
 ) -> Boxed<'a, Infallible> {
-    let (nick, content) = M::edit(nick, editor.text());
+    let editor = self::editor::<M>(context, 0, editor);
+    let Some(preview) = reply_preview else {
+        return editor;
+    };
 
-    Join5::horizontal(
-        Seen::new(true).segment().with_fixed(true),
-        Time::new(None, style_pseudo_highlight())
+    let style = context.theme.reply_preview(context.theme_mode);
+    Join2::vertical(
+        Text::new(preview)
             .padding()
-            .with_right(1)
-            .with_stretch(true)
+            .with_horizontal(1)
+            .background()
+            .with_style(style)
             .segment()
             .with_fixed(true),
-        Indent::new(indent, style_pseudo_highlight())
+        editor.segment(),
+    )
+    .boxed()
+}
+
+pub fn pseudo<'a, M: Msg + ChatMsg>(
+    context: &TreeContext<M::Id>,
+    indent_level: usize,
+    editor: &'a mut EditorState,
+) -> Boxed<'a, Infallible> {
+    let theme = context.theme;
+    let mode = context.theme_mode;
+    let (nick, content) = M::edit(&context.nick, editor.text());
+    let nick = own_nick(context, nick);
+
+    Join5::horizontal(
+        seen_marker(context, true).segment().with_fixed(true),
+        time_and_delivery(
+            context,
+            None,
+            theme.pseudo_highlight(mode),
+            Some(Delivery::Sending),
+        )
+        .segment()
+        .with_fixed(true),
+        indent(context, indent_level, theme.pseudo_highlight(mode))
             .segment()
             .with_fixed(true),
         Join2::vertical(
@@ -189,7 +406,7 @@ pub fn pseudo<'a, M: ChatMsg>(
                 .with_right(1)
                 .segment()
                 .with_fixed(true),
-            Indent::new(1, style_indent(false)).segment(),
+            indent(context, 1, theme.msg_indent(mode, false)).segment(),
         )
         .segment()
         .with_fixed(true),
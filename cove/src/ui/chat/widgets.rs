@@ -1,6 +1,5 @@
 use std::convert::Infallible;
 
-use crossterm::style::Stylize;
 use time::format_description::FormatItem;
 use time::macros::format_description;
 use time::OffsetDateTime;
@@ -9,17 +8,32 @@ use toss::{Frame, Pos, Size, Style, Widget, WidgetExt, WidthDb};
 
 use crate::util::InfallibleExt;
 
+use super::Delivery;
+
 pub const INDENT_STR: &str = "│ ";
+pub const INDENT_STR_PLAIN: &str = "  ";
 pub const INDENT_WIDTH: usize = 2;
 
 pub struct Indent {
     level: usize,
     style: Style,
+    plain: bool,
 }
 
 impl Indent {
     pub fn new(level: usize, style: Style) -> Self {
-        Self { level, style }
+        Self {
+            level,
+            style,
+            plain: false,
+        }
+    }
+
+    /// Draw the indentation using only spaces instead of box drawing
+    /// characters.
+    pub fn with_plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
     }
 }
 
@@ -36,7 +50,12 @@ impl<E> Widget<E> for Indent {
 
     fn draw(self, frame: &mut Frame) -> Result<(), E> {
         let size = frame.size();
-        let indent_string = INDENT_STR.repeat(self.level);
+        let indent_str = if self.plain {
+            INDENT_STR_PLAIN
+        } else {
+            INDENT_STR
+        };
+        let indent_string = indent_str.repeat(self.level);
 
         for y in 0..size.height {
             frame.write(Pos::new(0, y.into()), (&indent_string, self.style))
@@ -68,6 +87,24 @@ impl Time {
         };
         Self(widget)
     }
+
+    /// A time column showing how many seconds are left until something
+    /// happens instead of a time.
+    pub fn countdown(secs: u64, style: Style) -> Self {
+        let width = usize::from(TIME_WIDTH);
+        let text = format!("{:>width$}", format!("wait {secs}s"));
+        Self(
+            Text::new((text, style))
+                .background()
+                .with_style(style)
+                .boxed(),
+        )
+    }
+
+    /// A time column without any width.
+    pub fn hidden() -> Self {
+        Self(Empty::new().boxed())
+    }
 }
 
 impl<E> Widget<E> for Time {
@@ -89,15 +126,22 @@ impl<E> Widget<E> for Time {
 pub struct Seen(Boxed<'static, Infallible>);
 
 impl Seen {
-    pub fn new(seen: bool) -> Self {
+    pub fn new(seen: bool, glyph: &str, style: Style) -> Self {
         let widget = if seen {
             Empty::new().with_width(1).boxed()
         } else {
-            let style = Style::new().black().on_green();
-            Text::new("*").background().with_style(style).boxed()
+            Text::new((glyph.to_string(), style))
+                .background()
+                .with_style(style)
+                .boxed()
         };
         Self(widget)
     }
+
+    /// A seen marker column without any width.
+    pub fn hidden() -> Self {
+        Self(Empty::new().boxed())
+    }
 }
 
 impl<E> Widget<E> for Seen {
@@ -115,3 +159,33 @@ impl<E> Widget<E> for Seen {
         Ok(())
     }
 }
+
+pub struct DeliveryIndicator(Boxed<'static, Infallible>);
+
+impl DeliveryIndicator {
+    pub fn new(delivery: Option<Delivery>, style: Style) -> Self {
+        let glyph = match delivery {
+            None => return Self(Empty::new().with_width(1).boxed()),
+            Some(Delivery::Sending) => "…",
+            Some(Delivery::Sent) => "✓",
+            Some(Delivery::Failed) => "✗",
+        };
+        Self(Text::new((glyph, style)).boxed())
+    }
+}
+
+impl<E> Widget<E> for DeliveryIndicator {
+    fn size(
+        &self,
+        widthdb: &mut WidthDb,
+        max_width: Option<u16>,
+        max_height: Option<u16>,
+    ) -> Result<Size, E> {
+        Ok(self.0.size(widthdb, max_width, max_height).infallible())
+    }
+
+    fn draw(self, frame: &mut Frame) -> Result<(), E> {
+        self.0.draw(frame).infallible();
+        Ok(())
+    }
+}
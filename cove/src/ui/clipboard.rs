@@ -0,0 +1,93 @@
+//! Copying text to the system clipboard.
+
+use std::io::{self, Write};
+use std::process::Stdio;
+
+use anyhow::{bail, Context};
+use cove_config::Config;
+use tokio::io::AsyncWriteExt;
+
+use crate::util;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (n >> (18 - 6 * i)) & 0b11_1111;
+                result.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Ask the terminal to copy the text via the OSC 52 escape sequence.
+fn copy_via_terminal(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
+
+async fn copy_via_command(command: &str, text: &str) -> anyhow::Result<()> {
+    let mut child = util::shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to run clipboard command")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).await?;
+        // Dropping stdin closes it, signalling the end of the input.
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        bail!("clipboard command failed ({status})");
+    }
+
+    Ok(())
+}
+
+/// Copy text to the clipboard, either via the configured `clipboard_command`
+/// or via the terminal.
+pub async fn copy(config: &Config, text: &str) -> anyhow::Result<()> {
+    match &config.clipboard_command {
+        Some(command) => copy_via_command(command, text).await,
+        None => Ok(copy_via_terminal(text)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::base64;
+
+    #[test]
+    fn base64_matches_rfc_4648_test_vectors() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn base64_uses_whole_alphabet() {
+        assert_eq!(base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+        assert_eq!(base64("ä".as_bytes()), "w6Q=");
+    }
+}
@@ -1,8 +1,18 @@
 mod account;
+mod activity;
 mod auth;
+pub mod commands;
+mod confirm_send;
+mod draft;
 mod inspect;
 mod links;
 mod nick;
 mod nick_list;
+mod note;
+mod pinned;
 mod popup;
 pub mod room;
+mod scheduled;
+mod snippets;
+mod stats;
+mod toast;
@@ -1,4 +1,4 @@
-use cove_config::Keys;
+use cove_config::{Keys, Theme};
 use cove_input::InputEvent;
 use crossterm::style::Stylize;
 use euphoxide::api::PersonalAccountView;
@@ -66,7 +66,7 @@ impl LoggedOut {
 pub struct LoggedIn(PersonalAccountView);
 
 impl LoggedIn {
-    fn widget(&self) -> impl Widget<UiError> {
+    fn widget(&self, theme: &Theme) -> impl Widget<UiError> {
         let bold = Style::new().bold();
         Join5::vertical(
             Text::new(("Logged in", bold.green())).segment(),
@@ -80,7 +80,7 @@ impl LoggedIn {
             )
             .segment(),
             Empty::new().with_height(1).segment(),
-            Text::new(("Log out", Style::new().black().on_white())).segment(),
+            Text::new(("Log out", theme.cursor())).segment(),
         )
     }
 }
@@ -109,15 +109,15 @@ impl AccountUiState {
         }
     }
 
-    pub fn widget(&mut self) -> impl Widget<UiError> + '_ {
+    pub fn widget(&mut self, theme: &Theme) -> impl Widget<UiError> + '_ {
         let inner = match self {
             Self::LoggedOut(logged_out) => logged_out.widget().first2(),
-            Self::LoggedIn(logged_in) => logged_in.widget().second2(),
+            Self::LoggedIn(logged_in) => logged_in.widget(theme).second2(),
         }
         .resize()
         .with_min_width(40);
 
-        Popup::new(inner, "Account")
+        Popup::new(theme, inner, "Account")
     }
 
     pub fn handle_input_event(
@@ -0,0 +1,161 @@
+//! Popup showing on which days a room was active.
+
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use cove_vault::RoomActivity;
+use crossterm::style::Stylize;
+use euphoxide::api::Time;
+use time::OffsetDateTime;
+use toss::widgets::Text;
+use toss::{Style, Styled, Widget};
+
+use crate::ui::widgets::Popup;
+use crate::ui::UiError;
+
+use super::popup::PopupResult;
+
+/// Amount of weeks shown, one column per week.
+const WEEKS: usize = 26;
+
+const SECONDS_PER_DAY: i64 = 60 * 60 * 24;
+
+const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+
+/// Abbreviated names of the days of the week, starting with monday.
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Names of the months, starting with january.
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Days since the unix epoch.
+fn day_of(time: OffsetDateTime) -> i64 {
+    time.unix_timestamp().div_euclid(SECONDS_PER_DAY)
+}
+
+/// Index of the day in the week, starting with monday. The unix epoch was a
+/// thursday.
+fn weekday_of(day: i64) -> usize {
+    (day + 3).rem_euclid(7) as usize
+}
+
+fn format_day(day: i64) -> String {
+    let date = OffsetDateTime::from_unix_timestamp(day * SECONDS_PER_DAY)
+        .expect("timestamp in range")
+        .date();
+    let weekday = WEEKDAYS[weekday_of(day)];
+    let month = MONTHS[usize::from(u8::from(date.month())) - 1];
+    format!("{weekday} {month} {}, {}", date.day(), date.year())
+}
+
+pub struct ActivityState {
+    /// First day shown, always a monday.
+    start: i64,
+    today: i64,
+    msgs: [[u64; WEEKS]; 7],
+}
+
+impl ActivityState {
+    /// Start of the oldest day that is shown.
+    pub fn start() -> Time {
+        let today = day_of(OffsetDateTime::now_utc());
+        let start = today - weekday_of(today) as i64 - 7 * (WEEKS as i64 - 1);
+        let start = OffsetDateTime::from_unix_timestamp(start * SECONDS_PER_DAY)
+            .expect("timestamp in range");
+        Time(start)
+    }
+
+    pub fn new(start: Time, activity: &[RoomActivity]) -> Self {
+        let start = day_of(start.0);
+        let mut msgs = [[0; WEEKS]; 7];
+        for entry in activity {
+            let day = day_of(entry.day.0);
+            let week = usize::try_from((day - start) / 7)
+                .ok()
+                .filter(|w| *w < WEEKS);
+            if let Some(week) = week {
+                msgs[weekday_of(day)][week] += entry.msgs;
+            }
+        }
+
+        Self {
+            start,
+            today: day_of(OffsetDateTime::now_utc()),
+            msgs,
+        }
+    }
+
+    fn day(&self, weekday: usize, week: usize) -> i64 {
+        self.start + 7 * week as i64 + weekday as i64
+    }
+
+    fn busiest_day(&self) -> Option<(i64, u64)> {
+        (0..7)
+            .flat_map(|weekday| (0..WEEKS).map(move |week| (weekday, week)))
+            .map(|(weekday, week)| (self.day(weekday, week), self.msgs[weekday][week]))
+            .filter(|(_, msgs)| *msgs > 0)
+            .max_by_key(|(_, msgs)| *msgs)
+    }
+
+    pub fn widget(&self, theme: &Theme) -> impl Widget<UiError> {
+        let label_style = Style::new().cyan();
+        let empty_style = theme.info();
+        let shade_style = Style::new().green();
+        let max = self.msgs.iter().flatten().copied().max().unwrap_or(0);
+
+        let mut text = Styled::new_plain(format!(
+            "Last {WEEKS} weeks, one column per week, oldest first\n\n"
+        ));
+
+        for (weekday, weeks) in self.msgs.iter().enumerate() {
+            // Only every other day is labelled so the rows are easier to tell
+            // apart.
+            let label = if weekday % 2 == 0 {
+                WEEKDAYS[weekday]
+            } else {
+                ""
+            };
+            text = text.then(format!("{label:3} "), label_style);
+            for (week, &msgs) in weeks.iter().enumerate() {
+                let day = self.day(weekday, week);
+                text = if day > self.today {
+                    text.then_plain(" ")
+                } else if msgs == 0 {
+                    text.then("·", empty_style)
+                } else {
+                    let shade = (msgs * SHADES.len() as u64 - 1) / max;
+                    text.then(SHADES[shade as usize].to_string(), shade_style)
+                };
+            }
+            text = text.then_plain("\n");
+        }
+
+        let total = self.msgs.iter().flatten().sum::<u64>();
+        text = text.then_plain(format!("\n{total} messages total"));
+        if let Some((day, msgs)) = self.busiest_day() {
+            text = text.then_plain(format!(", most on {} ({msgs})", format_day(day)));
+        }
+
+        Popup::new(theme, Text::new(text), "Room activity")
+    }
+}
+
+pub fn handle_input_event(event: &mut InputEvent<'_>, keys: &Keys) -> PopupResult {
+    if event.matches(&keys.general.abort) {
+        return PopupResult::Close;
+    }
+
+    PopupResult::NotHandled
+}
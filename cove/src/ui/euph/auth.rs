@@ -1,4 +1,4 @@
-use cove_config::Keys;
+use cove_config::{Keys, Theme};
 use cove_input::InputEvent;
 use toss::widgets::EditorState;
 use toss::Widget;
@@ -13,8 +13,9 @@ pub fn new() -> EditorState {
     EditorState::new()
 }
 
-pub fn widget(editor: &mut EditorState) -> impl Widget<UiError> + '_ {
+pub fn widget<'a>(theme: &Theme, editor: &'a mut EditorState) -> impl Widget<UiError> + 'a {
     Popup::new(
+        theme,
         editor.widget().with_hidden_default_placeholder(),
         "Enter password",
     )
@@ -0,0 +1,206 @@
+//! Client-side commands typed into the message editor, like `/nick`.
+
+use time::Duration;
+
+/// All commands, used for completion.
+const COMMANDS: [&str; 9] = [
+    "/broadcast",
+    "/connect",
+    "/disconnect",
+    "/ignore",
+    "/in",
+    "/me",
+    "/nick",
+    "/search",
+    "/unignore",
+];
+
+pub enum Command {
+    Nick(String),
+    Connect,
+    Disconnect,
+    Search(String),
+    /// Hide a nick or user id from the nick list and people summary.
+    Ignore(String),
+    Unignore(String),
+    /// Send a message once the delay has passed.
+    Schedule {
+        delay: Duration,
+        content: String,
+    },
+    /// Send a message to several rooms, each given as `room` or `room@domain`.
+    Broadcast {
+        rooms: Vec<String>,
+        content: String,
+    },
+}
+
+pub enum Parsed {
+    /// Send the message to the room.
+    Send(String),
+    Command(Command),
+    Error(String),
+}
+
+/// Interpret a composed message.
+///
+/// `/me` messages are sent as-is since clients display them specially. To send
+/// a message starting with `/`, start it with `//` instead.
+pub fn parse(content: String) -> Parsed {
+    if !content.starts_with('/') {
+        return Parsed::Send(content);
+    }
+    if let Some(content) = content.strip_prefix('/').filter(|c| c.starts_with('/')) {
+        return Parsed::Send(content.to_string());
+    }
+
+    let (name, args) = content
+        .split_once(char::is_whitespace)
+        .unwrap_or((&content, ""));
+    let args = args.trim();
+
+    let command = match name {
+        "/me" => return Parsed::Send(content),
+        "/nick" if args.is_empty() => return Parsed::Error("Usage: /nick <nick>".to_string()),
+        "/nick" => Command::Nick(args.to_string()),
+        "/connect" => Command::Connect,
+        "/disconnect" => Command::Disconnect,
+        "/search" => Command::Search(args.to_string()),
+        "/ignore" if args.is_empty() => {
+            return Parsed::Error("Usage: /ignore <nick or user id>".to_string())
+        }
+        "/ignore" => Command::Ignore(args.to_string()),
+        "/unignore" if args.is_empty() => {
+            return Parsed::Error("Usage: /unignore <nick or user id>".to_string())
+        }
+        "/unignore" => Command::Unignore(args.to_string()),
+        "/in" => match parse_schedule(args) {
+            Some((delay, content)) => Command::Schedule { delay, content },
+            None => {
+                let usage = "Usage: /in <delay> <message>, e.g. /in 1h30m hello";
+                return Parsed::Error(usage.to_string());
+            }
+        },
+        "/broadcast" => match parse_broadcast(args) {
+            Some((rooms, content)) => Command::Broadcast { rooms, content },
+            None => {
+                let usage = "Usage: /broadcast <&room>... <message>, e.g. /broadcast &a &b hello";
+                return Parsed::Error(usage.to_string());
+            }
+        },
+        _ => {
+            return Parsed::Error(format!(
+                "Unknown command {name}. Start the message with // to send it as-is."
+            ))
+        }
+    };
+    Parsed::Command(command)
+}
+
+/// Parse a delay like `90s`, `10m` or `1d2h` followed by a message.
+fn parse_schedule(args: &str) -> Option<(Duration, String)> {
+    let (delay, content) = args.split_once(char::is_whitespace)?;
+    let content = content.trim();
+    if content.is_empty() {
+        return None;
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = delay;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let amount = rest[..digits].parse::<i64>().ok()?;
+        let unit = match rest[digits..].chars().next()? {
+            's' => Duration::seconds(1),
+            'm' => Duration::minutes(1),
+            'h' => Duration::hours(1),
+            'd' => Duration::days(1),
+            _ => return None,
+        };
+        total += unit.checked_mul(i32::try_from(amount).ok()?)?;
+        rest = &rest[digits + 1..];
+    }
+
+    if total.is_zero() {
+        return None;
+    }
+    Some((total, content.to_string()))
+}
+
+/// Parse a list of rooms like `&a &b@example.com` followed by a message.
+fn parse_broadcast(args: &str) -> Option<(Vec<String>, String)> {
+    let mut rooms = vec![];
+    let mut rest = args;
+    while let Some(after) = rest.strip_prefix('&') {
+        let (room, after) = after.split_once(char::is_whitespace)?;
+        if room.is_empty() {
+            return None;
+        }
+        rooms.push(room.to_string());
+        rest = after.trim_start();
+    }
+
+    if rooms.is_empty() || rest.is_empty() {
+        return None;
+    }
+    Some((rooms, rest.to_string()))
+}
+
+/// Complete a partially typed command name, if it is unambiguous.
+pub fn complete(text: &str) -> Option<String> {
+    if !text.starts_with('/') || text.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let mut candidates = COMMANDS.iter().filter(|c| c.starts_with(text));
+    match (candidates.next(), candidates.next()) {
+        (Some(command), None) => Some(format!("{command} ")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, parse_broadcast, Command, Parsed};
+
+    #[test]
+    fn rooms_then_message() {
+        let (rooms, content) = parse_broadcast("&a &b@example.com hello there").unwrap();
+        assert_eq!(rooms, ["a", "b@example.com"]);
+        assert_eq!(content, "hello there");
+    }
+
+    #[test]
+    fn message_keeps_later_rooms_and_newlines() {
+        let (rooms, content) = parse_broadcast("&a  see &b\nbye").unwrap();
+        assert_eq!(rooms, ["a"]);
+        assert_eq!(content, "see &b\nbye");
+    }
+
+    #[test]
+    fn rooms_separated_by_newline() {
+        let (rooms, content) = parse_broadcast("&a\n&b hi").unwrap();
+        assert_eq!(rooms, ["a", "b"]);
+        assert_eq!(content, "hi");
+    }
+
+    #[test]
+    fn requires_rooms_and_message() {
+        assert_eq!(parse_broadcast("hello"), None);
+        assert_eq!(parse_broadcast("&a"), None);
+        assert_eq!(parse_broadcast("&a &b"), None);
+        assert_eq!(parse_broadcast(""), None);
+    }
+
+    #[test]
+    fn rejects_empty_room_name() {
+        assert_eq!(parse_broadcast("& hello"), None);
+    }
+
+    #[test]
+    fn ignore_takes_nick_or_user_id() {
+        let parsed = parse("/ignore  agent:abc ".to_string());
+        assert!(matches!(parsed, Parsed::Command(Command::Ignore(who)) if who == "agent:abc"));
+        assert!(matches!(parse("/unignore".to_string()), Parsed::Error(_)));
+    }
+}
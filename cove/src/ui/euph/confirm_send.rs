@@ -0,0 +1,55 @@
+//! Popup asking for confirmation before sending a message in rooms with the
+//! `confirm_send` config option.
+
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use euphoxide::api::MessageId;
+use toss::widgets::Text;
+use toss::{Styled, Widget};
+
+use crate::euph::SmallMessage;
+use crate::ui::widgets::Popup;
+use crate::ui::{key_bindings, ChatMsg, UiError};
+
+use super::popup::PopupResult;
+
+pub struct ConfirmSendState {
+    parent: Option<MessageId>,
+    content: String,
+}
+
+impl ConfirmSendState {
+    pub fn new(parent: Option<MessageId>, content: String) -> Self {
+        Self { parent, content }
+    }
+
+    pub fn widget(&self, theme: &Theme, keys: &Keys, nick: &str) -> impl Widget<UiError> {
+        let (nick, content) = SmallMessage::pseudo(nick, &self.content);
+        let text = Styled::new_plain("[")
+            .and_then(nick)
+            .then_plain("] ")
+            .and_then(content)
+            .then_plain("\n\nPress ")
+            .and_then(key_bindings::format_binding(&keys.general.confirm))
+            .then_plain(" to send this message or ")
+            .and_then(key_bindings::format_binding(&keys.general.abort))
+            .then_plain(" to continue editing it.");
+
+        Popup::new(theme, Text::new(text), "Send message?")
+    }
+
+    pub fn handle_input_event(&self, event: &mut InputEvent<'_>, keys: &Keys) -> PopupResult {
+        if event.matches(&keys.general.confirm) {
+            return PopupResult::Send {
+                parent: self.parent,
+                content: self.content.clone(),
+            };
+        }
+
+        if event.matches(&keys.general.abort) {
+            return PopupResult::CancelSend;
+        }
+
+        PopupResult::NotHandled
+    }
+}
@@ -0,0 +1,36 @@
+//! Popup offering to restore a message that was left unsent when cove last
+//! exited.
+
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use toss::widgets::Text;
+use toss::{Styled, Widget};
+
+use crate::ui::widgets::Popup;
+use crate::ui::{key_bindings, UiError};
+
+use super::popup::PopupResult;
+
+pub fn widget(theme: &Theme, keys: &Keys, draft: &str) -> impl Widget<UiError> {
+    let text = Styled::new_plain("This message was not sent before cove exited:\n\n")
+        .then(draft, theme.info())
+        .then_plain("\n\nPress ")
+        .and_then(key_bindings::format_binding(&keys.general.confirm))
+        .then_plain(" to continue editing it or ")
+        .and_then(key_bindings::format_binding(&keys.general.abort))
+        .then_plain(" to discard it.");
+
+    Popup::new(theme, Text::new(text), "Restore message")
+}
+
+pub fn handle_input_event(event: &mut InputEvent<'_>, keys: &Keys, draft: &str) -> PopupResult {
+    if event.matches(&keys.general.confirm) {
+        return PopupResult::RestoreDraft(draft.to_string());
+    }
+
+    if event.matches(&keys.general.abort) {
+        return PopupResult::DiscardDraft;
+    }
+
+    PopupResult::NotHandled
+}
@@ -1,4 +1,4 @@
-use cove_config::Keys;
+use cove_config::{Keys, Theme};
 use cove_input::InputEvent;
 use crossterm::style::Stylize;
 use euphoxide::api::{Message, NickEvent, SessionView};
@@ -7,7 +7,7 @@ use toss::widgets::Text;
 use toss::{Style, Styled, Widget};
 
 use crate::ui::widgets::Popup;
-use crate::ui::UiError;
+use crate::ui::{key_bindings, UiError};
 
 use super::popup::PopupResult;
 
@@ -22,7 +22,7 @@ macro_rules! line {
             .then($name, Style::new().cyan())
             .then_plain(format!(" {:?}\n", $val));
     };
-    ( $text:ident, $name:expr, $val:expr, optional ) => {
+    ( $text:ident, $name:expr, $val:expr, optional $theme:ident ) => {
         if let Some(val) = $val {
             $text = $text
                 .then($name, Style::new().cyan())
@@ -31,7 +31,7 @@ macro_rules! line {
             $text = $text
                 .then($name, Style::new().cyan())
                 .then_plain(" ")
-                .then("none", Style::new().italic().grey())
+                .then("none", $theme.info().italic())
                 .then_plain("\n");
         }
     };
@@ -44,7 +44,7 @@ macro_rules! line {
     };
 }
 
-fn session_view_lines(mut text: Styled, session: &SessionView) -> Styled {
+fn session_view_lines(theme: &Theme, mut text: Styled, session: &SessionView) -> Styled {
     line!(text, "id", session.id);
     line!(text, "name", session.name);
     line!(text, "name (raw)", session.name, debug);
@@ -57,13 +57,13 @@ fn session_view_lines(mut text: Styled, session: &SessionView) -> Styled {
         text,
         "client_address",
         session.client_address.as_ref(),
-        optional
+        optional theme
     );
     line!(
         text,
         "real_client_address",
         session.real_client_address.as_ref(),
-        optional
+        optional theme
     );
 
     text
@@ -78,26 +78,26 @@ fn nick_event_lines(mut text: Styled, event: &NickEvent) -> Styled {
     text
 }
 
-fn message_lines(mut text: Styled, msg: &Message) -> Styled {
+fn message_lines(theme: &Theme, mut text: Styled, msg: &Message) -> Styled {
     line!(text, "id", msg.id.0);
-    line!(text, "parent", msg.parent.map(|p| p.0), optional);
-    line!(text, "previous_edit_id", msg.previous_edit_id, optional);
+    line!(text, "parent", msg.parent.map(|p| p.0), optional theme);
+    line!(text, "previous_edit_id", msg.previous_edit_id, optional theme);
     line!(text, "time", msg.time.0);
-    line!(text, "encryption_key_id", &msg.encryption_key_id, optional);
-    line!(text, "edited", msg.edited.map(|t| t.0), optional);
-    line!(text, "deleted", msg.deleted.map(|t| t.0), optional);
+    line!(text, "encryption_key_id", &msg.encryption_key_id, optional theme);
+    line!(text, "edited", msg.edited.map(|t| t.0), optional theme);
+    line!(text, "deleted", msg.deleted.map(|t| t.0), optional theme);
     line!(text, "truncated", msg.truncated, yes or no);
 
     text
 }
 
-pub fn session_widget(session: &SessionInfo) -> impl Widget<UiError> {
+pub fn session_widget(theme: &Theme, session: &SessionInfo) -> impl Widget<UiError> {
     let heading_style = Style::new().bold();
 
     let text = match session {
         SessionInfo::Full(session) => {
             let text = Styled::new("Full session", heading_style).then_plain("\n");
-            session_view_lines(text, session)
+            session_view_lines(theme, text, session)
         }
         SessionInfo::Partial(event) => {
             let text = Styled::new("Partial session", heading_style).then_plain("\n");
@@ -105,24 +105,70 @@ pub fn session_widget(session: &SessionInfo) -> impl Widget<UiError> {
         }
     };
 
-    Popup::new(Text::new(text), "Inspect session")
+    Popup::new(theme, Text::new(text), "Inspect session")
 }
 
-pub fn message_widget(msg: &Message) -> impl Widget<UiError> {
-    let heading_style = Style::new().bold();
+pub struct InspectMessageState {
+    msg: Message,
+    /// The content the message had before the server deleted it, see the
+    /// `deleted_retention` config option.
+    deleted_content: Option<String>,
+    /// Whether to show the message as raw JSON instead of a list of fields.
+    raw: bool,
+}
 
-    let mut text = Styled::new("Message", heading_style).then_plain("\n");
+impl InspectMessageState {
+    pub fn new(msg: Message, deleted_content: Option<String>) -> Self {
+        Self {
+            msg,
+            deleted_content,
+            raw: false,
+        }
+    }
 
-    text = message_lines(text, msg);
+    pub fn widget(&self, theme: &Theme, keys: &Keys) -> impl Widget<UiError> {
+        let heading_style = Style::new().bold();
 
-    text = text
-        .then_plain("\n")
-        .then("Sender", heading_style)
-        .then_plain("\n");
+        let mut text = if self.raw {
+            let json = serde_json::to_string_pretty(&self.msg)
+                .unwrap_or_else(|err| format!("Failed to serialize message: {err}"));
+            Styled::new_plain(json).then_plain("\n")
+        } else {
+            let text = Styled::new("Message", heading_style).then_plain("\n");
+            let mut text = message_lines(theme, text, &self.msg);
+            if let Some(content) = &self.deleted_content {
+                text = text
+                    .then_plain("\n")
+                    .then("Content before deletion", heading_style)
+                    .then_plain("\n")
+                    .then_plain(content)
+                    .then_plain("\n");
+            }
+            let text = text
+                .then_plain("\n")
+                .then("Sender", heading_style)
+                .then_plain("\n");
+            session_view_lines(theme, text, &self.msg.sender)
+        };
 
-    text = session_view_lines(text, &msg.sender);
+        let what = if self.raw { "fields" } else { "raw JSON" };
+        text = text
+            .then_plain("\n")
+            .then("Press ", theme.info())
+            .and_then(key_bindings::format_binding(&keys.general.focus))
+            .then(format!(" to show {what}."), theme.info());
 
-    Popup::new(Text::new(text), "Inspect message")
+        Popup::new(theme, Text::new(text), "Inspect message")
+    }
+
+    pub fn handle_input_event(&mut self, event: &mut InputEvent<'_>, keys: &Keys) -> PopupResult {
+        if event.matches(&keys.general.focus) {
+            self.raw = !self.raw;
+            return PopupResult::Handled;
+        }
+
+        handle_input_event(event, keys)
+    }
 }
 
 pub fn handle_input_event(event: &mut InputEvent<'_>, keys: &Keys) -> PopupResult {
@@ -4,7 +4,7 @@ use crossterm::event::KeyCode;
 use crossterm::style::Stylize;
 use linkify::{LinkFinder, LinkKind};
 use toss::widgets::{Join2, Text};
-use toss::{Style, Styled, Widget, WidgetExt};
+use toss::{Styled, Widget, WidgetExt};
 
 use crate::ui::widgets::{ListBuilder, ListState, Popup};
 use crate::ui::{key_bindings, util, UiError};
@@ -36,12 +36,13 @@ impl LinksState {
     }
 
     pub fn widget(&mut self) -> impl Widget<UiError> + '_ {
-        let style_selected = Style::new().black().on_white();
+        let theme = &self.config.theme;
+        let style_selected = theme.cursor();
 
         let mut list_builder = ListBuilder::new();
 
         if self.links.is_empty() {
-            list_builder.add_unsel(Text::new(("No links found", Style::new().grey().italic())))
+            list_builder.add_unsel(Text::new(("No links found", theme.info().italic())))
         }
 
         for (id, link) in self.links.iter().enumerate() {
@@ -53,7 +54,7 @@ impl LinksState {
                             .then(" ", style_selected)
                             .then(link, style_selected)
                     } else {
-                        Styled::new(format!("[{number_key}]"), Style::new().dark_grey().bold())
+                        Styled::new(format!("[{number_key}]"), theme.faint().bold())
                             .then_plain(" ")
                             .then_plain(link)
                     };
@@ -71,7 +72,7 @@ impl LinksState {
             }
         }
 
-        let hint_style = Style::new().grey().italic();
+        let hint_style = theme.info().italic();
         let hint = Styled::new("Open links with ", hint_style)
             .and_then(key_bindings::format_binding(
                 &self.config.keys.general.confirm,
@@ -79,6 +80,7 @@ impl LinksState {
             .then(" or the number keys.", hint_style);
 
         Popup::new(
+            theme,
             Join2::vertical(
                 list_builder.build(&mut self.list).segment(),
                 Text::new(hint)
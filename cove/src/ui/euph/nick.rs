@@ -1,8 +1,9 @@
-use cove_config::Keys;
+use cove_config::{Keys, Theme};
 use cove_input::InputEvent;
+use crossterm::style::Stylize;
 use euphoxide::conn::Joined;
-use toss::widgets::EditorState;
-use toss::{Style, Widget};
+use toss::widgets::{EditorState, Empty, Join3, Text};
+use toss::{Style, Styled, Widget, WidgetExt};
 
 use crate::euph::{self, Room};
 use crate::ui::widgets::Popup;
@@ -10,38 +11,96 @@ use crate::ui::{util, UiError};
 
 use super::popup::PopupResult;
 
-pub fn new(joined: Joined) -> EditorState {
-    EditorState::with_initial_text(joined.session.name)
+pub struct NickState {
+    editor: EditorState,
+    /// Whether the nick was sent to the server and is waiting for a reply.
+    pending: bool,
+    /// Why the server rejected the last nick, if it did.
+    error: Option<String>,
 }
 
-pub fn widget(editor: &mut EditorState) -> impl Widget<UiError> + '_ {
-    let inner = editor
-        .widget()
-        .with_highlight(|s| euph::style_nick_exact(s, Style::new()));
+impl NickState {
+    pub fn new(joined: Joined) -> Self {
+        Self {
+            editor: EditorState::with_initial_text(joined.session.name),
+            pending: false,
+            error: None,
+        }
+    }
 
-    Popup::new(inner, "Choose nick")
-}
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
 
-pub fn handle_input_event(
-    event: &mut InputEvent<'_>,
-    keys: &Keys,
-    room: &Option<Room>,
-    editor: &mut EditorState,
-) -> PopupResult {
-    if event.matches(&keys.general.abort) {
-        return PopupResult::Close;
+    /// The server rejected the nick. The popup stays open so the user can try
+    /// again.
+    pub fn failed(&mut self, reason: String) {
+        self.pending = false;
+        self.error = Some(reason);
     }
 
-    if event.matches(&keys.general.confirm) {
-        if let Some(room) = &room {
-            let _ = room.nick(editor.text().to_string());
+    fn preview(&self, theme: &Theme) -> Styled {
+        let nick = self.editor.text();
+        let info = theme.info();
+
+        let mut text = Styled::new("Preview: ", info)
+            .then_plain("[")
+            .and_then(euph::style_nick(nick, Style::new()))
+            .then_plain("]")
+            .then(format!(" (hue {:.0})", euph::nick_hue(nick)), info);
+
+        if self.pending {
+            text = text.then_plain("\n").then("Setting nick...", info);
+        } else if let Some(error) = &self.error {
+            text = text.then_plain("\n").then(error, Style::new().red());
         }
-        return PopupResult::Close;
+
+        text
     }
 
-    if util::handle_editor_input_event(editor, event, keys, |c| c != '\n') {
-        return PopupResult::Handled;
+    pub fn widget(&mut self, theme: &Theme) -> impl Widget<UiError> + '_ {
+        let preview = self.preview(theme);
+        let editor = self
+            .editor
+            .widget()
+            .with_highlight(|s| euph::style_nick_exact(s, Style::new()));
+
+        let inner = Join3::vertical(
+            editor.segment(),
+            Empty::new().with_height(1).segment().with_fixed(true),
+            Text::new(preview).segment().with_fixed(true),
+        );
+
+        Popup::new(theme, inner, "Choose nick")
     }
 
-    PopupResult::NotHandled
+    pub fn handle_input_event(
+        &mut self,
+        event: &mut InputEvent<'_>,
+        keys: &Keys,
+        room: &Option<Room>,
+    ) -> PopupResult {
+        if event.matches(&keys.general.abort) {
+            return PopupResult::Close;
+        }
+
+        if event.matches(&keys.general.confirm) {
+            let Some(room) = &room else {
+                return PopupResult::Close;
+            };
+            if room.nick(self.editor.text().to_string()).is_err() {
+                return PopupResult::Close;
+            }
+            self.pending = true;
+            self.error = None;
+            return PopupResult::Handled;
+        }
+
+        if util::handle_editor_input_event(&mut self.editor, event, keys, |c| c != '\n') {
+            self.error = None;
+            return PopupResult::Handled;
+        }
+
+        PopupResult::NotHandled
+    }
 }
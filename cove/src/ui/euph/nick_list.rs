@@ -1,22 +1,25 @@
 use std::iter;
 
+use cove_config::Theme;
 use crossterm::style::{Color, Stylize};
 use euphoxide::api::{NickEvent, SessionId, SessionType, SessionView, UserId};
 use euphoxide::conn::{Joined, SessionInfo};
 use toss::widgets::{Background, Text};
 use toss::{Style, Styled, Widget, WidgetExt};
 
-use crate::euph;
+use crate::euph::{self, SessionFilter};
 use crate::ui::widgets::{ListBuilder, ListState};
 use crate::ui::UiError;
 
 pub fn widget<'a>(
+    theme: &Theme,
     list: &'a mut ListState<SessionId>,
     joined: &Joined,
+    filter: &SessionFilter,
     focused: bool,
 ) -> impl Widget<UiError> + 'a {
     let mut list_builder = ListBuilder::new();
-    render_rows(&mut list_builder, joined, focused);
+    render_rows(theme, &mut list_builder, joined, filter, focused);
     list_builder.build(list)
 }
 
@@ -59,8 +62,10 @@ impl HalfSession {
 }
 
 fn render_rows(
+    theme: &Theme,
     list_builder: &mut ListBuilder<'_, SessionId, Background<Text>>,
     joined: &Joined,
+    filter: &SessionFilter,
     focused: bool,
 ) {
     let mut people = vec![];
@@ -68,9 +73,16 @@ fn render_rows(
     let mut lurkers = vec![];
     let mut nurkers = vec![];
 
+    let mut hidden = 0;
+
     let sessions = joined
         .listing
         .values()
+        .filter(|sess| {
+            let shown = filter.shows(sess.id(), sess.name());
+            hidden += usize::from(!shown);
+            shown
+        })
         .map(HalfSession::from_session_info)
         .chain(iter::once(HalfSession::from_session_view(&joined.session)));
     for sess in sessions {
@@ -87,13 +99,41 @@ fn render_rows(
     lurkers.sort_unstable();
     nurkers.sort_unstable();
 
-    render_section(list_builder, "People", &people, &joined.session, focused);
-    render_section(list_builder, "Bots", &bots, &joined.session, focused);
-    render_section(list_builder, "Lurkers", &lurkers, &joined.session, focused);
-    render_section(list_builder, "Nurkers", &nurkers, &joined.session, focused);
+    render_section(
+        theme,
+        list_builder,
+        "People",
+        &people,
+        &joined.session,
+        focused,
+    );
+    render_section(theme, list_builder, "Bots", &bots, &joined.session, focused);
+    render_section(
+        theme,
+        list_builder,
+        "Lurkers",
+        &lurkers,
+        &joined.session,
+        focused,
+    );
+    render_section(
+        theme,
+        list_builder,
+        "Nurkers",
+        &nurkers,
+        &joined.session,
+        focused,
+    );
+
+    if hidden > 0 {
+        let row = Styled::new(format!(" {hidden} hidden"), theme.info());
+        list_builder.add_unsel(Text::new("").background());
+        list_builder.add_unsel(Text::new(row).background());
+    }
 }
 
 fn render_section(
+    theme: &Theme,
     list_builder: &mut ListBuilder<'_, SessionId, Background<Text>>,
     name: &str,
     sessions: &[HalfSession],
@@ -116,11 +156,12 @@ fn render_section(
     list_builder.add_unsel(Text::new(row).background());
 
     for session in sessions {
-        render_row(list_builder, session, own_session, focused);
+        render_row(theme, list_builder, session, own_session, focused);
     }
 }
 
 fn render_row(
+    theme: &Theme,
     list_builder: &mut ListBuilder<'_, SessionId, Background<Text>>,
     session: &HalfSession,
     own_session: &SessionView,
@@ -128,8 +169,8 @@ fn render_row(
 ) {
     let (name, style, style_inv, perms_style_inv) = if session.name.is_empty() {
         let name = "lurk".to_string();
-        let style = Style::new().grey();
-        let style_inv = Style::new().black().on_grey();
+        let style = theme.info();
+        let style_inv = theme.cursor();
         (name, style, style_inv, style_inv)
     } else {
         let name = &session.name as &str;
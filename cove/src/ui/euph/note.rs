@@ -0,0 +1,73 @@
+//! Editing the local note attached to a message. Notes are only stored in the
+//! vault and never sent to the room.
+
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use cove_vault::EuphRoomVault;
+use crossterm::style::Stylize;
+use euphoxide::api::MessageId;
+use toss::widgets::{EditorState, Join2, Text};
+use toss::{Styled, Widget, WidgetExt};
+
+use crate::macros::logging_unwrap;
+use crate::ui::widgets::Popup;
+use crate::ui::{util, UiError};
+
+use super::popup::PopupResult;
+
+pub struct NoteState {
+    id: MessageId,
+    editor: EditorState,
+}
+
+impl NoteState {
+    pub async fn new(vault: &EuphRoomVault, id: MessageId) -> Self {
+        let note = logging_unwrap!(vault.note(id).await);
+        Self {
+            id,
+            editor: EditorState::with_initial_text(note.unwrap_or_default()),
+        }
+    }
+
+    pub fn widget(&mut self, theme: &Theme) -> impl Widget<UiError> + '_ {
+        let hint = Styled::new(
+            "Only stored locally. Leave empty to remove the note.",
+            theme.info().italic(),
+        );
+
+        let inner = Join2::vertical(
+            self.editor.widget().segment(),
+            Text::new(hint)
+                .padding()
+                .with_top(1)
+                .segment()
+                .with_fixed(true),
+        );
+
+        Popup::new(theme, inner, "Note")
+    }
+
+    pub async fn handle_input_event(
+        &mut self,
+        event: &mut InputEvent<'_>,
+        keys: &Keys,
+        vault: &EuphRoomVault,
+    ) -> PopupResult {
+        if event.matches(&keys.general.abort) {
+            return PopupResult::Close;
+        }
+
+        if event.matches(&keys.general.confirm) {
+            let note = self.editor.text().trim();
+            let note = (!note.is_empty()).then(|| note.to_string());
+            logging_unwrap!(vault.set_note(self.id, note).await);
+            return PopupResult::Close;
+        }
+
+        if util::handle_editor_input_event(&mut self.editor, event, keys, |c| c != '\n') {
+            return PopupResult::Handled;
+        }
+
+        PopupResult::NotHandled
+    }
+}
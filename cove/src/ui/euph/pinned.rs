@@ -0,0 +1,124 @@
+//! Listing the messages considered pinned according to the `pin_nick` and
+//! `pin_prefix` config options.
+
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use crossterm::style::Stylize;
+use euphoxide::api::MessageId;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use toss::widgets::{Join2, Text};
+use toss::{Styled, Widget, WidgetExt};
+
+use crate::euph::SmallMessage;
+use crate::store::Msg;
+use crate::ui::widgets::{ListBuilder, ListState, Popup};
+use crate::ui::{key_bindings, util, ChatMsg, UiError};
+
+use super::popup::PopupResult;
+
+/// How many of the most recent pinned messages are listed.
+pub const PINNED_AMOUNT: usize = 100;
+
+const DATE_FORMAT: &[FormatItem<'_>] = format_description!("[year]-[month]-[day]");
+
+pub struct PinnedState {
+    /// Whether a convention for pinning messages is configured for the room.
+    configured: bool,
+    msgs: Vec<SmallMessage>,
+    list: ListState<MessageId>,
+}
+
+impl PinnedState {
+    pub fn new(configured: bool, msgs: Vec<SmallMessage>) -> Self {
+        Self {
+            configured,
+            msgs,
+            list: ListState::new(),
+        }
+    }
+
+    pub fn widget(&mut self, theme: &Theme, keys: &Keys) -> impl Widget<UiError> + '_ {
+        let style_selected = theme.cursor();
+        let style_time = theme.time();
+
+        let mut list_builder = ListBuilder::new();
+
+        if !self.configured {
+            list_builder.add_unsel(Text::new((
+                "Set pin_nick or pin_prefix for this room to see pinned messages",
+                theme.info().italic(),
+            )));
+        } else if self.msgs.is_empty() {
+            list_builder.add_unsel(Text::new((
+                "No pinned messages found",
+                theme.info().italic(),
+            )));
+        }
+
+        for msg in &self.msgs {
+            let date = match msg.time() {
+                Some(time) => time.format(DATE_FORMAT).unwrap_or_default(),
+                None => String::new(),
+            };
+            let (nick, content) = msg.styled();
+            // Only the first line of multi-line messages is shown.
+            let preview = content
+                .text()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            list_builder.add_sel(msg.id, move |selected| {
+                let text = if selected {
+                    Styled::new(date, style_selected)
+                        .then(" [", style_selected)
+                        .then(nick.text(), style_selected.bold())
+                        .then("] ", style_selected)
+                        .then(preview, style_selected)
+                } else {
+                    Styled::new(date, style_time)
+                        .then_plain(" [")
+                        .and_then(nick)
+                        .then_plain("] ")
+                        .then_plain(preview)
+                };
+                Text::new(text).with_wrap(false)
+            });
+        }
+
+        let hint = Styled::new_plain("Press ")
+            .and_then(key_bindings::format_binding(&keys.general.confirm))
+            .then_plain(" to jump to a message.");
+
+        let inner = Join2::vertical(
+            list_builder.build(&mut self.list).segment(),
+            Text::new(hint)
+                .padding()
+                .with_top(1)
+                .segment()
+                .with_fixed(true),
+        );
+
+        Popup::new(theme, inner, "Pinned messages")
+    }
+
+    pub fn handle_input_event(&mut self, event: &mut InputEvent<'_>, keys: &Keys) -> PopupResult {
+        if event.matches(&keys.general.abort) {
+            return PopupResult::Close;
+        }
+
+        if event.matches(&keys.general.confirm) {
+            if let Some(id) = self.list.selected() {
+                return PopupResult::JumpToMsg(*id);
+            }
+            return PopupResult::Handled;
+        }
+
+        if util::handle_list_input_event(&mut self.list, event, keys) {
+            return PopupResult::Handled;
+        }
+
+        PopupResult::NotHandled
+    }
+}
@@ -1,18 +1,22 @@
 use std::io;
 
+use cove_config::Theme;
+use cove_vault::ScheduledMsg;
 use crossterm::style::Stylize;
-use toss::widgets::Text;
-use toss::{Style, Styled, Widget};
+use euphoxide::api::MessageId;
+use toss::widgets::{Boxed, Text};
+use toss::{Style, Styled, Widget, WidgetExt};
 
 use crate::ui::widgets::Popup;
 use crate::ui::UiError;
 
 pub enum RoomPopup {
     Error { description: String, reason: String },
+    Notice { description: String },
 }
 
 impl RoomPopup {
-    fn server_error_widget(description: &str, reason: &str) -> impl Widget<UiError> {
+    fn server_error_widget(theme: &Theme, description: &str, reason: &str) -> impl Widget<UiError> {
         let border_style = Style::new().red().bold();
         let text = Styled::new_plain(description)
             .then_plain("\n\n")
@@ -20,15 +24,22 @@ impl RoomPopup {
             .then_plain(" ")
             .then_plain(reason);
 
-        Popup::new(Text::new(text), ("Error", border_style)).with_border_style(border_style)
+        Popup::new(theme, Text::new(text), ("Error", border_style)).with_border_style(border_style)
     }
 
-    pub fn widget(&self) -> impl Widget<UiError> {
+    fn notice_widget(theme: &Theme, description: &str) -> impl Widget<UiError> {
+        let border_style = Style::new().yellow().bold();
+        Popup::new(theme, Text::new(description), ("Notice", border_style))
+            .with_border_style(border_style)
+    }
+
+    pub fn widget(&self, theme: &Theme) -> Boxed<'static, UiError> {
         match self {
             Self::Error {
                 description,
                 reason,
-            } => Self::server_error_widget(description, reason),
+            } => Self::server_error_widget(theme, description, reason).boxed(),
+            Self::Notice { description } => Self::notice_widget(theme, description).boxed(),
         }
     }
 }
@@ -37,5 +48,19 @@ pub enum PopupResult {
     NotHandled,
     Handled,
     Close,
-    ErrorOpeningLink { link: String, error: io::Error },
+    ErrorOpeningLink {
+        link: String,
+        error: io::Error,
+    },
+    InsertSnippet(String),
+    RestoreDraft(String),
+    DiscardDraft,
+    EditScheduled(ScheduledMsg),
+    CancelScheduled(i64),
+    Send {
+        parent: Option<MessageId>,
+        content: String,
+    },
+    CancelSend,
+    JumpToMsg(MessageId),
 }
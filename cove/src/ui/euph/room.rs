@@ -1,27 +1,46 @@
 use std::collections::VecDeque;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 
-use cove_config::{Config, Keys};
+use cove_config::{Config, Keys, OpenAt, Theme};
 use cove_input::InputEvent;
+use cove_vault::{EuphRoomVault, ScheduledMsg};
 use crossterm::style::Stylize;
-use euphoxide::api::{Data, Message, MessageId, PacketType, SessionId};
+use euphoxide::api::packet::ParsedPacket;
+use euphoxide::api::{Data, Message, MessageId, PacketType, SendEvent, SessionId, Time, UserId};
 use euphoxide::bot::instance::{Event, ServerConfig};
 use euphoxide::conn::{self, Joined, Joining, SessionInfo};
+use log::warn;
+use parking_lot::Mutex;
+use time::OffsetDateTime;
 use tokio::sync::oneshot::error::TryRecvError;
 use tokio::sync::{mpsc, oneshot};
 use toss::widgets::{BoxedAsync, EditorState, Join2, Layer, Text};
 use toss::{Style, Styled, Widget, WidgetExt};
 
-use crate::euph;
+use crate::euph::{self, SessionFilter};
 use crate::macros::logging_unwrap;
-use crate::ui::chat::{ChatState, Reaction};
+use crate::store::{MsgStore, StoreChange};
+use crate::ui::chat::{ChatState, Reaction, Translation};
 use crate::ui::widgets::ListState;
-use crate::ui::{util, UiError, UiEvent};
-use crate::vault::EuphRoomVault;
+use crate::ui::{clipboard, key_bindings, util, UiError, UiEvent};
+use crate::version;
 
 use super::account::AccountUiState;
+use super::activity::ActivityState;
+use super::commands::{self, Command, Parsed};
+use super::confirm_send::ConfirmSendState;
+use super::inspect::InspectMessageState;
 use super::links::LinksState;
+use super::nick::NickState;
+use super::note::NoteState;
+use super::pinned::{PinnedState, PINNED_AMOUNT};
 use super::popup::{PopupResult, RoomPopup};
-use super::{auth, inspect, nick, nick_list};
+use super::scheduled::ScheduledState;
+use super::snippets::SnippetsState;
+use super::stats::StatsState;
+use super::toast::Toast;
+use super::{activity, auth, draft, inspect, nick_list, snippets, stats, toast};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Focus {
@@ -33,31 +52,181 @@ enum Focus {
 enum State {
     Normal,
     Auth(EditorState),
-    Nick(EditorState),
+    Nick(NickState),
     Account(AccountUiState),
     Links(LinksState),
-    InspectMessage(Message),
+    InspectMessage(InspectMessageState),
     InspectSession(SessionInfo),
+    Stats(StatsState),
+    Activity(ActivityState),
+    Note(NoteState),
+    Snippets(SnippetsState),
+    Scheduled(ScheduledState),
+    Pinned(PinnedState),
+    ConfirmSend(ConfirmSendState),
+    RestoreDraft(String),
 }
 
+/// Optional parts of the protocol that not every server implements.
+///
+/// Servers don't advertise them, so they are assumed to be supported until the
+/// server rejects one of their commands as unknown. Since an update may add
+/// support, they are reset whenever the server version changes.
+#[derive(Debug, Clone, Copy)]
+struct Capabilities {
+    accounts: bool,
+    edits: bool,
+    pms: bool,
+}
+
+impl Capabilities {
+    fn new() -> Self {
+        Self {
+            accounts: true,
+            edits: true,
+            pms: true,
+        }
+    }
+
+    fn of_reply(&mut self, r#type: PacketType) -> Option<&mut bool> {
+        match r#type {
+            PacketType::ChangeEmailReply
+            | PacketType::ChangeNameReply
+            | PacketType::ChangePasswordReply
+            | PacketType::LoginReply
+            | PacketType::LogoutReply
+            | PacketType::RegisterAccountReply
+            | PacketType::ResendVerificationEmailReply
+            | PacketType::ResetPasswordReply => Some(&mut self.accounts),
+            PacketType::EditMessageReply => Some(&mut self.edits),
+            PacketType::PmInitiateReply => Some(&mut self.pms),
+            _ => None,
+        }
+    }
+}
+
+/// Whether an error reply means the server doesn't know the command at all.
+///
+/// Servers reject unknown commands with an error naming the command, like
+/// `command type edit-message not implemented`. Other errors that merely sound
+/// similar, for example about the arguments, don't count.
+fn is_unknown_command(r#type: PacketType, reason: &str) -> bool {
+    let Ok(serde_json::Value::String(reply)) = serde_json::to_value(r#type) else {
+        return false;
+    };
+    let Some(command) = reply.strip_suffix("-reply") else {
+        return false;
+    };
+
+    let reason = reason.to_lowercase();
+    reason.contains(command)
+        && [
+            "not implemented",
+            "not supported",
+            "unknown",
+            "invalid command",
+        ]
+        .iter()
+        .any(|pattern| reason.contains(pattern))
+}
+
+/// Format a clock skew compactly for the status bar, ignoring its sign.
+fn format_clock_skew(skew: time::Duration) -> String {
+    let seconds = skew.whole_seconds().unsigned_abs();
+    if seconds < 2 * 60 {
+        format!("{seconds}s")
+    } else if seconds < 2 * 60 * 60 {
+        format!("{}min", seconds / 60)
+    } else {
+        format!("{}h", seconds / (60 * 60))
+    }
+}
+
+const NOT_SUPPORTED: &str = "not supported by this server";
+
 type EuphChatState = ChatState<euph::SmallMessage, EuphRoomVault>;
 
 pub struct EuphRoom {
     config: &'static Config,
     server_config: ServerConfig,
     room_config: cove_config::EuphRoom,
+    webhooks: euph::Webhooks,
     ui_event_tx: mpsc::UnboundedSender<UiEvent>,
+    /// Whether packets are replayed from a recording instead of received from
+    /// the server, see `--replay`. Connecting then never reaches the server.
+    replaying: bool,
+    /// Passed on to [`Self::room`] whenever it connects.
+    low_bandwidth: bool,
 
     room: Option<euph::Room>,
+    /// Whether the current connection was opened because the previous one
+    /// stalled. Reset once the room is joined again.
+    stalled: bool,
+    /// How many attempts to connect failed before giving up, according to the
+    /// `reconnect_attempts` config options. Reset when connecting manually.
+    gave_up: Option<u64>,
+    /// How far the local clock was ahead of the server's clock according to
+    /// the most recent ping event.
+    clock_skew: Option<time::Duration>,
 
     focus: Focus,
     state: State,
     popups: VecDeque<RoomPopup>,
+    /// Failed commands, most recent first. Unlike popups, they expire on their
+    /// own.
+    toasts: VecDeque<Toast>,
+
+    /// Server version and privacy of the room as of the last hello event,
+    /// remembered across reconnects to notice when they change.
+    server_version: Option<String>,
+    room_is_private: Option<bool>,
+    capabilities: Capabilities,
 
     chat: EuphChatState,
     last_msg_sent: Option<oneshot::Receiver<MessageId>>,
+    /// When the last message was sent, to enforce the `send_interval` config
+    /// options.
+    last_sent_at: Option<Instant>,
+    /// Whether the unsent message saved in the vault was already loaded, see
+    /// [`Self::stabilize_draft`].
+    draft_loaded: bool,
+    /// The unsent message as it was last saved to the vault.
+    saved_draft: Option<String>,
+    draft_saved_at: Instant,
+    /// Whether the ignored nicks and user ids were already loaded from the
+    /// vault into [`Self::session_filter`].
+    ignored_loaded: bool,
+    /// Messages waiting to be sent, as loaded from the vault. Set to `None`
+    /// whenever they change so they are loaded again.
+    scheduled: Option<Vec<ScheduledMsg>>,
+    /// The scheduled message currently being edited in the editor. It is only
+    /// replaced in the vault once the edited version is submitted.
+    editing_scheduled: Option<ScheduledMsg>,
+    /// A command that must be executed outside of the room, see
+    /// [`Self::take_command`].
+    command: Option<Command>,
+    translations_tx: mpsc::UnboundedSender<(MessageId, Translation)>,
+    translations_rx: mpsc::UnboundedReceiver<(MessageId, Translation)>,
 
     nick_list: ListState<SessionId>,
+    /// Which sessions to leave out of the nick list and people summary.
+    session_filter: SessionFilter,
+
+    /// The unseen messages as of the last count, see
+    /// [`Self::unseen_msgs_count`].
+    unseen: Arc<Mutex<UnseenCount>>,
+}
+
+/// Counting unseen messages requires a query, so the count is only redone
+/// after the messages of the room changed.
+#[derive(Default)]
+struct UnseenCount {
+    /// Incremented whenever the messages change, to detect changes made while
+    /// counting.
+    generation: u64,
+    count: Option<usize>,
+    /// Unseen mentions of the nick and user id they were counted for.
+    mentions: Option<(String, UserId, usize)>,
 }
 
 impl EuphRoom {
@@ -66,20 +235,84 @@ impl EuphRoom {
         server_config: ServerConfig,
         room_config: cove_config::EuphRoom,
         vault: EuphRoomVault,
+        webhooks: euph::Webhooks,
         ui_event_tx: mpsc::UnboundedSender<UiEvent>,
+        replaying: bool,
     ) -> Self {
+        let (translations_tx, translations_rx) = mpsc::unbounded_channel();
+
+        let unseen = Arc::new(Mutex::new(UnseenCount::default()));
+        tokio::task::spawn(Self::watch_store(
+            vault.subscribe(),
+            Arc::downgrade(&unseen),
+            ui_event_tx.clone(),
+        ));
+
         Self {
             config,
             server_config,
             room_config,
+            webhooks,
             ui_event_tx,
+            replaying,
+            low_bandwidth: false,
             room: None,
+            stalled: false,
+            gave_up: None,
+            clock_skew: None,
             focus: Focus::Chat,
             state: State::Normal,
             popups: VecDeque::new(),
-            chat: ChatState::new(vault),
+            toasts: VecDeque::new(),
+            server_version: None,
+            room_is_private: None,
+            capabilities: Capabilities::new(),
+            chat: ChatState::new(config, vault),
             last_msg_sent: None,
+            last_sent_at: None,
+            draft_loaded: false,
+            saved_draft: None,
+            draft_saved_at: Instant::now(),
+            ignored_loaded: false,
+            scheduled: None,
+            editing_scheduled: None,
+            command: None,
+            translations_tx,
+            translations_rx,
             nick_list: ListState::new(),
+            session_filter: SessionFilter::new(config),
+            unseen,
+        }
+    }
+
+    /// Redraw whenever the messages change and forget the unseen count if it
+    /// may have changed, until the room is dropped.
+    async fn watch_store(
+        mut changes: mpsc::UnboundedReceiver<StoreChange>,
+        unseen: Weak<Mutex<UnseenCount>>,
+        ui_event_tx: mpsc::UnboundedSender<UiEvent>,
+    ) {
+        while let Some(change) = changes.recv().await {
+            // Changes often arrive in bursts, e.g. while downloading logs. The
+            // whole burst only needs to be counted and redrawn once. Notes
+            // don't affect the count.
+            let mut recount = change != StoreChange::Note;
+            while let Ok(change) = changes.try_recv() {
+                recount |= change != StoreChange::Note;
+            }
+
+            let Some(unseen) = unseen.upgrade() else {
+                break;
+            };
+            if recount {
+                let mut unseen = unseen.lock();
+                unseen.generation += 1;
+                unseen.count = None;
+                unseen.mentions = None;
+            }
+            if ui_event_tx.send(UiEvent::StoreChanged).is_err() {
+                break;
+            }
         }
     }
 
@@ -96,7 +329,10 @@ impl EuphRoom {
     }
 
     pub fn connect(&mut self, next_instance_id: &mut usize) {
-        if self.room.is_none() {
+        self.gave_up = None;
+        if self.room.is_none() && self.replaying {
+            self.room = Some(self.replay_room());
+        } else if self.room.is_none() {
             let room = self.vault().room();
             let instance_config = self
                 .server_config
@@ -112,7 +348,10 @@ impl EuphRoom {
             let tx = self.ui_event_tx.clone();
             self.room = Some(euph::Room::new(
                 self.vault().clone(),
+                &self.config.hooks,
+                self.webhooks.clone(),
                 instance_config,
+                self.low_bandwidth,
                 move |e| {
                     let _ = tx.send(UiEvent::Euph(e));
                 },
@@ -120,10 +359,95 @@ impl EuphRoom {
         }
     }
 
+    fn replay_room(&self) -> euph::Room {
+        euph::Room::replay(
+            self.vault().clone(),
+            &self.config.hooks,
+            self.webhooks.clone(),
+        )
+    }
+
+    pub fn set_low_bandwidth(&mut self, enabled: bool) {
+        self.low_bandwidth = enabled;
+        if let Some(room) = &mut self.room {
+            room.set_low_bandwidth(enabled);
+        }
+    }
+
     pub fn disconnect(&mut self) {
         self.room = None;
     }
 
+    /// A command the user entered that affects more than just this room, like
+    /// `/connect` or `/search`.
+    pub fn take_command(&mut self) -> Option<Command> {
+        self.command.take()
+    }
+
+    /// Whether the server didn't send anything for longer than the timeout.
+    pub fn is_stalled(&self, timeout: Duration) -> bool {
+        let idle_time = self.room.as_ref().and_then(|r| r.idle_time());
+        idle_time.is_some_and(|t| t > timeout)
+    }
+
+    /// Reconnect if the server didn't send anything for longer than the
+    /// timeout. Returns whether the room was reconnected.
+    pub fn reconnect_if_stalled(
+        &mut self,
+        timeout: Duration,
+        next_instance_id: &mut usize,
+    ) -> bool {
+        if !self.is_stalled(timeout) {
+            return false;
+        }
+
+        warn!(
+            room:? = self.vault().room();
+            "{}: connection stalled, reconnecting",
+            self.name()
+        );
+        self.disconnect();
+        self.connect(next_instance_id);
+        self.stalled = true;
+        true
+    }
+
+    /// Whether the room's messages are shown in the combined feed.
+    pub fn in_feed(&self) -> bool {
+        self.room_config.feed
+    }
+
+    /// Whether cove stopped trying to connect to the room, see
+    /// [`Self::give_up_if_out_of_attempts`].
+    pub fn gave_up(&self) -> bool {
+        self.gave_up.is_some()
+    }
+
+    /// Stop trying to connect if too many attempts in a row failed.
+    fn give_up_if_out_of_attempts(&mut self) {
+        let max = self
+            .room_config
+            .reconnect_attempts
+            .or(self.config.reconnect_attempts);
+        let Some(max) = max else { return };
+        let Some(room) = &self.room else { return };
+
+        let attempts = room.attempts();
+        if matches!(room.state(), euph::State::Disconnected) && attempts >= max {
+            warn!(
+                room:? = self.vault().room();
+                "{}: giving up after {attempts} attempts",
+                self.name()
+            );
+            self.disconnect();
+            self.gave_up = Some(attempts);
+        }
+    }
+
+    pub fn session_filter(&self) -> SessionFilter {
+        self.session_filter.clone()
+    }
+
     pub fn room_state(&self) -> Option<&euph::State> {
         if let Some(room) = &self.room {
             Some(room.state())
@@ -148,8 +472,82 @@ impl EuphRoom {
         }
     }
 
+    /// Move the cursor to the oldest unseen message sent since the given time.
+    pub async fn catch_up(&mut self, since: Time) {
+        if let Some(id) = logging_unwrap!(self.vault().oldest_unseen_msg_id_since(since).await) {
+            self.chat.move_cursor_to(id);
+        }
+    }
+
+    /// Move the cursor to where it should be when the room is opened, according
+    /// to the `open_at` config options.
+    pub async fn move_cursor_on_open(&mut self) {
+        let open_at = self.room_config.open_at.unwrap_or(self.config.open_at);
+        if let OpenAt::OldestUnseen = open_at {
+            if let Some(id) = logging_unwrap!(self.vault().oldest_unseen_msg_id().await) {
+                self.chat.move_cursor_to(id);
+            }
+        }
+    }
+
+    pub fn move_cursor_to(&mut self, id: MessageId) {
+        self.chat.move_cursor_to(id);
+    }
+
     pub async fn unseen_msgs_count(&self) -> usize {
-        logging_unwrap!(self.vault().unseen_msgs_count().await)
+        let generation = {
+            let unseen = self.unseen.lock();
+            if let Some(count) = unseen.count {
+                return count;
+            }
+            unseen.generation
+        };
+
+        let count = logging_unwrap!(self.vault().unseen_msgs_count().await);
+
+        let mut unseen = self.unseen.lock();
+        if unseen.generation == generation {
+            unseen.count = Some(count);
+        }
+        count
+    }
+
+    /// Amount of unseen messages mentioning the current nick or replying to the
+    /// user's messages. Always zero while not joined.
+    pub async fn unseen_mentions_count(&self) -> usize {
+        let Some(joined) = self.room_state_joined() else {
+            return 0;
+        };
+        let nick = joined.session.name.clone();
+        let user_id = joined.session.id.clone();
+
+        // Finding mentions means looking at every unseen message, which can be
+        // skipped entirely in the common case of a room without any.
+        if self.unseen_msgs_count().await == 0 {
+            return 0;
+        }
+
+        let generation = {
+            let unseen = self.unseen.lock();
+            if let Some((counted_nick, counted_user_id, count)) = &unseen.mentions {
+                if *counted_nick == nick && *counted_user_id == user_id {
+                    return *count;
+                }
+            }
+            unseen.generation
+        };
+
+        let count = self
+            .vault()
+            .unseen_mentions_count(nick.clone(), user_id.clone())
+            .await;
+        let count = logging_unwrap!(count);
+
+        let mut unseen = self.unseen.lock();
+        if unseen.generation == generation {
+            unseen.mentions = Some((nick, user_id, count));
+        }
+        count
     }
 
     async fn stabilize_pseudo_msg(&mut self) {
@@ -168,12 +566,24 @@ impl EuphRoom {
         }
     }
 
+    fn stabilize_translations(&mut self) {
+        while let Ok((id, translation)) = self.translations_rx.try_recv() {
+            self.chat.set_translation(id, translation);
+        }
+    }
+
     fn stabilize_focus(&mut self) {
-        if self.room_state_joined().is_none() {
+        if self.room_state_joined().is_none() || self.chat.zen() {
             self.focus = Focus::Chat; // There is no nick list to focus on
         }
     }
 
+    fn stabilize_stalled(&mut self) {
+        if self.room_state_joined().is_some() {
+            self.stalled = false;
+        }
+    }
+
     fn stabilize_state(&mut self) {
         let room_state = self.room.as_ref().map(|r| r.state());
         match (&mut self.state, room_state) {
@@ -201,23 +611,172 @@ impl EuphRoom {
         }
     }
 
+    /// Load the message left unsent when cove last exited, if any, and offer
+    /// to restore it. This only happens once, when the room is first shown.
+    async fn stabilize_draft(&mut self) {
+        if self.draft_loaded {
+            return;
+        }
+        self.draft_loaded = true;
+
+        let draft = logging_unwrap!(self.vault().draft().await);
+        if let (Some(draft), State::Normal) = (&draft, &self.state) {
+            self.state = State::RestoreDraft(draft.clone());
+        }
+        self.saved_draft = draft;
+    }
+
+    async fn stabilize_ignored(&mut self) {
+        if self.ignored_loaded {
+            return;
+        }
+        self.ignored_loaded = true;
+        self.session_filter.ignored = logging_unwrap!(self.vault().ignored().await);
+    }
+
+    /// Remove toasts that were shown long enough. Returns whether any were
+    /// removed.
+    pub fn expire_toasts(&mut self) -> bool {
+        let before = self.toasts.len();
+        self.toasts.retain(|toast| !toast.expired());
+        self.toasts.len() != before
+    }
+
+    /// Seconds left until the next message may be sent according to the
+    /// `send_interval` config options, if any.
+    pub fn send_cooldown(&self) -> Option<u64> {
+        let interval = self
+            .room_config
+            .send_interval
+            .or(self.config.send_interval)?;
+        let elapsed = self.last_sent_at?.elapsed();
+        let left = Duration::from_secs(interval).checked_sub(elapsed)?;
+        Some(left.as_secs_f64().ceil() as u64).filter(|secs| *secs > 0)
+    }
+
+    fn stabilize_send_cooldown(&mut self) {
+        self.chat.set_send_cooldown(self.send_cooldown());
+    }
+
     async fn stabilize(&mut self) {
+        self.stabilize_draft().await;
+        self.stabilize_ignored().await;
         self.stabilize_pseudo_msg().await;
+        self.stabilize_send_cooldown();
+        self.stabilize_translations();
         self.stabilize_focus();
+        self.stabilize_stalled();
         self.stabilize_state();
     }
 
+    /// Mark the messages as seen that were visible during the last redraw,
+    /// according to the `mark_seen` config option.
+    pub async fn mark_seen(&mut self) {
+        logging_unwrap!(self.chat.mark_seen().await);
+    }
+
+    /// Save the message currently being composed to the vault so it can be
+    /// restored if cove exits without sending it. Does nothing if the last save
+    /// was less than `interval` ago.
+    pub async fn save_draft(&mut self, interval: Duration) {
+        if !self.draft_loaded || matches!(self.state, State::RestoreDraft(_)) {
+            return;
+        }
+        if self.draft_saved_at.elapsed() < interval {
+            return;
+        }
+        self.draft_saved_at = Instant::now();
+
+        let draft = self
+            .chat
+            .composing()
+            .filter(|content| !content.trim().is_empty())
+            .map(|content| content.to_string());
+        if draft == self.saved_draft {
+            return;
+        }
+
+        // Rooms that were never connected to have no entry in the vault, so
+        // this may fail without anything being wrong.
+        if let Err(err) = self.vault().set_draft(draft.clone()).await {
+            warn!("Failed to save unsent message in {:?}: {err}", self.name());
+            return;
+        }
+        self.saved_draft = draft;
+    }
+
+    /// Send all scheduled messages whose time has come. Messages stay in the
+    /// vault until the room is joined so they can be sent. A message that is
+    /// currently being edited is held back until the edit is done.
+    pub async fn send_scheduled(&mut self) {
+        if self.room_state_joined().is_none() {
+            return;
+        }
+
+        if self.scheduled.is_none() {
+            self.scheduled = Some(logging_unwrap!(self.vault().scheduled().await));
+        }
+        let editing = self.editing_scheduled.as_ref().map(|msg| msg.id);
+        let Some(scheduled) = &mut self.scheduled else {
+            return;
+        };
+
+        let now = OffsetDateTime::now_utc();
+        let due = scheduled.iter().take_while(|msg| msg.time.0 <= now).count();
+        let due = scheduled.drain(..due).collect::<Vec<_>>();
+        for msg in due {
+            if Some(msg.id) == editing {
+                continue;
+            }
+            let Some(room) = &self.room else { return };
+            if room.send(msg.parent, msg.content).is_err() {
+                // The connection was lost, so try again later.
+                self.scheduled = None;
+                return;
+            }
+            logging_unwrap!(self.vault().delete_scheduled(msg.id).await);
+        }
+    }
+
+    /// Whether the editor contains a message that would be lost when quitting.
+    /// It is not lost if it was already saved as a draft.
+    pub fn has_unsaved_msg(&self) -> bool {
+        let Some(content) = self.chat.composing() else {
+            return false;
+        };
+        !content.trim().is_empty() && Some(content) != self.saved_draft.as_deref()
+    }
+
+    /// Whether a message was sent but not yet acknowledged by the server.
+    pub fn is_sending(&self) -> bool {
+        self.last_msg_sent.is_some()
+    }
+
+    /// Whether the room is shown in zen mode, without any surrounding UI.
+    pub fn zen(&self) -> bool {
+        self.chat.zen()
+    }
+
     pub async fn widget(&mut self) -> BoxedAsync<'_, UiError> {
+        let theme = &self.config.theme;
         self.stabilize().await;
 
         let room_state = self.room.as_ref().map(|room| room.state());
         let status_widget = self.status_widget(room_state).await;
         let chat = match room_state.and_then(|s| s.joined()) {
+            _ if self.chat.zen() => {
+                let session = room_state.and_then(|s| s.joined()).map(|j| &j.session);
+                let nick = session.map(|s| s.name.clone()).unwrap_or_default();
+                let user_id = session.map(|s| s.id.0.clone());
+                self.chat.widget(nick, user_id, true)
+            }
             Some(joined) => Self::widget_with_nick_list(
+                theme,
                 &mut self.chat,
                 status_widget,
                 &mut self.nick_list,
                 joined,
+                &self.session_filter,
                 self.focus,
             ),
             None => Self::widget_without_nick_list(&mut self.chat, status_widget),
@@ -227,20 +786,51 @@ impl EuphRoom {
 
         match &mut self.state {
             State::Normal => {}
-            State::Auth(editor) => layers.push(auth::widget(editor).desync().boxed_async()),
-            State::Nick(editor) => layers.push(nick::widget(editor).desync().boxed_async()),
-            State::Account(account) => layers.push(account.widget().desync().boxed_async()),
+            State::Auth(editor) => layers.push(auth::widget(theme, editor).desync().boxed_async()),
+            State::Nick(nick) => layers.push(nick.widget(theme).desync().boxed_async()),
+            State::Account(account) => layers.push(account.widget(theme).desync().boxed_async()),
             State::Links(links) => layers.push(links.widget().desync().boxed_async()),
             State::InspectMessage(message) => {
-                layers.push(inspect::message_widget(message).desync().boxed_async())
+                let widget = message.widget(theme, &self.config.keys);
+                layers.push(widget.desync().boxed_async())
+            }
+            State::InspectSession(session) => layers.push(
+                inspect::session_widget(theme, session)
+                    .desync()
+                    .boxed_async(),
+            ),
+            State::Stats(stats) => layers.push(stats.widget(theme).desync().boxed_async()),
+            State::Activity(activity) => layers.push(activity.widget(theme).desync().boxed_async()),
+            State::Note(note) => layers.push(note.widget(theme).desync().boxed_async()),
+            State::Snippets(snippets) => layers.push(snippets.widget().desync().boxed_async()),
+            State::Scheduled(scheduled) => {
+                let widget = scheduled.widget(theme, &self.config.keys);
+                layers.push(widget.desync().boxed_async())
             }
-            State::InspectSession(session) => {
-                layers.push(inspect::session_widget(session).desync().boxed_async())
+            State::Pinned(pinned) => {
+                let widget = pinned.widget(theme, &self.config.keys);
+                layers.push(widget.desync().boxed_async())
+            }
+            State::ConfirmSend(confirm) => {
+                let nick = room_state
+                    .and_then(|s| s.joined())
+                    .map(|joined| joined.session.name.clone())
+                    .unwrap_or_default();
+                let widget = confirm.widget(theme, &self.config.keys, &nick);
+                layers.push(widget.desync().boxed_async())
+            }
+            State::RestoreDraft(text) => {
+                let widget = draft::widget(theme, &self.config.keys, text);
+                layers.push(widget.desync().boxed_async())
             }
         }
 
+        if !self.toasts.is_empty() {
+            layers.push(toast::widget(theme, &self.toasts).desync().boxed_async());
+        }
+
         for popup in &self.popups {
-            layers.push(popup.widget().desync().boxed_async());
+            layers.push(popup.widget(theme).desync().boxed_async());
         }
 
         Layer::new(layers).boxed_async()
@@ -250,7 +840,7 @@ impl EuphRoom {
         chat: &mut EuphChatState,
         status_widget: impl Widget<UiError> + Send + Sync + 'static,
     ) -> BoxedAsync<'_, UiError> {
-        let chat_widget = chat.widget(String::new(), true);
+        let chat_widget = chat.widget(String::new(), None, true);
 
         Join2::vertical(
             status_widget.desync().segment().with_fixed(true),
@@ -260,19 +850,24 @@ impl EuphRoom {
     }
 
     fn widget_with_nick_list<'a>(
+        theme: &Theme,
         chat: &'a mut EuphChatState,
         status_widget: impl Widget<UiError> + Send + Sync + 'static,
         nick_list: &'a mut ListState<SessionId>,
         joined: &Joined,
+        filter: &SessionFilter,
         focus: Focus,
     ) -> BoxedAsync<'a, UiError> {
-        let nick_list_widget = nick_list::widget(nick_list, joined, focus == Focus::NickList)
+        let focused = focus == Focus::NickList;
+        let nick_list_widget = nick_list::widget(theme, nick_list, joined, filter, focused)
             .padding()
             .with_right(1)
             .border()
             .desync();
 
-        let chat_widget = chat.widget(joined.session.name.clone(), focus == Focus::Chat);
+        let nick = joined.session.name.clone();
+        let user_id = Some(joined.session.id.0.clone());
+        let chat_widget = chat.widget(nick, user_id, focus == Focus::Chat);
 
         Join2::horizontal(
             Join2::vertical(
@@ -286,12 +881,28 @@ impl EuphRoom {
     }
 
     async fn status_widget(&self, state: Option<&euph::State>) -> impl Widget<UiError> {
-        let room_style = Style::new().bold().blue();
-        let mut info = Styled::new(format!("{} ", self.domain()), Style::new().grey())
+        let theme = &self.config.theme;
+        let room_style = theme.room();
+        let mut info = Styled::new(format!("{} ", self.domain()), theme.info())
             .then(format!("&{}", self.name()), room_style);
 
         info = match state {
-            None | Some(euph::State::Stopped) => info.then_plain(", archive"),
+            None | Some(euph::State::Stopped) => {
+                let (what, action) = match self.gave_up {
+                    Some(attempts) => (format!("gave up after {attempts} attempts"), "retry"),
+                    None => ("offline".to_string(), "connect"),
+                };
+                info.then_plain(", ")
+                    .then(what, Style::new().bold().yellow())
+                    .then_plain(", press ")
+                    .and_then(key_bindings::format_binding(
+                        &self.config.keys.room.action.connect,
+                    ))
+                    .then_plain(format!(" to {action}"))
+            }
+            Some(euph::State::Disconnected | euph::State::Connecting) if self.stalled => {
+                info.then_plain(", stalled, reconnecting...")
+            }
             Some(euph::State::Disconnected) => info.then_plain(", waiting..."),
             Some(euph::State::Connecting) => info.then_plain(", connecting..."),
             Some(euph::State::Connected(_, conn::State::Joining(j))) if j.bounce.is_some() => {
@@ -302,15 +913,53 @@ impl EuphRoom {
             }
             Some(euph::State::Connected(_, conn::State::Joined(j))) => {
                 let nick = &j.session.name;
-                if nick.is_empty() {
+                let info = if self.room_is_private == Some(true) {
+                    info.then_plain(", private")
+                } else {
+                    info
+                };
+                let info = if nick.is_empty() {
                     info.then_plain(", present without nick")
                 } else {
                     info.then_plain(", present as ")
                         .and_then(euph::style_nick(nick, Style::new()))
-                }
+                };
+                let pbln = euph::format_pbln(j, &self.session_filter);
+                info.then(format!(" [{pbln}]"), theme.info())
             }
         };
 
+        let connected = matches!(state, Some(euph::State::Connected(..)));
+        if let Some(skew) = self.clock_skew().filter(|_| connected) {
+            let direction = if skew.is_positive() {
+                "ahead"
+            } else {
+                "behind"
+            };
+            let warning = format!(", clock {} {direction}", format_clock_skew(skew));
+            info = info.then(warning, Style::new().bold().red());
+        }
+
+        // In ephemeral mode, every room is kept only in memory.
+        if !self.config.ephemeral && self.vault().vault().vault().ephemeral() {
+            info = info.then(", incognito", Style::new().magenta());
+        }
+
+        if self.chat.following() {
+            info = info.then(", following", Style::new().bold().cyan());
+        }
+
+        if let Some(position) = logging_unwrap!(self.chat.thread_position().await) {
+            if let Some((index, siblings)) = position.sibling {
+                info = info.then(format!(", reply {index}/{siblings}"), theme.info());
+            }
+            match position.replies {
+                0 => {}
+                1 => info = info.then(", 1 reply below", theme.info()),
+                n => info = info.then(format!(", {n} replies below"), theme.info()),
+            }
+        }
+
         let unseen = self.unseen_msgs_count().await;
         if unseen > 0 {
             info = info
@@ -319,6 +968,24 @@ impl EuphRoom {
                 .then_plain(")");
         }
 
+        let joined = state.and_then(|s| s.joined());
+        if let (Some(content), Some(joined)) = (self.chat.composing(), joined) {
+            for (mention, suggestion) in euph::unknown_mentions(content, joined) {
+                let warning = match suggestion {
+                    Some(nick) => {
+                        format!("\nNobody here is called @{mention}, did you mean @{nick}?")
+                    }
+                    None => format!("\nNobody here is called @{mention}"),
+                };
+                info = info.then(warning, Style::new().yellow());
+            }
+        }
+
+        if self.config.screen_reader {
+            let description = logging_unwrap!(self.chat.cursor_description().await);
+            info = info.then_plain("\nCursor is ").and_then(description);
+        }
+
         let title = if unseen > 0 {
             format!("&{} ({unseen})", self.name())
         } else {
@@ -333,28 +1000,201 @@ impl EuphRoom {
     }
 
     async fn handle_chat_input_event(&mut self, event: &mut InputEvent<'_>, keys: &Keys) -> bool {
-        let can_compose = self.room_state_joined().is_some();
+        // While offline, only commands like `/connect` can be composed.
+        let can_compose = self.room_state_joined().is_some() || self.stopped();
+
+        if event.matches(&keys.general.focus) {
+            if let Some(completed) = self.chat.composing().and_then(commands::complete) {
+                self.chat.set_composing(event.widthdb(), completed);
+                return true;
+            }
+        }
+
+        if event.matches(&keys.editor.action.snippet) {
+            if let Some(text) = self.chat.composing() {
+                match snippets::expansion(self.config, text) {
+                    Some((name, snippet)) => {
+                        let text = format!("{}{snippet}", &text[..text.len() - name.len()]);
+                        self.chat.set_composing(event.widthdb(), text);
+                    }
+                    None => self.state = State::Snippets(SnippetsState::new(self.config)),
+                }
+                return true;
+            }
+        }
 
         let reaction = self.chat.handle_input_event(event, keys, can_compose).await;
         let reaction = logging_unwrap!(reaction);
 
+        // Leaving the editor without submitting keeps the scheduled message as
+        // it was.
+        let composed = matches!(reaction, Reaction::Composed { .. });
+        if self.editing_scheduled.is_some() && !composed && self.chat.composing().is_none() {
+            self.editing_scheduled = None;
+            self.scheduled = None;
+        }
+
         match reaction {
             Reaction::NotHandled => {}
             Reaction::Handled => return true,
-            Reaction::Composed { parent, content } => {
+            Reaction::FetchMsg(id) => {
                 if let Some(room) = &self.room {
-                    match room.send(parent, content) {
-                        Ok(id_rx) => self.last_msg_sent = Some(id_rx),
-                        Err(_) => self.chat.send_failed(),
-                    }
+                    let _ = room.get_msg(id);
+                }
+                return true;
+            }
+            Reaction::FetchAncestors(id) => {
+                if let Some(room) = &mut self.room {
+                    let _ = room.get_msg_with_ancestors(id);
+                }
+                return true;
+            }
+            Reaction::Composed { parent, content } => {
+                if let Some(scheduled) = self.editing_scheduled.take() {
+                    self.reschedule(scheduled, parent, content).await;
                     return true;
                 }
+
+                match commands::parse(content) {
+                    Parsed::Send(_) if self.send_cooldown().is_some() => {
+                        // Reopen the editor so the message can be sent once
+                        // the countdown is over.
+                        self.chat.command_executed(false);
+                    }
+                    Parsed::Send(content) if self.room_config.confirm_send => {
+                        let confirm = ConfirmSendState::new(parent, content);
+                        self.state = State::ConfirmSend(confirm);
+                    }
+                    Parsed::Send(content) => self.send(parent, content),
+                    Parsed::Command(Command::Schedule { delay, content }) => {
+                        let time = Time(OffsetDateTime::now_utc() + delay);
+                        match self.vault().add_scheduled(parent, time, content).await {
+                            Ok(()) => {
+                                self.scheduled = None;
+                                self.chat.command_executed(true);
+                            }
+                            Err(err) => {
+                                self.chat.command_executed(false);
+                                self.popups.push_front(RoomPopup::Error {
+                                    description: "Failed to schedule message".to_string(),
+                                    reason: format!("{err}"),
+                                });
+                            }
+                        }
+                    }
+                    Parsed::Command(Command::Ignore(who)) => self.set_ignored(who, true).await,
+                    Parsed::Command(Command::Unignore(who)) => self.set_ignored(who, false).await,
+                    Parsed::Command(command) => {
+                        let executed = self.run_command(command);
+                        self.chat.command_executed(executed);
+                    }
+                    Parsed::Error(reason) => {
+                        self.chat.command_executed(false);
+                        self.popups.push_front(RoomPopup::Error {
+                            description: "Invalid command".to_string(),
+                            reason,
+                        });
+                    }
+                }
+                return true;
             }
         }
 
         false
     }
 
+    /// Send a message as a new thread without involving the editor, for
+    /// example when broadcasting it to several rooms. Returns the receiver for
+    /// the sent message's id, or `None` if the room is not connected.
+    pub fn send_detached(&self, content: String) -> Option<oneshot::Receiver<MessageId>> {
+        match &self.room {
+            Some(room) if self.room_state_joined().is_some() => room.send(None, content).ok(),
+            _ => None,
+        }
+    }
+
+    /// Replace a scheduled message by its edited version, keeping its time.
+    /// The old version is only removed once the new one is stored.
+    async fn reschedule(
+        &mut self,
+        scheduled: ScheduledMsg,
+        parent: Option<MessageId>,
+        content: String,
+    ) {
+        let vault = self.vault();
+        let result = async {
+            vault.add_scheduled(parent, scheduled.time, content).await?;
+            vault.delete_scheduled(scheduled.id).await
+        }
+        .await;
+
+        self.scheduled = None;
+        match result {
+            Ok(()) => self.chat.command_executed(true),
+            Err(err) => {
+                self.editing_scheduled = Some(scheduled);
+                self.chat.command_executed(false);
+                self.popups.push_front(RoomPopup::Error {
+                    description: "Failed to schedule message".to_string(),
+                    reason: format!("{err}"),
+                });
+            }
+        }
+    }
+
+    fn send(&mut self, parent: Option<MessageId>, content: String) {
+        match &self.room {
+            Some(room) => match room.send(parent, content) {
+                Ok(id_rx) => {
+                    self.last_msg_sent = Some(id_rx);
+                    self.last_sent_at = Some(Instant::now());
+                }
+                Err(_) => self.chat.send_failed(),
+            },
+            None => self.chat.send_failed(),
+        }
+    }
+
+    /// Returns whether the command could be executed.
+    fn run_command(&mut self, command: Command) -> bool {
+        match command {
+            Command::Nick(nick) => match &self.room {
+                Some(room) if self.room_state_joined().is_some() => {
+                    let _ = room.nick(nick);
+                }
+                _ => {
+                    let description = "Failed to change nick.".to_string();
+                    let reason = "not joined to the room".to_string();
+                    self.toasts.push_front(Toast::new(description, reason));
+                    return false;
+                }
+            },
+            Command::Disconnect => self.disconnect(),
+            command => self.command = Some(command),
+        }
+        true
+    }
+
+    /// Start or stop ignoring a nick or user id, see `/ignore`.
+    async fn set_ignored(&mut self, who: String, ignored: bool) {
+        match self.vault().set_ignored(who.clone(), ignored).await {
+            Ok(()) => {
+                self.session_filter.ignored.retain(|w| *w != who);
+                if ignored {
+                    self.session_filter.ignored.push(who);
+                }
+                self.chat.command_executed(true);
+            }
+            Err(err) => {
+                self.chat.command_executed(false);
+                self.popups.push_front(RoomPopup::Error {
+                    description: "Failed to update ignored users".to_string(),
+                    reason: format!("{err}"),
+                });
+            }
+        }
+    }
+
     async fn handle_room_input_event(&mut self, event: &mut InputEvent<'_>, keys: &Keys) -> bool {
         match self.room_state() {
             // Authenticating
@@ -373,7 +1213,7 @@ impl EuphRoom {
             // Joined
             Some(euph::State::Connected(_, conn::State::Joined(joined))) => {
                 if event.matches(&keys.room.action.nick) {
-                    self.state = State::Nick(nick::new(joined.clone()));
+                    self.state = State::Nick(NickState::new(joined.clone()));
                     return true;
                 }
                 if event.matches(&keys.room.action.more_messages) {
@@ -383,7 +1223,22 @@ impl EuphRoom {
                     return true;
                 }
                 if event.matches(&keys.room.action.account) {
-                    self.state = State::Account(AccountUiState::new());
+                    if self.capabilities.accounts {
+                        self.state = State::Account(AccountUiState::new());
+                    } else {
+                        self.popups.push_front(RoomPopup::Error {
+                            description: "Can't manage account.".to_string(),
+                            reason: NOT_SUPPORTED.to_string(),
+                        });
+                    }
+                    return true;
+                }
+                if event.matches(&keys.room.action.toggle_bots) {
+                    self.session_filter.hide_bots = !self.session_filter.hide_bots;
+                    return true;
+                }
+                if event.matches(&keys.room.action.toggle_lurkers) {
+                    self.session_filter.hide_lurkers = !self.session_filter.hide_lurkers;
                     return true;
                 }
             }
@@ -410,10 +1265,40 @@ impl EuphRoom {
             return true;
         }
 
+        if event.matches(&keys.room.action.stats) {
+            let start = StatsState::start();
+            let stats = logging_unwrap!(self.vault().stats(Time(start)).await);
+            self.state = State::Stats(StatsState::new(start, &stats));
+            return true;
+        }
+
+        if event.matches(&keys.room.action.activity) {
+            let start = ActivityState::start();
+            let activity = logging_unwrap!(self.vault().activity(start).await);
+            self.state = State::Activity(ActivityState::new(start, &activity));
+            return true;
+        }
+
+        if event.matches(&keys.room.action.scheduled) {
+            let scheduled = logging_unwrap!(self.vault().scheduled().await);
+            self.state = State::Scheduled(ScheduledState::new(scheduled));
+            return true;
+        }
+
+        if event.matches(&keys.room.action.pinned) {
+            let nick = self.room_config.pin_nick.clone();
+            let prefix = self.room_config.pin_prefix.clone();
+            let configured = nick.is_some() || prefix.is_some();
+            let pinned = logging_unwrap!(self.vault().pinned(nick, prefix, PINNED_AMOUNT).await);
+            self.state = State::Pinned(PinnedState::new(configured, pinned));
+            return true;
+        }
+
         if event.matches(&keys.tree.action.inspect) {
             if let Some(id) = self.chat.cursor() {
                 if let Some(msg) = logging_unwrap!(self.vault().full_msg(*id).await) {
-                    self.state = State::InspectMessage(msg);
+                    let deleted = logging_unwrap!(self.vault().deleted_content(*id).await);
+                    self.state = State::InspectMessage(InspectMessageState::new(msg, deleted));
                 }
             }
             return true;
@@ -428,9 +1313,86 @@ impl EuphRoom {
             return true;
         }
 
+        if event.matches(&keys.tree.action.translate) {
+            if let Some(id) = self.chat.cursor().copied() {
+                if !self.chat.toggle_translation(&id) {
+                    self.request_translation(id).await;
+                }
+            }
+            return true;
+        }
+
+        if event.matches(&keys.tree.action.note) {
+            if let Some(id) = self.chat.cursor().copied() {
+                self.state = State::Note(NoteState::new(self.vault(), id).await);
+            }
+            return true;
+        }
+
+        let copy_id = event.matches(&keys.tree.action.copy_id);
+        let copy_sender_id = event.matches(&keys.tree.action.copy_sender_id);
+        if copy_id || copy_sender_id {
+            if let Some(id) = self.chat.cursor() {
+                if let Some(msg) = logging_unwrap!(self.vault().full_msg(*id).await) {
+                    let text = if copy_id {
+                        msg.id.0.to_string()
+                    } else {
+                        msg.sender.id.to_string()
+                    };
+                    if let Err(err) = clipboard::copy(self.config, &text).await {
+                        self.popups.push_front(RoomPopup::Error {
+                            description: "Failed to copy to clipboard.".to_string(),
+                            reason: format!("{err:#}"),
+                        });
+                    }
+                }
+            }
+            return true;
+        }
+
+        if event.matches(&keys.tree.action.subscribe) {
+            if let Some(id) = self.chat.cursor().copied() {
+                let subscribed = !logging_unwrap!(self.vault().subscribed(id).await);
+                logging_unwrap!(self.vault().set_subscribed(id, subscribed).await);
+                let description = if subscribed {
+                    "Replies to this message now count as mentions."
+                } else {
+                    "Replies to this message no longer count as mentions."
+                };
+                self.popups.push_front(RoomPopup::Notice {
+                    description: description.to_string(),
+                });
+            }
+            return true;
+        }
+
         false
     }
 
+    async fn request_translation(&mut self, id: MessageId) {
+        let Some(msg) = logging_unwrap!(self.vault().msg(id).await) else {
+            return;
+        };
+
+        self.chat.set_translation(id, Translation::Pending);
+
+        let config = &self.config.translate;
+        let user_agent = version::user_agent(self.config);
+        let translations_tx = self.translations_tx.clone();
+        let ui_event_tx = self.ui_event_tx.clone();
+        tokio::task::spawn(async move {
+            let translation = match euph::translate(config, &user_agent, &msg.content).await {
+                Ok(text) => Translation::Done(text),
+                Err(err) => {
+                    warn!("failed to translate message: {err:#}");
+                    Translation::Failed
+                }
+            };
+            let _ = translations_tx.send((id, translation));
+            let _ = ui_event_tx.send(UiEvent::Translated);
+        });
+    }
+
     fn handle_nick_list_focus_input_event(
         &mut self,
         event: &mut InputEvent<'_>,
@@ -464,7 +1426,8 @@ impl EuphRoom {
                     return true;
                 }
 
-                if self.room_state_joined().is_some() && event.matches(&keys.general.focus) {
+                let has_nick_list = self.room_state_joined().is_some() && !self.chat.zen();
+                if has_nick_list && event.matches(&keys.general.focus) {
                     self.focus = Focus::NickList;
                     return true;
                 }
@@ -497,12 +1460,22 @@ impl EuphRoom {
         let result = match &mut self.state {
             State::Normal => return self.handle_normal_input_event(event, keys).await,
             State::Auth(editor) => auth::handle_input_event(event, keys, &self.room, editor),
-            State::Nick(editor) => nick::handle_input_event(event, keys, &self.room, editor),
+            State::Nick(nick) => nick.handle_input_event(event, keys, &self.room),
             State::Account(account) => account.handle_input_event(event, keys, &self.room),
             State::Links(links) => links.handle_input_event(event, keys),
-            State::InspectMessage(_) | State::InspectSession(_) => {
-                inspect::handle_input_event(event, keys)
+            State::InspectMessage(message) => message.handle_input_event(event, keys),
+            State::InspectSession(_) => inspect::handle_input_event(event, keys),
+            State::Stats(_) => stats::handle_input_event(event, keys),
+            State::Activity(_) => activity::handle_input_event(event, keys),
+            State::Note(note) => {
+                note.handle_input_event(event, keys, self.chat.store())
+                    .await
             }
+            State::Snippets(snippets) => snippets.handle_input_event(event, keys),
+            State::Scheduled(scheduled) => scheduled.handle_input_event(event, keys),
+            State::Pinned(pinned) => pinned.handle_input_event(event, keys),
+            State::ConfirmSend(confirm) => confirm.handle_input_event(event, keys),
+            State::RestoreDraft(text) => draft::handle_input_event(event, keys, text),
         };
 
         match result {
@@ -519,13 +1492,68 @@ impl EuphRoom {
                 });
                 true
             }
+            PopupResult::InsertSnippet(snippet) => {
+                self.state = State::Normal;
+                self.chat.insert_composing(event.widthdb(), &snippet);
+                true
+            }
+            PopupResult::RestoreDraft(text) => {
+                self.state = State::Normal;
+                self.chat.restore_composing(event.widthdb(), None, text);
+                true
+            }
+            PopupResult::DiscardDraft => {
+                self.state = State::Normal;
+                logging_unwrap!(self.vault().set_draft(None).await);
+                self.saved_draft = None;
+                true
+            }
+            PopupResult::EditScheduled(msg) => {
+                self.state = State::Normal;
+                let (parent, content) = (msg.parent, msg.content.clone());
+                if self
+                    .chat
+                    .restore_composing(event.widthdb(), parent, content)
+                {
+                    self.editing_scheduled = Some(msg);
+                } else {
+                    self.popups.push_front(RoomPopup::Error {
+                        description: "Failed to edit scheduled message".to_string(),
+                        reason: "The editor is not empty".to_string(),
+                    });
+                }
+                true
+            }
+            PopupResult::Send { parent, content } => {
+                self.state = State::Normal;
+                self.send(parent, content);
+                true
+            }
+            PopupResult::CancelSend => {
+                self.state = State::Normal;
+                self.chat.command_executed(false);
+                true
+            }
+            PopupResult::JumpToMsg(id) => {
+                self.state = State::Normal;
+                self.chat.move_cursor_to(id);
+                true
+            }
+            PopupResult::CancelScheduled(id) => {
+                logging_unwrap!(self.vault().delete_scheduled(id).await);
+                self.scheduled = None;
+                true
+            }
         }
     }
 
-    pub async fn handle_event(&mut self, event: Event) -> bool {
+    /// Handle an event of the room's connection. `focused` is whether the
+    /// user is currently looking at the room.
+    pub async fn handle_event(&mut self, event: Event, focused: bool) -> bool {
         let Some(room) = &self.room else { return false };
 
-        if event.config().name != room.instance().config().name {
+        let current = room.instance().map(|i| &i.config().name);
+        if current != Some(&event.config().name) {
             // If we allowed names other than the current one, old instances
             // that haven't yet shut down properly could mess up our state.
             return false;
@@ -534,10 +1562,7 @@ impl EuphRoom {
         // We handle the packet internally first because the room event handling
         // will consume it while we only need a reference.
         let handled = if let Event::Packet(_, packet, _) = &event {
-            match &packet.content {
-                Ok(data) => self.handle_euph_data(data),
-                Err(reason) => self.handle_euph_error(packet.r#type, reason),
-            }
+            self.handle_packet(packet, focused).await
         } else {
             // The room state changes, which always means a redraw.
             true
@@ -550,17 +1575,89 @@ impl EuphRoom {
             .handle_event(event)
             .await;
 
+        self.give_up_if_out_of_attempts();
+
         handled
     }
 
+    /// Handle a packet recorded in an earlier session, see `--replay`.
+    pub async fn handle_replayed_packet(&mut self, packet: ParsedPacket, focused: bool) -> bool {
+        if self.room.is_none() {
+            self.room = Some(self.replay_room());
+        }
+
+        let handled = self.handle_packet(&packet, focused).await;
+        self.room
+            .as_mut()
+            .expect("room was just created")
+            .replay_packet(packet)
+            .await;
+        handled
+    }
+
+    async fn handle_packet(&mut self, packet: &ParsedPacket, focused: bool) -> bool {
+        if let Ok(Data::SendEvent(SendEvent(msg))) = &packet.content {
+            // Replayed messages were already notified about when recording.
+            if !focused && !self.replaying {
+                self.notify(msg).await;
+            }
+        }
+
+        match &packet.content {
+            Ok(data) => self.handle_euph_data(data),
+            Err(reason) => self.handle_euph_error(packet.r#type, reason),
+        }
+    }
+
+    /// Run `notify.command` for a new message if it matches `notify.on`.
+    async fn notify(&self, msg: &Message) {
+        let Some(joined) = self.room_state_joined() else {
+            return;
+        };
+        let on = self
+            .room_config
+            .notify_on
+            .as_ref()
+            .unwrap_or(&self.config.notify.on);
+        euph::notify(&self.config.notify, on, self.vault(), joined, msg).await;
+    }
+
+    /// Compare the server's time from a ping event with the local clock.
+    ///
+    /// Returns whether the clock skew warning appeared or disappeared.
+    fn update_clock_skew(&mut self, server_time: Time) -> bool {
+        let was_skewed = self.clock_skew().is_some();
+        self.clock_skew = Some(OffsetDateTime::now_utc() - server_time.0);
+        let skewed = self.clock_skew();
+        if let Some(skew) = skewed.filter(|_| !was_skewed) {
+            warn!(
+                room:? = self.vault().room();
+                "{}: local clock is off by {} seconds",
+                self.name(),
+                skew.whole_seconds()
+            );
+        }
+        was_skewed != skewed.is_some()
+    }
+
+    /// The clock skew, if it exceeds the `clock_skew_warning` config option.
+    fn clock_skew(&self) -> Option<time::Duration> {
+        let threshold = self.config.clock_skew_warning;
+        self.clock_skew
+            .filter(|_| threshold > 0)
+            .filter(|skew| skew.whole_seconds().unsigned_abs() > threshold)
+    }
+
     fn handle_euph_data(&mut self, data: &Data) -> bool {
         // These packets don't result in any noticeable change in the UI.
         #[allow(clippy::match_like_matches_macro)]
         let handled = match data {
-            Data::PingEvent(_) | Data::PingReply(_) => {
-                // Pings are displayed nowhere in the room UI.
-                false
+            Data::PingEvent(ping) => {
+                // Pings are displayed nowhere in the room UI, but the clock
+                // skew warning derived from them is.
+                self.update_clock_skew(ping.time)
             }
+            Data::PingReply(_) => false,
             Data::DisconnectEvent(_) => {
                 // Followed by the server closing the connection, meaning that
                 // we'll get an `EuphRoomEvent::Disconnected` soon after this.
@@ -582,16 +1679,60 @@ impl EuphRoom {
         if let Some((action, reason)) = error {
             let description = format!("Failed to {action}.");
             let reason = reason.unwrap_or_else(|| "no idea, the server wouldn't say".to_string());
-            self.popups.push_front(RoomPopup::Error {
-                description,
-                reason,
-            });
+            self.toasts.push_front(Toast::new(description, reason));
+        }
+
+        if let (Data::NickReply(_), State::Nick(nick)) = (data, &self.state) {
+            if nick.pending() {
+                self.state = State::Normal;
+            }
+        }
+
+        if let Data::HelloEvent(hello) = data {
+            self.on_hello(&hello.version, hello.room_is_private);
+        }
+
+        if let Data::SendEvent(_) = data {
+            self.chat.msg_arrived();
         }
 
         handled
     }
 
+    fn on_hello(&mut self, version: &str, is_private: bool) {
+        let mut changes = vec![];
+
+        if let Some(old) = self.server_version.as_ref().filter(|old| *old != version) {
+            changes.push(format!("The server was updated from {old} to {version}."));
+            self.capabilities = Capabilities::new();
+        }
+        if self.room_is_private.is_some_and(|old| old != is_private) {
+            changes.push(if is_private {
+                "The room is now private.".to_string()
+            } else {
+                "The room is no longer private.".to_string()
+            });
+        }
+
+        self.server_version = Some(version.to_string());
+        self.room_is_private = Some(is_private);
+
+        if !changes.is_empty() {
+            self.popups.push_front(RoomPopup::Notice {
+                description: changes.join("\n"),
+            });
+        }
+    }
+
     fn handle_euph_error(&mut self, r#type: PacketType, reason: &str) -> bool {
+        // Shown inside the popup instead, so the user can try again right away
+        if let (PacketType::NickReply, State::Nick(nick)) = (r#type, &mut self.state) {
+            if nick.pending() {
+                nick.failed(reason.to_string());
+                return true;
+            }
+        }
+
         let action = match r#type {
             PacketType::AuthReply => "authenticate",
             PacketType::NickReply => "set nick",
@@ -614,11 +1755,22 @@ impl EuphRoom {
             PacketType::UnbanReply => "unban",
             _ => return false,
         };
+
+        let mut reason = reason.to_string();
+        if is_unknown_command(r#type, &reason) {
+            if let Some(supported) = self.capabilities.of_reply(r#type) {
+                *supported = false;
+                reason = NOT_SUPPORTED.to_string();
+            }
+        }
+
+        // Don't leave the user in a popup whose commands can't succeed
+        if !self.capabilities.accounts && matches!(self.state, State::Account(_)) {
+            self.state = State::Normal;
+        }
+
         let description = format!("Failed to {action}.");
-        self.popups.push_front(RoomPopup::Error {
-            description,
-            reason: reason.to_string(),
-        });
+        self.toasts.push_front(Toast::new(description, reason));
         true
     }
 }
@@ -0,0 +1,120 @@
+//! Listing, editing and cancelling messages scheduled with `/in`.
+
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use cove_vault::ScheduledMsg;
+use crossterm::style::Stylize;
+use time::OffsetDateTime;
+use toss::widgets::{Join2, Text};
+use toss::{Style, Styled, Widget, WidgetExt};
+
+use crate::ui::widgets::{ListBuilder, ListState, Popup};
+use crate::ui::{key_bindings, util, UiError};
+
+use super::popup::PopupResult;
+
+fn format_remaining(msg: &ScheduledMsg) -> String {
+    let secs = (msg.time.0 - OffsetDateTime::now_utc())
+        .whole_seconds()
+        .max(0);
+    match secs {
+        s if s < 60 => format!("in {s}s"),
+        s if s < 60 * 60 => format!("in {}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("in {}h{}m", s / 60 / 60, s / 60 % 60),
+        s => format!("in {}d{}h", s / 60 / 60 / 24, s / 60 / 60 % 24),
+    }
+}
+
+pub struct ScheduledState {
+    msgs: Vec<ScheduledMsg>,
+    list: ListState<i64>,
+}
+
+impl ScheduledState {
+    pub fn new(msgs: Vec<ScheduledMsg>) -> Self {
+        Self {
+            msgs,
+            list: ListState::new(),
+        }
+    }
+
+    pub fn widget(&mut self, theme: &Theme, keys: &Keys) -> impl Widget<UiError> + '_ {
+        let style_selected = theme.cursor();
+
+        let mut list_builder = ListBuilder::new();
+
+        if self.msgs.is_empty() {
+            list_builder.add_unsel(Text::new((
+                "No messages scheduled, send one later with /in <delay> <message>",
+                theme.info().italic(),
+            )));
+        }
+
+        for msg in &self.msgs {
+            let remaining = format!("{:>7}", format_remaining(msg));
+            // Only the first line of multi-line messages is shown.
+            let preview = msg.content.lines().next().unwrap_or_default().to_string();
+            list_builder.add_sel(msg.id, move |selected| {
+                let text = if selected {
+                    Styled::new(remaining, style_selected.bold())
+                        .then(" ", style_selected)
+                        .then(preview, style_selected)
+                } else {
+                    Styled::new(remaining, Style::new().bold())
+                        .then_plain(" ")
+                        .then_plain(preview)
+                };
+                Text::new(text).with_wrap(false)
+            });
+        }
+
+        let hint = Styled::new_plain("Press ")
+            .and_then(key_bindings::format_binding(&keys.general.confirm))
+            .then_plain(" to edit or ")
+            .and_then(key_bindings::format_binding(&keys.editor.action.delete))
+            .then_plain(" to cancel a message.");
+
+        let inner = Join2::vertical(
+            list_builder.build(&mut self.list).segment(),
+            Text::new(hint)
+                .padding()
+                .with_top(1)
+                .segment()
+                .with_fixed(true),
+        );
+
+        Popup::new(theme, inner, "Scheduled messages")
+    }
+
+    fn selected(&self) -> Option<&ScheduledMsg> {
+        let id = self.list.selected()?;
+        self.msgs.iter().find(|msg| msg.id == *id)
+    }
+
+    pub fn handle_input_event(&mut self, event: &mut InputEvent<'_>, keys: &Keys) -> PopupResult {
+        if event.matches(&keys.general.abort) {
+            return PopupResult::Close;
+        }
+
+        if event.matches(&keys.general.confirm) {
+            if let Some(msg) = self.selected() {
+                return PopupResult::EditScheduled(msg.clone());
+            }
+            return PopupResult::Handled;
+        }
+
+        if event.matches(&keys.editor.action.delete) {
+            if let Some(id) = self.selected().map(|msg| msg.id) {
+                self.msgs.retain(|msg| msg.id != id);
+                return PopupResult::CancelScheduled(id);
+            }
+            return PopupResult::Handled;
+        }
+
+        if util::handle_list_input_event(&mut self.list, event, keys) {
+            return PopupResult::Handled;
+        }
+
+        PopupResult::NotHandled
+    }
+}
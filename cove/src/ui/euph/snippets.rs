@@ -0,0 +1,100 @@
+//! Inserting the snippets configured via the `snippets` config option.
+
+use cove_config::{Config, Keys};
+use cove_input::InputEvent;
+use crossterm::style::Stylize;
+use toss::widgets::Text;
+use toss::{Style, Styled, Widget};
+
+use crate::ui::widgets::{ListBuilder, ListState, Popup};
+use crate::ui::{util, UiError};
+
+use super::popup::PopupResult;
+
+/// The snippet whose name is at the end of the text, along with the name.
+///
+/// The name must either be the entire text or be preceded by whitespace.
+pub fn expansion<'a>(config: &'a Config, text: &str) -> Option<(&'a str, &'a str)> {
+    config
+        .snippets
+        .iter()
+        .filter(|(name, _)| !name.is_empty())
+        .filter(|(name, _)| match text.strip_suffix(name.as_str()) {
+            Some(before) => before.is_empty() || before.ends_with(char::is_whitespace),
+            None => false,
+        })
+        // Prefer longer names if one name is a suffix of another
+        .max_by_key(|(name, _)| name.len())
+        .map(|(name, snippet)| (name.as_str(), snippet.as_str()))
+}
+
+pub struct SnippetsState {
+    config: &'static Config,
+    list: ListState<String>,
+}
+
+impl SnippetsState {
+    pub fn new(config: &'static Config) -> Self {
+        Self {
+            config,
+            list: ListState::new(),
+        }
+    }
+
+    pub fn widget(&mut self) -> impl Widget<UiError> + '_ {
+        let theme = &self.config.theme;
+        let style_selected = theme.cursor();
+
+        let mut list_builder = ListBuilder::new();
+
+        if self.config.snippets.is_empty() {
+            list_builder.add_unsel(Text::new(("No snippets configured", theme.info().italic())));
+        }
+
+        let mut names = self.config.snippets.keys().collect::<Vec<_>>();
+        names.sort_unstable();
+        for name in names {
+            // Only the first line of multi-line snippets is shown.
+            let snippet = &self.config.snippets[name];
+            let preview = snippet.lines().next().unwrap_or_default().to_string();
+            let name = name.clone();
+            list_builder.add_sel(name.clone(), move |selected| {
+                let text = if selected {
+                    Styled::new(name, style_selected.bold())
+                        .then(" ", style_selected)
+                        .then(preview, style_selected)
+                } else {
+                    Styled::new(name, Style::new().bold())
+                        .then_plain(" ")
+                        .then(preview, theme.info())
+                };
+                Text::new(text).with_wrap(false)
+            });
+        }
+
+        Popup::new(theme, list_builder.build(&mut self.list), "Snippets")
+    }
+
+    pub fn handle_input_event(&mut self, event: &mut InputEvent<'_>, keys: &Keys) -> PopupResult {
+        if event.matches(&keys.general.abort) {
+            return PopupResult::Close;
+        }
+
+        if event.matches(&keys.general.confirm) {
+            if let Some(snippet) = self
+                .list
+                .selected()
+                .and_then(|n| self.config.snippets.get(n))
+            {
+                return PopupResult::InsertSnippet(snippet.clone());
+            }
+            return PopupResult::Handled;
+        }
+
+        if util::handle_list_input_event(&mut self.list, event, keys) {
+            return PopupResult::Handled;
+        }
+
+        PopupResult::NotHandled
+    }
+}
@@ -0,0 +1,81 @@
+//! Popup showing how much traffic a room caused recently.
+
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use cove_vault::RoomStats;
+use crossterm::style::Stylize;
+use time::{Duration, OffsetDateTime};
+use toss::widgets::Text;
+use toss::{Style, Styled, Widget};
+
+use crate::ui::widgets::Popup;
+use crate::ui::UiError;
+
+use super::popup::PopupResult;
+
+/// Amount of hours shown, one column per hour.
+const HOURS: usize = 24;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+fn start_of_hour(time: OffsetDateTime) -> OffsetDateTime {
+    let timestamp = time.unix_timestamp();
+    OffsetDateTime::from_unix_timestamp(timestamp - timestamp.rem_euclid(60 * 60))
+        .expect("timestamp in range")
+}
+
+fn sparkline(values: &[u64]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&value| match value {
+            0 => ' ',
+            _ => SPARKS[(value * (SPARKS.len() as u64 - 1) / max) as usize],
+        })
+        .collect()
+}
+
+pub struct StatsState {
+    events: [u64; HOURS],
+}
+
+impl StatsState {
+    /// Start of the oldest hour that is shown.
+    pub fn start() -> OffsetDateTime {
+        start_of_hour(OffsetDateTime::now_utc()) - Duration::hours(HOURS as i64 - 1)
+    }
+
+    pub fn new(start: OffsetDateTime, stats: &[RoomStats]) -> Self {
+        let mut events = [0; HOURS];
+        for stat in stats {
+            let hour = (stat.hour.0 - start).whole_hours();
+            if let Some(i) = usize::try_from(hour).ok().filter(|i| *i < HOURS) {
+                events[i] += stat.events;
+            }
+        }
+        Self { events }
+    }
+
+    pub fn widget(&self, theme: &Theme) -> impl Widget<UiError> {
+        let label_style = Style::new().cyan();
+        let spark_style = Style::new().green();
+        let total_events = self.events.iter().sum::<u64>();
+
+        let text = Styled::new_plain(format!(
+            "Last {HOURS} hours, one column per hour, oldest first\n\n"
+        ))
+        .then("events ", label_style)
+        .then(sparkline(&self.events), spark_style)
+        .then_plain(format!(" {total_events} total"));
+
+        Popup::new(theme, Text::new(text), "Room stats")
+    }
+}
+
+pub fn handle_input_event(event: &mut InputEvent<'_>, keys: &Keys) -> PopupResult {
+    if event.matches(&keys.general.abort) {
+        return PopupResult::Close;
+    }
+
+    PopupResult::NotHandled
+}
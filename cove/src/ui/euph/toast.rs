@@ -0,0 +1,64 @@
+//! Short-lived notifications about failed commands that, unlike popups, don't
+//! need to be dismissed.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use cove_config::Theme;
+use crossterm::style::Stylize;
+use toss::widgets::Text;
+use toss::{Style, Styled, Widget, WidgetExt};
+
+use crate::ui::UiError;
+
+/// How long a toast stays visible.
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+pub struct Toast {
+    description: String,
+    reason: String,
+    since: Instant,
+}
+
+impl Toast {
+    pub fn new(description: String, reason: String) -> Self {
+        Self {
+            description,
+            reason,
+            since: Instant::now(),
+        }
+    }
+
+    pub fn expired(&self) -> bool {
+        self.since.elapsed() >= TOAST_DURATION
+    }
+}
+
+/// The most recent toast in the top right corner. If there are more, only
+/// their number is shown.
+pub fn widget(theme: &Theme, toasts: &VecDeque<Toast>) -> impl Widget<UiError> {
+    let mut text = Styled::default();
+    if let Some(toast) = toasts.front() {
+        text = text
+            .then(&toast.description, Style::new().bold())
+            .then_plain("\n")
+            .then_plain(&toast.reason);
+        if toasts.len() > 1 {
+            let more = format!("\n(and {} more)", toasts.len() - 1);
+            text = text.then(more, theme.info().italic());
+        }
+    }
+
+    let border_style = Style::new().red().bold();
+    Text::new(text)
+        .padding()
+        .with_horizontal(1)
+        .border()
+        .with_style(border_style)
+        .background()
+        .float()
+        .with_top()
+        .with_right()
+        .padding()
+        .with_top(1)
+}
@@ -2,7 +2,7 @@
 
 use std::convert::Infallible;
 
-use cove_config::{Config, Keys};
+use cove_config::{Config, Keys, Theme};
 use cove_input::{InputEvent, KeyBinding, KeyBindingInfo, KeyGroupInfo};
 use crossterm::style::Stylize;
 use toss::widgets::{Either2, Join2, Padding, Text};
@@ -79,7 +79,8 @@ pub fn widget<'a>(
         render_group_info(&mut list_builder, group_info);
     }
 
-    let scroll_info_style = Style::new().grey().italic();
+    let theme = &config.theme;
+    let scroll_info_style = theme.info().italic();
     let scroll_info = Styled::new("(Scroll with ", scroll_info_style)
         .and_then(format_binding(&config.keys.cursor.down))
         .then(" and ", scroll_info_style)
@@ -95,7 +96,7 @@ pub fn widget<'a>(
             .with_growing(false),
     );
 
-    Popup::new(inner, "Key bindings")
+    Popup::new(theme, inner, "Key bindings")
 }
 
 pub fn handle_input_event(
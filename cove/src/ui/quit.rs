@@ -0,0 +1,24 @@
+//! Popup asking for confirmation before quitting while something would be lost,
+//! see the `confirm_quit` config option.
+
+use cove_config::{Keys, Theme};
+use toss::widgets::Text;
+use toss::{Styled, Widget};
+
+use super::widgets::Popup;
+use super::{key_bindings, UiError};
+
+pub fn widget(theme: &Theme, reasons: &[String], keys: &Keys) -> impl Widget<UiError> {
+    let mut text = Styled::default();
+    for reason in reasons {
+        text = text.then_plain(format!("- {reason}\n"));
+    }
+    let text = text
+        .then_plain("\nPress ")
+        .and_then(key_bindings::format_binding(&keys.general.confirm))
+        .then_plain(" to quit anyway or ")
+        .and_then(key_bindings::format_binding(&keys.general.abort))
+        .then_plain(" to stay.");
+
+    Popup::new(theme, Text::new(text), "Quit?")
+}
@@ -0,0 +1,187 @@
+//! Recording and replaying sessions.
+//!
+//! Recordings are stored in the [JSON Lines](https://jsonlines.org/) format.
+//! Each line contains a single event along with the amount of milliseconds
+//! that passed between the start of the recording and the event.
+//!
+//! Terminal events, packets received from euphoria servers and ticks are
+//! recorded. During a replay, they are fed to the UI in the same order without
+//! waiting. Instead, a virtual clock advances to the time each event was
+//! recorded at, so events are handled in the same batches and the screen is
+//! redrawn at the same points as in the original session.
+//!
+//! Rooms don't connect during a replay. Changes to the vault and anything that
+//! reads the current time, like the timestamps of sent messages, still happen
+//! anew, so a replay only reproduces the original session if it starts from
+//! the same vault.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use cove_vault::RoomIdentifier;
+use euphoxide::api::packet::{Packet, ParsedPacket};
+use euphoxide::bot::instance::Event;
+use serde::{Deserialize, Serialize};
+
+use super::UiEvent;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Recorded {
+    Term(crossterm::event::Event),
+    Packet {
+        domain: String,
+        room: String,
+        packet: Packet,
+    },
+    Tick,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    time: u64,
+    event: Recorded,
+}
+
+pub struct Recorder {
+    start: Instant,
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            start: Instant::now(),
+            out: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    /// Record an event if it is one of the recorded kinds, ignore it otherwise.
+    pub fn record(&mut self, event: &UiEvent) -> io::Result<()> {
+        let event = match event {
+            UiEvent::Term(event) => Recorded::Term(event.clone()),
+            UiEvent::Tick => Recorded::Tick,
+            UiEvent::Euph(Event::Packet(config, packet, _)) => Recorded::Packet {
+                domain: config.server.domain.clone(),
+                room: config.room.clone(),
+                packet: packet.clone().into_packet()?,
+            },
+            _ => return Ok(()),
+        };
+
+        let time = u64::try_from(self.start.elapsed().as_millis()).unwrap_or(u64::MAX);
+        serde_json::to_writer(&mut self.out, &Line { time, event })?;
+        writeln!(self.out)?;
+        // Flush after every event so the recording is still useful if cove
+        // crashes or is killed.
+        self.out.flush()
+    }
+}
+
+struct RecordedEvent {
+    time: Duration,
+    event: UiEvent,
+}
+
+fn invalid_data(line_nr: usize, reason: impl ToString) -> io::Error {
+    let reason = reason.to_string();
+    io::Error::new(ErrorKind::InvalidData, format!("line {line_nr}: {reason}"))
+}
+
+/// Recorded events waiting to be replayed.
+pub struct Replay {
+    events: VecDeque<RecordedEvent>,
+    /// The virtual time of the current batch.
+    clock: Duration,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut events = VecDeque::new();
+        for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line_nr = i + 1;
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let line: Line = serde_json::from_str(&line).map_err(|e| invalid_data(line_nr, e))?;
+            let event = match line.event {
+                Recorded::Term(event) => UiEvent::Term(event),
+                Recorded::Tick => UiEvent::Tick,
+                Recorded::Packet {
+                    domain,
+                    room,
+                    packet,
+                } => {
+                    let packet =
+                        ParsedPacket::from_packet(packet).map_err(|e| invalid_data(line_nr, e))?;
+                    UiEvent::Replayed(RoomIdentifier::new(domain, room), packet)
+                }
+            };
+
+            events.push_back(RecordedEvent {
+                time: Duration::from_millis(line.time),
+                event,
+            });
+        }
+
+        Ok(Self {
+            events,
+            clock: Duration::ZERO,
+        })
+    }
+
+    /// Advance the virtual clock to the next event and return it along with
+    /// all events recorded less than `batch_time` after it.
+    ///
+    /// Like during a live session, a batch ends early after a resize so the UI
+    /// is laid out again right away.
+    pub fn next_batch(&mut self, batch_time: Duration) -> Option<Vec<UiEvent>> {
+        let first = self.events.front()?;
+        self.clock = self.clock.max(first.time);
+        let end = self.clock + batch_time;
+
+        let mut batch = vec![];
+        while let Some(event) = self.events.pop_front() {
+            if event.time >= end && !batch.is_empty() {
+                self.events.push_front(event);
+                break;
+            }
+
+            let resized = matches!(
+                event.event,
+                UiEvent::Term(crossterm::event::Event::Resize(_, _))
+            );
+            batch.push(event.event);
+            if resized {
+                break;
+            }
+        }
+
+        self.clock = end;
+        Some(batch)
+    }
+}
+
+/// What to do with the events of a session.
+#[derive(Default)]
+pub enum Recording {
+    #[default]
+    Off,
+    Record(Recorder),
+    Replay(Replay),
+}
+
+impl Recording {
+    pub fn new(record: Option<&Path>, replay: Option<&Path>) -> io::Result<Self> {
+        Ok(match (record, replay) {
+            (Some(path), _) => Self::Record(Recorder::create(path)?),
+            (None, Some(path)) => Self::Replay(Replay::load(path)?),
+            (None, None) => Self::Off,
+        })
+    }
+}
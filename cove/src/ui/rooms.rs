@@ -1,39 +1,68 @@
+mod away;
+mod broadcast;
 mod connect;
 mod delete;
+mod directory;
+mod feed;
+mod history;
+mod recent;
+mod search;
 
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
-use std::iter;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use cove_config::{Config, Keys, RoomsSortOrder};
+use cove_config::{Config, ConnectOnOpen, ConnectWindow, Keys, RoomsSortOrder, Theme, ThemeMode};
 use cove_input::InputEvent;
+use cove_vault::{EuphVault, RoomIdentifier, Vault};
 use crossterm::style::Stylize;
-use euphoxide::api::SessionType;
+use euphoxide::api::packet::ParsedPacket;
+use euphoxide::api::{Data, Message, MessageId, SendEvent, Time};
 use euphoxide::bot::instance::{Event, ServerConfig};
-use euphoxide::conn::{self, Joined};
+use euphoxide::conn;
+use time::OffsetDateTime;
 use tokio::sync::mpsc;
-use toss::widgets::{BoxedAsync, Empty, Join2, Text};
+use toss::widgets::{BoxedAsync, Empty, Join2, Layer, Text};
 use toss::{Style, Styled, Widget, WidgetExt};
 
 use crate::euph;
 use crate::macros::logging_unwrap;
-use crate::vault::{EuphVault, RoomIdentifier, Vault};
-use crate::version::{NAME, VERSION};
+use crate::startup;
+use crate::version::{self, NAME, VERSION};
 
+use self::away::Away;
+use self::broadcast::{BroadcastResult, BroadcastState};
 use self::connect::{ConnectResult, ConnectState};
 use self::delete::{DeleteResult, DeleteState};
+use self::directory::{DirectoryResult, DirectoryState};
+use self::feed::{Feed, FeedResult};
+use self::history::History;
+use self::recent::{RecentResult, RecentState};
+use self::search::{SearchReaction, SearchState};
 
+use super::euph::commands::Command;
 use super::euph::room::EuphRoom;
-use super::widgets::{ListBuilder, ListState};
+use super::wakeup::Wakeup;
+use super::widgets::{ListBuilder, ListState, Popup, Sidebar};
 use super::{key_bindings, util, UiError, UiEvent};
 
+/// Width of the rooms sidebar, including its border.
+const SIDEBAR_WIDTH: u16 = 32;
+
 enum State {
     ShowList,
     ShowRoom(RoomIdentifier),
+    /// Show a room and ask whether to connect to it.
+    AskConnect(RoomIdentifier),
     Connect(ConnectState),
     Delete(DeleteState),
+    Search(SearchState),
+    Recent(RecentState),
+    Directory(DirectoryState),
+    Feed,
+    /// Show a room and the progress of sending a message to several rooms.
+    Broadcast(BroadcastState),
 }
 
 #[derive(Clone, Copy)]
@@ -51,6 +80,15 @@ impl Order {
     }
 }
 
+struct RoomRow<'a> {
+    id: &'a RoomIdentifier,
+    state: Option<&'a euph::State>,
+    gave_up: bool,
+    session_filter: euph::SessionFilter,
+    unseen: usize,
+    mentions: usize,
+}
+
 struct EuphServer {
     config: ServerConfig,
     next_instance_id: usize,
@@ -71,16 +109,48 @@ impl EuphServer {
     }
 }
 
+/// A room to show right away, either when cove starts or when another instance
+/// hands it over.
+pub struct StartRoom {
+    pub room: RoomIdentifier,
+    /// A message to move the cursor to.
+    pub msg: Option<MessageId>,
+}
+
 pub struct Rooms {
     config: &'static Config,
 
     vault: Vault,
+    /// Vault for rooms whose history should not be persisted.
+    incognito_vault: Vault,
+    webhooks: euph::Webhooks,
     ui_event_tx: mpsc::UnboundedSender<UiEvent>,
+    /// Whether server packets are replayed from a recording, see `--replay`.
+    replaying: bool,
 
     state: State,
 
     list: ListState<RoomIdentifier>,
     order: Order,
+    away: Away,
+    /// Starts out as the `low_bandwidth` config option and is toggled via the
+    /// `general.low_bandwidth` key binding.
+    low_bandwidth: bool,
+    history: History,
+    feed: Feed,
+    /// Whether the rooms sidebar has focus instead of the current room.
+    sidebar_focused: bool,
+    /// Rooms that use [`Self::incognito_vault`].
+    incognito: HashSet<RoomIdentifier>,
+    /// Names of the room templates chosen for rooms, see the `euph.templates`
+    /// config option.
+    templates: HashMap<RoomIdentifier, String>,
+    /// Whether rooms with a `schedule` were inside one of their windows as of
+    /// the last [`Self::apply_schedules`] call.
+    in_schedule: HashMap<RoomIdentifier, bool>,
+    /// Whether any room was waiting for its `send_interval` to pass as of the
+    /// last [`Self::send_cooldown_changed`] call.
+    send_cooldown_running: bool,
 
     euph_servers: HashMap<String, EuphServer>,
     euph_rooms: HashMap<RoomIdentifier, EuphRoom>,
@@ -91,32 +161,115 @@ impl Rooms {
         config: &'static Config,
         vault: Vault,
         ui_event_tx: mpsc::UnboundedSender<UiEvent>,
+        replaying: bool,
     ) -> Self {
+        let templates = logging_unwrap!(vault.euph().room_templates().await)
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        let incognito = config
+            .euph
+            .servers
+            .iter()
+            .flat_map(|(domain, server)| {
+                server
+                    .rooms
+                    .iter()
+                    .filter(|(_, room)| room.incognito)
+                    .map(|(name, _)| RoomIdentifier::new(domain.clone(), name.clone()))
+            })
+            .chain(
+                templates
+                    .iter()
+                    .filter(|(id, template)| {
+                        config
+                            .euph_room(&id.domain, &id.name, Some(template.as_str()))
+                            .incognito
+                    })
+                    .map(|(id, _)| id.clone()),
+            )
+            .collect();
+
+        let time_zone = vault.time_zone();
         let mut result = Self {
             config,
+            incognito_vault: logging_unwrap!(vault.launch_in_memory_sibling()),
             vault,
+            webhooks: euph::Webhooks::new(&config.webhooks, &version::user_agent(config)),
             ui_event_tx,
+            replaying,
             state: State::ShowList,
             list: ListState::new(),
             order: Order::from_rooms_sort_order(config.rooms_sort_order),
+            away: Away::new(config),
+            low_bandwidth: config.low_bandwidth,
+            history: History::default(),
+            feed: Feed::new(time_zone),
+            sidebar_focused: false,
+            incognito,
+            templates,
+            in_schedule: HashMap::new(),
+            send_cooldown_running: false,
             euph_servers: HashMap::new(),
             euph_rooms: HashMap::new(),
         };
+        startup::step("set up rooms");
 
         if !config.offline {
             for (domain, server) in &config.euph.servers {
                 for (name, room) in &server.rooms {
-                    if room.autojoin {
+                    // Scheduled rooms are connected by apply_schedules
+                    if room.autojoin && room.schedule.is_none() {
                         let id = RoomIdentifier::new(domain.clone(), name.clone());
                         result.connect_to_room(id).await;
                     }
                 }
             }
+
+            let from_templates = result
+                .templates
+                .keys()
+                .filter(|id| {
+                    let config = result.room_config(id);
+                    config.autojoin && config.schedule.is_none()
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            for id in from_templates {
+                result.connect_to_room(id).await;
+            }
         }
+        startup::step("autojoin");
 
         result
     }
 
+    /// Show a room, for example because it was specified on the command line.
+    pub async fn open(&mut self, start: StartRoom) {
+        self.show_room(start.room.clone()).await;
+        if let Some(msg) = start.msg {
+            self.get_or_insert_room(start.room)
+                .await
+                .move_cursor_to(msg);
+        }
+    }
+
+    /// The user pressed a key.
+    pub fn activity(&mut self) {
+        self.away.activity();
+    }
+
+    pub fn toggle_away(&mut self) {
+        self.away.toggle();
+    }
+
+    pub fn toggle_low_bandwidth(&mut self) {
+        self.low_bandwidth = !self.low_bandwidth;
+        for room in self.euph_rooms.values_mut() {
+            room.set_low_bandwidth(self.low_bandwidth);
+        }
+    }
+
     async fn get_or_insert_server<'a>(
         vault: &Vault,
         euph_servers: &'a mut HashMap<String, EuphServer>,
@@ -131,40 +284,106 @@ impl Rooms {
         }
     }
 
+    /// The options of a room, see [`Config::euph_room`].
+    fn room_config(&self, room: &RoomIdentifier) -> cove_config::EuphRoom {
+        let template = self.templates.get(room).map(|t| t as &str);
+        self.config.euph_room(&room.domain, &room.name, template)
+    }
+
+    /// Base a room on a template from now on. The room is recreated so the
+    /// template's options take effect.
+    async fn set_template(&mut self, room: RoomIdentifier, template: String) {
+        if let Some(mut old) = self.euph_rooms.remove(&room) {
+            old.disconnect();
+        }
+        self.templates.insert(room.clone(), template.clone());
+
+        // Storing the template of an incognito room would record that the room
+        // exists, so it is only remembered until cove exits.
+        let incognito = self.room_config(&room).incognito;
+        let stored = if incognito { None } else { Some(template) };
+        let euph = self.vault.euph().room(room.clone());
+        logging_unwrap!(euph.set_template(stored).await);
+
+        if incognito {
+            self.incognito.insert(room);
+        } else {
+            self.incognito.remove(&room);
+        }
+    }
+
     async fn get_or_insert_room(&mut self, room: RoomIdentifier) -> &mut EuphRoom {
-        let server =
-            Self::get_or_insert_server(&self.vault, &mut self.euph_servers, room.domain.clone())
-                .await;
+        if !self.euph_rooms.contains_key(&room) {
+            let room_config = self.room_config(&room);
 
-        self.euph_rooms.entry(room.clone()).or_insert_with(|| {
-            EuphRoom::new(
+            let server = Self::get_or_insert_server(
+                &self.vault,
+                &mut self.euph_servers,
+                room.domain.clone(),
+            )
+            .await;
+
+            let vault = if self.incognito.contains(&room) {
+                &self.incognito_vault
+            } else {
+                &self.vault
+            };
+
+            let mut euph_room = EuphRoom::new(
                 self.config,
                 server.config.clone(),
-                self.config.euph_room(&room.domain, &room.name),
-                self.vault.euph().room(room),
+                room_config,
+                vault.euph().room(room.clone()),
+                self.webhooks.clone(),
                 self.ui_event_tx.clone(),
-            )
-        })
+                self.replaying,
+            );
+            euph_room.set_low_bandwidth(self.low_bandwidth);
+            self.euph_rooms.insert(room.clone(), euph_room);
+        }
+
+        self.euph_rooms
+            .get_mut(&room)
+            .expect("room exists after insertion")
     }
 
     async fn connect_to_room(&mut self, room: RoomIdentifier) {
+        self.get_or_insert_room(room.clone()).await;
+
         let server =
             Self::get_or_insert_server(&self.vault, &mut self.euph_servers, room.domain.clone())
                 .await;
-
-        let room = self.euph_rooms.entry(room.clone()).or_insert_with(|| {
-            EuphRoom::new(
-                self.config,
-                server.config.clone(),
-                self.config.euph_room(&room.domain, &room.name),
-                self.vault.euph().room(room),
-                self.ui_event_tx.clone(),
-            )
-        });
+        let room = self
+            .euph_rooms
+            .get_mut(&room)
+            .expect("room exists after insertion");
 
         room.connect(&mut server.next_instance_id);
     }
 
+    /// Switch a room between recording to the vault and keeping its history
+    /// only in memory. The room reconnects if it was connected.
+    async fn toggle_incognito(&mut self, room: RoomIdentifier) {
+        if !self.incognito.remove(&room) {
+            self.incognito.insert(room.clone());
+        }
+
+        let connected = match self.euph_rooms.remove(&room) {
+            Some(mut old) => {
+                let connected = !old.stopped();
+                old.disconnect();
+                connected
+            }
+            None => false,
+        };
+
+        if connected {
+            self.connect_to_room(room).await;
+        } else {
+            self.get_or_insert_room(room).await;
+        }
+    }
+
     async fn connect_to_all_rooms(&mut self) {
         for (id, room) in &mut self.euph_rooms {
             let server =
@@ -175,6 +394,193 @@ impl Rooms {
         }
     }
 
+    /// Reconnect rooms right away after the system resumed or the network
+    /// changed.
+    ///
+    /// After a resume, all connected rooms are reconnected since their
+    /// connections are likely dead. After a network change, only rooms that
+    /// are currently waiting to reconnect are affected.
+    pub async fn handle_wakeup(&mut self, wakeup: Wakeup) {
+        for (id, room) in &mut self.euph_rooms {
+            let joined = room.room_state_joined().is_some();
+            if room.stopped() || (wakeup == Wakeup::NetworkChanged && joined) {
+                continue;
+            }
+
+            let server =
+                Self::get_or_insert_server(&self.vault, &mut self.euph_servers, id.domain.clone())
+                    .await;
+
+            room.disconnect();
+            room.connect(&mut server.next_instance_id);
+        }
+    }
+
+    /// Mark messages as seen in all rooms according to the `mark_seen` config
+    /// option.
+    pub async fn mark_seen(&mut self) {
+        for room in self.euph_rooms.values_mut() {
+            room.mark_seen().await;
+        }
+    }
+
+    /// Save the unsent messages of all rooms according to the `draft_autosave`
+    /// config option.
+    pub async fn save_drafts(&mut self) {
+        let Some(interval) = self.config.draft_autosave else {
+            return;
+        };
+        let interval = Duration::from_secs(interval);
+
+        for room in self.euph_rooms.values_mut() {
+            room.save_draft(interval).await;
+        }
+    }
+
+    /// What would be lost or interrupted by quitting right now, according to the
+    /// `confirm_quit` config option.
+    pub fn quit_warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+
+        let connected = self
+            .euph_rooms
+            .values()
+            .filter(|room| room.room_state_joined().is_some())
+            .count();
+        if connected == 1 {
+            warnings.push("1 room is connected".to_string());
+        } else if connected > 1 {
+            warnings.push(format!("{connected} rooms are connected"));
+        }
+
+        let mut ids = self.euph_rooms.keys().collect::<Vec<_>>();
+        ids.sort_unstable();
+        for id in ids {
+            let room = &self.euph_rooms[id];
+            if room.has_unsaved_msg() {
+                warnings.push(format!("&{} has an unsent message", id.name));
+            }
+            if room.is_sending() {
+                warnings.push(format!("&{} is still sending a message", id.name));
+            }
+        }
+
+        warnings
+    }
+
+    /// Send the scheduled messages of all rooms whose time has come.
+    pub async fn send_scheduled(&mut self) {
+        for room in self.euph_rooms.values_mut() {
+            room.send_scheduled().await;
+        }
+    }
+
+    /// Whether the countdown shown in the editor of rooms waiting for their
+    /// `send_interval` to pass must be redrawn.
+    pub fn send_cooldown_changed(&mut self) -> bool {
+        let running = self
+            .euph_rooms
+            .values()
+            .any(|room| room.send_cooldown().is_some());
+        // Once the countdown is over, it must be redrawn one last time.
+        let changed = running || self.send_cooldown_running;
+        self.send_cooldown_running = running;
+        changed
+    }
+
+    /// Remove expired toasts from all rooms. Returns whether any were removed.
+    pub fn expire_toasts(&mut self) -> bool {
+        let mut expired = false;
+        for room in self.euph_rooms.values_mut() {
+            expired |= room.expire_toasts();
+        }
+        expired
+    }
+
+    /// Reconnect rooms whose connection stalled according to the
+    /// `stall_timeout` config option. Returns whether any room was reconnected.
+    pub async fn reconnect_stalled_rooms(&mut self) -> bool {
+        let Some(timeout) = self.config.stall_timeout else {
+            return false;
+        };
+        let timeout = Duration::from_secs(timeout);
+
+        let mut reconnected = false;
+        for (id, room) in &mut self.euph_rooms {
+            // Checked first so the server is only looked up when needed, as
+            // this runs on every tick.
+            if !room.is_stalled(timeout) {
+                continue;
+            }
+
+            let server =
+                Self::get_or_insert_server(&self.vault, &mut self.euph_servers, id.domain.clone())
+                    .await;
+
+            reconnected |= room.reconnect_if_stalled(timeout, &mut server.next_instance_id);
+        }
+        reconnected
+    }
+
+    /// Rooms with a `schedule` config option and their windows.
+    fn scheduled_rooms(&self) -> Vec<(RoomIdentifier, &'static [ConnectWindow])> {
+        let config: &'static Config = self.config;
+        let from_config = config.euph.servers.iter().flat_map(|(domain, server)| {
+            server.rooms.iter().filter_map(|(name, room)| {
+                let id = RoomIdentifier::new(domain.clone(), name.clone());
+                Some((id, room.schedule.as_deref()?))
+            })
+        });
+
+        let from_templates = self.templates.iter().filter_map(|(id, template)| {
+            let listed = config
+                .euph
+                .servers
+                .get(&id.domain)
+                .is_some_and(|server| server.rooms.contains_key(&id.name));
+            if listed {
+                return None;
+            }
+            let schedule = config.euph.templates.get(template)?.schedule.as_deref()?;
+            Some((id.clone(), schedule))
+        });
+
+        from_config.chain(from_templates).collect()
+    }
+
+    /// Connect and disconnect rooms according to their `schedule` config
+    /// option. Rooms are only connected or disconnected when one of their
+    /// windows starts or ends, so they can be connected to or disconnected
+    /// from manually in between. Returns whether any room was affected.
+    pub async fn apply_schedules(&mut self) -> bool {
+        if self.config.offline {
+            return false;
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let now = crate::util::convert_to_time_zone(self.vault.time_zone(), now).unwrap_or(now);
+        let day = now.weekday().number_days_from_monday().into();
+        let minute = u16::from(now.hour()) * 60 + u16::from(now.minute());
+
+        let mut changes = vec![];
+        for (id, windows) in self.scheduled_rooms() {
+            let inside = windows.iter().any(|window| window.contains(day, minute));
+            if self.in_schedule.insert(id.clone(), inside) != Some(inside) {
+                changes.push((id, inside));
+            }
+        }
+
+        let changed = !changes.is_empty();
+        for (id, inside) in changes {
+            if inside {
+                self.connect_to_room(id).await;
+            } else {
+                self.disconnect_from_room(&id);
+            }
+        }
+        changed
+    }
+
     fn disconnect_from_room(&mut self, room: &RoomIdentifier) {
         if let Some(room) = self.euph_rooms.get_mut(room) {
             room.disconnect();
@@ -215,8 +621,14 @@ impl Rooms {
 
         // Prevent room that is currently being shown from being removed. This
         // could otherwise happen after connecting to a room that doesn't exist.
-        if let State::ShowRoom(name) = &self.state {
-            rooms_set.insert(name.clone());
+        match &self.state {
+            State::ShowRoom(name) | State::AskConnect(name) => {
+                rooms_set.insert(name.clone());
+            }
+            State::Broadcast(broadcast) => {
+                rooms_set.insert(broadcast.origin().clone());
+            }
+            _ => {}
         }
 
         // Now `rooms_set` contains all rooms that must exist. Other rooms may
@@ -232,7 +644,7 @@ impl Rooms {
 
     pub async fn widget(&mut self) -> BoxedAsync<'_, UiError> {
         match &self.state {
-            State::ShowRoom(_) => {}
+            State::ShowRoom(_) | State::AskConnect(_) | State::Broadcast(_) => {}
             _ => self.stabilize_rooms().await,
         }
 
@@ -242,6 +654,8 @@ impl Rooms {
                 self.config,
                 &mut self.list,
                 self.order,
+                &self.away,
+                self.low_bandwidth,
                 &self.euph_rooms,
             )
             .await
@@ -249,11 +663,43 @@ impl Rooms {
             .boxed_async(),
 
             State::ShowRoom(id) => {
-                self.euph_rooms
+                let zen = self.euph_rooms.get(id).is_some_and(|room| room.zen());
+                let min_width = self.config.sidebar_min_width.filter(|_| !zen);
+                let Some(min_width) = min_width else {
+                    return self
+                        .euph_rooms
+                        .get_mut(id)
+                        .expect("room exists after stabilization")
+                        .widget()
+                        .await;
+                };
+
+                let sidebar = Self::sidebar_widget(
+                    self.config,
+                    &mut self.list,
+                    self.order,
+                    &self.euph_rooms,
+                    self.sidebar_focused,
+                )
+                .await;
+                let room = self
+                    .euph_rooms
                     .get_mut(id)
                     .expect("room exists after stabilization")
                     .widget()
-                    .await
+                    .await;
+                Sidebar::new(sidebar.desync(), room, SIDEBAR_WIDTH, min_width).boxed_async()
+            }
+
+            State::AskConnect(id) => {
+                let popup = Self::ask_connect_widget(self.config, id);
+                let room = self
+                    .euph_rooms
+                    .get_mut(id)
+                    .expect("room exists after stabilization")
+                    .widget()
+                    .await;
+                Layer::new(vec![room, popup.desync().boxed_async()]).boxed_async()
             }
 
             State::Connect(connect) => Self::rooms_widget(
@@ -261,10 +707,12 @@ impl Rooms {
                 self.config,
                 &mut self.list,
                 self.order,
+                &self.away,
+                self.low_bandwidth,
                 &self.euph_rooms,
             )
             .await
-            .below(connect.widget())
+            .below(connect.widget(&self.config.theme))
             .desync()
             .boxed_async(),
 
@@ -273,58 +721,70 @@ impl Rooms {
                 self.config,
                 &mut self.list,
                 self.order,
+                &self.away,
+                self.low_bandwidth,
                 &self.euph_rooms,
             )
             .await
-            .below(delete.widget())
+            .below(delete.widget(&self.config.theme))
             .desync()
             .boxed_async(),
-        }
-    }
-
-    fn format_pbln(joined: &Joined) -> String {
-        let mut p = 0_usize;
-        let mut b = 0_usize;
-        let mut l = 0_usize;
-        let mut n = 0_usize;
 
-        let sessions = joined
-            .listing
-            .values()
-            .map(|s| (s.id(), s.name()))
-            .chain(iter::once((
-                &joined.session.id,
-                &joined.session.name as &str,
-            )));
-        for (user_id, name) in sessions {
-            match user_id.session_type() {
-                Some(SessionType::Bot) if name.is_empty() => n += 1,
-                Some(SessionType::Bot) => b += 1,
-                _ if name.is_empty() => l += 1,
-                _ => p += 1,
+            State::Search(search) => {
+                search.update_unseen_counts(&self.vault.euph()).await;
+                search.widget().desync().boxed_async()
             }
-        }
 
-        // There must always be either one p, b, l or n since we're including
-        // ourselves.
-        let mut result = vec![];
-        if p > 0 {
-            result.push(format!("{p}p"));
-        }
-        if b > 0 {
-            result.push(format!("{b}b"));
-        }
-        if l > 0 {
-            result.push(format!("{l}l"));
-        }
-        if n > 0 {
-            result.push(format!("{n}n"));
+            State::Recent(recent) => Self::rooms_widget(
+                &self.vault,
+                self.config,
+                &mut self.list,
+                self.order,
+                &self.away,
+                self.low_bandwidth,
+                &self.euph_rooms,
+            )
+            .await
+            .below(recent.widget(&self.config.theme))
+            .desync()
+            .boxed_async(),
+
+            State::Directory(directory) => Self::rooms_widget(
+                &self.vault,
+                self.config,
+                &mut self.list,
+                self.order,
+                &self.away,
+                self.low_bandwidth,
+                &self.euph_rooms,
+            )
+            .await
+            .below(directory.widget(&self.config.theme, &self.config.keys))
+            .desync()
+            .boxed_async(),
+
+            State::Feed => self.feed.widget(&self.config.theme).desync().boxed_async(),
+
+            State::Broadcast(broadcast) => {
+                broadcast.stabilize();
+                let room = self
+                    .euph_rooms
+                    .get_mut(broadcast.origin())
+                    .expect("room exists after stabilization")
+                    .widget()
+                    .await;
+                let popup = broadcast
+                    .widget(&self.config.theme, &self.config.keys)
+                    .desync()
+                    .boxed_async();
+                Layer::new(vec![room, popup]).boxed_async()
+            }
         }
-        result.join(" ")
     }
 
-    fn format_room_state(state: Option<&euph::State>) -> Option<String> {
-        match state {
+    fn format_room_state(row: &RoomRow<'_>) -> Option<String> {
+        match row.state {
+            None | Some(euph::State::Stopped) if row.gave_up => Some("gave up".to_string()),
             None | Some(euph::State::Stopped) => None,
             Some(euph::State::Disconnected) => Some("waiting".to_string()),
             Some(euph::State::Connecting) => Some("connecting".to_string()),
@@ -333,24 +793,26 @@ impl Rooms {
                     Some("auth required".to_string())
                 }
                 conn::State::Joining(_) => Some("joining".to_string()),
-                conn::State::Joined(joined) => Some(Self::format_pbln(joined)),
+                conn::State::Joined(joined) => Some(euph::format_pbln(joined, &row.session_filter)),
             },
         }
     }
 
-    fn format_unseen_msgs(unseen: usize) -> Option<String> {
+    fn format_unseen_msgs(unseen: usize, mentions: usize) -> Option<String> {
         if unseen == 0 {
             None
-        } else {
+        } else if mentions == 0 {
             Some(format!("{unseen}"))
+        } else {
+            Some(format!("{mentions}!/{unseen}"))
         }
     }
 
-    fn format_room_info(state: Option<&euph::State>, unseen: usize) -> Styled {
+    fn format_room_info(row: &RoomRow<'_>) -> Styled {
         let unseen_style = Style::new().bold().green();
 
-        let state = Self::format_room_state(state);
-        let unseen = Self::format_unseen_msgs(unseen);
+        let state = Self::format_room_state(row);
+        let unseen = Self::format_unseen_msgs(row.unseen, row.mentions);
 
         match (state, unseen) {
             (None, None) => Styled::default(),
@@ -366,40 +828,53 @@ impl Rooms {
         }
     }
 
-    fn sort_rooms(rooms: &mut [(&RoomIdentifier, Option<&euph::State>, usize)], order: Order) {
+    fn sort_rooms(rooms: &mut [RoomRow<'_>], order: Order) {
         match order {
-            Order::Alphabet => rooms.sort_unstable_by_key(|(id, _, _)| *id),
-            Order::Importance => rooms
-                .sort_unstable_by_key(|(id, state, unseen)| (state.is_none(), *unseen == 0, *id)),
+            Order::Alphabet => rooms.sort_unstable_by_key(|row| row.id),
+            Order::Importance => rooms.sort_unstable_by_key(|row| {
+                (
+                    row.state.is_none(),
+                    row.mentions == 0,
+                    row.unseen == 0,
+                    row.id,
+                )
+            }),
         }
     }
 
     async fn render_rows(
         list_builder: &mut ListBuilder<'_, RoomIdentifier, Text>,
         order: Order,
+        mode: ThemeMode,
+        theme: &Theme,
         euph_rooms: &HashMap<RoomIdentifier, EuphRoom>,
     ) {
+        let (info_style, room_style, cursor_style) = (theme.info(), theme.room(), theme.cursor());
         let mut rooms = vec![];
         for (id, room) in euph_rooms {
-            let state = room.room_state();
-            let unseen = room.unseen_msgs_count().await;
-            rooms.push((id, state, unseen));
+            rooms.push(RoomRow {
+                id,
+                state: room.room_state(),
+                gave_up: room.gave_up(),
+                session_filter: room.session_filter(),
+                unseen: room.unseen_msgs_count().await,
+                mentions: room.unseen_mentions_count().await,
+            });
         }
         Self::sort_rooms(&mut rooms, order);
-        for (id, state, unseen) in rooms {
-            let id = id.clone();
-            let info = Self::format_room_info(state, unseen);
+        for row in rooms {
+            let id = row.id.clone();
+            let info = Self::format_room_info(&row);
             list_builder.add_sel(id.clone(), move |selected| {
-                let domain_style = if selected {
-                    Style::new().black().on_white()
-                } else {
-                    Style::new().grey()
-                };
-
-                let room_style = if selected {
-                    Style::new().bold().black().on_white()
-                } else {
-                    Style::new().bold().blue()
+                let (domain_style, room_style) = match (mode, selected) {
+                    (ThemeMode::Color, false) => (info_style, room_style),
+                    (ThemeMode::Color, true) => (cursor_style, cursor_style.bold()),
+                    (ThemeMode::HighContrast | ThemeMode::Mono, false) => {
+                        (Style::new(), Style::new().bold())
+                    }
+                    (ThemeMode::HighContrast | ThemeMode::Mono, true) => {
+                        (Style::new().reverse(), Style::new().bold().reverse())
+                    }
                 };
 
                 let text = Styled::new(format!("{} ", id.domain), domain_style)
@@ -416,14 +891,16 @@ impl Rooms {
         config: &Config,
         list: &'a mut ListState<RoomIdentifier>,
         order: Order,
+        away: &Away,
+        low_bandwidth: bool,
         euph_rooms: &HashMap<RoomIdentifier, EuphRoom>,
     ) -> impl Widget<UiError> + 'a {
         let version_info = Styled::new_plain("Welcome to ")
             .then(format!("{NAME} {VERSION}"), Style::new().yellow().bold())
             .then_plain("!");
-        let help_info = Styled::new("Press ", Style::new().grey())
+        let help_info = Styled::new("Press ", config.theme.info())
             .and_then(key_bindings::format_binding(&config.keys.general.help))
-            .then(" for key bindings.", Style::new().grey());
+            .then(" for key bindings.", config.theme.info());
         let info = Join2::vertical(
             Text::new(version_info).float().with_center_h().segment(),
             Text::new(help_info).segment(),
@@ -450,9 +927,16 @@ impl Rooms {
         } else {
             heading = heading.then_plain(format!(" ({connected_rooms}/{total_rooms})"))
         }
+        if away.since().is_some() {
+            heading = heading.then(" away", Style::new().bold().yellow());
+        }
+        if low_bandwidth {
+            heading = heading.then(" low bandwidth", Style::new().bold().yellow());
+        }
 
         let mut list_builder = ListBuilder::new();
-        Self::render_rows(&mut list_builder, order, euph_rooms).await;
+        let mode = config.theme_mode;
+        Self::render_rows(&mut list_builder, order, mode, &config.theme, euph_rooms).await;
 
         Join2::horizontal(
             Join2::vertical(
@@ -467,6 +951,49 @@ impl Rooms {
         .title(title)
     }
 
+    async fn sidebar_widget<'a>(
+        config: &Config,
+        list: &'a mut ListState<RoomIdentifier>,
+        order: Order,
+        euph_rooms: &HashMap<RoomIdentifier, EuphRoom>,
+        focused: bool,
+    ) -> impl Widget<UiError> + 'a {
+        let border_style = if focused {
+            Style::new().bold()
+        } else {
+            config.theme.info()
+        };
+
+        let mut list_builder = ListBuilder::new();
+        let mode = config.theme_mode;
+        Self::render_rows(&mut list_builder, order, mode, &config.theme, euph_rooms).await;
+
+        Join2::vertical(
+            Text::new(Styled::new("Rooms", Style::new().bold()))
+                .segment()
+                .with_fixed(true),
+            list_builder.build(list).segment(),
+        )
+        .border()
+        .with_style(border_style)
+    }
+
+    fn ask_connect_widget(config: &Config, id: &RoomIdentifier) -> impl Widget<UiError> {
+        let theme = &config.theme;
+        let room_style = theme.room();
+        let text = Styled::new_plain("Connect to ")
+            .then(format!("&{}", id.name), room_style)
+            .then_plain(" on the ")
+            .then(&id.domain, theme.info())
+            .then_plain(" server?\n\nPress ")
+            .and_then(key_bindings::format_binding(&config.keys.general.confirm))
+            .then_plain(" to connect or ")
+            .and_then(key_bindings::format_binding(&config.keys.general.abort))
+            .then_plain(" to stay offline.");
+
+        Popup::new(theme, Text::new(text), "Connect")
+    }
+
     async fn handle_showlist_input_event(
         &mut self,
         event: &mut InputEvent<'_>,
@@ -474,8 +1001,8 @@ impl Rooms {
     ) -> bool {
         // Open room
         if event.matches(&keys.general.confirm) {
-            if let Some(name) = self.list.selected() {
-                self.state = State::ShowRoom(name.clone());
+            if let Some(name) = self.list.selected().cloned() {
+                self.show_room(name).await;
             }
             return true;
         }
@@ -516,16 +1043,31 @@ impl Rooms {
             return true;
         }
         if event.matches(&keys.rooms.action.disconnect_non_autojoin) {
-            for (id, room) in &mut self.euph_rooms {
-                let autojoin = self.config.euph_room(&id.domain, &id.name).autojoin;
-                if !autojoin {
+            let non_autojoin = self
+                .euph_rooms
+                .keys()
+                .filter(|id| !self.room_config(id).autojoin)
+                .cloned()
+                .collect::<Vec<_>>();
+            for id in non_autojoin {
+                if let Some(room) = self.euph_rooms.get_mut(&id) {
                     room.disconnect();
                 }
             }
             return true;
         }
         if event.matches(&keys.rooms.action.new) {
-            self.state = State::Connect(ConnectState::new());
+            self.state = State::Connect(ConnectState::new(self.config));
+            return true;
+        }
+        if event.matches(&keys.rooms.action.browse) {
+            let directory = DirectoryState::new(self.config, self.ui_event_tx.clone());
+            self.state = State::Directory(directory);
+            return true;
+        }
+        if event.matches(&keys.rooms.action.feed) {
+            self.feed.follow();
+            self.state = State::Feed;
             return true;
         }
         if event.matches(&keys.rooms.action.delete) {
@@ -541,6 +1083,159 @@ impl Rooms {
             };
             return true;
         }
+        if event.matches(&keys.rooms.action.mark_away_seen) {
+            if let Some((start, end)) = self.away.last() {
+                let euph = self.vault.euph();
+                logging_unwrap!(euph.set_seen_between(Time(start), Time(end), true).await);
+            }
+            return true;
+        }
+
+        false
+    }
+
+    /// Run a command entered in a room's editor.
+    async fn run_room_command(&mut self, room: RoomIdentifier, command: Command) {
+        match command {
+            Command::Connect => self.connect_to_room(room).await,
+            Command::Search(query) => {
+                // Messages of incognito rooms are only in the incognito vault
+                let incognito = self.incognito.contains(&room);
+                let mut search = SearchState::new(self.config);
+                search.set_room(room);
+                search.set_query(query.clone());
+                let (query, filter) = search.search(&query);
+                let vault = if incognito {
+                    &self.incognito_vault
+                } else {
+                    &self.vault
+                };
+                let results = vault.euph().search(query, filter, search::LIMIT).await;
+                search.set_results(logging_unwrap!(results));
+                self.state = State::Search(search);
+            }
+            Command::Broadcast { rooms, content } => {
+                let rooms = rooms
+                    .into_iter()
+                    .map(|name| match name.split_once('@') {
+                        Some((name, domain)) => RoomIdentifier::new(domain.into(), name.into()),
+                        None => RoomIdentifier::new(room.domain.clone(), name),
+                    })
+                    .collect();
+                self.state = State::Broadcast(BroadcastState::new(room, rooms, content));
+            }
+            // Handled by the room itself
+            Command::Nick(_)
+            | Command::Disconnect
+            | Command::Ignore(_)
+            | Command::Unignore(_)
+            | Command::Schedule { .. } => {}
+        }
+    }
+
+    /// Show a room and remember it in the history.
+    async fn show_room(&mut self, room: RoomIdentifier) {
+        self.history.visit(room.clone());
+        self.get_or_insert_room(room.clone())
+            .await
+            .move_cursor_on_open()
+            .await;
+        self.enter_room(room).await;
+    }
+
+    /// Show a room without touching the history.
+    async fn enter_room(&mut self, room: RoomIdentifier) {
+        self.sidebar_focused = false;
+
+        // The room may have been removed since it was last shown.
+        let stopped = self.get_or_insert_room(room.clone()).await.stopped();
+        if !stopped {
+            self.state = State::ShowRoom(room);
+            return;
+        }
+
+        match self.config.connect_on_open {
+            ConnectOnOpen::Never => self.state = State::ShowRoom(room),
+            ConnectOnOpen::Always => {
+                self.connect_to_room(room.clone()).await;
+                self.state = State::ShowRoom(room);
+            }
+            ConnectOnOpen::Ask => self.state = State::AskConnect(room),
+        }
+    }
+
+    async fn handle_sidebar_input_event(
+        &mut self,
+        event: &mut InputEvent<'_>,
+        keys: &Keys,
+    ) -> bool {
+        let zen = match &self.state {
+            State::ShowRoom(id) => self.euph_rooms.get(id).is_some_and(|room| room.zen()),
+            _ => false,
+        };
+        let visible = match self.config.sidebar_min_width {
+            Some(min_width) => !zen && event.frame().size().width >= min_width,
+            None => false,
+        };
+        if !visible {
+            self.sidebar_focused = false;
+            return false;
+        }
+
+        if event.matches(&keys.general.sidebar) {
+            self.sidebar_focused = !self.sidebar_focused;
+            return true;
+        }
+
+        if !self.sidebar_focused {
+            return false;
+        }
+
+        if event.matches(&keys.general.abort) {
+            self.sidebar_focused = false;
+            return true;
+        }
+
+        // Open room
+        if event.matches(&keys.general.confirm) {
+            if let Some(room) = self.list.selected().cloned() {
+                self.show_room(room).await;
+            }
+            return true;
+        }
+
+        util::handle_list_input_event(&mut self.list, event, keys)
+    }
+
+    async fn handle_history_input_event(
+        &mut self,
+        event: &mut InputEvent<'_>,
+        keys: &Keys,
+    ) -> bool {
+        if event.matches(&keys.general.back) {
+            // From the rooms list, going back returns to the last room.
+            let room = match self.state {
+                State::ShowRoom(_) => self.history.back(),
+                _ => self.history.current().cloned(),
+            };
+            if let Some(room) = room {
+                self.enter_room(room).await;
+            }
+            return true;
+        }
+
+        if event.matches(&keys.general.forward) {
+            if let Some(room) = self.history.forward() {
+                self.enter_room(room).await;
+            }
+            return true;
+        }
+
+        if event.matches(&keys.general.recent) {
+            let rooms = self.history.recent().to_vec();
+            self.state = State::Recent(RecentState::new(rooms));
+            return true;
+        }
 
         false
     }
@@ -548,15 +1243,51 @@ impl Rooms {
     pub async fn handle_input_event(&mut self, event: &mut InputEvent<'_>, keys: &Keys) -> bool {
         self.stabilize_rooms().await;
 
+        if let State::ShowRoom(_) = self.state {
+            if self.handle_sidebar_input_event(event, keys).await {
+                return true;
+            }
+        }
+
         match &mut self.state {
             State::ShowList => {
                 if self.handle_showlist_input_event(event, keys).await {
                     return true;
                 }
             }
+            // Events not handled by the sidebar must not reach the room
+            State::ShowRoom(_) if self.sidebar_focused => {}
             State::ShowRoom(name) => {
                 if let Some(room) = self.euph_rooms.get_mut(name) {
                     if room.handle_input_event(event, keys).await {
+                        if let Some(command) = room.take_command() {
+                            let name = name.clone();
+                            self.run_room_command(name, command).await;
+                        }
+                        return true;
+                    }
+                    if event.matches(&keys.room.action.catch_up) {
+                        if let Some((start, _)) = self.away.last() {
+                            room.catch_up(Time(start)).await;
+                        }
+                        return true;
+                    }
+                    if event.matches(&keys.room.action.incognito) {
+                        let name = name.clone();
+                        self.toggle_incognito(name).await;
+                        return true;
+                    }
+                    if event.matches(&keys.room.action.search) {
+                        let mut search = SearchState::new(self.config);
+                        search.set_room(name.clone());
+                        self.state = State::Search(search);
+                        return true;
+                    }
+                    if event.matches(&keys.room.action.connect) {
+                        if room.stopped() {
+                            let name = name.clone();
+                            self.connect_to_room(name).await;
+                        }
                         return true;
                     }
                     if event.matches(&keys.general.abort) {
@@ -565,14 +1296,31 @@ impl Rooms {
                     }
                 }
             }
+            State::AskConnect(name) => {
+                if event.matches(&keys.general.confirm) {
+                    let name = name.clone();
+                    self.connect_to_room(name.clone()).await;
+                    self.state = State::ShowRoom(name);
+                    return true;
+                }
+                if event.matches(&keys.general.abort) {
+                    self.state = State::ShowRoom(name.clone());
+                    return true;
+                }
+                // Prevent event from reaching the room below the popup
+                return false;
+            }
             State::Connect(connect) => match connect.handle_input_event(event, keys) {
                 ConnectResult::Close => {
                     self.state = State::ShowList;
                     return true;
                 }
-                ConnectResult::Connect(room) => {
+                ConnectResult::Connect(room, template) => {
+                    if let Some(template) = template {
+                        self.set_template(room.clone(), template).await;
+                    }
                     self.connect_to_room(room.clone()).await;
-                    self.state = State::ShowRoom(room);
+                    self.show_room(room).await;
                     return true;
                 }
                 ConnectResult::Handled => {
@@ -587,6 +1335,8 @@ impl Rooms {
                 }
                 DeleteResult::Delete(room) => {
                     self.euph_rooms.remove(&room);
+                    self.history.forget(&room);
+                    self.templates.remove(&room);
                     logging_unwrap!(self.vault.euph().room(room).delete().await);
                     self.state = State::ShowList;
                     return true;
@@ -596,6 +1346,111 @@ impl Rooms {
                 }
                 DeleteResult::Unhandled => {}
             },
+            State::Search(search) => match search.handle_input_event(event, keys) {
+                SearchReaction::Close => {
+                    self.state = match search.room() {
+                        Some(room) => State::ShowRoom(room.clone()),
+                        None => State::ShowList,
+                    };
+                    return true;
+                }
+                SearchReaction::Search(query, filter) => {
+                    let incognito = search
+                        .room()
+                        .is_some_and(|room| self.incognito.contains(room));
+                    let vault = if incognito {
+                        &self.incognito_vault
+                    } else {
+                        &self.vault
+                    };
+                    let results = vault.euph().search(query, filter, search::LIMIT).await;
+                    search.set_results(logging_unwrap!(results));
+                    return true;
+                }
+                SearchReaction::Open(room, id) => {
+                    self.show_room(room.clone()).await;
+                    self.get_or_insert_room(room).await.move_cursor_to(id);
+                    return true;
+                }
+                SearchReaction::Handled => {
+                    return true;
+                }
+                SearchReaction::Unhandled => {}
+            },
+            State::Recent(recent) => match recent.handle_input_event(event, keys) {
+                RecentResult::Close => {
+                    self.state = State::ShowList;
+                    return true;
+                }
+                RecentResult::Open(room) => {
+                    self.show_room(room).await;
+                    return true;
+                }
+                RecentResult::Handled => {
+                    return true;
+                }
+                RecentResult::Unhandled => {}
+            },
+            State::Directory(directory) => match directory.handle_input_event(event, keys) {
+                DirectoryResult::Close => {
+                    self.state = State::ShowList;
+                    return true;
+                }
+                DirectoryResult::Connect(room) => {
+                    self.connect_to_room(room.clone()).await;
+                    self.show_room(room).await;
+                    return true;
+                }
+                DirectoryResult::Handled => {
+                    return true;
+                }
+                DirectoryResult::Unhandled => {}
+            },
+            State::Feed => match self.feed.handle_input_event(event, keys) {
+                FeedResult::Close => {
+                    self.state = State::ShowList;
+                    return true;
+                }
+                FeedResult::Open(room, id) => {
+                    self.show_room(room.clone()).await;
+                    self.get_or_insert_room(room).await.move_cursor_to(id);
+                    return true;
+                }
+                FeedResult::Handled => {
+                    return true;
+                }
+                FeedResult::Unhandled => {}
+            },
+            State::Broadcast(broadcast) => match broadcast.handle_input_event(event, keys) {
+                BroadcastResult::Close => {
+                    self.state = State::ShowRoom(broadcast.origin().clone());
+                    return true;
+                }
+                BroadcastResult::Send(idx) => {
+                    let content = broadcast.content().to_string();
+                    let reply = self
+                        .euph_rooms
+                        .get(broadcast.room(idx))
+                        .and_then(|room| room.send_detached(content));
+                    broadcast.set_sending(idx, reply);
+                    return true;
+                }
+                BroadcastResult::Handled => {
+                    return true;
+                }
+                BroadcastResult::Unhandled => {}
+            },
+        }
+
+        if let State::ShowList | State::ShowRoom(_) = self.state {
+            if self.handle_history_input_event(event, keys).await {
+                return true;
+            }
+        }
+
+        if event.matches(&keys.general.search) && !matches!(self.state, State::Search(_)) {
+            self.state = State::Search(SearchState::new(self.config));
+            return true;
         }
 
         false
@@ -604,16 +1459,63 @@ impl Rooms {
     pub async fn handle_euph_event(&mut self, event: Event) -> bool {
         let config = event.config();
         let room_id = RoomIdentifier::new(config.server.domain.clone(), config.room.clone());
+        let focused = self.is_focused(&room_id);
         let Some(room) = self.euph_rooms.get_mut(&room_id) else {
             return false;
         };
 
-        let handled = room.handle_event(event).await;
+        let feed_msg = match &event {
+            Event::Packet(_, packet, _) if room.in_feed() => feed_msg(packet),
+            _ => None,
+        };
+
+        let handled = room.handle_event(event, focused).await;
 
-        let room_visible = match &self.state {
-            State::ShowRoom(id) => *id == room_id,
-            _ => true,
+        // Messages from old instances of the room are not handled
+        if let Some(msg) = feed_msg.filter(|_| handled) {
+            self.feed.push(room_id.clone(), &msg);
+        }
+
+        handled && self.is_visible(&room_id)
+    }
+
+    /// Handle a packet recorded in an earlier session, see `--replay`.
+    pub async fn handle_replayed_packet(
+        &mut self,
+        room_id: RoomIdentifier,
+        packet: ParsedPacket,
+    ) -> bool {
+        let focused = self.is_focused(&room_id);
+        let room = self.get_or_insert_room(room_id.clone()).await;
+        let feed_msg = if room.in_feed() {
+            feed_msg(&packet)
+        } else {
+            None
         };
-        handled && room_visible
+
+        let handled = room.handle_replayed_packet(packet, focused).await;
+        if let Some(msg) = feed_msg {
+            self.feed.push(room_id.clone(), &msg);
+        }
+
+        handled && self.is_visible(&room_id)
+    }
+
+    fn is_focused(&self, room_id: &RoomIdentifier) -> bool {
+        matches!(&self.state, State::ShowRoom(id) if id == room_id) && self.away.since().is_none()
+    }
+
+    fn is_visible(&self, room_id: &RoomIdentifier) -> bool {
+        match &self.state {
+            State::ShowRoom(id) | State::AskConnect(id) => id == room_id,
+            _ => true,
+        }
+    }
+}
+
+fn feed_msg(packet: &ParsedPacket) -> Option<Message> {
+    match &packet.content {
+        Ok(Data::SendEvent(SendEvent(msg))) => Some(msg.clone()),
+        _ => None,
     }
 }
@@ -0,0 +1,57 @@
+//! Keeping track of when the user is away, either because they said so or
+//! because they didn't press any keys for a while.
+
+use cove_config::Config;
+use time::{Duration, OffsetDateTime};
+
+pub struct Away {
+    idle_after: Option<Duration>,
+    last_activity: OffsetDateTime,
+    /// Start of the current away period, if the user is away right now.
+    since: Option<OffsetDateTime>,
+    /// Start and end of the last finished away period.
+    last: Option<(OffsetDateTime, OffsetDateTime)>,
+}
+
+impl Away {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            idle_after: config
+                .away_after
+                .map(|minutes| Duration::minutes(minutes as i64)),
+            last_activity: OffsetDateTime::now_utc(),
+            since: None,
+            last: None,
+        }
+    }
+
+    pub fn since(&self) -> Option<OffsetDateTime> {
+        self.since
+    }
+
+    pub fn last(&self) -> Option<(OffsetDateTime, OffsetDateTime)> {
+        self.last
+    }
+
+    /// The user pressed a key.
+    ///
+    /// This only ends idle periods. Being away manually must be stopped via
+    /// [`Self::toggle`], since the user may want to look around a bit before.
+    pub fn activity(&mut self) {
+        let now = OffsetDateTime::now_utc();
+        if let (None, Some(idle_after)) = (self.since, self.idle_after) {
+            if now - self.last_activity >= idle_after {
+                self.last = Some((self.last_activity, now));
+            }
+        }
+        self.last_activity = now;
+    }
+
+    pub fn toggle(&mut self) {
+        let now = OffsetDateTime::now_utc();
+        match self.since.take() {
+            Some(since) => self.last = Some((since, now)),
+            None => self.since = Some(now),
+        }
+    }
+}
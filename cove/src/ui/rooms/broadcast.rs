@@ -0,0 +1,186 @@
+//! Sending the same message to several rooms, see the `/broadcast` command.
+
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use cove_vault::RoomIdentifier;
+use crossterm::style::Stylize;
+use euphoxide::api::MessageId;
+use tokio::sync::oneshot;
+use tokio::sync::oneshot::error::TryRecvError;
+use toss::widgets::{Join2, Text};
+use toss::{Style, Styled, Widget, WidgetExt};
+
+use crate::ui::widgets::{ListBuilder, ListState, Popup};
+use crate::ui::{key_bindings, util, UiError};
+
+enum Status {
+    /// The user has not yet confirmed sending the message to the room.
+    Waiting,
+    /// The message was handed to the room and the server's reply is pending.
+    Sending(oneshot::Receiver<MessageId>),
+    Sent,
+    /// The room was not connected or the server didn't accept the message.
+    /// The user may try again.
+    Failed,
+}
+
+struct Target {
+    room: RoomIdentifier,
+    status: Status,
+}
+
+pub struct BroadcastState {
+    /// The room the command was entered in.
+    origin: RoomIdentifier,
+    content: String,
+    targets: Vec<Target>,
+    list: ListState<usize>,
+}
+
+pub enum BroadcastResult {
+    Close,
+    /// Send the message to the target with this index.
+    Send(usize),
+    Handled,
+    Unhandled,
+}
+
+impl BroadcastState {
+    pub fn new(origin: RoomIdentifier, rooms: Vec<RoomIdentifier>, content: String) -> Self {
+        let targets = rooms
+            .into_iter()
+            .map(|room| Target {
+                room,
+                status: Status::Waiting,
+            })
+            .collect();
+
+        Self {
+            origin,
+            content,
+            targets,
+            list: ListState::new(),
+        }
+    }
+
+    pub fn origin(&self) -> &RoomIdentifier {
+        &self.origin
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn room(&self, idx: usize) -> &RoomIdentifier {
+        &self.targets[idx].room
+    }
+
+    /// Record that the message was sent to a target and move on to the next
+    /// target. Without a reply to wait for, sending failed right away.
+    pub fn set_sending(&mut self, idx: usize, reply: Option<oneshot::Receiver<MessageId>>) {
+        self.targets[idx].status = match reply {
+            Some(reply) => Status::Sending(reply),
+            None => Status::Failed,
+        };
+        self.list.move_cursor_down();
+    }
+
+    /// Update the targets whose server reply has arrived since the last call.
+    pub fn stabilize(&mut self) {
+        for target in &mut self.targets {
+            if let Status::Sending(reply) = &mut target.status {
+                match reply.try_recv() {
+                    Ok(_) => target.status = Status::Sent,
+                    Err(TryRecvError::Empty) => {} // Wait a bit longer
+                    Err(TryRecvError::Closed) => target.status = Status::Failed,
+                }
+            }
+        }
+    }
+
+    pub fn handle_input_event(
+        &mut self,
+        event: &mut InputEvent<'_>,
+        keys: &Keys,
+    ) -> BroadcastResult {
+        if event.matches(&keys.general.abort) {
+            return BroadcastResult::Close;
+        }
+
+        if event.matches(&keys.general.confirm) {
+            if let Some(&idx) = self.list.selected() {
+                if let Status::Waiting | Status::Failed = self.targets[idx].status {
+                    return BroadcastResult::Send(idx);
+                }
+            }
+            return BroadcastResult::Handled;
+        }
+
+        if util::handle_list_input_event(&mut self.list, event, keys) {
+            return BroadcastResult::Handled;
+        }
+
+        BroadcastResult::Unhandled
+    }
+
+    pub fn widget(&mut self, theme: &Theme, keys: &Keys) -> impl Widget<UiError> + '_ {
+        let room_style = theme.room();
+        let domain_style = theme.info();
+        let selected_style = theme.cursor();
+
+        let first_line = self.content.lines().next().unwrap_or_default();
+        let info = Styled::new_plain("Sending ")
+            .then(first_line, Style::new().italic())
+            .then_plain(if self.content.contains('\n') {
+                " [...]"
+            } else {
+                ""
+            })
+            .then_plain("\n\nPress ")
+            .and_then(key_bindings::format_binding(&keys.general.confirm))
+            .then_plain(" to send it to the selected room or retry a failed one and ")
+            .and_then(key_bindings::format_binding(&keys.general.abort))
+            .then_plain(" when done.\n");
+
+        let mut list_builder = ListBuilder::new();
+        for (idx, target) in self.targets.iter().enumerate() {
+            let name = format!("&{}", target.room.name);
+            let domain = format!(" {}", target.room.domain);
+            let (status, status_style) = match target.status {
+                Status::Waiting => (" waiting", theme.info()),
+                Status::Sending(_) => (" sending...", theme.info()),
+                Status::Sent => (" sent", Style::new().green()),
+                Status::Failed => (" not sent", Style::new().red()),
+            };
+            list_builder.add_sel(idx, move |selected| {
+                let text = if selected {
+                    Styled::new(name, selected_style.bold()).then(domain, selected_style)
+                } else {
+                    Styled::new(name, room_style).then(domain, domain_style)
+                };
+                Text::new(text.then(status, status_style))
+            });
+        }
+
+        let (sent, failed) = self
+            .targets
+            .iter()
+            .fold((0, 0), |(s, f), t| match t.status {
+                Status::Waiting | Status::Sending(_) => (s, f),
+                Status::Sent => (s + 1, f),
+                Status::Failed => (s, f + 1),
+            });
+        let total = self.targets.len();
+        let title = format!("Broadcast ({sent}/{total} sent, {failed} failed)");
+
+        let inner = Join2::vertical(
+            Text::new(info)
+                .resize()
+                .with_max_width(54)
+                .segment()
+                .with_fixed(true),
+            list_builder.build(&mut self.list).segment(),
+        );
+        Popup::new(theme, inner, title)
+    }
+}
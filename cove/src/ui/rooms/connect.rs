@@ -1,47 +1,70 @@
-use cove_config::Keys;
+use cove_config::{Config, Keys, Theme};
 use cove_input::InputEvent;
+use cove_vault::RoomIdentifier;
 use crossterm::style::Stylize;
-use toss::widgets::{EditorState, Empty, Join2, Join3, Text};
+use toss::widgets::{Boxed, EditorState, Empty, Join2, Join3, Text};
 use toss::{Style, Styled, Widget, WidgetExt};
 
 use crate::ui::widgets::Popup;
 use crate::ui::{util, UiError};
-use crate::vault::RoomIdentifier;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum Focus {
     Name,
     Domain,
-}
-
-impl Focus {
-    fn advance(self) -> Self {
-        match self {
-            Self::Name => Self::Domain,
-            Self::Domain => Self::Name,
-        }
-    }
+    Template,
 }
 
 pub struct ConnectState {
     focus: Focus,
     name: EditorState,
     domain: EditorState,
+    /// Names of the room templates from the config, sorted alphabetically.
+    templates: Vec<String>,
+    template: EditorState,
 }
 
 pub enum ConnectResult {
     Close,
-    Connect(RoomIdentifier),
+    /// Connect to a room, basing it on a template if one was chosen.
+    Connect(RoomIdentifier, Option<String>),
     Handled,
     Unhandled,
 }
 
 impl ConnectState {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let mut templates = config.euph.templates.keys().cloned().collect::<Vec<_>>();
+        templates.sort_unstable();
+
         Self {
             focus: Focus::Name,
             name: EditorState::new(),
             domain: EditorState::with_initial_text("euphoria.leet.nu".to_string()),
+            templates,
+            template: EditorState::new(),
+        }
+    }
+
+    fn advance_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Name => Focus::Domain,
+            // The template field is only shown if there are any templates
+            Focus::Domain if self.templates.is_empty() => Focus::Name,
+            Focus::Domain => Focus::Template,
+            Focus::Template => Focus::Name,
+        };
+    }
+
+    /// The chosen template, or `Err` if no template has the entered name.
+    fn chosen_template(&self) -> Result<Option<String>, ()> {
+        let name = self.template.text();
+        if name.is_empty() {
+            Ok(None)
+        } else if self.templates.iter().any(|t| t == name) {
+            Ok(Some(name.to_string()))
+        } else {
+            Err(())
         }
     }
 
@@ -51,7 +74,7 @@ impl ConnectState {
         }
 
         if event.matches(&keys.general.focus) {
-            self.focus = self.focus.advance();
+            self.advance_focus();
             return ConnectResult::Handled;
         }
 
@@ -60,18 +83,26 @@ impl ConnectState {
                 domain: self.domain.text().to_string(),
                 name: self.name.text().to_string(),
             };
-            if !id.domain.is_empty() && !id.name.is_empty() {
-                return ConnectResult::Connect(id);
+            if let Ok(template) = self.chosen_template() {
+                if !id.domain.is_empty() && !id.name.is_empty() {
+                    return ConnectResult::Connect(id, template);
+                }
             }
         }
 
         let handled = match self.focus {
             Focus::Name => {
-                util::handle_editor_input_event(&mut self.name, event, keys, util::is_room_char)
+                let handled =
+                    util::handle_editor_input_event(&mut self.name, event, keys, |c| c != '\n');
+                self.split_name(event);
+                handled
             }
             Focus::Domain => {
                 util::handle_editor_input_event(&mut self.domain, event, keys, |c| c != '\n')
             }
+            Focus::Template => {
+                util::handle_editor_input_event(&mut self.template, event, keys, |c| c != '\n')
+            }
         };
 
         if handled {
@@ -81,9 +112,35 @@ impl ConnectState {
         ConnectResult::Unhandled
     }
 
-    pub fn widget(&mut self) -> impl Widget<UiError> + '_ {
-        let room_style = Style::new().bold().blue();
-        let domain_style = Style::new().grey();
+    /// Allow entering rooms as `&room@domain` by moving everything after the
+    /// `@` into the domain field and dropping characters not allowed in room
+    /// names.
+    fn split_name(&mut self, event: &mut InputEvent<'_>) {
+        let text = self.name.text().to_string();
+        let (name, domain) = match text.split_once('@') {
+            Some((name, domain)) => (name, Some(domain)),
+            None => (&text as &str, None),
+        };
+
+        let name = name
+            .chars()
+            .filter(|c| util::is_room_char(*c))
+            .collect::<String>();
+        if name != text {
+            self.name.clear();
+            self.name.insert_str(event.widthdb(), &name);
+        }
+
+        if let Some(domain) = domain {
+            self.domain.clear();
+            self.domain.insert_str(event.widthdb(), domain);
+            self.focus = Focus::Domain;
+        }
+    }
+
+    pub fn widget(&mut self, theme: &Theme) -> impl Widget<UiError> + '_ {
+        let room_style = theme.room();
+        let domain_style = theme.info();
 
         let name = Join2::horizontal(
             Text::new(Styled::new_plain("Room:   ").then("&", room_style))
@@ -114,7 +171,37 @@ impl ConnectState {
             name.segment().with_fixed(true),
             domain.segment().with_fixed(true),
         );
+        if self.templates.is_empty() {
+            let inner: Boxed<'_, UiError> = inner.boxed();
+            return Popup::new(theme, inner, "Connect to");
+        }
+
+        let template_style = if self.chosen_template().is_ok() {
+            Style::new()
+        } else {
+            Style::new().red()
+        };
+        let template = Join2::horizontal(
+            Text::new("Template: ")
+                .with_wrap(false)
+                .segment()
+                .with_fixed(true),
+            self.template
+                .widget()
+                .with_highlight(|s| Styled::new(s, template_style))
+                .with_focus(self.focus == Focus::Template)
+                .segment(),
+        );
+        let available = Styled::new("Available: ", theme.info())
+            .then(self.templates.join(", "), theme.info().italic());
+
+        let inner: Boxed<'_, UiError> = Join3::vertical(
+            inner.segment().with_fixed(true),
+            template.segment().with_fixed(true),
+            Text::new(available).segment().with_fixed(true),
+        )
+        .boxed();
 
-        Popup::new(inner, "Connect to")
+        Popup::new(theme, inner, "Connect to")
     }
 }
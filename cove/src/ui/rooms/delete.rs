@@ -1,12 +1,12 @@
-use cove_config::Keys;
+use cove_config::{Keys, Theme};
 use cove_input::InputEvent;
+use cove_vault::RoomIdentifier;
 use crossterm::style::Stylize;
 use toss::widgets::{EditorState, Empty, Join2, Text};
 use toss::{Style, Styled, Widget, WidgetExt};
 
 use crate::ui::widgets::Popup;
 use crate::ui::{util, UiError};
-use crate::vault::RoomIdentifier;
 
 pub struct DeleteState {
     id: RoomIdentifier,
@@ -44,18 +44,18 @@ impl DeleteState {
         DeleteResult::Unhandled
     }
 
-    pub fn widget(&mut self) -> impl Widget<UiError> + '_ {
+    pub fn widget(&mut self, theme: &Theme) -> impl Widget<UiError> + '_ {
         let warn_style = Style::new().bold().red();
-        let room_style = Style::new().bold().blue();
+        let room_style = theme.room();
         let text = Styled::new_plain("Are you sure you want to delete ")
             .then("&", room_style)
             .then(&self.id.name, room_style)
             .then_plain(" on the ")
-            .then(&self.id.domain, Style::new().grey())
+            .then(&self.id.domain, theme.info())
             .then_plain(" server?\n\n")
             .then_plain("This will delete the entire room history from your vault. ")
             .then_plain("To shrink your vault afterwards, run ")
-            .then("cove gc", Style::new().italic().grey())
+            .then("cove gc", theme.info().italic())
             .then_plain(".\n\n")
             .then_plain("To confirm the deletion, ")
             .then_plain("enter the full name of the room and press enter:");
@@ -82,6 +82,6 @@ impl DeleteState {
             .segment(),
         );
 
-        Popup::new(inner, "Delete room").with_border_style(warn_style)
+        Popup::new(theme, inner, "Delete room").with_border_style(warn_style)
     }
 }
@@ -0,0 +1,205 @@
+//! Browsing the room directories configured via the `directory` server config
+//! option.
+
+use cove_config::{Config, Keys, Theme};
+use cove_input::InputEvent;
+use cove_vault::RoomIdentifier;
+use crossterm::style::Stylize;
+use log::warn;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+use toss::widgets::{Join2, Text};
+use toss::{Style, Styled, Widget, WidgetExt};
+
+use crate::ui::widgets::{ListBuilder, ListState, Popup};
+use crate::ui::{key_bindings, util, UiError, UiEvent};
+use crate::version;
+
+pub struct DirectoryRoom {
+    id: RoomIdentifier,
+    description: Option<String>,
+}
+
+async fn fetch(user_agent: &str, domain: &str, url: &str) -> anyhow::Result<Vec<DirectoryRoom>> {
+    let response: Value = reqwest::Client::new()
+        .get(url)
+        .header(reqwest::header::USER_AGENT, user_agent)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let Some(rooms) = response.as_array() else {
+        anyhow::bail!("directory is not a list of rooms");
+    };
+
+    let rooms = rooms
+        .iter()
+        .filter_map(|room| {
+            let name = room["name"].as_str()?;
+            let description = room["description"].as_str().map(|d| d.to_string());
+            Some(DirectoryRoom {
+                id: RoomIdentifier::new(domain.to_string(), name.to_string()),
+                description,
+            })
+        })
+        .collect();
+    Ok(rooms)
+}
+
+/// Fetch the directories of all servers that have one configured.
+async fn fetch_all(config: &'static Config) -> (Vec<DirectoryRoom>, Vec<String>) {
+    let user_agent = version::user_agent(config);
+    let mut rooms = vec![];
+    let mut failed = vec![];
+
+    let mut servers = config.euph.servers.iter().collect::<Vec<_>>();
+    servers.sort_unstable_by_key(|(domain, _)| *domain);
+    for (domain, server) in servers {
+        let Some(url) = &server.directory else {
+            continue;
+        };
+        match fetch(&user_agent, domain, url).await {
+            Ok(found) => rooms.extend(found),
+            Err(err) => {
+                warn!("Failed to fetch room directory of {domain}: {err}");
+                failed.push(domain.clone());
+            }
+        }
+    }
+
+    (rooms, failed)
+}
+
+enum Directory {
+    Loading(oneshot::Receiver<(Vec<DirectoryRoom>, Vec<String>)>),
+    Loaded {
+        rooms: Vec<DirectoryRoom>,
+        failed: Vec<String>,
+    },
+}
+
+pub struct DirectoryState {
+    directory: Directory,
+    list: ListState<RoomIdentifier>,
+}
+
+pub enum DirectoryResult {
+    Close,
+    Connect(RoomIdentifier),
+    Handled,
+    Unhandled,
+}
+
+impl DirectoryState {
+    pub fn new(config: &'static Config, ui_event_tx: mpsc::UnboundedSender<UiEvent>) -> Self {
+        let (tx, rx) = oneshot::channel();
+        tokio::task::spawn(async move {
+            let _ = tx.send(fetch_all(config).await);
+            let _ = ui_event_tx.send(UiEvent::DirectoryLoaded);
+        });
+
+        Self {
+            directory: Directory::Loading(rx),
+            list: ListState::new(),
+        }
+    }
+
+    fn stabilize(&mut self) {
+        if let Directory::Loading(rx) = &mut self.directory {
+            if let Ok((rooms, failed)) = rx.try_recv() {
+                self.directory = Directory::Loaded { rooms, failed };
+            }
+        }
+    }
+
+    pub fn handle_input_event(
+        &mut self,
+        event: &mut InputEvent<'_>,
+        keys: &Keys,
+    ) -> DirectoryResult {
+        if event.matches(&keys.general.abort) {
+            return DirectoryResult::Close;
+        }
+
+        if event.matches(&keys.general.confirm) {
+            if let Some(id) = self.list.selected() {
+                return DirectoryResult::Connect(id.clone());
+            }
+            return DirectoryResult::Handled;
+        }
+
+        if util::handle_list_input_event(&mut self.list, event, keys) {
+            return DirectoryResult::Handled;
+        }
+
+        DirectoryResult::Unhandled
+    }
+
+    pub fn widget(&mut self, theme: &Theme, keys: &Keys) -> impl Widget<UiError> + '_ {
+        self.stabilize();
+
+        let style_info = theme.info().italic();
+        let style_selected = theme.cursor();
+        let style_room = theme.room();
+        let style_domain = theme.info();
+
+        let mut list_builder = ListBuilder::new();
+
+        match &self.directory {
+            Directory::Loading(_) => {
+                list_builder.add_unsel(Text::new(("Loading...", style_info)));
+            }
+            Directory::Loaded { rooms, failed } => {
+                for domain in failed {
+                    let text = format!("Failed to load directory of {domain}");
+                    list_builder.add_unsel(Text::new((text, Style::new().red())));
+                }
+                if rooms.is_empty() && failed.is_empty() {
+                    list_builder.add_unsel(Text::new((
+                        "No rooms found. Set the directory option of a server to browse it.",
+                        style_info,
+                    )));
+                }
+
+                for room in rooms {
+                    let name = format!("&{}", room.id.name);
+                    let domain = format!(" {}", room.id.domain);
+                    let description = room
+                        .description
+                        .as_ref()
+                        .map(|d| format!(" {}", d.lines().next().unwrap_or_default()))
+                        .unwrap_or_default();
+                    list_builder.add_sel(room.id.clone(), move |selected| {
+                        let text = if selected {
+                            Styled::new(name, style_selected.bold())
+                                .then(domain, style_selected)
+                                .then(description, style_selected)
+                        } else {
+                            Styled::new(name, style_room)
+                                .then(domain, style_domain)
+                                .then_plain(description)
+                        };
+                        Text::new(text).with_wrap(false)
+                    });
+                }
+            }
+        }
+
+        let hint = Styled::new_plain("Press ")
+            .and_then(key_bindings::format_binding(&keys.general.confirm))
+            .then_plain(" to connect to a room.");
+
+        let inner = Join2::vertical(
+            list_builder.build(&mut self.list).segment(),
+            Text::new(hint)
+                .padding()
+                .with_top(1)
+                .segment()
+                .with_fixed(true),
+        );
+
+        Popup::new(theme, inner, "Room directory")
+    }
+}
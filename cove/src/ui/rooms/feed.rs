@@ -0,0 +1,156 @@
+//! A combined feed of the messages arriving in all rooms that have the `feed`
+//! config option enabled, for keeping an eye on several rooms at once.
+
+use std::collections::VecDeque;
+
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use cove_vault::RoomIdentifier;
+use crossterm::style::Stylize;
+use euphoxide::api::{Message, MessageId};
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::OffsetDateTime;
+use toss::widgets::{Join2, Text};
+use toss::{Style, Styled, Widget, WidgetExt};
+use tz::TimeZone;
+
+use crate::euph;
+use crate::ui::widgets::{ListBuilder, ListState};
+use crate::ui::{util, UiError};
+
+/// How many messages the feed remembers. Older messages are dropped.
+const CAPACITY: usize = 1000;
+
+const TIME_FORMAT: &[FormatItem<'_>] = format_description!("[hour]:[minute]");
+
+struct Entry {
+    room: RoomIdentifier,
+    id: MessageId,
+    time: Option<OffsetDateTime>,
+    nick: String,
+    content: String,
+}
+
+pub struct Feed {
+    time_zone: &'static TimeZone,
+    /// Messages in the order they arrived, oldest first. They are shown newest
+    /// first.
+    entries: VecDeque<Entry>,
+    list: ListState<(RoomIdentifier, MessageId)>,
+    /// Whether to keep the cursor on the newest message as messages arrive.
+    follow: bool,
+}
+
+pub enum FeedResult {
+    Close,
+    Open(RoomIdentifier, MessageId),
+    Handled,
+    Unhandled,
+}
+
+impl Feed {
+    pub fn new(time_zone: &'static TimeZone) -> Self {
+        Self {
+            time_zone,
+            entries: VecDeque::new(),
+            list: ListState::new(),
+            follow: true,
+        }
+    }
+
+    /// A new message arrived in a room that is part of the feed.
+    pub fn push(&mut self, room: RoomIdentifier, msg: &Message) {
+        if self.entries.len() >= CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            room,
+            id: msg.id,
+            time: crate::util::convert_to_time_zone(self.time_zone, msg.time.0),
+            nick: msg.sender.name.clone(),
+            content: msg.content.clone(),
+        });
+    }
+
+    /// Move the cursor to the newest message and keep it there.
+    pub fn follow(&mut self) {
+        self.follow = true;
+    }
+
+    pub fn handle_input_event(&mut self, event: &mut InputEvent<'_>, keys: &Keys) -> FeedResult {
+        if event.matches(&keys.general.abort) {
+            return FeedResult::Close;
+        }
+
+        if event.matches(&keys.general.confirm) {
+            if let Some((room, id)) = self.list.selected() {
+                return FeedResult::Open(room.clone(), *id);
+            }
+            return FeedResult::Handled;
+        }
+
+        if util::handle_list_input_event(&mut self.list, event, keys) {
+            // Only follow new messages while the cursor is on the newest one
+            let newest = self.entries.back().map(|e| (e.room.clone(), e.id));
+            self.follow = newest.is_none() || self.list.selected() == newest.as_ref();
+            return FeedResult::Handled;
+        }
+
+        FeedResult::Unhandled
+    }
+
+    pub fn widget(&mut self, theme: &Theme) -> impl Widget<UiError> + '_ {
+        let (cursor, time_style, room_style) = (theme.cursor(), theme.time(), theme.room());
+
+        // The newest message is shown at the top, so a fresh list has its
+        // cursor on it and is scrolled all the way up.
+        if self.follow {
+            self.list = ListState::new();
+        }
+
+        let mut list_builder = ListBuilder::new();
+
+        if self.entries.is_empty() {
+            list_builder.add_unsel(Text::new((
+                "No messages yet. Messages sent in rooms with the feed option enabled appear here.",
+                theme.info().italic(),
+            )));
+        }
+
+        for entry in self.entries.iter().rev() {
+            let time = entry
+                .time
+                .and_then(|time| time.format(TIME_FORMAT).ok())
+                .unwrap_or_else(|| "??:??".to_string());
+            let room = format!("&{}", entry.room.name);
+            let nick = entry.nick.clone();
+            let content = entry.content.lines().next().unwrap_or_default().to_string();
+            list_builder.add_sel((entry.room.clone(), entry.id), move |selected| {
+                let (base, time_style, room_style) = if selected {
+                    (cursor, cursor, cursor.bold())
+                } else {
+                    (Style::new(), time_style, room_style)
+                };
+                let text = Styled::new(time, time_style)
+                    .then_plain(" ")
+                    .then(room, room_style)
+                    .then_plain(" ")
+                    .then("[", base)
+                    .and_then(euph::style_nick(&nick, base))
+                    .then("] ", base)
+                    .then(content, base);
+                Text::new(text).with_wrap(false)
+            });
+        }
+
+        let heading = Styled::new("Feed", Style::new().bold())
+            .then_plain(format!(" ({} messages)", self.entries.len()));
+
+        Join2::vertical(
+            Text::new(heading).segment().with_fixed(true),
+            list_builder.build(&mut self.list).segment(),
+        )
+        .title("Feed")
+    }
+}
@@ -0,0 +1,72 @@
+//! Keeping track of which rooms the user viewed, for navigating back and forth
+//! between them like in a browser.
+
+use cove_vault::RoomIdentifier;
+
+#[derive(Default)]
+pub struct History {
+    /// Rooms viewed before the current one, oldest first.
+    back: Vec<RoomIdentifier>,
+    /// Rooms left by going back, most recently left last.
+    forward: Vec<RoomIdentifier>,
+    /// The room viewed most recently. It stays the current room while the
+    /// rooms list is shown.
+    current: Option<RoomIdentifier>,
+    /// All viewed rooms, most recently viewed first.
+    recent: Vec<RoomIdentifier>,
+}
+
+impl History {
+    pub fn current(&self) -> Option<&RoomIdentifier> {
+        self.current.as_ref()
+    }
+
+    pub fn recent(&self) -> &[RoomIdentifier] {
+        &self.recent
+    }
+
+    fn touch(&mut self, room: &RoomIdentifier) {
+        self.recent.retain(|r| r != room);
+        self.recent.insert(0, room.clone());
+    }
+
+    /// The user opened a room without using back or forward.
+    pub fn visit(&mut self, room: RoomIdentifier) {
+        self.touch(&room);
+        if self.current.as_ref() == Some(&room) {
+            return;
+        }
+        if let Some(current) = self.current.replace(room) {
+            self.back.push(current);
+        }
+        self.forward.clear();
+    }
+
+    pub fn back(&mut self) -> Option<RoomIdentifier> {
+        let room = self.back.pop()?;
+        self.touch(&room);
+        if let Some(current) = self.current.replace(room.clone()) {
+            self.forward.push(current);
+        }
+        Some(room)
+    }
+
+    pub fn forward(&mut self) -> Option<RoomIdentifier> {
+        let room = self.forward.pop()?;
+        self.touch(&room);
+        if let Some(current) = self.current.replace(room.clone()) {
+            self.back.push(current);
+        }
+        Some(room)
+    }
+
+    /// Remove a room that no longer exists.
+    pub fn forget(&mut self, room: &RoomIdentifier) {
+        self.back.retain(|r| r != room);
+        self.forward.retain(|r| r != room);
+        self.recent.retain(|r| r != room);
+        if self.current.as_ref() == Some(room) {
+            self.current = self.back.pop();
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use cove_config::{Keys, Theme};
+use cove_input::InputEvent;
+use cove_vault::RoomIdentifier;
+use crossterm::style::Stylize;
+use toss::widgets::Text;
+use toss::{Styled, Widget};
+
+use crate::ui::widgets::{ListBuilder, ListState, Popup};
+use crate::ui::{util, UiError};
+
+pub struct RecentState {
+    rooms: Vec<RoomIdentifier>,
+    list: ListState<RoomIdentifier>,
+}
+
+pub enum RecentResult {
+    Close,
+    Open(RoomIdentifier),
+    Handled,
+    Unhandled,
+}
+
+impl RecentState {
+    pub fn new(rooms: Vec<RoomIdentifier>) -> Self {
+        Self {
+            rooms,
+            list: ListState::new(),
+        }
+    }
+
+    pub fn handle_input_event(&mut self, event: &mut InputEvent<'_>, keys: &Keys) -> RecentResult {
+        if event.matches(&keys.general.abort) {
+            return RecentResult::Close;
+        }
+
+        if event.matches(&keys.general.confirm) {
+            if let Some(id) = self.list.selected() {
+                return RecentResult::Open(id.clone());
+            }
+            return RecentResult::Handled;
+        }
+
+        if util::handle_list_input_event(&mut self.list, event, keys) {
+            return RecentResult::Handled;
+        }
+
+        RecentResult::Unhandled
+    }
+
+    pub fn widget(&mut self, theme: &Theme) -> impl Widget<UiError> + '_ {
+        let style_selected = theme.cursor();
+        let style_room = theme.room();
+        let style_domain = theme.info();
+
+        let mut list_builder = ListBuilder::new();
+
+        if self.rooms.is_empty() {
+            list_builder.add_unsel(Text::new(("No rooms visited yet", theme.info().italic())));
+        }
+
+        for id in &self.rooms {
+            let name = format!("&{}", id.name);
+            let domain = format!(" {}", id.domain);
+            list_builder.add_sel(id.clone(), move |selected| {
+                let text = if selected {
+                    Styled::new(name, style_selected.bold()).then(domain, style_selected)
+                } else {
+                    Styled::new(name, style_room).then(domain, style_domain)
+                };
+                Text::new(text)
+            });
+        }
+
+        Popup::new(theme, list_builder.build(&mut self.list), "Recent rooms")
+    }
+}
@@ -0,0 +1,395 @@
+//! Searching messages across all rooms or within a single room.
+
+use std::collections::HashMap;
+
+use cove_config::{Config, Keys, SavedSearch};
+use cove_input::InputEvent;
+use cove_vault::{
+    EuphVault, MsgChange, MsgChangeKind, RoomIdentifier, SearchFilter, SearchResult, SNIPPET_END,
+    SNIPPET_START,
+};
+use crossterm::style::Stylize;
+use euphoxide::api::{MessageId, Time};
+use time::{Duration, OffsetDateTime};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::TryRecvError;
+use toss::widgets::{EditorState, Join2, Text};
+use toss::{Style, Styled, Widget, WidgetExt};
+
+use crate::euph;
+use crate::macros::logging_unwrap;
+use crate::ui::widgets::{ListBuilder, ListState};
+use crate::ui::{util, UiError};
+
+/// Maximum amount of results shown for a single query.
+pub const LIMIT: usize = 500;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Query,
+    Results,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Entry {
+    Saved(String),
+    Msg(RoomIdentifier, MessageId),
+}
+
+pub struct SearchState {
+    config: &'static Config,
+    focus: Focus,
+    query: EditorState,
+    /// Name of the saved search whose results are currently shown.
+    saved: Option<String>,
+    /// The room that searches are restricted to, if any.
+    room: Option<RoomIdentifier>,
+    /// Amount of unseen matches for each saved search.
+    unseen: HashMap<String, usize>,
+    /// Changes to the vault since [`Self::unseen`] was last counted. `None`
+    /// until the first count.
+    unseen_changes: Option<broadcast::Receiver<MsgChange>>,
+    /// The results of the last search, grouped by room.
+    results: Vec<SearchResult>,
+    list: ListState<Entry>,
+}
+
+pub enum SearchReaction {
+    Close,
+    Search(String, SearchFilter),
+    Open(RoomIdentifier, MessageId),
+    Handled,
+    Unhandled,
+}
+
+/// Move words like `from:nick` from the query to the filter.
+fn parse_query(query: &str, mut filter: SearchFilter) -> (String, SearchFilter) {
+    let mut words = vec![];
+    for word in query.split_whitespace() {
+        match word.strip_prefix("from:") {
+            Some(nick) if !nick.is_empty() => filter.nick = Some(nick.to_string()),
+            _ => words.push(word),
+        }
+    }
+    (words.join(" "), filter)
+}
+
+fn saved_search(search: &SavedSearch) -> (String, SearchFilter) {
+    let filter = SearchFilter {
+        domain: search.domain.clone(),
+        room: search.room.clone(),
+        since: search
+            .days
+            .map(|days| Time(OffsetDateTime::now_utc() - Duration::days(days as i64))),
+        nick: None,
+    };
+    parse_query(&search.query, filter)
+}
+
+/// Receive all pending changes. Returns whether there were any.
+fn drain_changes(changes: &mut broadcast::Receiver<MsgChange>) -> bool {
+    let mut changed = false;
+    loop {
+        match changes.try_recv() {
+            // Notes don't affect the counts.
+            Ok(change) if change.kind == MsgChangeKind::Note => {}
+            // Lagging behind also means that something changed.
+            Ok(_) | Err(TryRecvError::Lagged(_)) => changed = true,
+            Err(TryRecvError::Empty | TryRecvError::Closed) => return changed,
+        }
+    }
+}
+
+fn style_snippet(snippet: &str) -> Styled {
+    let match_style = Style::new().bold().yellow();
+    let mut result = Styled::default();
+    let mut rest = snippet;
+    while let Some((before, after)) = rest.split_once(SNIPPET_START) {
+        let (term, after) = after.split_once(SNIPPET_END).unwrap_or((after, ""));
+        result = result.then_plain(before).then(term, match_style);
+        rest = after;
+    }
+    result.then_plain(rest)
+}
+
+impl SearchState {
+    pub fn new(config: &'static Config) -> Self {
+        Self {
+            config,
+            focus: Focus::Query,
+            query: EditorState::new(),
+            saved: None,
+            room: None,
+            unseen: HashMap::new(),
+            unseen_changes: None,
+            results: vec![],
+            list: ListState::new(),
+        }
+    }
+
+    /// Restrict searches to a single room.
+    pub fn set_room(&mut self, room: RoomIdentifier) {
+        self.room = Some(room);
+    }
+
+    pub fn room(&self) -> Option<&RoomIdentifier> {
+        self.room.as_ref()
+    }
+
+    /// The query and filter to search with for the query entered by the user.
+    pub fn search(&self, query: &str) -> (String, SearchFilter) {
+        let filter = SearchFilter {
+            domain: self.room.as_ref().map(|room| room.domain.clone()),
+            room: self.room.as_ref().map(|room| room.name.clone()),
+            ..SearchFilter::default()
+        };
+        parse_query(query, filter)
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.saved = None;
+        self.query = EditorState::with_initial_text(query);
+    }
+
+    pub fn set_results(&mut self, mut results: Vec<SearchResult>) {
+        // Results arrive newest first. The sort is stable, so they stay that
+        // way within each room.
+        results.sort_by(|a, b| a.room.cmp(&b.room));
+        self.results = results;
+        self.list = ListState::new();
+        if !self.results.is_empty() {
+            self.focus = Focus::Results;
+        }
+    }
+
+    /// Count the unseen matches of the saved searches again, but only if any
+    /// messages changed since the last count.
+    pub async fn update_unseen_counts(&mut self, vault: &EuphVault) {
+        if let Some(changes) = &mut self.unseen_changes {
+            if !drain_changes(changes) {
+                return;
+            }
+        } else {
+            self.unseen_changes = Some(vault.vault().subscribe());
+        }
+
+        for (name, search) in &self.config.searches {
+            let (query, filter) = saved_search(search);
+            let count = vault.search_unseen_count(query, filter).await;
+            self.unseen.insert(name.clone(), logging_unwrap!(count));
+        }
+    }
+
+    pub fn handle_input_event(
+        &mut self,
+        event: &mut InputEvent<'_>,
+        keys: &Keys,
+    ) -> SearchReaction {
+        if event.matches(&keys.general.abort) {
+            return SearchReaction::Close;
+        }
+
+        if event.matches(&keys.general.focus) {
+            self.focus = match self.focus {
+                Focus::Query => Focus::Results,
+                Focus::Results => Focus::Query,
+            };
+            return SearchReaction::Handled;
+        }
+
+        match self.focus {
+            Focus::Query => {
+                if event.matches(&keys.general.confirm) {
+                    let query = self.query.text().to_string();
+                    let saved = self
+                        .saved
+                        .as_ref()
+                        .and_then(|n| self.config.searches.get(n));
+                    let (query, filter) = match saved {
+                        Some(saved) if saved.query == query => saved_search(saved),
+                        _ => {
+                            self.saved = None;
+                            self.search(&query)
+                        }
+                    };
+                    return SearchReaction::Search(query, filter);
+                }
+                if util::handle_editor_input_event(&mut self.query, event, keys, |c| c != '\n') {
+                    return SearchReaction::Handled;
+                }
+            }
+            Focus::Results => {
+                if event.matches(&keys.general.confirm) {
+                    return match self.list.selected() {
+                        Some(Entry::Saved(name)) => {
+                            let search = &self.config.searches[name];
+                            self.saved = Some(name.clone());
+                            self.query = EditorState::with_initial_text(search.query.clone());
+                            let (query, filter) = saved_search(search);
+                            SearchReaction::Search(query, filter)
+                        }
+                        Some(Entry::Msg(room, id)) => SearchReaction::Open(room.clone(), *id),
+                        None => SearchReaction::Handled,
+                    };
+                }
+                if util::handle_list_input_event(&mut self.list, event, keys) {
+                    return SearchReaction::Handled;
+                }
+            }
+        }
+
+        SearchReaction::Unhandled
+    }
+
+    pub fn widget(&mut self) -> impl Widget<UiError> + '_ {
+        let theme = &self.config.theme;
+        let room_style = theme.room();
+        let domain_style = theme.info();
+
+        let mut list_builder = ListBuilder::new();
+
+        if !self.config.searches.is_empty() {
+            list_builder.add_unsel(Text::new(Styled::new(
+                "Saved searches",
+                Style::new().bold(),
+            )));
+            let mut names = self.config.searches.keys().collect::<Vec<_>>();
+            names.sort_unstable();
+            for name in names {
+                let unseen = self.unseen.get(name).copied().unwrap_or(0);
+                let name = name.clone();
+                list_builder.add_sel(Entry::Saved(name.clone()), move |selected| {
+                    let base = if selected {
+                        theme.cursor()
+                    } else {
+                        Style::new()
+                    };
+                    let mut text = Styled::new_plain("  ").then(name, base);
+                    if unseen > 0 {
+                        text = text
+                            .then_plain(" (")
+                            .then(format!("{unseen} new"), Style::new().bold().green())
+                            .then_plain(")");
+                    }
+                    Text::new(text).with_wrap(false)
+                });
+            }
+        }
+
+        let mut last_room = None;
+        for result in &self.results {
+            if last_room != Some(&result.room) {
+                if last_room.is_some() || !self.config.searches.is_empty() {
+                    list_builder.add_unsel(Text::new(""));
+                }
+                let heading = Styled::new(format!("{} ", result.room.domain), domain_style)
+                    .then(format!("&{}", result.room.name), room_style);
+                list_builder.add_unsel(Text::new(heading));
+                last_room = Some(&result.room);
+            }
+
+            let nick = result.nick.clone();
+            let snippet = result.snippet.clone();
+            list_builder.add_sel(
+                Entry::Msg(result.room.clone(), result.id),
+                move |selected| {
+                    let base = if selected {
+                        theme.cursor()
+                    } else {
+                        Style::new()
+                    };
+                    let text = Styled::new_plain("  ")
+                        .then("[", base)
+                        .and_then(euph::style_nick(&nick, base))
+                        .then("] ", base)
+                        .and_then(style_snippet(&snippet));
+                    Text::new(text).with_wrap(false)
+                },
+            );
+        }
+
+        let mut heading = match &self.saved {
+            Some(name) => {
+                Styled::new("Saved search ", Style::new().bold()).then(name, theme.room())
+            }
+            None => match &self.room {
+                Some(room) => Styled::new("Search ", Style::new().bold())
+                    .then(format!("&{}", room.name), theme.room()),
+                None => Styled::new("Search all rooms", Style::new().bold()),
+            },
+        };
+        if !self.results.is_empty() {
+            heading = heading.then_plain(format!(" ({} results)", self.results.len()));
+        }
+
+        let query = self
+            .query
+            .widget()
+            .with_focus(self.focus == Focus::Query)
+            .padding()
+            .with_horizontal(1)
+            .border();
+
+        Join2::vertical(
+            Join2::vertical(
+                Text::new(heading).segment().with_fixed(true),
+                query.segment().with_fixed(true),
+            )
+            .segment()
+            .with_fixed(true),
+            list_builder.build(&mut self.list).segment(),
+        )
+        .title("Search")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cove_vault::SearchFilter;
+
+    use super::parse_query;
+
+    #[test]
+    fn plain_words_stay_in_query() {
+        let (query, filter) = parse_query("  hello   world ", SearchFilter::default());
+        assert_eq!(query, "hello world");
+        assert_eq!(filter.nick, None);
+    }
+
+    #[test]
+    fn from_moves_nick_to_filter() {
+        let (query, filter) = parse_query("hello from:Some_Nick world", SearchFilter::default());
+        assert_eq!(query, "hello world");
+        assert_eq!(filter.nick.as_deref(), Some("Some_Nick"));
+    }
+
+    #[test]
+    fn only_from_leaves_empty_query() {
+        let (query, filter) = parse_query("from:nick", SearchFilter::default());
+        assert_eq!(query, "");
+        assert_eq!(filter.nick.as_deref(), Some("nick"));
+    }
+
+    #[test]
+    fn last_from_wins() {
+        let (_, filter) = parse_query("from:a from:b", SearchFilter::default());
+        assert_eq!(filter.nick.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn empty_from_is_a_word() {
+        let (query, filter) = parse_query("from: x", SearchFilter::default());
+        assert_eq!(query, "from: x");
+        assert_eq!(filter.nick, None);
+    }
+
+    #[test]
+    fn other_filters_are_kept() {
+        let filter = SearchFilter {
+            room: Some("test".to_string()),
+            ..SearchFilter::default()
+        };
+        let (_, filter) = parse_query("from:nick", filter);
+        assert_eq!(filter.room.as_deref(), Some("test"));
+    }
+}
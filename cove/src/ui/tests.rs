@@ -0,0 +1,185 @@
+//! Headless snapshots of the whole UI.
+//!
+//! Each snapshot is drawn by [`Ui::run_scripted`] in a child process attached
+//! to a pseudo terminal of a fixed size. The child is this test binary itself,
+//! running only [`child`] and told which script to run via an environment
+//! variable. The parent reads everything written to the terminal and
+//! interprets it to find out what ended up on the screen.
+//!
+//! Snapshots are stored in `src/ui/snapshots`. A test fails if its snapshot is
+//! missing. Set `UPDATE_SNAPSHOTS` to create missing snapshots or overwrite
+//! existing ones after an intentional change, then review the diff.
+
+use std::io::Read;
+use std::path::Path;
+use std::{env, fs, mem, process};
+
+use cove_config::Config;
+use cove_vault::{RoomIdentifier, Vault};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use euphoxide::api::{Message, Time};
+use portable_pty::{CommandBuilder, PtySize};
+use serde_json::json;
+use toss::Terminal;
+use tz::TimeZone;
+
+use super::{StartRoom, Ui, UiEvent};
+
+const WIDTH: u16 = 80;
+const HEIGHT: u16 = 24;
+
+/// Tells [`child`] which script to run.
+const SCRIPT_VAR: &str = "COVE_SNAPSHOT_SCRIPT";
+
+fn room() -> RoomIdentifier {
+    RoomIdentifier::new("euphoria.leet.nu".to_string(), "test".to_string())
+}
+
+fn msg(id: &str, parent: Option<&str>, nick: &str, content: &str) -> Message {
+    let msg = json!({
+        "id": id,
+        "parent": parent,
+        "time": 1_700_000_000,
+        "sender": {
+            "id": format!("agent:{nick}"),
+            "name": nick,
+            "server_id": "test",
+            "server_era": "test",
+            "session_id": nick,
+        },
+        "content": content,
+    });
+    serde_json::from_value(msg).unwrap()
+}
+
+async fn populate(vault: &Vault) {
+    let other = vault.euph().room(RoomIdentifier::new(
+        "euphoria.leet.nu".to_string(),
+        "other".to_string(),
+    ));
+    other.join(Time::now()).await.unwrap();
+
+    let room = vault.euph().room(room());
+    room.join(Time::now()).await.unwrap();
+    let msgs = vec![
+        msg("0000000000001", None, "alice", "hello"),
+        msg("0000000000002", Some("0000000000001"), "bob", "hi alice"),
+        msg(
+            "0000000000003",
+            Some("0000000000002"),
+            "alice",
+            "how are you?",
+        ),
+        msg("0000000000004", None, "carol", "a new thread"),
+    ];
+    room.add_msgs(msgs, None, None).await.unwrap();
+}
+
+fn key(code: KeyCode) -> UiEvent {
+    UiEvent::Term(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)))
+}
+
+fn script(name: &str) -> Vec<UiEvent> {
+    match name {
+        "rooms_list" => vec![key(KeyCode::Down)],
+        "room" => vec![
+            UiEvent::Open(StartRoom {
+                room: room(),
+                msg: None,
+            }),
+            key(KeyCode::Up),
+            key(KeyCode::Up),
+        ],
+        _ => panic!("unknown script {name:?}"),
+    }
+}
+
+/// Draws a snapshot when run by [`snapshot`], does nothing otherwise.
+#[tokio::test]
+#[ignore = "only run by the snapshot tests in a pseudo terminal"]
+async fn child() {
+    let Ok(name) = env::var(SCRIPT_VAR) else {
+        return;
+    };
+
+    let config: Config = serde_json::from_value(json!({ "offline": true })).unwrap();
+    let config = Box::leak(Box::new(config));
+    let time_zone = Box::leak(Box::new(TimeZone::utc()));
+    let vault = cove_vault::launch_in_memory(time_zone).unwrap();
+    populate(&vault).await;
+
+    let mut terminal = Terminal::new().unwrap();
+    Ui::run_scripted(config, &mut terminal, vault, script(&name))
+        .await
+        .unwrap();
+
+    // Restoring the terminal would leave the alternate screen and hide what
+    // was drawn. Exiting right away also keeps the test harness from printing
+    // its results on top of it.
+    mem::forget(terminal);
+    process::exit(0);
+}
+
+/// Run a script in a child process and return the final screen contents.
+fn snapshot(name: &str) -> String {
+    let size = PtySize {
+        rows: HEIGHT,
+        cols: WIDTH,
+        pixel_width: 0,
+        pixel_height: 0,
+    };
+    let pty = portable_pty::native_pty_system().openpty(size).unwrap();
+
+    let mut cmd = CommandBuilder::new(env::current_exe().unwrap());
+    cmd.args(["ui::tests::child", "--exact", "--ignored", "--nocapture"]);
+    cmd.env(SCRIPT_VAR, name);
+    let mut child = pty.slave.spawn_command(cmd).unwrap();
+    drop(pty.slave);
+
+    // Once the child exits, reading fails instead of reaching the end on some
+    // platforms. Everything read until then is kept either way.
+    let mut output = vec![];
+    let mut reader = pty.master.try_clone_reader().unwrap();
+    let _ = reader.read_to_end(&mut output);
+    assert!(child.wait().unwrap().success(), "child failed");
+
+    let mut parser = vt100::Parser::new(HEIGHT, WIDTH, 0);
+    parser.process(&output);
+    parser.screen().contents()
+}
+
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/ui/snapshots")
+        .join(format!("{name}.txt"));
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    match fs::read_to_string(&path) {
+        Ok(expected) => assert_eq!(actual, expected, "snapshot {name:?} changed"),
+        Err(err) => {
+            panic!("can't read snapshot {name:?} ({err}), set UPDATE_SNAPSHOTS to create it")
+        }
+    }
+}
+
+#[test]
+fn rooms_list_snapshot() {
+    let screen = snapshot("rooms_list");
+    assert!(screen.contains("&other"));
+    assert!(screen.contains("&test"));
+    assert_snapshot("rooms_list", &screen);
+}
+
+#[test]
+fn room_snapshot() {
+    let screen = snapshot("room");
+    assert!(screen.contains("hello"));
+    assert!(screen.contains("how are you?"));
+    assert!(screen.contains("a new thread"));
+    assert_snapshot("room", &screen);
+}
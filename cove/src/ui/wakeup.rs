@@ -0,0 +1,80 @@
+//! Noticing when the system resumes from suspend or the network changes, so
+//! that rooms can reconnect right away instead of waiting for their next
+//! reconnect attempt.
+
+use std::collections::BTreeSet;
+use std::time::{Duration, SystemTime};
+
+use log::info;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::UiEvent;
+
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// If this much more wall clock time than [`INTERVAL`] passed between two
+/// checks, the system was most likely suspended in the meantime.
+///
+/// This works because tokio's timers use a monotonic clock which doesn't
+/// advance while the system is suspended.
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wakeup {
+    /// The system resumed from suspend. Existing connections are probably
+    /// dead even if they look fine.
+    Resumed,
+    /// The set of active network interfaces changed.
+    NetworkChanged,
+}
+
+/// Names of the network interfaces that are currently up.
+#[cfg(target_os = "linux")]
+fn network_interfaces() -> Option<BTreeSet<String>> {
+    let entries = std::fs::read_dir("/sys/class/net").ok()?;
+    let mut result = BTreeSet::new();
+    for entry in entries.flatten() {
+        let operstate = std::fs::read_to_string(entry.path().join("operstate"));
+        if operstate.is_ok_and(|state| state.trim() == "up") {
+            result.insert(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Some(result)
+}
+
+/// Network interfaces can't be inspected on this platform.
+#[cfg(not(target_os = "linux"))]
+fn network_interfaces() -> Option<BTreeSet<String>> {
+    None
+}
+
+pub async fn watch(event_tx: &UnboundedSender<UiEvent>) {
+    let mut last_check = SystemTime::now();
+    let mut last_interfaces = network_interfaces();
+
+    loop {
+        tokio::time::sleep(INTERVAL).await;
+
+        let now = SystemTime::now();
+        let elapsed = now.duration_since(last_check).unwrap_or_default();
+        last_check = now;
+
+        let interfaces = network_interfaces();
+        let network_changed = interfaces != last_interfaces;
+        last_interfaces = interfaces;
+
+        let wakeup = if elapsed > INTERVAL + SUSPEND_THRESHOLD {
+            info!("System resumed after {}s, reconnecting", elapsed.as_secs());
+            Wakeup::Resumed
+        } else if network_changed {
+            info!("Network interfaces changed, reconnecting");
+            Wakeup::NetworkChanged
+        } else {
+            continue;
+        };
+
+        if event_tx.send(UiEvent::Wakeup(wakeup)).is_err() {
+            return;
+        }
+    }
+}
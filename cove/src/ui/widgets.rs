@@ -1,5 +1,7 @@
 mod list;
 mod popup;
+mod sidebar;
 
 pub use self::list::*;
 pub use self::popup::*;
+pub use self::sidebar::*;
@@ -1,3 +1,4 @@
+use std::cell::{Cell, OnceCell};
 use std::vec;
 
 use toss::{Frame, Pos, Size, Widget, WidthDb};
@@ -291,7 +292,7 @@ impl<'a, Id, W> ListBuilder<'a, Id, W> {
         });
     }
 
-    pub fn build(self, state: &mut ListState<Id>) -> List<'_, Id, W>
+    pub fn build(self, state: &'a mut ListState<Id>) -> List<'a, Id, W>
     where
         Id: Clone + Eq,
     {
@@ -302,15 +303,46 @@ impl<'a, Id, W> ListBuilder<'a, Id, W> {
         let rows = self
             .rows
             .into_iter()
-            .map(|row| (row.widget)(row.id.as_ref() == selected))
+            .map(|row| LazyRow {
+                selected: row.id.as_ref() == selected,
+                render: Cell::new(Some(row.widget)),
+                widget: OnceCell::new(),
+            })
             .collect();
         List { state, rows }
     }
 }
 
+/// A row that is only rendered once it is needed, so long lists don't pay for
+/// rows that aren't visible.
+struct LazyRow<'a, W> {
+    selected: bool,
+    render: Cell<Option<Box<dyn FnOnce(bool) -> W + 'a>>>,
+    widget: OnceCell<W>,
+}
+
+impl<W> LazyRow<'_, W> {
+    fn get(&self) -> &W {
+        self.widget.get_or_init(|| {
+            let render = self.render.take().expect("row is rendered only once");
+            render(self.selected)
+        })
+    }
+
+    fn into_widget(self) -> W {
+        match self.widget.into_inner() {
+            Some(widget) => widget,
+            None => {
+                let render = self.render.into_inner().expect("row is rendered only once");
+                render(self.selected)
+            }
+        }
+    }
+}
+
 pub struct List<'a, Id, W> {
     state: &'a mut ListState<Id>,
-    rows: Vec<W>,
+    rows: Vec<LazyRow<'a, W>>,
 }
 
 impl<Id, E, W> Widget<E> for List<'_, Id, W>
@@ -322,11 +354,13 @@ where
         &self,
         widthdb: &mut WidthDb,
         max_width: Option<u16>,
-        _max_height: Option<u16>,
+        max_height: Option<u16>,
     ) -> Result<Size, E> {
+        // Only the rows that will be visible are measured.
+        let visible = max_height.map_or(usize::MAX, usize::from);
         let mut width = 0;
-        for row in &self.rows {
-            let size = row.size(widthdb, max_width, Some(1))?;
+        for row in self.rows.iter().skip(self.state.offset).take(visible) {
+            let size = row.get().size(widthdb, max_width, Some(1))?;
             width = width.max(size.width);
         }
         let height = self.rows.len().try_into().unwrap_or(u16::MAX);
@@ -346,7 +380,7 @@ where
             .enumerate()
         {
             frame.push(Pos::new(0, y as i32), Size::new(size.width, 1));
-            row.draw(frame)?;
+            row.into_widget().draw(frame)?;
             frame.pop();
         }
 
@@ -1,3 +1,4 @@
+use cove_config::Theme;
 use toss::widgets::{Background, Border, Desync, Float, Layer2, Padding, Text};
 use toss::{Frame, Size, Style, Styled, Widget, WidgetExt, WidthDb};
 
@@ -7,7 +8,7 @@ type Title = Float<Padding<Background<Padding<Text>>>>;
 pub struct Popup<I>(Float<Layer2<Body<I>, Desync<Title>>>);
 
 impl<I> Popup<I> {
-    pub fn new<S: Into<Styled>>(inner: I, title: S) -> Self {
+    pub fn new<S: Into<Styled>>(theme: &Theme, inner: I, title: S) -> Self {
         let title = Text::new(title)
             .padding()
             .with_horizontal(1)
@@ -23,7 +24,7 @@ impl<I> Popup<I> {
 
         let body = inner.padding().with_horizontal(1).border().background();
 
-        Self(title.above(body).float().with_center())
+        Self(title.above(body).float().with_center()).with_border_style(theme.popup_border())
     }
 
     pub fn with_border_style(mut self, style: Style) -> Self {
@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use toss::widgets::Join2;
+use toss::{AsyncWidget, Frame, Size, WidgetExt, WidthDb};
+
+use crate::ui::UiError;
+
+/// Shows a fixed-width sidebar next to the main widget, but only if the frame
+/// is at least `min_width` columns wide. Otherwise, only the main widget is
+/// shown.
+pub struct Sidebar<S, M> {
+    sidebar: S,
+    main: M,
+    width: u16,
+    min_width: u16,
+}
+
+impl<S, M> Sidebar<S, M> {
+    pub fn new(sidebar: S, main: M, width: u16, min_width: u16) -> Self {
+        Self {
+            sidebar,
+            main,
+            width,
+            min_width,
+        }
+    }
+}
+
+#[async_trait]
+impl<S, M> AsyncWidget<UiError> for Sidebar<S, M>
+where
+    S: AsyncWidget<UiError> + Send + Sync,
+    M: AsyncWidget<UiError> + Send + Sync,
+{
+    async fn size(
+        &self,
+        widthdb: &mut WidthDb,
+        max_width: Option<u16>,
+        max_height: Option<u16>,
+    ) -> Result<Size, UiError> {
+        self.main.size(widthdb, max_width, max_height).await
+    }
+
+    async fn draw(self, frame: &mut Frame) -> Result<(), UiError> {
+        if frame.size().width < self.min_width {
+            return self.main.draw(frame).await;
+        }
+
+        let sidebar = self
+            .sidebar
+            .resize()
+            .with_min_width(self.width)
+            .with_max_width(self.width);
+
+        Join2::horizontal(sidebar.segment().with_fixed(true), self.main.segment())
+            .draw(frame)
+            .await
+    }
+}
@@ -2,7 +2,10 @@ use std::convert::Infallible;
 use std::env;
 
 use time::{OffsetDateTime, UtcOffset};
+use tokio::process::Command;
 use tz::{TimeZone, TzError};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 pub trait InfallibleExt {
     type Inner;
@@ -67,3 +70,121 @@ pub fn caesar(text: &str, by: i8) -> String {
         })
         .collect()
 }
+
+/// Words longer than this many characters are treated according to the
+/// `long_words` config option.
+const LONG_WORD_CHARS: usize = 40;
+
+/// The words of a text that are longer than [`LONG_WORD_CHARS`], along with
+/// the byte offset they start at.
+fn long_words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_inclusive(char::is_whitespace)
+        .scan(0, |offset, piece| {
+            let start = *offset;
+            *offset += piece.len();
+            Some((start, piece.trim_end_matches(char::is_whitespace)))
+        })
+        .filter(|(_, word)| word.chars().count() > LONG_WORD_CHARS)
+}
+
+/// Byte offsets in a message's content after which lines may be broken if
+/// the `long_words` config option is `"punctuation"`, namely after punctuation
+/// in long words.
+///
+/// The content itself is left unchanged so that mentions, emoji and links in
+/// it are still recognized. The breaks are only added while styling it.
+pub fn long_word_breaks(text: &str) -> Vec<usize> {
+    let mut breaks = vec![];
+    for (start, word) in long_words(text) {
+        for (i, c) in word.char_indices() {
+            if c.is_ascii_punctuation() {
+                breaks.push(start + i + c.len_utf8());
+            }
+        }
+    }
+    breaks
+}
+
+/// Cut off long words in a message's content if the `long_words` config option
+/// is `"truncate"`. Truncated words end in `…`.
+pub fn truncate_long_words(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, word) in long_words(text) {
+        result.push_str(&text[last..start]);
+        result.extend(word.chars().take(LONG_WORD_CHARS - 1));
+        result.push('…');
+        last = start + word.len();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+/// Shorten text to at most `width` columns, ending it in `…` if anything was
+/// cut off.
+pub fn truncate_to_width(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+
+    let mut result = String::new();
+    let mut result_width = 0;
+    for grapheme in text.graphemes(true) {
+        result_width += grapheme.width();
+        if result_width + 1 > width {
+            break;
+        }
+        result.push_str(grapheme);
+    }
+    result.push('…');
+    result
+}
+
+/// Prepare a command that runs the given string via the system's shell.
+#[cfg(not(windows))]
+pub fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+/// Prepare a command that runs the given string via the system's shell.
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::truncate_to_width;
+
+    #[test]
+    fn short_text_is_unchanged() {
+        assert_eq!(truncate_to_width("hello", 5), "hello");
+        assert_eq!(truncate_to_width("", 0), "");
+    }
+
+    #[test]
+    fn long_text_ends_in_ellipsis() {
+        assert_eq!(truncate_to_width("hello world", 5), "hell…");
+        assert_eq!(truncate_to_width("hello", 1), "…");
+        assert_eq!(truncate_to_width("hello", 0), "");
+    }
+
+    #[test]
+    fn wide_chars_are_not_split() {
+        assert_eq!(truncate_to_width("日本語", 4), "日…");
+        assert_eq!(truncate_to_width("日本語", 5), "日本…");
+    }
+
+    #[test]
+    fn graphemes_are_not_split() {
+        let text = "e\u{301}e\u{301}e\u{301}";
+        assert_eq!(truncate_to_width(text, 2), "e\u{301}…");
+    }
+}
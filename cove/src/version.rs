@@ -1,2 +1,13 @@
+use cove_config::Config;
+
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The user agent for HTTP requests according to the `user_agent` config
+/// option.
+pub fn user_agent(config: &Config) -> String {
+    config
+        .user_agent
+        .clone()
+        .unwrap_or_else(|| format!("{NAME}/{VERSION}"))
+}